@@ -250,7 +250,7 @@ pub fn transfer_isolated_perp_position_deposit<'c: 'info, 'info>(
             validate_spot_margin_trading(user, &perp_market_map, &spot_market_map, oracle_map)?;
 
             if user.is_cross_margin_being_liquidated() {
-                user.exit_cross_margin_liquidation();
+                user.exit_cross_margin_liquidation()?;
             }
         } else {
             msg!("Cant transfer isolated position deposit without user stats");
@@ -27,14 +27,16 @@ use crate::math::constants::{
     SPOT_WEIGHT_PRECISION,
 };
 use crate::math::liquidation::{
-    calculate_asset_transfer_for_liability_transfer,
+    calculate_asset_transfer_for_liability_transfer, calculate_auto_deleverage_score,
     calculate_base_asset_amount_to_cover_margin_shortage,
-    calculate_cumulative_deposit_interest_delta_to_resolve_bankruptcy,
+    calculate_cumulative_deposit_interest_delta_to_resolve_bankruptcy, calculate_entry_pnl_pct,
     calculate_funding_rate_deltas_to_resolve_bankruptcy,
     calculate_liability_transfer_implied_by_asset_amount,
     calculate_liability_transfer_to_cover_margin_shortage, calculate_liquidation_multiplier,
-    calculate_max_pct_to_liquidate, calculate_perp_if_fee, calculate_spot_if_fee,
-    get_liquidation_fee, get_liquidation_order_params, validate_swap_within_liquidation_boundaries,
+    calculate_margin_shortage_covered_by_base_asset_amount, calculate_max_pct_to_liquidate,
+    calculate_max_perp_if_payment, calculate_perp_if_fee, calculate_spot_if_fee,
+    get_deleverage_order_params, get_liquidation_auction_order_params, get_liquidation_fee,
+    get_liquidation_order_params, validate_swap_within_liquidation_boundaries,
     validate_transfer_satisfies_limit_price, LiquidationMultiplierType,
 };
 use crate::math::margin::{
@@ -47,6 +49,7 @@ use crate::math::orders::{
     is_multiple_of_step_size, is_oracle_too_divergent_with_twap_5min,
     standardize_base_asset_amount, standardize_base_asset_amount_ceil,
 };
+use crate::math::portfolio_margin::PortfolioMarginGroups;
 use crate::math::position::calculate_base_asset_value_with_oracle_price;
 use crate::math::safe_math::SafeMath;
 
@@ -54,20 +57,23 @@ use crate::math::constants::LST_POOL_ID;
 use crate::math::spot_balance::get_token_value;
 use crate::state::events::{
     LiquidateBorrowForPerpPnlRecord, LiquidatePerpPnlForDepositRecord, LiquidatePerpRecord,
-    LiquidateSpotRecord, LiquidationRecord, LiquidationType, OrderAction, OrderActionExplanation,
-    OrderActionRecord, OrderRecord, PerpBankruptcyRecord, SpotBankruptcyRecord,
+    LiquidateSpotRecord, LiquidationBitFlag, LiquidationRecord, LiquidationType, OrderAction,
+    OrderActionExplanation, OrderActionRecord, OrderRecord, PerpBankruptcyRecord,
+    SpotBankruptcyRecord,
 };
 use crate::state::fill_mode::FillMode;
 use crate::state::margin_calculation::{MarginCalculation, MarginContext, MarketIdentifier};
 use crate::state::oracle_map::OracleMap;
 use crate::state::order_params::PlaceOrderOptions;
 use crate::state::paused_operations::{PerpOperation, SpotOperation};
-use crate::state::perp_market::MarketStatus;
+use crate::state::perp_market::{ContractTier, MarketStatus};
 use crate::state::perp_market_map::PerpMarketMap;
 use crate::state::spot_market::SpotBalanceType;
 use crate::state::spot_market_map::SpotMarketMap;
 use crate::state::state::State;
-use crate::state::user::{MarketType, Order, OrderStatus, OrderType, User, UserStats};
+use crate::state::user::{
+    MarketType, Order, OrderStatus, OrderType, PositionFlag, User, UserStats,
+};
 use crate::state::user_map::{UserMap, UserStatsMap};
 use crate::validate;
 use crate::{get_then_update_id, load_mut};
@@ -381,6 +387,18 @@ pub fn liquidate_perp(
         )?;
     }
 
+    let base_asset_amount_to_cover_margin_shortage = if is_portfolio_margin_hedge_leg(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+        MarketIdentifier::perp(market_index),
+    )? {
+        u64::MAX
+    } else {
+        base_asset_amount_to_cover_margin_shortage
+    };
+
     drop(market);
     drop(quote_spot_market);
 
@@ -692,6 +710,7 @@ pub fn liquidate_perp(
         trigger_price: None,
         builder_idx: None,
         builder_fee: None,
+        replaced_order_id: None,
     };
     emit!(fill_record);
 
@@ -996,6 +1015,17 @@ pub fn liquidate_perp_with_fill(
         )?,
         market.amm.order_step_size,
     )?;
+    let base_asset_amount_to_cover_margin_shortage = if is_portfolio_margin_hedge_leg(
+        &user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+        MarketIdentifier::perp(market_index),
+    )? {
+        u64::MAX
+    } else {
+        base_asset_amount_to_cover_margin_shortage
+    };
     drop(market);
     drop(quote_spot_market);
 
@@ -1092,6 +1122,7 @@ pub fn liquidate_perp_with_fill(
         FillMode::Liquidation,
         &mut None,
         false,
+        &mut None,
     )?;
 
     let mut user = load_mut!(user_loader)?;
@@ -1186,7 +1217,865 @@ pub fn liquidate_perp_with_fill(
             liquidator_fee: 0,
             if_fee: if_fee.abs().cast()?,
         },
-        bit_flags,
+        bit_flags,
+        ..LiquidationRecord::default()
+    });
+
+    Ok(())
+}
+
+pub fn begin_perp_liquidation_auction(
+    market_index: u16,
+    user: &mut User,
+    user_key: &Pubkey,
+    keeper_key: &Pubkey,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    clock: &Clock,
+    state: &State,
+) -> DriftResult {
+    let now = clock.unix_timestamp;
+    let slot = clock.slot;
+
+    let liquidation_margin_buffer_ratio = state.liquidation_margin_buffer_ratio;
+    let initial_pct_to_liquidate = state.initial_pct_to_liquidate as u128;
+    let liquidation_duration = state.liquidation_duration as u128;
+
+    validate!(
+        state.liquidation_auction_duration > 0,
+        ErrorCode::LiquidationAuctionsDisabled
+    )?;
+
+    let liquidation_mode = get_perp_liquidation_mode(user, market_index)?;
+
+    validate!(
+        !liquidation_mode.is_user_bankrupt(user)?,
+        ErrorCode::UserBankrupt,
+        "user bankrupt",
+    )?;
+
+    let market = perp_market_map.get_ref(&market_index)?;
+
+    validate!(
+        !market.is_operation_paused(PerpOperation::Liquidation),
+        ErrorCode::InvalidLiquidation,
+        "Liquidation operation is paused for market {}",
+        market_index
+    )?;
+
+    drop(market);
+
+    validate!(
+        user.liquidation_auction_order_id == 0
+            || user
+                .get_order_index(user.liquidation_auction_order_id)
+                .is_err(),
+        ErrorCode::InvalidLiquidation,
+        "liquidation auction order {} already open",
+        user.liquidation_auction_order_id
+    )?;
+
+    settle_funding_payment(
+        user,
+        user_key,
+        perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
+        now,
+    )?;
+
+    let margin_calculation = calculate_margin_requirement_and_total_collateral_and_liability_info(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        MarginContext::liquidation(liquidation_margin_buffer_ratio)
+            .track_market_margin_requirement(MarketIdentifier::perp(market_index))?,
+    )?;
+
+    let user_is_being_liquidated = liquidation_mode.user_is_being_liquidated(user)?;
+    if !user_is_being_liquidated
+        && liquidation_mode.meets_margin_requirements(&margin_calculation)?
+    {
+        msg!("margin calculation: {:?}", margin_calculation);
+        return Err(ErrorCode::SufficientCollateral);
+    } else if user_is_being_liquidated
+        && liquidation_mode.can_exit_liquidation(&margin_calculation)?
+    {
+        liquidation_mode.exit_liquidation(user)?;
+        return Ok(());
+    }
+
+    user.get_perp_position(market_index).map_err(|e| {
+        msg!(
+            "User does not have a position for perp market {}",
+            market_index
+        );
+        e
+    })?;
+
+    let liquidation_id = liquidation_mode.enter_liquidation(user, slot)?;
+
+    let position_index = get_position_index(&user.perp_positions, market_index)?;
+    validate!(
+        user.perp_positions[position_index].is_open_position()
+            || user.perp_positions[position_index].has_open_order(),
+        ErrorCode::PositionDoesntHaveOpenPositionOrOrders
+    )?;
+
+    let (cancel_orders_market_type, cancel_orders_market_index) =
+        liquidation_mode.get_cancel_orders_params();
+    let canceled_order_ids = orders::cancel_orders(
+        user,
+        user_key,
+        Some(keeper_key),
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        now,
+        slot,
+        OrderActionExplanation::Liquidation,
+        cancel_orders_market_type,
+        cancel_orders_market_index,
+        None,
+        true,
+    )?;
+
+    let mut market = perp_market_map.get_ref_mut(&market_index)?;
+    let oracle_price_data = oracle_map.get_price_data(&market.oracle_id())?;
+    let mm_oracle_price_data = market.get_mm_oracle_price_data(
+        *oracle_price_data,
+        slot,
+        &state.oracle_guard_rails.validity,
+    )?;
+
+    update_amm_and_check_validity(
+        &mut market,
+        &mm_oracle_price_data,
+        state,
+        now,
+        slot,
+        Some(DriftAction::Liquidate),
+    )?;
+
+    validate!(
+        market.status != MarketStatus::Settlement,
+        ErrorCode::InvalidLiquidation,
+        "cant open liquidation auction for settled perp market {}",
+        market_index
+    )?;
+
+    let oracle_price = oracle_price_data.price;
+
+    drop(market);
+
+    // check if user exited liquidation territory
+    let intermediate_margin_calculation = if !canceled_order_ids.is_empty() {
+        let intermediate_margin_calculation =
+            calculate_margin_requirement_and_total_collateral_and_liability_info(
+                user,
+                perp_market_map,
+                spot_market_map,
+                oracle_map,
+                MarginContext::liquidation(liquidation_margin_buffer_ratio)
+                    .track_market_margin_requirement(MarketIdentifier::perp(market_index))?,
+            )?;
+
+        let initial_margin_shortage = liquidation_mode.margin_shortage(&margin_calculation)?;
+        let new_margin_shortage =
+            liquidation_mode.margin_shortage(&intermediate_margin_calculation)?;
+
+        let margin_freed = initial_margin_shortage
+            .saturating_sub(new_margin_shortage)
+            .cast::<u64>()?;
+        liquidation_mode.increment_free_margin(user, margin_freed)?;
+
+        if liquidation_mode.can_exit_liquidation(&intermediate_margin_calculation)? {
+            let (margin_requirement, total_collateral, bit_flags) =
+                liquidation_mode.get_event_fields(&margin_calculation)?;
+            emit!(LiquidationRecord {
+                ts: now,
+                liquidation_id,
+                liquidation_type: LiquidationType::LiquidatePerp,
+                user: *user_key,
+                liquidator: *keeper_key,
+                margin_requirement,
+                total_collateral,
+                bankrupt: liquidation_mode.is_user_bankrupt(user)?,
+                canceled_order_ids,
+                margin_freed,
+                liquidate_perp: LiquidatePerpRecord {
+                    market_index,
+                    oracle_price,
+                    lp_shares: 0,
+                    ..LiquidatePerpRecord::default()
+                },
+                bit_flags: bit_flags | LiquidationBitFlag::LiquidationAuction as u8,
+                ..LiquidationRecord::default()
+            });
+
+            liquidation_mode.exit_liquidation(user)?;
+            return Ok(());
+        }
+
+        intermediate_margin_calculation
+    } else {
+        margin_calculation
+    };
+
+    if user.perp_positions[position_index].base_asset_amount == 0 {
+        msg!("User has no base asset amount");
+        return Ok(());
+    }
+
+    let oracle_price_too_divergent = is_oracle_too_divergent_with_twap_5min(
+        oracle_price,
+        perp_market_map
+            .get_ref(&market_index)?
+            .amm
+            .historical_oracle_data
+            .last_oracle_price_twap_5min,
+        state
+            .oracle_guard_rails
+            .max_oracle_twap_5min_percent_divergence()
+            .cast()?,
+    )?;
+
+    validate!(!oracle_price_too_divergent, ErrorCode::PriceBandsBreached)?;
+
+    let user_base_asset_amount = user.perp_positions[position_index]
+        .base_asset_amount
+        .unsigned_abs();
+
+    let margin_ratio = perp_market_map.get_ref(&market_index)?.get_margin_ratio(
+        user_base_asset_amount.cast()?,
+        MarginRequirementType::Maintenance,
+        user.is_high_leverage_mode(MarginRequirementType::Maintenance),
+    )?;
+
+    let margin_ratio_with_buffer = margin_ratio.safe_add(liquidation_margin_buffer_ratio)?;
+
+    let margin_shortage = liquidation_mode.margin_shortage(&intermediate_margin_calculation)?;
+
+    let market = perp_market_map.get_ref(&market_index)?;
+    let quote_spot_market = spot_market_map.get_ref(&market.quote_spot_market_index)?;
+    let quote_oracle_price = oracle_map
+        .get_price_data(&quote_spot_market.oracle_id())?
+        .price;
+    let max_liquidation_fee = market.get_max_liquidation_fee()?;
+    let if_liquidation_fee = calculate_perp_if_fee(
+        intermediate_margin_calculation.tracked_market_margin_shortage(margin_shortage)?,
+        user_base_asset_amount,
+        margin_ratio_with_buffer,
+        max_liquidation_fee,
+        oracle_price,
+        quote_oracle_price,
+        market.if_liquidation_fee,
+    )?;
+    // sized for the worst auction price so the position is covered wherever the auction fills
+    let base_asset_amount_to_cover_margin_shortage = standardize_base_asset_amount_ceil(
+        calculate_base_asset_amount_to_cover_margin_shortage(
+            margin_shortage,
+            margin_ratio_with_buffer,
+            max_liquidation_fee,
+            if_liquidation_fee,
+            oracle_price,
+            quote_oracle_price,
+        )?,
+        market.amm.order_step_size,
+    )?;
+    let base_asset_amount_to_cover_margin_shortage = if is_portfolio_margin_hedge_leg(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+        MarketIdentifier::perp(market_index),
+    )? {
+        u64::MAX
+    } else {
+        base_asset_amount_to_cover_margin_shortage
+    };
+    drop(market);
+    drop(quote_spot_market);
+
+    let max_pct_allowed = liquidation_mode.calculate_max_pct_to_liquidate(
+        user,
+        margin_shortage,
+        slot,
+        initial_pct_to_liquidate,
+        liquidation_duration,
+    )?;
+    let max_base_asset_amount_allowed_to_be_transferred =
+        base_asset_amount_to_cover_margin_shortage
+            .cast::<u128>()?
+            .saturating_mul(max_pct_allowed)
+            .safe_div(LIQUIDATION_PCT_PRECISION)?
+            .cast::<u64>()?;
+
+    if max_base_asset_amount_allowed_to_be_transferred == 0 {
+        msg!("max_base_asset_amount_allowed_to_be_transferred == 0");
+        return Ok(());
+    }
+
+    let base_asset_value =
+        calculate_base_asset_value_with_oracle_price(user_base_asset_amount.cast()?, oracle_price)?
+            .cast::<u64>()?;
+
+    // if position is less than $50, the auction can close all of it
+    let min_base_asset_amount = if base_asset_value > 50 * QUOTE_PRECISION_U64 {
+        0_u64
+    } else {
+        user_base_asset_amount
+    };
+
+    let base_asset_amount = user_base_asset_amount
+        .min(max_base_asset_amount_allowed_to_be_transferred.max(min_base_asset_amount));
+    let base_asset_amount = standardize_base_asset_amount_ceil(
+        base_asset_amount,
+        perp_market_map.get_ref(&market_index)?.amm.order_step_size,
+    )?;
+
+    let existing_direction = user.perp_positions[position_index].get_direction();
+
+    let order_params = get_liquidation_auction_order_params(
+        market_index,
+        existing_direction,
+        base_asset_amount,
+        oracle_price,
+        max_liquidation_fee,
+        state.liquidation_auction_duration,
+        now,
+    )?;
+
+    let order_id = place_perp_order(
+        state,
+        user,
+        *user_key,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        &None,
+        clock,
+        order_params,
+        PlaceOrderOptions::default().explanation(OrderActionExplanation::Liquidation),
+        &mut None,
+    )?
+    .ok_or_else(|| {
+        msg!("liquidation auction order not placed");
+        ErrorCode::InvalidLiquidation
+    })?;
+
+    user.liquidation_auction_order_id = order_id;
+    user.perp_positions[position_index].position_flag |= PositionFlag::LiquidationAuction as u8;
+
+    Ok(())
+}
+
+/// Places a reduce only order on the user's largest risk position while they're between maintenance
+/// and the deleverage buffer, at most the market's liquidator fee from the oracle. Once the user is
+/// back above the buffer, cancels the open deleverage order and resets the pacing instead
+pub fn deleverage_perp_position(
+    market_index: u16,
+    user: &mut User,
+    user_key: &Pubkey,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    clock: &Clock,
+    state: &State,
+) -> DriftResult {
+    let now = clock.unix_timestamp;
+    let slot = clock.slot;
+
+    let deleverage_margin_buffer_ratio = state.deleverage_margin_buffer_ratio.cast::<u32>()?;
+    let initial_pct_to_liquidate = state.initial_pct_to_liquidate as u128;
+    let liquidation_duration = state.liquidation_duration as u128;
+
+    validate!(
+        deleverage_margin_buffer_ratio > 0,
+        ErrorCode::DeleverageDisabled
+    )?;
+
+    let liquidation_mode = get_perp_liquidation_mode(user, market_index)?;
+
+    validate!(
+        !liquidation_mode.is_user_bankrupt(user)?,
+        ErrorCode::UserBankrupt,
+        "user bankrupt",
+    )?;
+
+    validate!(
+        !liquidation_mode.user_is_being_liquidated(user)?,
+        ErrorCode::InvalidDeleverage,
+        "user is being liquidated",
+    )?;
+
+    let market = perp_market_map.get_ref(&market_index)?;
+
+    validate!(
+        !market.is_operation_paused(PerpOperation::Liquidation),
+        ErrorCode::InvalidDeleverage,
+        "Liquidation operation is paused for market {}",
+        market_index
+    )?;
+
+    drop(market);
+
+    settle_funding_payment(
+        user,
+        user_key,
+        perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
+        now,
+    )?;
+
+    let margin_calculation = calculate_margin_requirement_and_total_collateral_and_liability_info(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        MarginContext::liquidation(deleverage_margin_buffer_ratio),
+    )?;
+
+    if liquidation_mode.can_exit_liquidation(&margin_calculation)?
+        && (user.deleverage_start_slot != 0 || user.deleverage_order_id != 0)
+    {
+        return end_perp_deleverage(
+            user,
+            user_key,
+            None,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            slot,
+        );
+    }
+
+    if !liquidation_mode.is_in_deleverage_band(&margin_calculation)? {
+        msg!("margin calculation: {:?}", margin_calculation);
+        return if liquidation_mode.meets_margin_requirements(&margin_calculation)? {
+            Err(ErrorCode::SufficientCollateral)
+        } else {
+            Err(ErrorCode::InvalidDeleverage)
+        };
+    }
+
+    validate!(
+        user.deleverage_order_id == 0 || user.get_order_index(user.deleverage_order_id).is_err(),
+        ErrorCode::InvalidDeleverage,
+        "deleverage order {} already open",
+        user.deleverage_order_id
+    )?;
+
+    let deleverage_market_index =
+        liquidation_mode.get_deleverage_market_index(user, perp_market_map, oracle_map)?;
+    validate!(
+        deleverage_market_index == Some(market_index),
+        ErrorCode::InvalidDeleverage,
+        "perp market {} is not the user's largest risk position ({:?})",
+        market_index,
+        deleverage_market_index
+    )?;
+
+    let position_index = get_position_index(&user.perp_positions, market_index)?;
+
+    let market = perp_market_map.get_ref(&market_index)?;
+    let oracle_price = oracle_map.get_price_data(&market.oracle_id())?.price;
+
+    let oracle_price_too_divergent = is_oracle_too_divergent_with_twap_5min(
+        oracle_price,
+        market
+            .amm
+            .historical_oracle_data
+            .last_oracle_price_twap_5min,
+        state
+            .oracle_guard_rails
+            .max_oracle_twap_5min_percent_divergence()
+            .cast()?,
+    )?;
+
+    validate!(!oracle_price_too_divergent, ErrorCode::PriceBandsBreached)?;
+
+    let perp_position = &user.perp_positions[position_index];
+    let user_base_asset_amount = perp_position.base_asset_amount.unsigned_abs();
+    let existing_direction = perp_position.get_direction();
+    // reduce only orders already resting against the position count towards the deleverage
+    let open_reducing_base_asset_amount = match existing_direction {
+        PositionDirection::Long => perp_position.open_asks.unsigned_abs(),
+        PositionDirection::Short => perp_position.open_bids.unsigned_abs(),
+    };
+
+    let margin_ratio = market.get_margin_ratio(
+        user_base_asset_amount.cast()?,
+        MarginRequirementType::Maintenance,
+        user.is_high_leverage_mode(MarginRequirementType::Maintenance),
+    )?;
+
+    let margin_ratio_with_buffer = margin_ratio.safe_add(deleverage_margin_buffer_ratio)?;
+
+    let margin_shortage = liquidation_mode.margin_shortage(&margin_calculation)?;
+
+    let quote_spot_market = spot_market_map.get_ref(&market.quote_spot_market_index)?;
+    let quote_oracle_price = oracle_map
+        .get_price_data(&quote_spot_market.oracle_id())?
+        .price;
+    let liquidator_fee = market.liquidator_fee;
+    let order_step_size = market.amm.order_step_size;
+    let base_asset_amount_to_cover_margin_shortage = standardize_base_asset_amount_ceil(
+        calculate_base_asset_amount_to_cover_margin_shortage(
+            margin_shortage,
+            margin_ratio_with_buffer,
+            liquidator_fee,
+            0,
+            oracle_price,
+            quote_oracle_price,
+        )?,
+        order_step_size,
+    )?;
+    let base_asset_amount_to_cover_margin_shortage = if is_portfolio_margin_hedge_leg(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+        MarketIdentifier::perp(market_index),
+    )? {
+        u64::MAX
+    } else {
+        base_asset_amount_to_cover_margin_shortage
+    };
+    drop(market);
+    drop(quote_spot_market);
+
+    user.begin_deleverage(slot);
+
+    let max_pct_allowed = liquidation_mode.calculate_max_pct_to_deleverage(
+        user,
+        margin_shortage,
+        slot,
+        initial_pct_to_liquidate,
+        liquidation_duration,
+    )?;
+    let max_base_asset_amount_allowed_to_be_transferred =
+        base_asset_amount_to_cover_margin_shortage
+            .cast::<u128>()?
+            .saturating_mul(max_pct_allowed)
+            .safe_div(LIQUIDATION_PCT_PRECISION)?
+            .cast::<u64>()?;
+
+    let base_asset_value =
+        calculate_base_asset_value_with_oracle_price(user_base_asset_amount.cast()?, oracle_price)?
+            .cast::<u64>()?;
+
+    // if position is less than $50, deleverage all of it
+    let min_base_asset_amount = if base_asset_value > 50 * QUOTE_PRECISION_U64 {
+        0_u64
+    } else {
+        user_base_asset_amount
+    };
+
+    let base_asset_amount = standardize_base_asset_amount_ceil(
+        user_base_asset_amount
+            .min(max_base_asset_amount_allowed_to_be_transferred.max(min_base_asset_amount)),
+        order_step_size,
+    )?
+    .saturating_sub(open_reducing_base_asset_amount);
+
+    validate!(
+        base_asset_amount > 0,
+        ErrorCode::InvalidDeleverage,
+        "reduce only orders already cover the deleverage for perp market {}",
+        market_index
+    )?;
+
+    let order_params = get_deleverage_order_params(
+        market_index,
+        existing_direction,
+        base_asset_amount,
+        oracle_price,
+        liquidator_fee,
+        state.liquidation_duration,
+        now,
+    )?;
+
+    let order_id = place_perp_order(
+        state,
+        user,
+        *user_key,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        &None,
+        clock,
+        order_params,
+        PlaceOrderOptions::default().explanation(OrderActionExplanation::Deleverage),
+        &mut None,
+    )?
+    .ok_or_else(|| {
+        msg!("deleverage order not placed");
+        ErrorCode::InvalidDeleverage
+    })?;
+
+    user.deleverage_order_id = order_id;
+
+    Ok(())
+}
+
+/// Deleverage orders are filled through the regular fill path, identified by the order id stored
+/// when the order was placed
+pub fn is_perp_deleverage_order(user: &User, order_index: usize) -> bool {
+    let order = &user.orders[order_index];
+    order.market_type == MarketType::Perp
+        && user.deleverage_order_id != 0
+        && order.order_id == user.deleverage_order_id
+}
+
+/// Counts the margin freed by a deleverage fill towards the deleverage pacing and ends the
+/// deleverage once the user is back above the buffer
+pub fn settle_perp_deleverage_fill(
+    user: &mut User,
+    user_key: &Pubkey,
+    filler_key: &Pubkey,
+    order_id: u32,
+    market_index: u16,
+    base_asset_amount: u64,
+    oracle_price: i64,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    now: i64,
+    slot: u64,
+    state: &State,
+) -> DriftResult {
+    let deleverage_margin_buffer_ratio = state.deleverage_margin_buffer_ratio.cast::<u32>()?;
+
+    if base_asset_amount != 0 {
+        let market = perp_market_map.get_ref(&market_index)?;
+        let user_base_asset_amount = user
+            .get_perp_position(market_index)?
+            .base_asset_amount
+            .unsigned_abs()
+            .safe_add(base_asset_amount)?;
+        let margin_ratio = market
+            .get_margin_ratio(
+                user_base_asset_amount.cast()?,
+                MarginRequirementType::Maintenance,
+                user.is_high_leverage_mode(MarginRequirementType::Maintenance),
+            )?
+            .safe_add(deleverage_margin_buffer_ratio)?;
+
+        let quote_spot_market = spot_market_map.get_ref(&market.quote_spot_market_index)?;
+        let quote_oracle_price = oracle_map
+            .get_price_data(&quote_spot_market.oracle_id())?
+            .price;
+
+        let margin_freed = calculate_margin_shortage_covered_by_base_asset_amount(
+            base_asset_amount,
+            margin_ratio,
+            market.liquidator_fee,
+            oracle_price,
+            quote_oracle_price,
+        )?;
+        drop(market);
+        drop(quote_spot_market);
+
+        user.increment_deleverage_margin_freed(margin_freed.cast()?)?;
+    }
+
+    let margin_calculation = calculate_margin_requirement_and_total_collateral_and_liability_info(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        MarginContext::liquidation(deleverage_margin_buffer_ratio),
+    )?;
+
+    if get_perp_liquidation_mode(user, market_index)?.can_exit_liquidation(&margin_calculation)? {
+        end_perp_deleverage(
+            user,
+            user_key,
+            Some(filler_key),
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            slot,
+        )?;
+    } else if user.get_order_index(order_id).is_err() {
+        user.deleverage_order_id = 0;
+    }
+
+    Ok(())
+}
+
+fn end_perp_deleverage(
+    user: &mut User,
+    user_key: &Pubkey,
+    filler_key: Option<&Pubkey>,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    now: i64,
+    slot: u64,
+) -> DriftResult {
+    if user.deleverage_order_id != 0 {
+        if let Ok(order_index) = user.get_order_index(user.deleverage_order_id) {
+            cancel_order(
+                order_index,
+                user,
+                user_key,
+                perp_market_map,
+                spot_market_map,
+                oracle_map,
+                now,
+                slot,
+                OrderActionExplanation::None,
+                filler_key,
+                0,
+                false,
+            )?;
+        }
+    }
+
+    user.end_deleverage();
+
+    Ok(())
+}
+
+/// Liquidation auction orders are filled through the regular fill path, identified by the order id
+/// stored when the auction began while the user is still being liquidated
+pub fn is_perp_liquidation_auction_order(user: &User, order_index: usize) -> DriftResult<bool> {
+    let order = &user.orders[order_index];
+    if order.market_type != MarketType::Perp
+        || !order.reduce_only
+        || user.liquidation_auction_order_id == 0
+        || order.order_id != user.liquidation_auction_order_id
+    {
+        return Ok(false);
+    }
+
+    match user.get_perp_position(order.market_index) {
+        Ok(perp_position) if perp_position.has_liquidation_auction() => {}
+        _ => return Ok(false),
+    }
+
+    get_perp_liquidation_mode(user, order.market_index)?.user_is_being_liquidated(user)
+}
+
+/// Charges the insurance fund fee on a liquidation auction fill and moves the user out of
+/// liquidation once they're healthy. The auction price improvement already accrued to the user
+pub fn settle_perp_liquidation_auction_fill(
+    user: &mut User,
+    user_key: &Pubkey,
+    filler_key: &Pubkey,
+    order_id: u32,
+    market_index: u16,
+    existing_direction: PositionDirection,
+    base_asset_amount: u64,
+    quote_asset_amount: u64,
+    fill_record_id: u64,
+    oracle_price: i64,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    now: i64,
+    slot: u64,
+    state: &State,
+) -> DriftResult {
+    let liquidation_mode = get_perp_liquidation_mode(user, market_index)?;
+
+    let if_fee = if base_asset_amount != 0 {
+        let mut market = perp_market_map.get_ref_mut(&market_index)?;
+
+        let if_fee = -quote_asset_amount
+            .cast::<u128>()?
+            .safe_mul(market.if_liquidation_fee.cast()?)?
+            .safe_div(LIQUIDATION_FEE_PRECISION_U128)?
+            .cast::<i64>()?;
+
+        let user_position = user.get_perp_position_mut(market_index)?;
+        update_quote_asset_and_break_even_amount(user_position, &mut market, if_fee)?;
+
+        market.amm.total_liquidation_fee = market
+            .amm
+            .total_liquidation_fee
+            .safe_add(if_fee.unsigned_abs().cast()?)?;
+
+        if_fee
+    } else {
+        0
+    };
+
+    let margin_calculation = calculate_margin_requirement_and_total_collateral_and_liability_info(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        MarginContext::liquidation(state.liquidation_margin_buffer_ratio),
+    )?;
+
+    let order_index = user.get_order_index(order_id).ok();
+    if liquidation_mode.can_exit_liquidation(&margin_calculation)? {
+        // cancel with a record before exiting, the exit itself only clears the auction state
+        if let Some(order_index) = order_index {
+            cancel_order(
+                order_index,
+                user,
+                user_key,
+                perp_market_map,
+                spot_market_map,
+                oracle_map,
+                now,
+                slot,
+                OrderActionExplanation::None,
+                Some(filler_key),
+                0,
+                false,
+            )?;
+        }
+
+        liquidation_mode.exit_liquidation(user)?;
+    } else {
+        if order_index.is_none() {
+            user.liquidation_auction_order_id = 0;
+            user.get_perp_position_mut(market_index)?.position_flag &=
+                !(PositionFlag::LiquidationAuction as u8);
+        }
+
+        if liquidation_mode.should_user_enter_bankruptcy(user)? {
+            liquidation_mode.enter_bankruptcy(user)?;
+        }
+    }
+
+    if base_asset_amount == 0 {
+        return Ok(());
+    }
+
+    let user_position_delta =
+        get_position_delta_for_fill(base_asset_amount, quote_asset_amount, existing_direction)?;
+
+    let (margin_requirement, total_collateral, bit_flags) =
+        liquidation_mode.get_event_fields(&margin_calculation)?;
+    emit!(LiquidationRecord {
+        ts: now,
+        liquidation_id: user.next_liquidation_id.safe_sub(1)?,
+        liquidation_type: LiquidationType::LiquidatePerp,
+        user: *user_key,
+        liquidator: *filler_key,
+        margin_requirement,
+        total_collateral,
+        bankrupt: liquidation_mode.is_user_bankrupt(user)?,
+        liquidate_perp: LiquidatePerpRecord {
+            market_index,
+            oracle_price,
+            base_asset_amount: user_position_delta.base_asset_amount,
+            quote_asset_amount: user_position_delta.quote_asset_amount,
+            lp_shares: 0,
+            user_order_id: order_id,
+            liquidator_order_id: 0,
+            fill_record_id,
+            liquidator_fee: 0,
+            if_fee: if_fee.abs().cast()?,
+        },
+        bit_flags: bit_flags | LiquidationBitFlag::LiquidationAuction as u8,
         ..LiquidationRecord::default()
     });
 
@@ -1435,7 +2324,7 @@ pub fn liquidate_spot(
     } else if user.is_cross_margin_being_liquidated()
         && margin_calculation.can_exit_cross_margin_liquidation()?
     {
-        user.exit_cross_margin_liquidation();
+        user.exit_cross_margin_liquidation()?;
         return Ok(());
     }
 
@@ -1505,7 +2394,7 @@ pub fn liquidate_spot(
                 ..LiquidationRecord::default()
             });
 
-            user.exit_cross_margin_liquidation();
+            user.exit_cross_margin_liquidation()?;
             return Ok(());
         }
 
@@ -1546,6 +2435,24 @@ pub fn liquidate_spot(
             liquidation_if_fee,
         )?;
 
+    let liability_transfer_to_cover_margin_shortage = if is_portfolio_margin_hedge_leg(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+        MarketIdentifier::spot(asset_market_index),
+    )? || is_portfolio_margin_hedge_leg(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+        MarketIdentifier::spot(liability_market_index),
+    )? {
+        u128::MAX
+    } else {
+        liability_transfer_to_cover_margin_shortage
+    };
+
     let max_pct_allowed = calculate_max_pct_to_liquidate(
         user,
         margin_shortage,
@@ -1726,7 +2633,7 @@ pub fn liquidate_spot(
     user.increment_margin_freed(margin_freed_from_liability)?;
 
     if liability_transfer >= liability_transfer_to_cover_margin_shortage {
-        user.exit_cross_margin_liquidation();
+        user.exit_cross_margin_liquidation()?;
     } else if is_cross_margin_bankrupt(user) {
         user.enter_cross_margin_bankruptcy();
     }
@@ -2067,6 +2974,24 @@ pub fn liquidate_spot_with_swap_begin(
             liability_if_fee,
         )?;
 
+    let liability_transfer_to_cover_margin_shortage = if is_portfolio_margin_hedge_leg(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+        MarketIdentifier::spot(asset_market_index),
+    )? || is_portfolio_margin_hedge_leg(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+        MarketIdentifier::spot(liability_market_index),
+    )? {
+        u128::MAX
+    } else {
+        liability_transfer_to_cover_margin_shortage
+    };
+
     let max_pct_allowed = calculate_max_pct_to_liquidate(
         user,
         margin_shortage,
@@ -2303,7 +3228,7 @@ pub fn liquidate_spot_with_swap_end(
     user.increment_margin_freed(margin_freed_from_liability)?;
 
     if margin_calulcation_after.can_exit_cross_margin_liquidation()? {
-        user.exit_cross_margin_liquidation();
+        user.exit_cross_margin_liquidation()?;
     } else if is_cross_margin_bankrupt(user) {
         user.enter_cross_margin_bankruptcy();
     }
@@ -2553,7 +3478,7 @@ pub fn liquidate_borrow_for_perp_pnl(
     } else if user.is_cross_margin_being_liquidated()
         && margin_calculation.can_exit_cross_margin_liquidation()?
     {
-        user.exit_cross_margin_liquidation();
+        user.exit_cross_margin_liquidation()?;
         return Ok(());
     }
 
@@ -2621,7 +3546,7 @@ pub fn liquidate_borrow_for_perp_pnl(
                 ..LiquidationRecord::default()
             });
 
-            user.exit_cross_margin_liquidation();
+            user.exit_cross_margin_liquidation()?;
             return Ok(());
         }
 
@@ -2648,6 +3573,18 @@ pub fn liquidate_borrow_for_perp_pnl(
             0,
         )?;
 
+    let liability_transfer_to_cover_margin_shortage = if is_portfolio_margin_hedge_leg(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+        MarketIdentifier::spot(liability_market_index),
+    )? {
+        u128::MAX
+    } else {
+        liability_transfer_to_cover_margin_shortage
+    };
+
     let max_pct_allowed = calculate_max_pct_to_liquidate(
         user,
         margin_shortage,
@@ -2773,7 +3710,7 @@ pub fn liquidate_borrow_for_perp_pnl(
     user.increment_margin_freed(margin_freed_from_liability)?;
 
     if liability_transfer >= liability_transfer_to_cover_margin_shortage {
-        user.exit_cross_margin_liquidation();
+        user.exit_cross_margin_liquidation()?;
     } else if is_cross_margin_bankrupt(user) {
         user.enter_cross_margin_bankruptcy();
     }
@@ -3150,6 +4087,18 @@ pub fn liquidate_perp_pnl_for_deposit(
             0, // no if fee
         )?;
 
+    let pnl_transfer_to_cover_margin_shortage = if is_portfolio_margin_hedge_leg(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+        MarketIdentifier::spot(asset_market_index),
+    )? {
+        u128::MAX
+    } else {
+        pnl_transfer_to_cover_margin_shortage
+    };
+
     let max_pct_allowed = liquidation_mode.calculate_max_pct_to_liquidate(
         user,
         margin_shortage,
@@ -3395,16 +4344,10 @@ pub fn resolve_perp_bankruptcy(
 
     let if_payment = {
         let mut perp_market = perp_market_map.get_ref_mut(&market_index)?;
-        let max_insurance_withdraw = perp_market
-            .insurance_claim
-            .quote_max_insurance
-            .safe_sub(perp_market.insurance_claim.quote_settled_insurance)?
-            .cast::<u128>()?;
-
-        let if_payment = loss
-            .unsigned_abs()
-            .min(insurance_fund_vault_balance.saturating_sub(1).cast()?)
-            .min(max_insurance_withdraw);
+        let if_payment = loss.unsigned_abs().min(calculate_max_perp_if_payment(
+            &perp_market,
+            insurance_fund_vault_balance,
+        )?);
 
         perp_market.insurance_claim.quote_settled_insurance = perp_market
             .insurance_claim
@@ -3542,6 +4485,409 @@ pub fn resolve_perp_bankruptcy(
     if_payment.cast()
 }
 
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum AutoDeleverageMode {
+    /// Bankruptcies the insurance fund can't cover are socialized across all open interest
+    SocializeLoss,
+    /// Bankrupt positions the insurance fund can't cover are closed against the highest ranked
+    /// opposite side positions at the bankruptcy price
+    RankedDeleverage,
+}
+
+pub fn get_auto_deleverage_mode(contract_tier: ContractTier) -> AutoDeleverageMode {
+    match contract_tier {
+        ContractTier::A | ContractTier::B => AutoDeleverageMode::SocializeLoss,
+        ContractTier::C
+        | ContractTier::Speculative
+        | ContractTier::HighlySpeculative
+        | ContractTier::Isolated => AutoDeleverageMode::RankedDeleverage,
+    }
+}
+
+/// Keeper submitted opposite side positions ranked by `calculate_auto_deleverage_score`, highest
+/// first. Positions whose pnl share of entry value is below their market side's aggregate are left
+/// out, so keepers can't deleverage the less profitable half of the side while the rest is
+/// untouched. Counterparties being liquidated or without profit are left out too
+pub fn get_auto_deleverage_queue(
+    counterparties: &UserMap,
+    user_key: &Pubkey,
+    market_index: u16,
+    existing_direction: PositionDirection,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    now: i64,
+) -> DriftResult<Vec<(Pubkey, u128)>> {
+    let (oracle_price, min_entry_pnl_pct) = {
+        let market = perp_market_map.get_ref(&market_index)?;
+        let oracle_price = oracle_map.get_price_data(&market.oracle_id())?.price;
+
+        let (base_asset_amount, quote_entry_amount) = match existing_direction {
+            PositionDirection::Long => (
+                market.amm.base_asset_amount_short,
+                market.amm.quote_entry_amount_short,
+            ),
+            PositionDirection::Short => (
+                market.amm.base_asset_amount_long,
+                market.amm.quote_entry_amount_long,
+            ),
+        };
+
+        (
+            oracle_price,
+            calculate_entry_pnl_pct(base_asset_amount, quote_entry_amount, oracle_price)?,
+        )
+    };
+
+    let mut queue = Vec::with_capacity(counterparties.0.len());
+    for counterparty_key in counterparties.0.keys() {
+        if counterparty_key == user_key {
+            continue;
+        }
+
+        let mut counterparty = counterparties.get_ref_mut(counterparty_key)?;
+
+        match counterparty.get_perp_position(market_index) {
+            Ok(perp_position)
+                if perp_position.base_asset_amount != 0
+                    && perp_position.get_direction() != existing_direction =>
+            {
+                let entry_pnl_pct = calculate_entry_pnl_pct(
+                    perp_position.base_asset_amount.cast()?,
+                    perp_position.quote_entry_amount.cast()?,
+                    oracle_price,
+                )?;
+
+                if entry_pnl_pct < min_entry_pnl_pct {
+                    continue;
+                }
+            }
+            _ => continue,
+        }
+
+        let counterparty_liquidation_mode = get_perp_liquidation_mode(&counterparty, market_index)?;
+        if counterparty.is_bankrupt()
+            || counterparty_liquidation_mode.user_is_being_liquidated(&counterparty)?
+        {
+            continue;
+        }
+
+        settle_funding_payment(
+            &mut counterparty,
+            counterparty_key,
+            perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
+            now,
+        )?;
+
+        let margin_calculation =
+            calculate_margin_requirement_and_total_collateral_and_liability_info(
+                &counterparty,
+                perp_market_map,
+                spot_market_map,
+                oracle_map,
+                MarginContext::standard(MarginRequirementType::Maintenance),
+            )?;
+        let (_, total_collateral, _) =
+            counterparty_liquidation_mode.get_event_fields(&margin_calculation)?;
+
+        let perp_position = counterparty.get_perp_position(market_index)?;
+        let score = calculate_auto_deleverage_score(
+            perp_position.get_unrealized_pnl(oracle_price)?,
+            perp_position.quote_entry_amount,
+            calculate_base_asset_value_with_oracle_price(
+                perp_position.base_asset_amount.cast()?,
+                oracle_price,
+            )?,
+            total_collateral,
+        )?;
+
+        if score > 0 {
+            queue.push((*counterparty_key, score));
+        }
+    }
+
+    queue.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    Ok(queue)
+}
+
+/// Closes a position the insurance fund can't cover against the auto deleverage queue at the
+/// bankruptcy price, the price at which the user's deficit beyond the insurance fund is zero. The
+/// deficit is capped at the position's loss, losses in other markets aren't put on this market's
+/// counterparties. Counterparties give up at most the pnl on the portion closed, any deficit left
+/// over goes through `resolve_perp_bankruptcy`
+pub fn auto_deleverage_perp_position(
+    market_index: u16,
+    user: &mut User,
+    user_key: &Pubkey,
+    counterparties: &UserMap,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    now: i64,
+    slot: u64,
+    state: &State,
+    insurance_fund_vault_balance: u64,
+) -> DriftResult {
+    let liquidation_mode = get_perp_liquidation_mode(user, market_index)?;
+
+    validate!(
+        !liquidation_mode.is_user_bankrupt(user)?,
+        ErrorCode::UserBankrupt,
+        "user bankrupt",
+    )?;
+
+    let market = perp_market_map.get_ref(&market_index)?;
+
+    validate!(
+        !market.is_operation_paused(PerpOperation::Liquidation),
+        ErrorCode::InvalidLiquidation,
+        "Liquidation operation is paused for market {}",
+        market_index
+    )?;
+
+    validate!(
+        get_auto_deleverage_mode(market.contract_tier) == AutoDeleverageMode::RankedDeleverage,
+        ErrorCode::InvalidAutoDeleverage,
+        "auto deleverage disabled for contract tier {:?}",
+        market.contract_tier
+    )?;
+
+    drop(market);
+
+    settle_funding_payment(
+        user,
+        user_key,
+        perp_market_map.get_ref_mut(&market_index)?.deref_mut(),
+        now,
+    )?;
+
+    let position_index = get_position_index(&user.perp_positions, market_index)?;
+    validate!(
+        user.perp_positions[position_index].base_asset_amount != 0,
+        ErrorCode::InvalidAutoDeleverage,
+        "user has no base asset amount for perp market {}",
+        market_index
+    )?;
+
+    let margin_calculation = calculate_margin_requirement_and_total_collateral_and_liability_info(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        MarginContext::liquidation(state.liquidation_margin_buffer_ratio),
+    )?;
+
+    let (margin_requirement, total_collateral, bit_flags) =
+        liquidation_mode.get_event_fields(&margin_calculation)?;
+
+    validate!(
+        total_collateral < 0,
+        ErrorCode::InvalidAutoDeleverage,
+        "user total collateral ({}) must be negative",
+        total_collateral
+    )?;
+
+    let oracle_price = {
+        let market = perp_market_map.get_ref(&market_index)?;
+        oracle_map.get_price_data(&market.oracle_id())?.price
+    };
+
+    let unrealized_pnl = user.perp_positions[position_index].get_unrealized_pnl(oracle_price)?;
+
+    validate!(
+        unrealized_pnl < 0,
+        ErrorCode::InvalidAutoDeleverage,
+        "user unrealized pnl ({}) for perp market {} must be negative",
+        unrealized_pnl,
+        market_index
+    )?;
+
+    let max_if_payment = calculate_max_perp_if_payment(
+        &perp_market_map.get_ref(&market_index)?,
+        insurance_fund_vault_balance,
+    )?;
+    let deficit = total_collateral
+        .unsigned_abs()
+        .min(unrealized_pnl.unsigned_abs());
+
+    validate!(
+        deficit > max_if_payment,
+        ErrorCode::InvalidAutoDeleverage,
+        "insurance fund can cover deficit ({}) with max payment ({})",
+        deficit,
+        max_if_payment
+    )?;
+
+    let mut deficit_remaining = deficit.safe_sub(max_if_payment)?;
+
+    let existing_direction = user.perp_positions[position_index].get_direction();
+    let direction_to_close = user.perp_positions[position_index].get_direction_to_close();
+
+    let queue = get_auto_deleverage_queue(
+        counterparties,
+        user_key,
+        market_index,
+        existing_direction,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        now,
+    )?;
+
+    validate!(
+        !queue.is_empty(),
+        ErrorCode::InvalidAutoDeleverage,
+        "no counterparty in the auto deleverage queue for perp market {}",
+        market_index
+    )?;
+
+    let liquidation_id = liquidation_mode.enter_liquidation(user, slot)?;
+
+    let (cancel_orders_market_type, cancel_orders_market_index) =
+        liquidation_mode.get_cancel_orders_params();
+    let mut canceled_order_ids = orders::cancel_orders(
+        user,
+        user_key,
+        None,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        now,
+        slot,
+        OrderActionExplanation::Liquidation,
+        cancel_orders_market_type,
+        cancel_orders_market_index,
+        None,
+        true,
+    )?;
+
+    let mut user_position_deltas = Vec::with_capacity(queue.len());
+    for (counterparty_key, _) in queue.iter() {
+        let base_asset_amount_remaining = user.perp_positions[position_index]
+            .base_asset_amount
+            .unsigned_abs();
+
+        if base_asset_amount_remaining == 0 {
+            break;
+        }
+
+        let mut counterparty = counterparties.get_ref_mut(counterparty_key)?;
+
+        let counterparty_position = counterparty.get_perp_position(market_index)?;
+        let counterparty_base_asset_amount = counterparty_position.base_asset_amount.unsigned_abs();
+        let base_asset_amount = base_asset_amount_remaining.min(counterparty_base_asset_amount);
+
+        let base_asset_value =
+            calculate_base_asset_value_with_oracle_price(base_asset_amount.cast()?, oracle_price)?;
+
+        // counterparty gives up at most the pnl on the portion closed
+        let counterparty_pnl = counterparty_position
+            .get_unrealized_pnl(oracle_price)?
+            .max(0)
+            .unsigned_abs()
+            .safe_mul(base_asset_amount.cast()?)?
+            .safe_div(counterparty_base_asset_amount.cast()?)?;
+
+        let deficit_share = deficit_remaining
+            .safe_mul(base_asset_amount.cast()?)?
+            .safe_div_ceil(base_asset_amount_remaining.cast()?)?
+            .min(counterparty_pnl);
+
+        // the user closes at a better price than the oracle by their share of the deficit
+        let (quote_asset_amount, deficit_share) = match existing_direction {
+            PositionDirection::Long => (base_asset_value.safe_add(deficit_share)?, deficit_share),
+            PositionDirection::Short => {
+                let deficit_share = deficit_share.min(base_asset_value);
+                (base_asset_value.safe_sub(deficit_share)?, deficit_share)
+            }
+        };
+        let quote_asset_amount = quote_asset_amount.cast::<u64>()?;
+
+        let user_position_delta =
+            get_position_delta_for_fill(base_asset_amount, quote_asset_amount, direction_to_close)?;
+        let counterparty_position_delta =
+            get_position_delta_for_fill(base_asset_amount, quote_asset_amount, existing_direction)?;
+
+        {
+            let mut market = perp_market_map.get_ref_mut(&market_index)?;
+
+            update_position_and_market(
+                &mut user.perp_positions[position_index],
+                &mut market,
+                &user_position_delta,
+            )?;
+
+            let counterparty_position = counterparty.get_perp_position_mut(market_index)?;
+            update_position_and_market(
+                counterparty_position,
+                &mut market,
+                &counterparty_position_delta,
+            )?;
+        }
+
+        orders::cancel_reduce_only_orders_increasing_position(
+            &mut counterparty,
+            counterparty_key,
+            None,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            slot,
+            market_index,
+        )?;
+
+        counterparty.update_last_active_slot(slot);
+
+        deficit_remaining = deficit_remaining.safe_sub(deficit_share)?;
+
+        user_position_deltas.push((*counterparty_key, user_position_delta));
+    }
+
+    let margin_calculation_after =
+        calculate_margin_requirement_and_total_collateral_and_liability_info(
+            user,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            MarginContext::liquidation(state.liquidation_margin_buffer_ratio),
+        )?;
+
+    if liquidation_mode.can_exit_liquidation(&margin_calculation_after)? {
+        liquidation_mode.exit_liquidation(user)?;
+    } else if liquidation_mode.should_user_enter_bankruptcy(user)? {
+        liquidation_mode.enter_bankruptcy(user)?;
+    }
+
+    let bankrupt = liquidation_mode.is_user_bankrupt(user)?;
+    for (counterparty_key, user_position_delta) in user_position_deltas {
+        emit!(LiquidationRecord {
+            ts: now,
+            liquidation_id,
+            liquidation_type: LiquidationType::PerpAutoDeleverage,
+            user: *user_key,
+            liquidator: counterparty_key,
+            margin_requirement,
+            total_collateral,
+            bankrupt,
+            canceled_order_ids: std::mem::take(&mut canceled_order_ids),
+            liquidate_perp: LiquidatePerpRecord {
+                market_index,
+                oracle_price,
+                base_asset_amount: user_position_delta.base_asset_amount,
+                quote_asset_amount: user_position_delta.quote_asset_amount,
+                lp_shares: 0,
+                ..LiquidatePerpRecord::default()
+            },
+            bit_flags,
+            ..LiquidationRecord::default()
+        });
+    }
+
+    Ok(())
+}
+
 pub fn resolve_spot_bankruptcy(
     market_index: u16,
     user: &mut User,
@@ -3696,6 +5042,32 @@ pub fn resolve_spot_bankruptcy(
     if_payment.cast()
 }
 
+/// Whether the position is one leg of a portfolio margin user's spot/perp hedge. Liquidating it
+/// frees less than the standard weights imply, so the margin shortage can't size the transfer
+pub fn is_portfolio_margin_hedge_leg(
+    user: &User,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    portfolio_margin_enabled: bool,
+    market: MarketIdentifier,
+) -> DriftResult<bool> {
+    if !user.is_portfolio_margin_mode() {
+        return Ok(false);
+    }
+
+    let portfolio_margin_groups = PortfolioMarginGroups::new(
+        user,
+        perp_market_map,
+        spot_market_map,
+        portfolio_margin_enabled,
+    )?;
+
+    Ok(match market.market_type {
+        MarketType::Perp => portfolio_margin_groups.contains_perp_market(market.market_index),
+        MarketType::Spot => portfolio_margin_groups.contains_spot_market(market.market_index),
+    })
+}
+
 pub fn calculate_margin_freed(
     user: &User,
     perp_market_map: &PerpMarketMap,
@@ -11030,3 +11030,357 @@ mod liquidation_mode {
         assert_eq!(cancel_market_index, Some(0));
     }
 }
+
+mod get_auto_deleverage_mode {
+    use crate::controller::liquidation::{get_auto_deleverage_mode, AutoDeleverageMode};
+    use crate::state::perp_market::ContractTier;
+
+    #[test]
+    fn test() {
+        assert_eq!(
+            get_auto_deleverage_mode(ContractTier::A),
+            AutoDeleverageMode::SocializeLoss
+        );
+        assert_eq!(
+            get_auto_deleverage_mode(ContractTier::B),
+            AutoDeleverageMode::SocializeLoss
+        );
+        assert_eq!(
+            get_auto_deleverage_mode(ContractTier::C),
+            AutoDeleverageMode::RankedDeleverage
+        );
+        assert_eq!(
+            get_auto_deleverage_mode(ContractTier::Speculative),
+            AutoDeleverageMode::RankedDeleverage
+        );
+        assert_eq!(
+            get_auto_deleverage_mode(ContractTier::HighlySpeculative),
+            AutoDeleverageMode::RankedDeleverage
+        );
+        assert_eq!(
+            get_auto_deleverage_mode(ContractTier::Isolated),
+            AutoDeleverageMode::RankedDeleverage
+        );
+    }
+}
+
+mod auto_deleverage_perp_position {
+    use std::str::FromStr;
+
+    use anchor_lang::prelude::AccountLoader;
+    use anchor_lang::Owner;
+    use solana_program::pubkey::Pubkey;
+
+    use crate::controller::liquidation::auto_deleverage_perp_position;
+    use crate::controller::position::PositionDirection;
+    use crate::create_anchor_account_info;
+    use crate::error::ErrorCode;
+    use crate::math::constants::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_I128, BASE_PRECISION_I64, BASE_PRECISION_U64,
+        LIQUIDATION_PCT_PRECISION, PEG_PRECISION, PRICE_PRECISION_I64, PRICE_PRECISION_U64,
+        QUOTE_PRECISION_I128, QUOTE_PRECISION_I64, SPOT_BALANCE_PRECISION_U64,
+        SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_WEIGHT_PRECISION,
+    };
+    use crate::state::oracle::{HistoricalOracleData, OracleSource};
+    use crate::state::oracle_map::OracleMap;
+    use crate::state::perp_market::{ContractTier, MarketStatus, PerpMarket, AMM};
+    use crate::state::perp_market_map::PerpMarketMap;
+    use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+    use crate::state::spot_market_map::SpotMarketMap;
+    use crate::state::state::State;
+    use crate::state::user::{
+        Order, OrderStatus, OrderType, PerpPosition, SpotPosition, User, UserStatus,
+    };
+    use crate::state::user_map::UserMap;
+    use crate::test_utils::{get_orders, get_positions, get_pyth_price, get_spot_positions};
+    use crate::{create_account_info, test_utils::*};
+
+    #[test]
+    pub fn ranked_queue_absorbs_market_deficit() {
+        let now = 0_i64;
+        let slot = 100_u64;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let amm = AMM {
+            base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+            sqrt_k: 100 * AMM_RESERVE_PRECISION,
+            peg_multiplier: 100 * PEG_PRECISION,
+            order_step_size: 10000000,
+            order_tick_size: 1,
+            oracle: oracle_price_key,
+            historical_oracle_data: HistoricalOracleData::default_price(oracle_price.agg.price),
+            ..AMM::default()
+        };
+
+        // longs: the bankrupt user. shorts: the counterparties a, b and c, +580 pnl on 1780 of
+        // entry value, so 32.6% is the bound to enter the queue
+        let mut market = PerpMarket {
+            market_index: 0,
+            amm: AMM {
+                base_asset_amount_long: 10 * BASE_PRECISION_I128,
+                base_asset_amount_short: -12 * BASE_PRECISION_I128,
+                quote_entry_amount_long: -1500 * QUOTE_PRECISION_I128,
+                quote_entry_amount_short: 1780 * QUOTE_PRECISION_I128,
+                base_asset_amount_with_amm: -2 * BASE_PRECISION_I128,
+                ..amm
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            number_of_users_with_base: 4,
+            number_of_users: 4,
+            status: MarketStatus::Active,
+            contract_tier: ContractTier::Speculative,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let mut other_market = PerpMarket {
+            market_index: 1,
+            amm: AMM {
+                base_asset_amount_short: -BASE_PRECISION_I128,
+                base_asset_amount_with_amm: -BASE_PRECISION_I128,
+                ..amm
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            number_of_users_with_base: 1,
+            number_of_users: 1,
+            status: MarketStatus::Active,
+            contract_tier: ContractTier::Speculative,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(other_market, PerpMarket, other_market_account_info);
+        let perp_market_map = PerpMarketMap::load_multiple(
+            vec![&market_account_info, &other_market_account_info],
+            true,
+        )
+        .unwrap();
+
+        let mut spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            cumulative_borrow_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData {
+                last_oracle_price_twap: PRICE_PRECISION_I64,
+                last_oracle_price_twap_5min: PRICE_PRECISION_I64,
+                ..HistoricalOracleData::default()
+            },
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+        let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
+
+        // long 10 @ 150 in the market and short 1 @ 50 in the other market, -550 total collateral
+        // of which -500 comes from the market
+        let user_key = Pubkey::new_unique();
+        let mut user = User {
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: 10 * BASE_PRECISION_I64,
+                quote_asset_amount: -1500 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -1500 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -1500 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            ..User::default()
+        };
+        user.perp_positions[1] = PerpPosition {
+            market_index: 1,
+            base_asset_amount: -BASE_PRECISION_I64,
+            quote_asset_amount: 50 * QUOTE_PRECISION_I64,
+            quote_entry_amount: 50 * QUOTE_PRECISION_I64,
+            quote_break_even_amount: 50 * QUOTE_PRECISION_I64,
+            ..PerpPosition::default()
+        };
+
+        let deposit = get_spot_positions(SpotPosition {
+            market_index: 0,
+            balance_type: SpotBalanceType::Deposit,
+            scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
+            ..SpotPosition::default()
+        });
+
+        // short 5 @ 120, +16.7% pnl is below the side's bound so it isn't in the queue
+        let b_key = Pubkey::new_unique();
+        let mut b = User {
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: -5 * BASE_PRECISION_I64,
+                quote_asset_amount: 600 * QUOTE_PRECISION_I64,
+                quote_entry_amount: 600 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: 600 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: deposit,
+            ..User::default()
+        };
+        create_anchor_account_info!(b, &b_key, User, b_account_info);
+
+        // short 5 @ 200 (+500 pnl, 5x leverage) with a reduce only bid
+        let a_key = Pubkey::new_unique();
+        let mut a = User {
+            orders: get_orders(Order {
+                status: OrderStatus::Open,
+                market_index: 0,
+                order_type: OrderType::Limit,
+                direction: PositionDirection::Long,
+                base_asset_amount: 2 * BASE_PRECISION_U64,
+                price: 90 * PRICE_PRECISION_U64,
+                reduce_only: true,
+                slot,
+                ..Order::default()
+            }),
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: -5 * BASE_PRECISION_I64,
+                quote_asset_amount: 1000 * QUOTE_PRECISION_I64,
+                quote_entry_amount: 1000 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: 1000 * QUOTE_PRECISION_I64,
+                open_orders: 1,
+                open_bids: 2 * BASE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: deposit,
+            open_orders: 1,
+            has_open_order: true,
+            ..User::default()
+        };
+        create_anchor_account_info!(a, &a_key, User, a_account_info);
+
+        // short 2 @ 90, unprofitable so it isn't in the queue
+        let c_key = Pubkey::new_unique();
+        let mut c = User {
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: -2 * BASE_PRECISION_I64,
+                quote_asset_amount: 180 * QUOTE_PRECISION_I64,
+                quote_entry_amount: 180 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: 180 * QUOTE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: deposit,
+            ..User::default()
+        };
+        create_anchor_account_info!(c, &c_key, User, c_account_info);
+
+        let mut counterparties = UserMap::empty();
+        counterparties
+            .insert(b_key, AccountLoader::try_from(&b_account_info).unwrap())
+            .unwrap();
+        counterparties
+            .insert(c_key, AccountLoader::try_from(&c_account_info).unwrap())
+            .unwrap();
+
+        let state = State {
+            liquidation_margin_buffer_ratio: 10,
+            initial_pct_to_liquidate: LIQUIDATION_PCT_PRECISION as u16,
+            liquidation_duration: 150,
+            ..Default::default()
+        };
+
+        // neither b nor c can be deleveraged
+        let result = auto_deleverage_perp_position(
+            0,
+            &mut user,
+            &user_key,
+            &counterparties,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+            slot,
+            &state,
+            0,
+        );
+        assert_eq!(result, Err(ErrorCode::InvalidAutoDeleverage));
+        assert_eq!(user.status, 0);
+
+        counterparties
+            .insert(a_key, AccountLoader::try_from(&a_account_info).unwrap())
+            .unwrap();
+
+        auto_deleverage_perp_position(
+            0,
+            &mut user,
+            &user_key,
+            &counterparties,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+            slot,
+            &state,
+            0,
+        )
+        .unwrap();
+
+        // deficit is the market's 500 loss, not the 550 total. a gives up 250 of its 500 pnl on half
+        // the position, the rest of the deficit is left for resolve_perp_bankruptcy
+        assert_eq!(
+            user.perp_positions[0].base_asset_amount,
+            5 * BASE_PRECISION_I64
+        );
+        assert_eq!(
+            user.perp_positions[0].quote_asset_amount,
+            -750 * QUOTE_PRECISION_I64
+        );
+        assert_eq!(
+            user.perp_positions[1].base_asset_amount,
+            -BASE_PRECISION_I64
+        );
+        assert_eq!(user.status, UserStatus::BeingLiquidated as u8);
+
+        let a = counterparties.get_ref(&a_key).unwrap();
+        assert_eq!(a.perp_positions[0].base_asset_amount, 0);
+        assert_eq!(
+            a.perp_positions[0].quote_asset_amount,
+            250 * QUOTE_PRECISION_I64
+        );
+        assert_eq!(a.orders[0].status, OrderStatus::Canceled);
+        assert_eq!(a.perp_positions[0].open_orders, 0);
+        assert_eq!(a.perp_positions[0].open_bids, 0);
+        assert_eq!(a.open_orders, 0);
+        drop(a);
+
+        let b = counterparties.get_ref(&b_key).unwrap();
+        assert_eq!(
+            b.perp_positions[0].base_asset_amount,
+            -5 * BASE_PRECISION_I64
+        );
+        assert_eq!(
+            b.perp_positions[0].quote_asset_amount,
+            600 * QUOTE_PRECISION_I64
+        );
+        drop(b);
+
+        let c = counterparties.get_ref(&c_key).unwrap();
+        assert_eq!(
+            c.perp_positions[0].base_asset_amount,
+            -2 * BASE_PRECISION_I64
+        );
+        assert_eq!(
+            c.perp_positions[0].quote_asset_amount,
+            180 * QUOTE_PRECISION_I64
+        );
+        drop(c);
+
+        let market = perp_market_map.get_ref(&0).unwrap();
+        assert_eq!(market.amm.base_asset_amount_long, 5 * BASE_PRECISION_I128);
+        assert_eq!(market.amm.base_asset_amount_short, -7 * BASE_PRECISION_I128);
+    }
+}
@@ -5,6 +5,7 @@ use std::u64;
 
 use crate::msg;
 use crate::state::high_leverage_mode_config::HighLeverageModeConfig;
+use crate::state::referral_campaign::ReferralCampaign;
 use crate::state::revenue_share::{
     RevenueShareEscrowZeroCopyMut, RevenueShareOrder, RevenueShareOrderBitFlag,
 };
@@ -54,7 +55,11 @@ use crate::math::spot_balance::{get_signed_token_amount, get_token_amount};
 use crate::math::spot_swap::select_margin_type_for_swap;
 use crate::math::{amm, fees, margin::*, orders::*};
 use crate::print_error;
-use crate::state::events::{emit_stack, get_order_action_record, OrderActionRecord, OrderRecord};
+use crate::state::delegate_permissions::DelegatePermissions;
+use crate::state::events::{
+    emit_stack, get_order_action_record, OrderActionRecord, OrderRecord,
+    ReferralCampaignBudgetDepletedRecord,
+};
 use crate::state::events::{OrderAction, OrderActionExplanation};
 use crate::state::fill_mode::FillMode;
 use crate::state::fulfillment::{PerpFulfillmentMethod, SpotFulfillmentMethod};
@@ -62,7 +67,7 @@ use crate::state::margin_calculation::{MarginCalculation, MarginContext, MarginT
 use crate::state::oracle::{OraclePriceData, StrictOraclePrice};
 use crate::state::oracle_map::OracleMap;
 use crate::state::order_params::{
-    ModifyOrderParams, OrderParams, PlaceOrderOptions, PostOnlyParam,
+    ModifyOrderParams, OrderParams, OrderParamsBitFlag, PlaceOrderOptions, PostOnlyParam,
 };
 use crate::state::paused_operations::{PerpOperation, SpotOperation};
 use crate::state::perp_market::{MarketStatus, PerpMarket};
@@ -75,7 +80,8 @@ use crate::state::state::FeeStructure;
 use crate::state::state::*;
 use crate::state::traits::Size;
 use crate::state::user::{
-    AssetType, Order, OrderBitFlag, OrderStatus, OrderTriggerCondition, OrderType, UserStats,
+    AssetType, Order, OrderBitFlag, OrderStatus, OrderTriggerCondition, OrderTriggerSource,
+    OrderType, UserStats,
 };
 use crate::state::user::{MarketType, User};
 use crate::state::user_map::{UserMap, UserStatsMap};
@@ -93,6 +99,7 @@ mod amm_jit_tests;
 #[cfg(test)]
 mod fuel_tests;
 
+/// Returns the id of the placed order, or None if the order was skipped
 pub fn place_perp_order(
     state: &State,
     user: &mut User,
@@ -105,7 +112,7 @@ pub fn place_perp_order(
     mut params: OrderParams,
     mut options: PlaceOrderOptions,
     rev_share_order: &mut Option<&mut RevenueShareOrder>,
-) -> DriftResult {
+) -> DriftResult<Option<u32>> {
     let now = clock.unix_timestamp;
     let slot: u64 = clock.slot;
 
@@ -260,24 +267,33 @@ pub fn place_perp_order(
         state.min_perp_auction_duration,
     )?;
 
-    let max_ts = match params.max_ts {
-        Some(max_ts) => max_ts,
-        None => match params.order_type {
-            OrderType::Market | OrderType::Oracle => now.safe_add(
-                30_i64.max(
-                    (auction_duration.safe_div(2)?)
-                        .cast::<i64>()?
-                        .safe_add(10_i64)?,
-                ),
-            )?,
-            _ => 0_i64,
-        },
-    };
+    let (max_ts, max_slot) = if params.is_good_til_slot() {
+        match get_good_til_slot_max_slot(&params, slot)? {
+            Some(max_slot) => (0, max_slot),
+            None => return Ok(None),
+        }
+    } else {
+        let max_ts = match params.max_ts {
+            Some(max_ts) => max_ts,
+            None => match params.order_type {
+                OrderType::Market | OrderType::Oracle => now.safe_add(
+                    30_i64.max(
+                        (auction_duration.safe_div(2)?)
+                            .cast::<i64>()?
+                            .safe_add(10_i64)?,
+                    ),
+                )?,
+                _ => 0_i64,
+            },
+        };
 
-    if max_ts != 0 && max_ts < now {
-        msg!("max_ts ({}) < now ({}), skipping order", max_ts, now);
-        return Ok(());
-    }
+        if max_ts != 0 && max_ts < now {
+            msg!("max_ts ({}) < now ({}), skipping order", max_ts, now);
+            return Ok(None);
+        }
+
+        (max_ts, 0)
+    };
 
     validate!(
         params.market_type == MarketType::Perp,
@@ -308,7 +324,27 @@ pub fn place_perp_order(
         bit_flags = set_order_bit_flag(bit_flags, true, OrderBitFlag::IsIsolatedPosition);
     }
 
-    let new_order = Order {
+    bit_flags = set_order_bit_flag(
+        bit_flags,
+        params.order_type == OrderType::TrailingStop && params.is_trailing_stop_bps(),
+        OrderBitFlag::TrailingStopBps,
+    );
+
+    bit_flags = set_order_bit_flag(
+        bit_flags,
+        options.bracket_pending,
+        OrderBitFlag::BracketPending,
+    );
+
+    let trigger_source = params.trigger_source.unwrap_or_default();
+    let trigger_price = standardize_trigger_price(
+        params.trigger_price.unwrap_or(0),
+        trigger_source,
+        market.amm.order_tick_size,
+        params.direction,
+    )?;
+
+    let mut new_order = Order {
         status: OrderStatus::Open,
         order_type: params.order_type,
         market_type: params.market_type,
@@ -328,11 +364,7 @@ pub fn place_perp_order(
         quote_asset_amount_filled: 0,
         direction: params.direction,
         reduce_only,
-        trigger_price: standardize_price(
-            params.trigger_price.unwrap_or(0),
-            market.amm.order_tick_size,
-            params.direction,
-        )?,
+        trigger_price,
         trigger_condition: params.trigger_condition,
         post_only: params.post_only != PostOnlyParam::None,
         oracle_price_offset: params.oracle_price_offset.unwrap_or(0),
@@ -343,9 +375,56 @@ pub fn place_perp_order(
         max_ts,
         posted_slot_tail: get_posted_slot_from_clock_slot(slot),
         bit_flags,
-        padding: [0; 1],
+        order_group: options.order_group,
+        max_slot,
+        trigger_source,
+        ..Order::default()
     };
 
+    if new_order.is_trailing_stop() {
+        new_order.trailing_stop_distance = params.trailing_stop_distance.unwrap_or(0);
+        new_order.trigger_price = calculate_trailing_stop_trigger_price(
+            &new_order,
+            oracle_price_data.price.unsigned_abs(),
+            market.amm.order_tick_size,
+        )?;
+    }
+
+    if params.is_iceberg() {
+        new_order.iceberg_clip_size = get_iceberg_clip_size(&params, market.amm.order_step_size)?;
+    }
+
+    if new_order.is_twap() {
+        // max_ts is when the last slice is due
+        let slice_interval = params.twap_slice_interval.unwrap_or(0).cast::<i64>()?;
+        validate!(
+            slice_interval > 0 && max_ts > now,
+            ErrorCode::InvalidTwapOrder,
+            "twap order must have a slice interval ({}) and a max_ts ({}) after now ({})",
+            slice_interval,
+            max_ts,
+            now
+        )?;
+
+        let num_slices = max_ts
+            .safe_sub(now)?
+            .safe_div(slice_interval)?
+            .min(u16::MAX as i64);
+
+        new_order.max_ts = now.safe_add(num_slices.safe_mul(slice_interval)?)?;
+        new_order.trigger_price = 0;
+        new_order.set_twap_params(slice_interval.cast()?, num_slices.cast()?, 0);
+
+        update_twap_order_slices(
+            &mut new_order,
+            market,
+            oracle_price_data,
+            state.min_perp_auction_duration,
+            slot,
+            now,
+        )?;
+    }
+
     let valid_oracle_price = Some(oracle_price_data.price);
     match validate_order(&new_order, market, valid_oracle_price, slot) {
         Ok(()) => {}
@@ -353,7 +432,7 @@ pub fn place_perp_order(
             if params.post_only == PostOnlyParam::TryPostOnly =>
         {
             // just want place to succeeds without error if TryPostOnly
-            return Ok(());
+            return Ok(None);
         }
         Err(err) => return Err(err),
     };
@@ -377,6 +456,15 @@ pub fn place_perp_order(
 
     options.update_risk_increasing(risk_increasing);
 
+    if let Some(delegate_permissions) = &options.delegate_permissions {
+        delegate_permissions.validate_perp_order(
+            &new_order,
+            &user.perp_positions[position_index],
+            market,
+            oracle_price_data.price,
+        )?;
+    }
+
     // when orders are placed in bulk, only need to check margin on last place
     if options.enforce_margin_check && !options.is_liquidation() {
         // if isolated position, use the isolated margin calculation
@@ -469,7 +557,64 @@ pub fn place_perp_order(
 
     user.update_last_active_slot(slot);
 
-    Ok(())
+    Ok(Some(user.orders[new_order_index].order_id))
+}
+
+/// Good til slot orders pass the last slot they can be filled in as max_ts. Returns None if the
+/// slot has already passed
+fn get_good_til_slot_max_slot(params: &OrderParams, slot: u64) -> DriftResult<Option<u64>> {
+    let max_slot = params.max_ts.unwrap_or(0);
+
+    validate!(
+        max_slot > 0,
+        ErrorCode::InvalidOrderMaxTs,
+        "good til slot order must set max_ts to its last slot"
+    )?;
+
+    let max_slot = max_slot.cast::<u64>()?;
+    if max_slot < slot {
+        msg!("max slot ({}) < slot ({}), skipping order", max_slot, slot);
+        return Ok(None);
+    }
+
+    Ok(Some(max_slot))
+}
+
+fn get_iceberg_clip_size(params: &OrderParams, step_size: u64) -> DriftResult<u64> {
+    validate!(
+        params.order_type == OrderType::Limit,
+        ErrorCode::InvalidIcebergOrder,
+        "only limit orders can be icebergs"
+    )?;
+
+    let clip_size =
+        standardize_base_asset_amount(params.iceberg_clip_size.unwrap_or(0), step_size)?;
+
+    validate!(
+        clip_size > 0,
+        ErrorCode::InvalidIcebergOrder,
+        "iceberg clip size must be at least the step size ({})",
+        step_size
+    )?;
+
+    Ok(clip_size)
+}
+
+/// Oracle and mark twap thresholds are prices and snap to the tick size. Funding and divergence
+/// thresholds are rates and are kept as is
+fn standardize_trigger_price(
+    trigger_price: u64,
+    trigger_source: OrderTriggerSource,
+    tick_size: u64,
+    direction: PositionDirection,
+) -> DriftResult<u64> {
+    if trigger_source.is_price() {
+        standardize_price(trigger_price, tick_size, direction)
+    } else {
+        // checks the rate threshold fits in 32 bits
+        trigger_source.get_threshold(trigger_price)?;
+        Ok(trigger_price)
+    }
 }
 
 fn get_auction_params(
@@ -601,6 +746,17 @@ pub fn cancel_orders(
         )?;
     }
 
+    update_order_groups(
+        user,
+        user_key,
+        filler_key,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        now,
+        slot,
+    )?;
+
     user.update_last_active_slot(slot);
 
     Ok(canceled_order_ids)
@@ -639,6 +795,17 @@ pub fn cancel_order_by_order_id(
         false,
     )?;
 
+    update_order_groups(
+        user,
+        &user_key,
+        None,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        clock.unix_timestamp,
+        clock.slot,
+    )?;
+
     user.update_last_active_slot(clock.slot);
 
     Ok(())
@@ -681,6 +848,17 @@ pub fn cancel_order_by_user_order_id(
         false,
     )?;
 
+    update_order_groups(
+        user,
+        &user_key,
+        None,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        clock.unix_timestamp,
+        clock.slot,
+    )?;
+
     user.update_last_active_slot(clock.slot);
 
     Ok(())
@@ -770,8 +948,10 @@ pub fn cancel_order(
         // only decrease open/bids ask if it's not a trigger order or if it's been triggered
         let update_open_bids_and_asks = user.orders[order_index].update_open_bids_and_asks();
         if update_open_bids_and_asks {
-            let base_asset_amount_unfilled =
-                user.orders[order_index].get_base_asset_amount_unfilled(None)?;
+            // twap and iceberg orders count their whole size at placement, not just what's released
+            let base_asset_amount_unfilled = user.orders[order_index]
+                .base_asset_amount
+                .safe_sub(user.orders[order_index].base_asset_amount_filled)?;
             position::decrease_open_bids_and_asks(
                 &mut user.perp_positions[position_index],
                 &order_direction,
@@ -788,8 +968,10 @@ pub fn cancel_order(
         // only decrease open/bids ask if it's not a trigger order or if it's been triggered
         let update_open_bids_and_asks = user.orders[order_index].update_open_bids_and_asks();
         if update_open_bids_and_asks {
-            let base_asset_amount_unfilled =
-                user.orders[order_index].get_base_asset_amount_unfilled(None)?;
+            // twap and iceberg orders count their whole size at placement, not just what's released
+            let base_asset_amount_unfilled = user.orders[order_index]
+                .base_asset_amount
+                .safe_sub(user.orders[order_index].base_asset_amount_filled)?;
             decrease_spot_open_bids_and_asks(
                 &mut user.spot_positions[spot_position_index],
                 &order_direction,
@@ -818,6 +1000,7 @@ pub fn modify_order(
     spot_market_map: &SpotMarketMap,
     oracle_map: &mut OracleMap,
     clock: &Clock,
+    delegate_permissions: Option<DelegatePermissions>,
 ) -> DriftResult {
     let user_key = user_loader.key();
     let mut user = load_mut!(user_loader)?;
@@ -851,6 +1034,20 @@ pub fn modify_order(
 
     let existing_order = user.orders[order_index];
 
+    validate!(
+        !existing_order.is_twap(),
+        ErrorCode::InvalidTwapOrder,
+        "twap orders can't be modified, cancel and place a new one"
+    )?;
+
+    // keep the modified order in its one-cancels-other/bracket group
+    let options = PlaceOrderOptions {
+        order_group: existing_order.order_group,
+        bracket_pending: existing_order.is_bracket_pending(),
+        delegate_permissions,
+        ..PlaceOrderOptions::default()
+    };
+
     cancel_order(
         order_index,
         &mut user,
@@ -872,6 +1069,9 @@ pub fn modify_order(
         merge_modify_order_params_with_existing_order(&existing_order, &modify_order_params)?;
 
     if let Some(order_params) = order_params {
+        // the replacement order takes over the group
+        user.orders[order_index].order_group = 0;
+
         if order_params.market_type == MarketType::Perp {
             place_perp_order(
                 state,
@@ -883,7 +1083,7 @@ pub fn modify_order(
                 &None,
                 clock,
                 order_params,
-                PlaceOrderOptions::default(),
+                options,
                 &mut None,
             )?;
         } else {
@@ -896,9 +1096,20 @@ pub fn modify_order(
                 oracle_map,
                 clock,
                 order_params,
-                PlaceOrderOptions::default(),
+                options,
             )?;
         }
+    } else {
+        update_order_groups(
+            &mut user,
+            &user_key,
+            None,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            clock.unix_timestamp,
+            clock.slot,
+        )?;
     }
 
     Ok(())
@@ -940,11 +1151,25 @@ fn merge_modify_order_params_with_existing_order(
         } else {
             PostOnlyParam::None
         });
-    let bit_flags = 0;
-    let max_ts = modify_order_params.max_ts.or(Some(existing_order.max_ts));
+    let mut bit_flags = 0_u8;
+    if existing_order.is_bit_flag_set(OrderBitFlag::TrailingStopBps) {
+        bit_flags |= OrderParamsBitFlag::TrailingStopBps as u8;
+    }
+    // good til slot orders pass their slot back in max_ts
+    let existing_max_ts = match existing_order.get_max_slot() {
+        Some(max_slot) => {
+            bit_flags |= OrderParamsBitFlag::ExtendedTimeInForce as u8;
+            max_slot.cast::<i64>()?
+        }
+        None => existing_order.max_ts,
+    };
+    let max_ts = modify_order_params.max_ts.or(Some(existing_max_ts));
     let trigger_price = modify_order_params
         .trigger_price
         .or(Some(existing_order.trigger_price));
+    let trigger_source = modify_order_params
+        .trigger_source
+        .or(Some(existing_order.trigger_source));
     let trigger_condition =
         modify_order_params
             .trigger_condition
@@ -959,6 +1184,9 @@ fn merge_modify_order_params_with_existing_order(
     let oracle_price_offset = modify_order_params
         .oracle_price_offset
         .or(Some(existing_order.oracle_price_offset));
+    let trailing_stop_distance = modify_order_params
+        .trailing_stop_distance
+        .or(Some(existing_order.trailing_stop_distance));
     let (auction_duration, auction_start_price, auction_end_price) =
         if modify_order_params.auction_duration.is_some()
             && modify_order_params.auction_start_price.is_some()
@@ -991,6 +1219,12 @@ fn merge_modify_order_params_with_existing_order(
         auction_duration,
         auction_start_price,
         auction_end_price,
+        trailing_stop_distance,
+        twap_slice_interval: None,
+        iceberg_clip_size: existing_order
+            .is_iceberg()
+            .then_some(existing_order.iceberg_clip_size),
+        trigger_source,
     }))
 }
 
@@ -1011,6 +1245,7 @@ pub fn fill_perp_order(
     fill_mode: FillMode,
     rev_share_escrow: &mut Option<&mut RevenueShareEscrowZeroCopyMut>,
     builder_referral_feature_enabled: bool,
+    referral_campaign: &mut Option<&mut ReferralCampaign>,
 ) -> DriftResult<(u64, u64)> {
     let now = clock.unix_timestamp;
     let slot = clock.slot;
@@ -1020,6 +1255,16 @@ pub fn fill_perp_order(
     let user = &mut load_mut!(user)?;
     let user_stats = &mut load_mut!(user_stats)?;
 
+    if let Some(referral_campaign) = referral_campaign.as_deref() {
+        validate!(
+            referral_campaign.referrer == user_stats.referrer,
+            ErrorCode::InvalidReferralCampaign,
+            "referral campaign referrer {} is not the user's referrer {}",
+            referral_campaign.referrer,
+            user_stats.referrer
+        )?;
+    }
+
     let order_index = user
         .orders
         .iter()
@@ -1040,6 +1285,17 @@ pub fn fill_perp_order(
         "must be perp order"
     )?;
 
+    // liquidation auction orders are filled by any filler but settle like a liquidation
+    let is_liquidation_auction_fill = fill_mode == FillMode::Fill
+        && controller::liquidation::is_perp_liquidation_auction_order(user, order_index)?;
+    let fill_mode = if is_liquidation_auction_fill {
+        FillMode::Liquidation
+    } else {
+        fill_mode
+    };
+    let is_deleverage_fill = fill_mode == FillMode::Fill
+        && controller::liquidation::is_perp_deleverage_order(user, order_index);
+
     // settle lp position so its tradeable
     let mut market = perp_market_map.get_ref_mut(&market_index)?;
     settle_funding_payment(user, &user_key, &mut market, now)?;
@@ -1111,6 +1367,49 @@ pub fn fill_perp_order(
         )?;
 
         let oracle_price_data = oracle_map.get_price_data(&market.oracle_id())?;
+
+        if user.orders[order_index].is_twap()
+            && update_twap_order_slices(
+                &mut user.orders[order_index],
+                market,
+                oracle_price_data,
+                state.min_perp_auction_duration,
+                slot,
+                now,
+            )?
+        {
+            let order_action_record = get_order_action_record(
+                now,
+                OrderAction::ReleaseSlice,
+                OrderActionExplanation::None,
+                market_index,
+                Some(filler_key),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(user_key),
+                Some(user.orders[order_index]),
+                None,
+                None,
+                oracle_price_data.price,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )?;
+            emit!(order_action_record);
+        }
+
         let mm_oracle_price_data = market.get_mm_oracle_price_data(
             *oracle_price_data,
             slot,
@@ -1216,6 +1515,44 @@ pub fn fill_perp_order(
         None
     };
 
+    // referees can't skip the referrer's campaign to avoid its budget. Once the campaign has ended,
+    // the first fill that includes it clears the requirement
+    if let Some((referrer_authority_key, _)) = &referrer_info {
+        let mut referrer_stats = makers_and_referrer_stats.get_ref_mut(referrer_authority_key)?;
+        if referrer_stats.has_referral_campaign() {
+            match referral_campaign.as_deref() {
+                None => {
+                    msg!(
+                        "referrer {} referral campaign must be provided",
+                        referrer_authority_key
+                    );
+                    return Err(ErrorCode::InvalidReferralCampaign);
+                }
+                Some(referral_campaign) if referral_campaign.has_ended(now) => {
+                    referrer_stats.update_referral_campaign_status(false);
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    // the second level referrer of a campaign gets a share of the referrer reward
+    let second_level_referrer_info = match (&referrer_info, referral_campaign.as_deref()) {
+        (Some(_), Some(referral_campaign))
+            if referral_campaign.has_second_level_referrer()
+                && referral_campaign.second_level_referrer != user.authority =>
+        {
+            get_referrer_keys(
+                referral_campaign.second_level_referrer,
+                &user_key,
+                makers_and_referrer,
+                makers_and_referrer_stats,
+                slot,
+            )?
+        }
+        _ => None,
+    };
+
     let oracle_too_divergent_with_twap_5min = is_oracle_too_divergent_with_twap_5min(
         oracle_price,
         oracle_twap_5min,
@@ -1239,7 +1576,7 @@ pub fn fill_perp_order(
         }
     }
 
-    let should_expire_order = should_expire_order(user, order_index, now)?;
+    let should_expire_order = should_expire_order(user, order_index, now, slot)?;
 
     let position_index =
         get_position_index(&user.perp_positions, user.orders[order_index].market_index)?;
@@ -1289,6 +1626,9 @@ pub fn fill_perp_order(
         return Ok((0, 0));
     }
 
+    let existing_direction = user.perp_positions[position_index].get_direction();
+    let fill_record_id = perp_market_map.get_ref(&market_index)?.next_fill_record_id;
+
     let (base_asset_amount, quote_asset_amount) = fulfill_perp_order(
         user,
         order_index,
@@ -1314,6 +1654,8 @@ pub fn fill_perp_order(
         oracle_stale_for_margin,
         rev_share_escrow,
         builder_referral_feature_enabled,
+        second_level_referrer_info,
+        referral_campaign,
     )?;
 
     if base_asset_amount != 0 {
@@ -1393,10 +1735,60 @@ pub fn fill_perp_order(
         )?;
     }
 
+    if is_liquidation_auction_fill {
+        controller::liquidation::settle_perp_liquidation_auction_fill(
+            user,
+            &user_key,
+            &filler_key,
+            order_id,
+            market_index,
+            existing_direction,
+            base_asset_amount,
+            quote_asset_amount,
+            fill_record_id,
+            oracle_price,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            slot,
+            state,
+        )?;
+    }
+
+    if is_deleverage_fill {
+        controller::liquidation::settle_perp_deleverage_fill(
+            user,
+            &user_key,
+            &filler_key,
+            order_id,
+            market_index,
+            base_asset_amount,
+            oracle_price,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            slot,
+            state,
+        )?;
+    }
+
     if base_asset_amount == 0 {
         return Ok((base_asset_amount, quote_asset_amount));
     }
 
+    update_order_groups(
+        user,
+        &user_key,
+        Some(&filler_key),
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        now,
+        slot,
+    )?;
+
     {
         let market = perp_market_map.get_ref(&market_index)?;
 
@@ -1588,7 +1980,7 @@ fn get_maker_orders_info(
                 )?
             };
 
-            let should_expire_order = should_expire_order(&maker, maker_order_index, now)?;
+            let should_expire_order = should_expire_order(&maker, maker_order_index, now, slot)?;
 
             let existing_base_asset_amount = maker
                 .get_perp_position(maker.orders[maker_order_index].market_index)?
@@ -1703,14 +2095,31 @@ fn get_referrer_info(
         return Ok(None);
     }
 
+    get_referrer_keys(
+        user_stats.referrer,
+        user_key,
+        makers_and_referrer,
+        makers_and_referrer_stats,
+        slot,
+    )
+}
+
+/// Finds the referrer's stats and sub account 0 in the maps, both must be provided. A referrer whose
+/// account still has the legacy order layout is skipped
+fn get_referrer_keys(
+    referrer_authority_key: Pubkey,
+    user_key: &Pubkey,
+    makers_and_referrer: &UserMap,
+    makers_and_referrer_stats: &UserStatsMap,
+    slot: u64,
+) -> DriftResult<Option<(Pubkey, Pubkey)>> {
     validate!(
         makers_and_referrer_stats
             .0
-            .contains_key(&user_stats.referrer),
+            .contains_key(&referrer_authority_key),
         ErrorCode::ReferrerStatsNotFound
     )?;
 
-    let referrer_authority_key = user_stats.referrer;
     let mut referrer_user_key = Pubkey::default();
     for (referrer_key, referrer) in makers_and_referrer.0.iter() {
         // if user is in makers and referrer map, skip to avoid invalid borrow
@@ -1735,6 +2144,15 @@ fn get_referrer_info(
     }
 
     if referrer_user_key == Pubkey::default() {
+        // referrer can't be paid until their legacy order layout account is migrated
+        if makers_and_referrer.has_legacy_user_for_authority(&referrer_authority_key) {
+            msg!(
+                "Skipping referrer {} with legacy order layout",
+                referrer_authority_key
+            );
+            return Ok(None);
+        }
+
         return Err(ErrorCode::ReferrerNotFound);
     }
 
@@ -1797,6 +2215,8 @@ fn fulfill_perp_order(
     oracle_stale_for_margin: bool,
     rev_share_escrow: &mut Option<&mut RevenueShareEscrowZeroCopyMut>,
     builder_referral_feature_enabled: bool,
+    second_level_referrer_info: Option<(Pubkey, Pubkey)>,
+    referral_campaign: &mut Option<&mut ReferralCampaign>,
 ) -> DriftResult<(u64, u64)> {
     let market_index = user.orders[user_order_index].market_index;
 
@@ -1829,6 +2249,19 @@ fn fulfill_perp_order(
 
     if fulfillment_methods.is_empty() {
         msg!("no fulfillment methods found");
+        enforce_time_in_force(
+            user,
+            user_order_index,
+            user_key,
+            filler_key,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            fill_mode,
+            0,
+            now,
+            slot,
+        )?;
         return Ok((0, 0));
     }
 
@@ -1851,6 +2284,12 @@ fn fulfill_perp_order(
                     makers_and_referrer_stats,
                     None,
                 )?;
+                let (mut second_level_referrer, mut second_level_referrer_stats) = get_referrer(
+                    &second_level_referrer_info,
+                    makers_and_referrer,
+                    makers_and_referrer_stats,
+                    None,
+                )?;
 
                 // maker may try to fill their own order (e.g. via jit)
                 // if amm takes fill, give maker filler reward
@@ -1893,6 +2332,9 @@ fn fulfill_perp_order(
                         fill_mode.is_liquidation(),
                         rev_share_escrow,
                         builder_referral_feature_enabled,
+                        &mut second_level_referrer.as_deref_mut(),
+                        &mut second_level_referrer_stats.as_deref_mut(),
+                        referral_campaign,
                     )?;
 
                 (fill_base_asset_amount, fill_quote_asset_amount)
@@ -1913,6 +2355,12 @@ fn fulfill_perp_order(
                     makers_and_referrer_stats,
                     Some(&maker),
                 )?;
+                let (mut second_level_referrer, mut second_level_referrer_stats) = get_referrer(
+                    &second_level_referrer_info,
+                    makers_and_referrer,
+                    makers_and_referrer_stats,
+                    Some(&maker),
+                )?;
 
                 let (fill_base_asset_amount, fill_quote_asset_amount, maker_fill_base_asset_amount) =
                     fulfill_perp_order_with_match(
@@ -1941,6 +2389,9 @@ fn fulfill_perp_order(
                         fill_mode.is_liquidation(),
                         rev_share_escrow,
                         builder_referral_feature_enabled,
+                        &mut second_level_referrer.as_deref_mut(),
+                        &mut second_level_referrer_stats.as_deref_mut(),
+                        referral_campaign,
                     )?;
 
                 if maker_fill_base_asset_amount != 0 {
@@ -1982,13 +2433,28 @@ fn fulfill_perp_order(
         base_asset_amount
     )?;
 
-    if !fill_mode.is_liquidation() {
-        // if the maker is long, the user sold so
-        let taker_base_asset_amount_delta = if maker_direction == PositionDirection::Long {
-            base_asset_amount as i64
-        } else {
-            -(base_asset_amount as i64)
-        };
+    enforce_time_in_force(
+        user,
+        user_order_index,
+        user_key,
+        filler_key,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        fill_mode,
+        base_asset_amount,
+        now,
+        slot,
+    )?;
+
+    // pair legs are margin checked once after both legs fill
+    if !fill_mode.is_liquidation() && !fill_mode.is_pair_leg() {
+        // if the maker is long, the user sold so
+        let taker_base_asset_amount_delta = if maker_direction == PositionDirection::Long {
+            base_asset_amount as i64
+        } else {
+            -(base_asset_amount as i64)
+        };
 
         let margin_requirement_type = if user_order_position_decreasing {
             MarginRequirementType::Maintenance
@@ -2063,6 +2529,17 @@ fn fulfill_perp_order(
     for (maker_key, (maker_base_asset_amount_filled, maker_is_isolated_position)) in maker_fills {
         let mut maker = makers_and_referrer.get_ref_mut(&maker_key)?;
 
+        update_order_groups(
+            &mut maker,
+            &maker_key,
+            Some(filler_key),
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            slot,
+        )?;
+
         let maker_stats = if maker.authority == user.authority {
             None
         } else {
@@ -2162,6 +2639,54 @@ fn fulfill_perp_order(
     Ok((base_asset_amount, quote_asset_amount))
 }
 
+/// Fill or kill takers roll back the whole fill unless it completely filled the order across the amm and
+/// makers. Immediate or cancel takers cancel whatever couldn't be filled
+fn enforce_time_in_force(
+    user: &mut User,
+    user_order_index: usize,
+    user_key: &Pubkey,
+    filler_key: &Pubkey,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    fill_mode: FillMode,
+    base_asset_amount_filled: u64,
+    now: i64,
+    slot: u64,
+) -> DriftResult {
+    let order_status = user.orders[user_order_index].status;
+
+    if fill_mode.is_fill_or_kill() {
+        validate!(
+            order_status == OrderStatus::Filled,
+            ErrorCode::FillOrKillOrderNotFilled,
+            "fill or kill order {} only filled {} of {}",
+            user.orders[user_order_index].order_id,
+            base_asset_amount_filled,
+            user.orders[user_order_index].base_asset_amount
+        )?;
+    }
+
+    if fill_mode.is_ioc() && order_status == OrderStatus::Open {
+        cancel_order(
+            user_order_index,
+            user,
+            user_key,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            slot,
+            OrderActionExplanation::None,
+            Some(filler_key),
+            0,
+            false,
+        )?;
+    }
+
+    Ok(())
+}
+
 #[allow(clippy::type_complexity)]
 fn get_referrer<'a>(
     referrer_info: &'a Option<(Pubkey, Pubkey)>,
@@ -2248,6 +2773,9 @@ pub fn fulfill_perp_order_with_amm(
     is_liquidation: bool,
     rev_share_escrow: &mut Option<&mut RevenueShareEscrowZeroCopyMut>,
     builder_referral_feature_enabled: bool,
+    second_level_referrer: &mut Option<&mut User>,
+    second_level_referrer_stats: &mut Option<&mut UserStats>,
+    referral_campaign: &mut Option<&mut ReferralCampaign>,
 ) -> DriftResult<(u64, u64)> {
     let position_index = get_position_index(&user.perp_positions, market.market_index)?;
     let existing_base_asset_amount = user.perp_positions[position_index].base_asset_amount;
@@ -2363,6 +2891,8 @@ pub fn fulfill_perp_order_with_amm(
         rev_share_escrow,
         builder_referral_feature_enabled,
     );
+    let use_referral_campaign =
+        reward_referrer && is_referral_campaign_active(referral_campaign, now);
     let reward_filler = can_reward_user_with_perp_pnl(filler, market.market_index)
         || can_reward_user_with_perp_pnl(maker, market.market_index);
 
@@ -2375,12 +2905,15 @@ pub fn fulfill_perp_order_with_amm(
             builder_referral_feature_enabled,
         );
 
+    let fee_override = market.get_fee_override();
+
     let FillFees {
         user_fee,
         fee_to_market,
         filler_reward,
         referee_discount,
         referrer_reward,
+        second_level_referrer_reward,
         fee_to_market_for_lp: _fee_to_market_for_lp,
         maker_rebate,
         builder_fee: builder_fee_option,
@@ -2398,14 +2931,44 @@ pub fn fulfill_perp_order_with_amm(
         market.fee_adjustment,
         user.is_high_leverage_mode(MarginRequirementType::Initial),
         builder_order_fee_bps,
+        referral_campaign
+            .as_deref()
+            .filter(|_| use_referral_campaign),
+        fee_override,
     )?;
 
+    if order_post_only {
+        market.update_maker_rebate_override_volume(&fee_override, quote_asset_amount)?;
+    }
+
+    let referrer_reward = if use_referral_campaign {
+        update_referral_campaign_budget(
+            referral_campaign,
+            referee_discount
+                .safe_add(referrer_reward)?
+                .safe_add(second_level_referrer_reward)?,
+            now,
+        )?;
+
+        pay_second_level_referrer_reward(
+            second_level_referrer,
+            second_level_referrer_stats,
+            market,
+            referrer_reward,
+            second_level_referrer_reward,
+            now,
+        )?
+    } else {
+        referrer_reward
+    };
+
     let builder_fee = builder_fee_option.unwrap_or(0);
 
     if builder_fee != 0 {
         if let (Some(idx), Some(escrow)) = (builder_order_idx, rev_share_escrow.as_mut()) {
             let order = escrow.get_order_mut(idx)?;
             order.fees_accrued = order.fees_accrued.safe_add(builder_fee)?;
+            order.volume_accrued = order.volume_accrued.safe_add(quote_asset_amount)?;
         } else {
             validate!(
                 false,
@@ -2669,6 +3232,9 @@ pub fn fulfill_perp_order_with_match(
     is_liquidation: bool,
     rev_share_escrow: &mut Option<&mut RevenueShareEscrowZeroCopyMut>,
     builder_referral_feature_enabled: bool,
+    second_level_referrer: &mut Option<&mut User>,
+    second_level_referrer_stats: &mut Option<&mut UserStats>,
+    referral_campaign: &mut Option<&mut ReferralCampaign>,
 ) -> DriftResult<(u64, u64, u64)> {
     if !are_orders_same_market_but_different_sides(
         &maker.orders[maker_order_index],
@@ -2693,7 +3259,7 @@ pub fn fulfill_perp_order_with_match(
             &taker_direction,
             amm_available_liquidity,
             oracle_price,
-            taker.orders[taker_order_index].seconds_til_expiry(now),
+            taker.orders[taker_order_index].seconds_til_expiry(now, slot),
         )?
     };
 
@@ -2787,6 +3353,9 @@ pub fn fulfill_perp_order_with_match(
                 is_liquidation,
                 rev_share_escrow,
                 builder_referral_feature_enabled,
+                &mut None,
+                &mut None,
+                &mut None,
             )?;
 
         total_base_asset_amount = base_asset_amount_filled_by_amm;
@@ -2885,6 +3454,8 @@ pub fn fulfill_perp_order_with_match(
         rev_share_escrow,
         builder_referral_feature_enabled,
     );
+    let use_referral_campaign =
+        reward_referrer && is_referral_campaign_active(referral_campaign, now);
     let reward_filler = can_reward_user_with_perp_pnl(filler, market.market_index);
 
     let (builder_order_idx, referrer_builder_order_idx, builder_order_fee_bps, builder_idx) =
@@ -2902,12 +3473,15 @@ pub fn fulfill_perp_order_with_match(
         0
     };
 
+    let fee_override = market.get_fee_override();
+
     let FillFees {
         user_fee: taker_fee,
         maker_rebate,
         fee_to_market,
         filler_reward,
         referrer_reward,
+        second_level_referrer_reward,
         referee_discount,
         builder_fee: builder_fee_option,
         ..
@@ -2925,13 +3499,42 @@ pub fn fulfill_perp_order_with_match(
         market.fee_adjustment,
         taker.is_high_leverage_mode(MarginRequirementType::Initial),
         builder_order_fee_bps,
+        referral_campaign
+            .as_deref()
+            .filter(|_| use_referral_campaign),
+        fee_override,
     )?;
+
+    market.update_maker_rebate_override_volume(&fee_override, quote_asset_amount)?;
+
+    let referrer_reward = if use_referral_campaign {
+        update_referral_campaign_budget(
+            referral_campaign,
+            referee_discount
+                .safe_add(referrer_reward)?
+                .safe_add(second_level_referrer_reward)?,
+            now,
+        )?;
+
+        pay_second_level_referrer_reward(
+            second_level_referrer,
+            second_level_referrer_stats,
+            market,
+            referrer_reward,
+            second_level_referrer_reward,
+            now,
+        )?
+    } else {
+        referrer_reward
+    };
+
     let builder_fee = builder_fee_option.unwrap_or(0);
 
     if builder_fee != 0 {
         if let (Some(idx), Some(escrow)) = (builder_order_idx, rev_share_escrow.as_deref_mut()) {
             let order = escrow.get_order_mut(idx)?;
             order.fees_accrued = order.fees_accrued.safe_add(builder_fee)?;
+            order.volume_accrued = order.volume_accrued.safe_add(quote_asset_amount)?;
         } else {
             validate!(
                 false,
@@ -3165,6 +3768,73 @@ fn get_taker_and_maker_for_order_record(
     }
 }
 
+/// Cancels the reduce only orders in a perp market that would now increase the position, for
+/// positions reduced outside of the user's own fills
+pub fn cancel_reduce_only_orders_increasing_position(
+    user: &mut User,
+    user_key: &Pubkey,
+    filler_key: Option<&Pubkey>,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    now: i64,
+    slot: u64,
+    perp_market_index: u16,
+) -> DriftResult {
+    let position_index = get_position_index(&user.perp_positions, perp_market_index)?;
+    let base_asset_amount = user.perp_positions[position_index].base_asset_amount;
+    let order_step_size = perp_market_map
+        .get_ref(&perp_market_index)?
+        .amm
+        .order_step_size;
+
+    for order_index in 0..user.orders.len() {
+        let order = &user.orders[order_index];
+        if order.market_type != MarketType::Perp
+            || order.market_index != perp_market_index
+            || (order.must_be_triggered() && !order.triggered())
+        {
+            continue;
+        }
+
+        if should_cancel_reduce_only_order(order, base_asset_amount, order_step_size)? {
+            cancel_order(
+                order_index,
+                user,
+                user_key,
+                perp_market_map,
+                spot_market_map,
+                oracle_map,
+                now,
+                slot,
+                OrderActionExplanation::ReduceOnlyOrderIncreasedPosition,
+                filler_key,
+                0,
+                false,
+            )?;
+        }
+    }
+
+    if base_asset_amount == 0
+        && user.perp_positions[position_index].open_asks == 0
+        && user.perp_positions[position_index].open_bids == 0
+    {
+        cancel_reduce_only_trigger_orders(
+            user,
+            user_key,
+            filler_key,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            slot,
+            perp_market_index,
+        )?;
+    }
+
+    Ok(())
+}
+
 fn cancel_reduce_only_trigger_orders(
     user: &mut User,
     user_key: &Pubkey,
@@ -3216,6 +3886,91 @@ fn cancel_reduce_only_trigger_orders(
     Ok(())
 }
 
+/// Resolves one-cancels-other and bracket groups after orders are filled, triggered or canceled.
+/// A filled or triggered order cancels its open siblings. A bracket entry that fills releases its
+/// pending siblings, and one canceled before any fill cancels them.
+pub fn update_order_groups(
+    user: &mut User,
+    user_key: &Pubkey,
+    filler_key: Option<&Pubkey>,
+    perp_market_map: &PerpMarketMap,
+    spot_market_map: &SpotMarketMap,
+    oracle_map: &mut OracleMap,
+    now: i64,
+    slot: u64,
+) -> DriftResult {
+    for order_index in 0..user.orders.len() {
+        let order = user.orders[order_index];
+        if order.order_group == 0 || order.is_bracket_pending() {
+            continue;
+        }
+
+        let is_open = order.status == OrderStatus::Open;
+        let has_fill = order.base_asset_amount_filled > 0;
+
+        let is_bracket_entry = user.orders.iter().enumerate().any(|(i, sibling)| {
+            i != order_index
+                && sibling.order_group == order.order_group
+                && sibling.status == OrderStatus::Open
+                && sibling.is_bracket_pending()
+        });
+
+        let cancel_siblings = if is_bracket_entry {
+            if is_open {
+                continue;
+            }
+
+            if has_fill {
+                for sibling in user.orders.iter_mut() {
+                    if sibling.order_group == order.order_group && sibling.is_bracket_pending() {
+                        sibling.remove_bit_flag(OrderBitFlag::BracketPending);
+                    }
+                }
+            }
+
+            !has_fill
+        } else {
+            if is_open && !has_fill && !order.triggered() {
+                continue;
+            }
+
+            has_fill || order.triggered()
+        };
+
+        if cancel_siblings {
+            for sibling_index in 0..user.orders.len() {
+                if sibling_index == order_index
+                    || user.orders[sibling_index].order_group != order.order_group
+                    || user.orders[sibling_index].status != OrderStatus::Open
+                {
+                    continue;
+                }
+
+                cancel_order(
+                    sibling_index,
+                    user,
+                    user_key,
+                    perp_market_map,
+                    spot_market_map,
+                    oracle_map,
+                    now,
+                    slot,
+                    OrderActionExplanation::OrderGroupSiblingFilled,
+                    filler_key,
+                    0,
+                    false,
+                )?;
+
+                user.orders[sibling_index].order_group = 0;
+            }
+        }
+
+        user.orders[order_index].order_group = 0;
+    }
+
+    Ok(())
+}
+
 pub fn trigger_order(
     order_id: u32,
     state: &State,
@@ -3259,6 +4014,12 @@ pub fn trigger_order(
         return Ok(());
     }
 
+    validate!(
+        !user.orders[order_index].is_bracket_pending(),
+        ErrorCode::BracketOrderPending,
+        "Order is waiting on its bracket entry order to fill"
+    )?;
+
     validate!(
         market_type == MarketType::Perp,
         ErrorCode::InvalidOrderMarketType,
@@ -3317,14 +4078,43 @@ pub fn trigger_order(
 
     let trigger_price =
         perp_market.get_trigger_price(oracle_price, now, state.use_median_trigger_price())?;
-    let can_trigger = order_satisfies_trigger_condition(&user.orders[order_index], trigger_price)?;
+
+    let trailing_stop_updated = update_trailing_stop_trigger_price(
+        &mut user.orders[order_index],
+        trigger_price,
+        perp_market.amm.order_tick_size,
+    )?;
+
+    let trigger_value = calculate_perp_trigger_value(
+        user.orders[order_index].trigger_source,
+        &perp_market,
+        trigger_price,
+    )?;
+
+    let can_trigger = order_satisfies_trigger_condition(&user.orders[order_index], trigger_value)?;
+
+    if !can_trigger && trailing_stop_updated {
+        emit_trailing_stop_update_record(
+            now,
+            market_index,
+            &filler_key,
+            &user_key,
+            &user.orders[order_index],
+            oracle_price,
+        )?;
+
+        user.update_last_active_slot(slot);
+
+        return Ok(());
+    }
 
     validate!(
         can_trigger,
         ErrorCode::OrderDidNotSatisfyTriggerCondition,
-        "Order did not satisfy trigger condition. trigger_price: {} oracle_price: {} trigger_condition: {:?}",
-        trigger_price,
-        &user.orders[order_index].trigger_price,
+        "Order did not satisfy trigger condition. trigger_value: {} trigger_threshold: {} trigger_source: {:?} trigger_condition: {:?}",
+        trigger_value,
+        user.orders[order_index].get_trigger_threshold()?,
+        user.orders[order_index].trigger_source,
         &user.orders[order_index].trigger_condition
     )?;
 
@@ -3412,36 +4202,204 @@ pub fn trigger_order(
         .get_perp_position(market_index)?
         .worst_case_liability_value(oracle_price, perp_market.contract_type)?;
 
-    let is_risk_increasing = worst_case_liability_value_after > worst_case_liability_value_before;
+    let is_risk_increasing = worst_case_liability_value_after > worst_case_liability_value_before;
+
+    drop(perp_market);
+
+    // If order increases risk and user is below initial margin, cancel it
+    if is_risk_increasing && !user.orders[order_index].reduce_only {
+        let meets_initial_margin_requirement =
+            meets_initial_margin_requirement(user, perp_market_map, spot_market_map, oracle_map)?;
+
+        if !meets_initial_margin_requirement {
+            cancel_order(
+                order_index,
+                user,
+                &user_key,
+                perp_market_map,
+                spot_market_map,
+                oracle_map,
+                now,
+                slot,
+                OrderActionExplanation::InsufficientFreeCollateral,
+                Some(&filler_key),
+                0,
+                false,
+            )?;
+        }
+    }
+
+    update_order_groups(
+        user,
+        &user_key,
+        Some(&filler_key),
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        now,
+        slot,
+    )?;
+
+    user.update_last_active_slot(slot);
+
+    Ok(())
+}
+
+/// Ratchets the trigger price of an untriggered trailing stop. Returns true if the trigger price moved
+fn update_trailing_stop_trigger_price(
+    order: &mut Order,
+    price: u64,
+    tick_size: u64,
+) -> DriftResult<bool> {
+    if !order.is_trailing_stop() || order.triggered() {
+        return Ok(false);
+    }
+
+    let new_trigger_price = calculate_trailing_stop_trigger_price(order, price, tick_size)?;
+    if new_trigger_price == order.trigger_price {
+        return Ok(false);
+    }
+
+    msg!(
+        "trailing stop trigger price {} -> {}",
+        order.trigger_price,
+        new_trigger_price
+    );
+
+    order.trigger_price = new_trigger_price;
+
+    Ok(true)
+}
+
+fn emit_trailing_stop_update_record(
+    now: i64,
+    market_index: u16,
+    filler_key: &Pubkey,
+    user_key: &Pubkey,
+    order: &Order,
+    oracle_price: i64,
+) -> DriftResult {
+    let order_action_record = get_order_action_record(
+        now,
+        OrderAction::UpdateTrigger,
+        OrderActionExplanation::None,
+        market_index,
+        Some(*filler_key),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(*user_key),
+        Some(*order),
+        None,
+        None,
+        oracle_price,
+        0,
+        None,
+        None,
+        None,
+        None,
+        Some(order.trigger_price),
+        None,
+        None,
+    )?;
+    emit!(order_action_record);
+
+    Ok(())
+}
+
+/// Links a replaced order to the order placed in its slot
+pub fn emit_replace_order_record(
+    now: i64,
+    user_key: &Pubkey,
+    old_order: &Order,
+    new_order: &Order,
+    oracle_price: i64,
+) -> DriftResult {
+    let mut order_action_record = get_order_action_record(
+        now,
+        OrderAction::Replace,
+        OrderActionExplanation::OrderReplaced,
+        new_order.market_index,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(*user_key),
+        Some(*new_order),
+        None,
+        None,
+        oracle_price,
+        0,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )?;
+    order_action_record.replaced_order_id = Some(old_order.order_id);
+    emit_stack::<_, { OrderActionRecord::SIZE }>(order_action_record)?;
+
+    Ok(())
+}
 
-    drop(perp_market);
+/// Releases the twap slices that have come due and restarts the order's auction for the newly
+/// released size. Returns true if a slice was released
+fn update_twap_order_slices(
+    order: &mut Order,
+    perp_market: &PerpMarket,
+    oracle_price_data: &OraclePriceData,
+    min_auction_duration: u8,
+    slot: u64,
+    now: i64,
+) -> DriftResult<bool> {
+    let slices_due = calculate_twap_slices_due(order, now)?;
+    if slices_due <= order.get_twap_slices_released() {
+        return Ok(false);
+    }
 
-    // If order increases risk and user is below initial margin, cancel it
-    if is_risk_increasing && !user.orders[order_index].reduce_only {
-        let meets_initial_margin_requirement =
-            meets_initial_margin_requirement(user, perp_market_map, spot_market_map, oracle_map)?;
+    order.set_twap_params(
+        order.get_twap_slice_interval(),
+        order.get_twap_num_slices(),
+        slices_due,
+    );
+    order.slot = slot;
 
-        if !meets_initial_margin_requirement {
-            cancel_order(
-                order_index,
-                user,
-                &user_key,
-                perp_market_map,
-                spot_market_map,
-                oracle_map,
-                now,
-                slot,
-                OrderActionExplanation::InsufficientFreeCollateral,
-                Some(&filler_key),
-                0,
-                false,
-            )?;
-        }
-    }
+    let (auction_duration, auction_start_price, auction_end_price) =
+        calculate_auction_params_for_trigger_order(
+            order,
+            oracle_price_data,
+            min_auction_duration,
+            Some(perp_market),
+        )?;
 
-    user.update_last_active_slot(slot);
+    msg!(
+        "twap released {}/{} slices. new auction duration {} start price {} end price {}",
+        slices_due,
+        order.get_twap_num_slices(),
+        auction_duration,
+        auction_start_price,
+        auction_end_price
+    );
 
-    Ok(())
+    order.auction_duration = auction_duration;
+    order.auction_start_price = auction_start_price;
+    order.auction_end_price = auction_end_price;
+
+    Ok(true)
 }
 
 fn update_trigger_order_params(
@@ -3484,7 +4442,10 @@ fn update_trigger_order_params(
     order.auction_start_price = auction_start_price;
     order.auction_end_price = auction_end_price;
 
-    if matches!(order.order_type, OrderType::TriggerMarket) {
+    if matches!(
+        order.order_type,
+        OrderType::TriggerMarket | OrderType::TrailingStop
+    ) {
         order.add_bit_flag(OrderBitFlag::OracleTriggerMarket);
     }
 
@@ -3647,6 +4608,72 @@ pub fn can_reward_user_with_referral_reward(
     }
 }
 
+fn is_referral_campaign_active(
+    referral_campaign: &Option<&mut ReferralCampaign>,
+    now: i64,
+) -> bool {
+    referral_campaign
+        .as_deref()
+        .map_or(false, |referral_campaign| referral_campaign.is_active(now))
+}
+
+fn update_referral_campaign_budget(
+    referral_campaign: &mut Option<&mut ReferralCampaign>,
+    amount: u64,
+    now: i64,
+) -> DriftResult {
+    if let Some(referral_campaign) = referral_campaign {
+        if referral_campaign.increment_budget_used(amount)? {
+            emit_stack::<_, { ReferralCampaignBudgetDepletedRecord::SIZE }>(
+                ReferralCampaignBudgetDepletedRecord {
+                    ts: now,
+                    referrer: referral_campaign.referrer,
+                    budget: referral_campaign.budget,
+                    budget_used: referral_campaign.budget_used,
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Credits the second level referrer's share of a campaign's referrer reward and returns what's left
+/// for the referrer. The share goes back to the referrer if the second level referrer can't be credited
+fn pay_second_level_referrer_reward(
+    second_level_referrer: &mut Option<&mut User>,
+    second_level_referrer_stats: &mut Option<&mut UserStats>,
+    market: &mut PerpMarket,
+    referrer_reward: u64,
+    second_level_referrer_reward: u64,
+    now: i64,
+) -> DriftResult<u64> {
+    if second_level_referrer_reward == 0 {
+        return Ok(referrer_reward);
+    }
+
+    if let (Some(second_level_referrer), Some(second_level_referrer_stats)) = (
+        second_level_referrer.as_mut(),
+        second_level_referrer_stats.as_mut(),
+    ) {
+        if let Ok(second_level_referrer_position) =
+            second_level_referrer.force_get_perp_position_mut(market.market_index)
+        {
+            update_quote_asset_amount(
+                second_level_referrer_position,
+                market,
+                second_level_referrer_reward.cast()?,
+            )?;
+            second_level_referrer_stats
+                .increment_total_referrer_reward(second_level_referrer_reward, now)?;
+
+            return Ok(referrer_reward);
+        }
+    }
+
+    referrer_reward.safe_add(second_level_referrer_reward)
+}
+
 pub fn pay_keeper_flat_reward_for_perps(
     user: &mut User,
     filler: Option<&mut User>,
@@ -3720,6 +4747,7 @@ pub fn pay_keeper_flat_reward_for_spot(
     Ok(filler_reward)
 }
 
+/// Returns the id of the placed order, or None if the order was skipped
 pub fn place_spot_order(
     state: &State,
     user: &mut User,
@@ -3730,7 +4758,7 @@ pub fn place_spot_order(
     clock: &Clock,
     params: OrderParams,
     mut options: PlaceOrderOptions,
-) -> DriftResult {
+) -> DriftResult<Option<u32>> {
     let now = clock.unix_timestamp;
     let slot = clock.slot;
 
@@ -3764,18 +4792,27 @@ pub fn place_spot_order(
         )?;
     }
 
-    let max_ts = match params.max_ts {
-        Some(max_ts) => max_ts,
-        None => match params.order_type {
-            OrderType::Market | OrderType::Oracle => now.safe_add(30)?,
-            _ => 0_i64,
-        },
-    };
+    let (max_ts, max_slot) = if params.is_good_til_slot() {
+        match get_good_til_slot_max_slot(&params, slot)? {
+            Some(max_slot) => (0, max_slot),
+            None => return Ok(None),
+        }
+    } else {
+        let max_ts = match params.max_ts {
+            Some(max_ts) => max_ts,
+            None => match params.order_type {
+                OrderType::Market | OrderType::Oracle => now.safe_add(30)?,
+                _ => 0_i64,
+            },
+        };
 
-    if max_ts != 0 && max_ts < now {
-        msg!("max_ts ({}) < now ({}), skipping order", max_ts, now);
-        return Ok(());
-    }
+        if max_ts != 0 && max_ts < now {
+            msg!("max_ts ({}) < now ({}), skipping order", max_ts, now);
+            return Ok(None);
+        }
+
+        (max_ts, 0)
+    };
 
     let new_order_index = user
         .orders
@@ -3889,6 +4926,11 @@ pub fn place_spot_order(
     )?;
 
     let mut bit_flags = 0;
+    bit_flags = set_order_bit_flag(
+        bit_flags,
+        options.is_signed_msg_order(),
+        OrderBitFlag::SignedMessage,
+    );
 
     let reduce_only = params.reduce_only || force_reduce_only;
     bit_flags = set_order_bit_flag(
@@ -3897,11 +4939,31 @@ pub fn place_spot_order(
         OrderBitFlag::NewTriggerReduceOnly,
     );
 
-    let new_order = Order {
+    bit_flags = set_order_bit_flag(
+        bit_flags,
+        params.order_type == OrderType::TrailingStop && params.is_trailing_stop_bps(),
+        OrderBitFlag::TrailingStopBps,
+    );
+
+    bit_flags = set_order_bit_flag(
+        bit_flags,
+        options.bracket_pending,
+        OrderBitFlag::BracketPending,
+    );
+
+    let trigger_source = params.trigger_source.unwrap_or_default();
+    let trigger_price = standardize_trigger_price(
+        params.trigger_price.unwrap_or(0),
+        trigger_source,
+        spot_market.order_tick_size,
+        params.direction,
+    )?;
+
+    let mut new_order = Order {
         status: OrderStatus::Open,
         order_type: params.order_type,
         market_type: params.market_type,
-        slot,
+        slot: options.get_order_slot(slot),
         order_id: get_then_update_id!(user, next_order_id),
         user_order_id: params.user_order_id,
         market_index: params.market_index,
@@ -3912,11 +4974,7 @@ pub fn place_spot_order(
         quote_asset_amount_filled: 0,
         direction: params.direction,
         reduce_only,
-        trigger_price: standardize_price(
-            params.trigger_price.unwrap_or(0),
-            spot_market.order_tick_size,
-            params.direction,
-        )?,
+        trigger_price,
         trigger_condition: params.trigger_condition,
         post_only: params.post_only != PostOnlyParam::None,
         oracle_price_offset: params.oracle_price_offset.unwrap_or(0),
@@ -3927,9 +4985,25 @@ pub fn place_spot_order(
         max_ts,
         posted_slot_tail: get_posted_slot_from_clock_slot(slot),
         bit_flags,
-        padding: [0; 1],
+        order_group: options.order_group,
+        max_slot,
+        trigger_source,
+        ..Order::default()
     };
 
+    if new_order.is_trailing_stop() {
+        new_order.trailing_stop_distance = params.trailing_stop_distance.unwrap_or(0);
+        new_order.trigger_price = calculate_trailing_stop_trigger_price(
+            &new_order,
+            oracle_price_data.price.unsigned_abs(),
+            spot_market.order_tick_size,
+        )?;
+    }
+
+    if params.is_iceberg() {
+        new_order.iceberg_clip_size = get_iceberg_clip_size(&params, spot_market.order_step_size)?;
+    }
+
     validate_spot_order(
         &new_order,
         spot_market.order_step_size,
@@ -3955,6 +5029,14 @@ pub fn place_spot_order(
 
     options.update_risk_increasing(risk_increasing);
 
+    if let Some(delegate_permissions) = &options.delegate_permissions {
+        delegate_permissions.validate_spot_order(
+            &new_order,
+            spot_market,
+            oracle_price_data.price,
+        )?;
+    }
+
     if options.enforce_margin_check {
         meets_place_order_margin_requirement(
             user,
@@ -4018,7 +5100,7 @@ pub fn place_spot_order(
 
     user.update_last_active_slot(slot);
 
-    Ok(())
+    Ok(Some(user.orders[new_order_index].order_id))
 }
 
 pub fn fill_spot_order(
@@ -4036,6 +5118,7 @@ pub fn fill_spot_order(
     jit_maker_order_id: Option<u32>,
     clock: &Clock,
     fulfillment_params: &mut dyn SpotFulfillmentParams,
+    fill_mode: FillMode,
 ) -> DriftResult<u64> {
     let now = clock.unix_timestamp;
     let slot = clock.slot;
@@ -4188,7 +5271,7 @@ pub fn fill_spot_order(
         }
     }
 
-    let should_expire_order = should_expire_order(user, order_index, now)?;
+    let should_expire_order = should_expire_order(user, order_index, now, slot)?;
 
     let should_cancel_reduce_only = if user.orders[order_index].reduce_only {
         let market_index = user.orders[order_index].market_index;
@@ -4269,6 +5352,7 @@ pub fn fill_spot_order(
         &state.spot_fee_structure,
         fulfillment_params,
         oracle_stale_for_margin,
+        fill_mode,
     )?;
 
     if base_asset_amount != 0 {
@@ -4362,6 +5446,19 @@ pub fn fill_spot_order(
         )?
     }
 
+    if base_asset_amount != 0 {
+        update_order_groups(
+            user,
+            &user_key,
+            Some(&filler_key),
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            slot,
+        )?;
+    }
+
     spot_market_map
         .get_ref(&order_market_index)?
         .validate_max_token_deposits_and_borrows(false)?;
@@ -4459,7 +5556,7 @@ fn get_spot_maker_orders_info(
                 )?
             };
 
-            let should_expire_order = should_expire_order(&maker, maker_order_index, now)?;
+            let should_expire_order = should_expire_order(&maker, maker_order_index, now, slot)?;
 
             let should_cancel_reduce_only_order = should_cancel_reduce_only_order(
                 &maker.orders[maker_order_index],
@@ -4537,6 +5634,7 @@ fn fulfill_spot_order(
     fee_structure: &FeeStructure,
     fulfillment_params: &mut dyn SpotFulfillmentParams,
     oracle_stale_for_margin: bool,
+    fill_mode: FillMode,
 ) -> DriftResult<(u64, u64)> {
     let base_market_index = user.orders[user_order_index].market_index;
     let order_direction = user.orders[user_order_index].direction;
@@ -4751,48 +5849,65 @@ fn fulfill_spot_order(
     drop(base_market);
     drop(quote_market);
 
-    let mut context = MarginContext::standard(margin_type)
-        .fuel_spot_deltas([
-            (
-                base_market_index,
-                base_token_amount_before.safe_sub(base_token_amount_after)?,
-            ),
-            (
-                QUOTE_SPOT_MARKET_INDEX,
-                quote_token_amount_before.safe_sub(quote_token_amount_after)?,
-            ),
-        ])
-        .fuel_numerator(user, now);
+    enforce_time_in_force(
+        user,
+        user_order_index,
+        user_key,
+        filler_key,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        fill_mode,
+        base_asset_amount,
+        now,
+        slot,
+    )?;
 
-    if oracle_stale_for_margin && taker_risk_increasing {
-        context = context.margin_ratio_override(MARGIN_PRECISION);
-    }
+    // pair legs are margin checked once after both legs fill
+    if !fill_mode.is_pair_leg() {
+        let mut context = MarginContext::standard(margin_type)
+            .fuel_spot_deltas([
+                (
+                    base_market_index,
+                    base_token_amount_before.safe_sub(base_token_amount_after)?,
+                ),
+                (
+                    QUOTE_SPOT_MARKET_INDEX,
+                    quote_token_amount_before.safe_sub(quote_token_amount_after)?,
+                ),
+            ])
+            .fuel_numerator(user, now);
+
+        if oracle_stale_for_margin && taker_risk_increasing {
+            context = context.margin_ratio_override(MARGIN_PRECISION);
+        }
+
+        let taker_margin_calculation =
+            calculate_margin_requirement_and_total_collateral_and_liability_info(
+                user,
+                perp_market_map,
+                spot_market_map,
+                oracle_map,
+                context,
+            )?;
 
-    let taker_margin_calculation =
-        calculate_margin_requirement_and_total_collateral_and_liability_info(
+        // user hasnt recieved initial fuel or below global start time
+        user_stats.update_fuel_bonus(
             user,
-            perp_market_map,
-            spot_market_map,
-            oracle_map,
-            context,
+            taker_margin_calculation.fuel_deposits,
+            taker_margin_calculation.fuel_borrows,
+            taker_margin_calculation.fuel_positions,
+            now,
         )?;
 
-    // user hasnt recieved initial fuel or below global start time
-    user_stats.update_fuel_bonus(
-        user,
-        taker_margin_calculation.fuel_deposits,
-        taker_margin_calculation.fuel_borrows,
-        taker_margin_calculation.fuel_positions,
-        now,
-    )?;
-
-    if !taker_margin_calculation.meets_margin_requirement() {
-        msg!(
-            "taker breached maintenance requirements (margin requirement {}) (total_collateral {})",
-            taker_margin_calculation.margin_requirement,
-            taker_margin_calculation.total_collateral
-        );
-        return Err(ErrorCode::InsufficientCollateral);
+        if !taker_margin_calculation.meets_margin_requirement() {
+            msg!(
+                "taker breached maintenance requirements (margin requirement {}) (total_collateral {})",
+                taker_margin_calculation.margin_requirement,
+                taker_margin_calculation.total_collateral
+            );
+            return Err(ErrorCode::InsufficientCollateral);
+        }
     }
 
     for (maker_key, _) in maker_fills {
@@ -4847,6 +5962,17 @@ fn fulfill_spot_order(
         drop(base_market);
         drop(quote_market);
 
+        update_order_groups(
+            &mut maker,
+            &maker_key,
+            Some(filler_key),
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            now,
+            slot,
+        )?;
+
         let mut context = MarginContext::standard(margin_type)
             .fuel_spot_deltas([
                 (
@@ -5063,6 +6189,8 @@ pub fn fulfill_spot_order_with_match(
         0
     };
 
+    let fee_override = base_market.get_fee_override();
+
     let FillFees {
         user_fee: taker_fee,
         maker_rebate,
@@ -5083,8 +6211,12 @@ pub fn fulfill_spot_order_with_match(
         base_market.fee_adjustment,
         false,
         None,
+        None,
+        fee_override,
     )?;
 
+    base_market.update_maker_rebate_override_volume(&fee_override, quote_asset_amount)?;
+
     // Update taker state
     update_spot_balances_and_cumulative_deposits(
         base_asset_amount.cast()?,
@@ -5224,6 +6356,11 @@ pub fn fulfill_spot_order_with_match(
     } else {
         OrderActionExplanation::OrderFilledWithMatch
     };
+    let order_action_bit_flags = set_order_bit_flag(
+        0,
+        taker.orders[taker_order_index].is_signed_msg(),
+        OrderBitFlag::SignedMessage,
+    );
     let order_action_record = get_order_action_record(
         now,
         OrderAction::Fill,
@@ -5244,7 +6381,7 @@ pub fn fulfill_spot_order_with_match(
         Some(*maker_key),
         Some(maker.orders[maker_order_index]),
         oracle_map.get_price_data(&base_market.oracle_id())?.price,
-        0,
+        order_action_bit_flags,
         None,
         None,
         None,
@@ -5397,6 +6534,7 @@ pub fn fulfill_spot_order_with_external_market(
         unsettled_referrer_rebate,
         fee_pool_amount.cast()?,
         base_market.fee_adjustment,
+        base_market.get_fee_override(),
     )?;
 
     let quote_spot_position_delta = match quote_update_direction {
@@ -5500,6 +6638,11 @@ pub fn fulfill_spot_order_with_external_market(
     base_market.total_spot_fee = base_market.total_spot_fee.safe_add(fee_to_market.cast()?)?;
 
     let fill_record_id = get_then_update_id!(base_market, next_fill_record_id);
+    let order_action_bit_flags = set_order_bit_flag(
+        0,
+        taker.orders[taker_order_index].is_signed_msg(),
+        OrderBitFlag::SignedMessage,
+    );
     let order_action_record = get_order_action_record(
         now,
         OrderAction::Fill,
@@ -5520,7 +6663,7 @@ pub fn fulfill_spot_order_with_external_market(
         None,
         None,
         oracle_price,
-        0,
+        order_action_bit_flags,
         None,
         None,
         None,
@@ -5586,6 +6729,12 @@ pub fn trigger_spot_order(
         "Order is already triggered"
     )?;
 
+    validate!(
+        !user.orders[order_index].is_bracket_pending(),
+        ErrorCode::BracketOrderPending,
+        "Order is waiting on its bracket entry order to fill"
+    )?;
+
     validate!(
         market_type == MarketType::Spot,
         ErrorCode::InvalidOrderMarketType,
@@ -5648,10 +6797,32 @@ pub fn trigger_spot_order(
         "oracle price vs twap too divergent"
     )?;
 
+    let trailing_stop_updated = update_trailing_stop_trigger_price(
+        &mut user.orders[order_index],
+        oracle_price.unsigned_abs(),
+        spot_market.order_tick_size,
+    )?;
+
     let can_trigger = order_satisfies_trigger_condition(
         &user.orders[order_index],
         oracle_price.unsigned_abs().cast()?,
     )?;
+
+    if !can_trigger && trailing_stop_updated {
+        emit_trailing_stop_update_record(
+            now,
+            market_index,
+            &filler_key,
+            &user_key,
+            &user.orders[order_index],
+            oracle_price,
+        )?;
+
+        user.update_last_active_slot(slot);
+
+        return Ok(());
+    }
+
     validate!(can_trigger, ErrorCode::OrderDidNotSatisfyTriggerCondition)?;
 
     let position_index = user.get_spot_position_index(market_index)?;
@@ -5778,6 +6949,17 @@ pub fn trigger_spot_order(
         }
     }
 
+    update_order_groups(
+        user,
+        &user_key,
+        Some(&filler_key),
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        now,
+        slot,
+    )?;
+
     user.update_last_active_slot(slot);
 
     Ok(())
@@ -5793,7 +6975,7 @@ pub fn expire_orders(
     slot: u64,
 ) -> DriftResult {
     for order_index in 0..user.orders.len() {
-        if !should_expire_order(user, order_index, now)? {
+        if !should_expire_order(user, order_index, now, slot)? {
             continue;
         }
 
@@ -5813,5 +6995,16 @@ pub fn expire_orders(
         )?;
     }
 
+    update_order_groups(
+        user,
+        user_key,
+        None,
+        perp_market_map,
+        spot_market_map,
+        oracle_map,
+        now,
+        slot,
+    )?;
+
     Ok(())
 }
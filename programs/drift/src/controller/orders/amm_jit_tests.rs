@@ -369,6 +369,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -574,6 +576,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -790,6 +794,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -1002,6 +1008,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -1217,6 +1225,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -1440,6 +1450,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -1647,6 +1659,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -1862,6 +1876,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -2065,6 +2081,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -2280,6 +2298,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -2533,6 +2553,8 @@ pub mod amm_jit {
                 false,
                 &mut None,
                 false,
+                None,
+                &mut None,
             )
             .unwrap();
 
@@ -2833,6 +2855,8 @@ pub mod amm_jit {
                 false,
                 &mut None,
                 false,
+                None,
+                &mut None,
             )
             .unwrap();
 
@@ -3078,6 +3102,8 @@ pub mod amm_jit {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
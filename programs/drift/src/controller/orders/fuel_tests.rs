@@ -307,6 +307,8 @@ pub mod fuel_scoring {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
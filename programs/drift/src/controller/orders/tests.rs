@@ -316,6 +316,7 @@ pub mod fill_order_protected_maker {
             FillMode::Fill,
             &mut None,
             false,
+            &mut None,
         )
         .unwrap();
 
@@ -427,6 +428,7 @@ pub mod fill_order_protected_maker {
             FillMode::Fill,
             &mut None,
             false,
+            &mut None,
         )
         .unwrap();
 
@@ -542,6 +544,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -667,6 +672,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -792,6 +800,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -917,6 +928,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -1041,6 +1055,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -1132,6 +1149,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -1224,6 +1244,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -1316,6 +1339,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -1408,6 +1434,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -1520,6 +1549,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -1637,6 +1669,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -1759,6 +1794,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -1882,6 +1920,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -2029,6 +2070,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -2151,6 +2195,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -2285,6 +2332,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -2438,6 +2488,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -2589,6 +2642,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -2741,6 +2797,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -2874,6 +2933,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -3006,6 +3068,9 @@ pub mod fulfill_order_with_maker_order {
             false,
             &mut None,
             false,
+            &mut None,
+            &mut None,
+            &mut None,
         )
         .unwrap();
 
@@ -3409,6 +3474,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -3668,6 +3735,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -3873,6 +3942,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -4094,6 +4165,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -4275,6 +4348,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -4488,6 +4563,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         );
 
         assert!(result.is_ok());
@@ -4690,6 +4767,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         );
 
         assert_eq!(result, Err(ErrorCode::InsufficientCollateral));
@@ -4845,6 +4924,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -5027,6 +5108,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -5205,6 +5288,7 @@ pub mod fulfill_order {
             FillMode::Fill,
             &mut None,
             false,
+            &mut None,
         )
         .unwrap();
 
@@ -5232,6 +5316,7 @@ pub mod fulfill_order {
             FillMode::Fill,
             &mut None,
             false,
+            &mut None,
         )
         .unwrap();
 
@@ -5383,6 +5468,8 @@ pub mod fulfill_order {
     //         true,
     //         &mut None,
     //         false,
+    //         None,
+    //         &mut None,
     //     )
     //     .unwrap();
     //
@@ -5632,6 +5719,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -5891,6 +5980,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -6074,6 +6165,8 @@ pub mod fulfill_order {
             false,
             &mut None,
             false,
+            None,
+            &mut None,
         )
         .unwrap();
 
@@ -6308,6 +6401,7 @@ pub mod fill_order {
             FillMode::Fill,
             &mut None,
             false,
+            &mut None,
         )
         .unwrap();
 
@@ -6512,6 +6606,7 @@ pub mod fill_order {
             FillMode::Fill,
             &mut None,
             false,
+            &mut None,
         )
         .unwrap();
 
@@ -6643,6 +6738,7 @@ pub mod fill_order {
             FillMode::Fill,
             &mut None,
             false,
+            &mut None,
         )
         .unwrap();
 
@@ -6807,6 +6903,7 @@ pub mod fill_order {
             FillMode::Fill,
             &mut None,
             false,
+            &mut None,
         );
 
         assert_eq!(err, Err(ErrorCode::MaxOpenInterest));
@@ -9414,6 +9511,7 @@ pub mod fulfill_spot_order {
         LAMPORTS_PER_SOL_I64, LAMPORTS_PER_SOL_U64, PRICE_PRECISION_I64, PRICE_PRECISION_U64,
         SPOT_BALANCE_PRECISION, SPOT_BALANCE_PRECISION_U64,
     };
+    use crate::state::fill_mode::FillMode;
     use crate::state::oracle::HistoricalOracleData;
     use crate::state::perp_market_map::PerpMarketMap;
     use crate::state::spot_fulfillment_params::TestFulfillmentParams;
@@ -9855,6 +9953,7 @@ pub mod fulfill_spot_order {
             None,
             &clock,
             &mut TestFulfillmentParams {},
+            FillMode::Fill,
         )
         .unwrap();
 
@@ -10136,6 +10235,7 @@ pub mod fulfill_spot_order {
             None,
             &clock,
             &mut TestFulfillmentParams {},
+            FillMode::Fill,
         )
         .unwrap();
 
@@ -10316,6 +10416,7 @@ pub mod fulfill_spot_order {
             None,
             &clock,
             &mut TestFulfillmentParams {},
+            FillMode::Fill,
         );
 
         assert_eq!(result, Err(ErrorCode::InsufficientCollateral));
@@ -10335,6 +10436,7 @@ pub mod fill_spot_order {
         LAMPORTS_PER_SOL_I64, LAMPORTS_PER_SOL_U64, PRICE_PRECISION_I64, PRICE_PRECISION_U64,
         SPOT_BALANCE_PRECISION, SPOT_BALANCE_PRECISION_U64,
     };
+    use crate::state::fill_mode::FillMode;
     use crate::state::oracle::HistoricalOracleData;
     use crate::state::perp_market_map::PerpMarketMap;
     use crate::state::spot_fulfillment_params::TestFulfillmentParams;
@@ -10493,6 +10595,7 @@ pub mod fill_spot_order {
             None,
             &clock,
             &mut TestFulfillmentParams {},
+            FillMode::Fill,
         )
         .unwrap();
 
@@ -13564,3 +13667,738 @@ mod order_is_low_risk_for_amm {
         assert!(!is_low);
     }
 }
+
+pub mod cancel_order {
+    use std::str::FromStr;
+
+    use crate::controller::orders::cancel_order;
+    use crate::controller::position::PositionDirection;
+    use crate::create_account_info;
+    use crate::create_anchor_account_info;
+    use crate::math::constants::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_I64, BASE_PRECISION_U64, PEG_PRECISION,
+        PRICE_PRECISION_U64,
+    };
+    use crate::state::events::OrderActionExplanation;
+    use crate::state::oracle::{HistoricalOracleData, OracleSource};
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::state::perp_market_map::PerpMarketMap;
+    use crate::state::spot_market_map::SpotMarketMap;
+    use crate::state::user::{MarketType, OrderStatus, OrderType, User};
+    use crate::test_utils::*;
+    use crate::test_utils::{create_account_info, get_positions, get_pyth_price};
+
+    use super::*;
+
+    fn cancel_perp_order(order: Order, open_bids: i64) -> User {
+        let slot = 6;
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                order_step_size: 1000,
+                order_tick_size: 1,
+                oracle: oracle_price_key,
+                oracle_source: OracleSource::Pyth,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price_twap: oracle_price.twap,
+                    last_oracle_price: oracle_price.agg.price,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            status: MarketStatus::Active,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+        let spot_market_map = SpotMarketMap::empty();
+
+        let mut orders = [Order::default(); 32];
+        orders[0] = order;
+
+        let mut user = User {
+            orders,
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: BASE_PRECISION_I64,
+                open_orders: 1,
+                open_bids,
+                ..PerpPosition::default()
+            }),
+            ..User::default()
+        };
+
+        cancel_order(
+            0,
+            &mut user,
+            &Pubkey::default(),
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            0,
+            slot,
+            OrderActionExplanation::None,
+            None,
+            0,
+            false,
+        )
+        .unwrap();
+
+        user
+    }
+
+    #[test]
+    fn twap_releases_unreleased_size() {
+        let mut twap_order = Order {
+            market_index: 0,
+            order_id: 1,
+            status: OrderStatus::Open,
+            order_type: OrderType::Twap,
+            market_type: MarketType::Perp,
+            direction: PositionDirection::Long,
+            base_asset_amount: 4 * BASE_PRECISION_U64,
+            base_asset_amount_filled: BASE_PRECISION_U64,
+            price: 102 * PRICE_PRECISION_U64,
+            max_ts: 240,
+            ..Order::default()
+        };
+        // two of four slices released, one filled
+        twap_order.set_twap_params(60, 4, 2);
+
+        let user = cancel_perp_order(twap_order, 3 * BASE_PRECISION_I64);
+
+        assert_eq!(user.orders[0].status, OrderStatus::Canceled);
+        assert_eq!(user.perp_positions[0].open_orders, 0);
+        assert_eq!(user.perp_positions[0].open_bids, 0);
+    }
+
+    #[test]
+    fn iceberg_releases_reserve() {
+        let iceberg_order = Order {
+            market_index: 0,
+            order_id: 1,
+            status: OrderStatus::Open,
+            order_type: OrderType::Limit,
+            market_type: MarketType::Perp,
+            direction: PositionDirection::Long,
+            base_asset_amount: 10 * BASE_PRECISION_U64,
+            base_asset_amount_filled: BASE_PRECISION_U64,
+            price: 102 * PRICE_PRECISION_U64,
+            iceberg_clip_size: 2 * BASE_PRECISION_U64,
+            ..Order::default()
+        };
+
+        let user = cancel_perp_order(iceberg_order, 9 * BASE_PRECISION_I64);
+
+        assert_eq!(user.orders[0].status, OrderStatus::Canceled);
+        assert_eq!(user.perp_positions[0].open_orders, 0);
+        assert_eq!(user.perp_positions[0].open_bids, 0);
+    }
+}
+
+pub mod update_order_groups {
+    use std::str::FromStr;
+
+    use anchor_lang::prelude::{AccountLoader, Clock};
+
+    use crate::controller::orders::{expire_orders, fill_perp_order, trigger_order};
+    use crate::controller::position::PositionDirection;
+    use crate::create_account_info;
+    use crate::create_anchor_account_info;
+    use crate::math::constants::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_I64, BASE_PRECISION_U64, PEG_PRECISION,
+        PRICE_PRECISION_I64, PRICE_PRECISION_U64, QUOTE_PRECISION_I64, SPOT_BALANCE_PRECISION_U64,
+        SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_WEIGHT_PRECISION,
+    };
+    use crate::state::oracle::{HistoricalOracleData, OracleSource};
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::state::perp_market_map::PerpMarketMap;
+    use crate::state::spot_market::{SpotBalanceType, SpotMarket};
+    use crate::state::spot_market_map::SpotMarketMap;
+    use crate::state::user::{
+        MarketType, OrderBitFlag, OrderStatus, OrderTriggerCondition, OrderType, SpotPosition,
+        User, UserStats,
+    };
+    use crate::state::user_map::{UserMap, UserStatsMap};
+    use crate::test_utils::*;
+    use crate::test_utils::{
+        create_account_info, get_orders, get_positions, get_pyth_price, get_spot_positions,
+    };
+
+    use super::*;
+
+    #[test]
+    fn fill_cancels_siblings() {
+        let clock = Clock {
+            slot: 56,
+            epoch_start_timestamp: 0,
+            epoch: 0,
+            leader_schedule_epoch: 0,
+            unix_timestamp: 0,
+        };
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, clock.slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                terminal_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 100,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 1000,
+                order_tick_size: 1,
+                oracle: oracle_price_key,
+                max_spread: 1000,
+                base_spread: 0,
+                long_spread: 0,
+                short_spread: 0,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price_twap: oracle_price.twap,
+                    last_oracle_price_twap_5min: oracle_price.twap,
+                    last_oracle_price: oracle_price.agg.price,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Initialized,
+            ..PerpMarket::default()
+        };
+        market.status = MarketStatus::Active;
+        market.amm.max_base_asset_reserve = i128::MAX as u128;
+        market.amm.min_base_asset_reserve = 0;
+        let (new_ask_base_asset_reserve, new_ask_quote_asset_reserve) =
+            crate::math::amm_spread::calculate_spread_reserves(&market, PositionDirection::Long)
+                .unwrap();
+        let (new_bid_base_asset_reserve, new_bid_quote_asset_reserve) =
+            crate::math::amm_spread::calculate_spread_reserves(&market, PositionDirection::Short)
+                .unwrap();
+        market.amm.ask_base_asset_reserve = new_ask_base_asset_reserve;
+        market.amm.bid_base_asset_reserve = new_bid_base_asset_reserve;
+        market.amm.ask_quote_asset_reserve = new_ask_quote_asset_reserve;
+        market.amm.bid_quote_asset_reserve = new_bid_quote_asset_reserve;
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData::default_price(QUOTE_PRECISION_I64),
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+        let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
+
+        // a market order and a resting limit order sharing a group
+        let mut orders = get_orders(Order {
+            market_index: 0,
+            order_id: 1,
+            status: OrderStatus::Open,
+            order_type: OrderType::Market,
+            direction: PositionDirection::Long,
+            market_type: MarketType::Perp,
+            base_asset_amount: BASE_PRECISION_U64,
+            slot: 0,
+            auction_start_price: 0,
+            auction_end_price: 100 * PRICE_PRECISION_I64,
+            auction_duration: 5,
+            price: 100 * PRICE_PRECISION_U64,
+            order_group: 1,
+            ..Order::default()
+        });
+        orders[1] = Order {
+            market_index: 0,
+            order_id: 2,
+            status: OrderStatus::Open,
+            order_type: OrderType::Limit,
+            direction: PositionDirection::Long,
+            market_type: MarketType::Perp,
+            base_asset_amount: BASE_PRECISION_U64,
+            slot: 0,
+            price: 90 * PRICE_PRECISION_U64,
+            order_group: 1,
+            ..Order::default()
+        };
+        let mut user = User {
+            authority: Pubkey::from_str("My11111111111111111111111111111111111111111").unwrap(), // different authority than filler
+            orders,
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                open_orders: 2,
+                open_bids: 2 * BASE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: get_spot_positions(SpotPosition {
+                market_index: 0,
+                balance_type: SpotBalanceType::Deposit,
+                scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
+                ..SpotPosition::default()
+            }),
+            open_orders: 2,
+            has_open_order: true,
+            ..User::default()
+        };
+        create_anchor_account_info!(user, User, user_account_info);
+        let user_account_loader: AccountLoader<User> =
+            AccountLoader::try_from(&user_account_info).unwrap();
+
+        create_anchor_account_info!(UserStats::default(), UserStats, user_stats_account_info);
+        let user_stats_account_loader: AccountLoader<UserStats> =
+            AccountLoader::try_from(&user_stats_account_info).unwrap();
+
+        let maker_key = Pubkey::from_str("My11111111111111111111111111111111111111113").unwrap();
+        let maker_authority =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let mut maker = User {
+            authority: maker_authority,
+            orders: get_orders(Order {
+                market_index: 0,
+                order_id: 1,
+                status: OrderStatus::Open,
+                order_type: OrderType::Limit,
+                market_type: MarketType::Perp,
+                direction: PositionDirection::Short,
+                base_asset_amount: BASE_PRECISION_U64,
+                slot: 0,
+                price: 100 * PRICE_PRECISION_U64,
+                post_only: true,
+                ..Order::default()
+            }),
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                open_orders: 1,
+                open_asks: -BASE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            spot_positions: get_spot_positions(SpotPosition {
+                market_index: 0,
+                balance_type: SpotBalanceType::Deposit,
+                scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
+                ..SpotPosition::default()
+            }),
+            ..User::default()
+        };
+        create_anchor_account_info!(maker, &maker_key, User, maker_account_info);
+        let makers_and_referrers = UserMap::load_one(&maker_account_info).unwrap();
+
+        let mut maker_stats = UserStats {
+            authority: maker_authority,
+            ..UserStats::default()
+        };
+        create_anchor_account_info!(maker_stats, UserStats, maker_stats_account_info);
+        let maker_and_referrer_stats = UserStatsMap::load_one(&maker_stats_account_info).unwrap();
+
+        let filler_key = Pubkey::from_str("My11111111111111111111111111111111111111111").unwrap();
+        create_anchor_account_info!(User::default(), &filler_key, User, filler_account_info);
+        let filler_account_loader: AccountLoader<User> =
+            AccountLoader::try_from(&filler_account_info).unwrap();
+
+        create_anchor_account_info!(UserStats::default(), UserStats, filler_stats_account_info);
+        let filler_stats_account_loader: AccountLoader<UserStats> =
+            AccountLoader::try_from(&filler_stats_account_info).unwrap();
+
+        let state = State {
+            min_perp_auction_duration: 1,
+            default_market_order_time_in_force: 10,
+            ..State::default()
+        };
+
+        let (base_asset_amount, _) = fill_perp_order(
+            1,
+            &state,
+            &user_account_loader,
+            &user_stats_account_loader,
+            &spot_market_map,
+            &market_map,
+            &mut oracle_map,
+            &filler_account_loader,
+            &filler_stats_account_loader,
+            &makers_and_referrers,
+            &maker_and_referrer_stats,
+            None,
+            &clock,
+            FillMode::Fill,
+            &mut None,
+            false,
+            &mut None,
+        )
+        .unwrap();
+
+        assert_eq!(base_asset_amount, BASE_PRECISION_U64);
+
+        let user = user_account_loader.load().unwrap();
+        assert_eq!(user.orders[0].status, OrderStatus::Filled);
+        assert_eq!(user.orders[1].status, OrderStatus::Canceled);
+        assert_eq!(user.orders[1].order_group, 0);
+        assert_eq!(user.perp_positions[0].open_orders, 0);
+        assert_eq!(user.perp_positions[0].open_bids, 0);
+    }
+
+    #[test]
+    fn trigger_cancels_siblings() {
+        let clock = Clock {
+            slot: 6,
+            epoch_start_timestamp: 0,
+            epoch: 0,
+            leader_schedule_epoch: 0,
+            unix_timestamp: 0,
+        };
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, clock.slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                terminal_quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                max_slippage_ratio: 100,
+                max_fill_reserve_fraction: 100,
+                order_step_size: 1000,
+                order_tick_size: 1,
+                oracle: oracle_price_key,
+                max_spread: 1000,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price_twap: oracle_price.twap,
+                    last_oracle_price_twap_5min: oracle_price.twap,
+                    last_oracle_price: oracle_price.agg.price,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            margin_ratio_initial: 1000,
+            margin_ratio_maintenance: 500,
+            status: MarketStatus::Active,
+            ..PerpMarket::default()
+        };
+        market.amm.max_base_asset_reserve = i128::MAX as u128;
+        market.amm.min_base_asset_reserve = 0;
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+
+        let mut spot_market = SpotMarket {
+            market_index: 0,
+            oracle_source: OracleSource::QuoteAsset,
+            cumulative_deposit_interest: SPOT_CUMULATIVE_INTEREST_PRECISION,
+            decimals: 6,
+            initial_asset_weight: SPOT_WEIGHT_PRECISION,
+            maintenance_asset_weight: SPOT_WEIGHT_PRECISION,
+            historical_oracle_data: HistoricalOracleData::default_price(QUOTE_PRECISION_I64),
+            ..SpotMarket::default()
+        };
+        create_anchor_account_info!(spot_market, SpotMarket, spot_market_account_info);
+        let spot_market_map = SpotMarketMap::load_one(&spot_market_account_info, true).unwrap();
+
+        // take profit above 90 and stop loss below 50 on a long
+        let mut orders = get_orders(Order {
+            market_index: 0,
+            order_id: 1,
+            status: OrderStatus::Open,
+            order_type: OrderType::TriggerMarket,
+            direction: PositionDirection::Short,
+            market_type: MarketType::Perp,
+            base_asset_amount: BASE_PRECISION_U64,
+            trigger_price: 90 * PRICE_PRECISION_U64,
+            trigger_condition: OrderTriggerCondition::Above,
+            reduce_only: true,
+            order_group: 1,
+            ..Order::default()
+        });
+        orders[1] = Order {
+            order_id: 2,
+            trigger_price: 50 * PRICE_PRECISION_U64,
+            trigger_condition: OrderTriggerCondition::Below,
+            ..orders[0]
+        };
+        let user_key = Pubkey::from_str("My11111111111111111111111111111111111111113").unwrap();
+        let mut user = User {
+            orders,
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                base_asset_amount: BASE_PRECISION_I64,
+                quote_asset_amount: -100 * QUOTE_PRECISION_I64,
+                quote_entry_amount: -100 * QUOTE_PRECISION_I64,
+                quote_break_even_amount: -100 * QUOTE_PRECISION_I64,
+                open_orders: 2,
+                ..PerpPosition::default()
+            }),
+            spot_positions: get_spot_positions(SpotPosition {
+                market_index: 0,
+                balance_type: SpotBalanceType::Deposit,
+                scaled_balance: 100 * SPOT_BALANCE_PRECISION_U64,
+                ..SpotPosition::default()
+            }),
+            open_orders: 2,
+            has_open_order: true,
+            ..User::default()
+        };
+        create_anchor_account_info!(user, &user_key, User, user_account_info);
+        let user_account_loader: AccountLoader<User> =
+            AccountLoader::try_from(&user_account_info).unwrap();
+
+        let filler_key = Pubkey::from_str("My11111111111111111111111111111111111111111").unwrap();
+        create_anchor_account_info!(User::default(), &filler_key, User, filler_account_info);
+        let filler_account_loader: AccountLoader<User> =
+            AccountLoader::try_from(&filler_account_info).unwrap();
+
+        let state = State::default();
+
+        trigger_order(
+            1,
+            &state,
+            &user_account_loader,
+            &spot_market_map,
+            &market_map,
+            &mut oracle_map,
+            &filler_account_loader,
+            &clock,
+        )
+        .unwrap();
+
+        let user = user_account_loader.load().unwrap();
+        assert!(user.orders[0].triggered());
+        assert_eq!(user.orders[0].status, OrderStatus::Open);
+        assert_eq!(user.orders[1].status, OrderStatus::Canceled);
+        assert_eq!(user.orders[1].order_group, 0);
+        assert_eq!(user.perp_positions[0].open_orders, 1);
+    }
+
+    #[test]
+    fn expired_bracket_entry_cancels_pending_siblings() {
+        let slot = 6;
+        let now = 20;
+
+        let mut oracle_price = get_pyth_price(100, 6);
+        let oracle_price_key =
+            Pubkey::from_str("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix").unwrap();
+        let pyth_program = crate::ids::pyth_program::id();
+        create_account_info!(
+            oracle_price,
+            &oracle_price_key,
+            &pyth_program,
+            oracle_account_info
+        );
+        let mut oracle_map = OracleMap::load_one(&oracle_account_info, slot, None).unwrap();
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                quote_asset_reserve: 100 * AMM_RESERVE_PRECISION,
+                sqrt_k: 100 * AMM_RESERVE_PRECISION,
+                peg_multiplier: 100 * PEG_PRECISION,
+                order_step_size: 1000,
+                order_tick_size: 1,
+                oracle: oracle_price_key,
+                oracle_source: OracleSource::Pyth,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price_twap: oracle_price.twap,
+                    last_oracle_price: oracle_price.agg.price,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            status: MarketStatus::Active,
+            ..PerpMarket::default()
+        };
+        create_anchor_account_info!(market, PerpMarket, market_account_info);
+        let market_map = PerpMarketMap::load_one(&market_account_info, true).unwrap();
+        let spot_market_map = SpotMarketMap::empty();
+
+        // an entry that expired before filling, with its stop loss and take profit waiting on it
+        let mut orders = get_orders(Order {
+            market_index: 0,
+            order_id: 1,
+            status: OrderStatus::Open,
+            order_type: OrderType::Limit,
+            direction: PositionDirection::Long,
+            market_type: MarketType::Perp,
+            base_asset_amount: BASE_PRECISION_U64,
+            price: 100 * PRICE_PRECISION_U64,
+            max_ts: 10,
+            order_group: 1,
+            ..Order::default()
+        });
+        let mut stop_loss = Order {
+            market_index: 0,
+            order_id: 2,
+            status: OrderStatus::Open,
+            order_type: OrderType::TriggerMarket,
+            direction: PositionDirection::Short,
+            market_type: MarketType::Perp,
+            base_asset_amount: BASE_PRECISION_U64,
+            trigger_price: 90 * PRICE_PRECISION_U64,
+            trigger_condition: OrderTriggerCondition::Below,
+            reduce_only: true,
+            order_group: 1,
+            ..Order::default()
+        };
+        stop_loss.add_bit_flag(OrderBitFlag::BracketPending);
+        orders[1] = stop_loss;
+        orders[2] = Order {
+            order_id: 3,
+            trigger_price: 110 * PRICE_PRECISION_U64,
+            trigger_condition: OrderTriggerCondition::Above,
+            ..stop_loss
+        };
+
+        let mut user = User {
+            orders,
+            perp_positions: get_positions(PerpPosition {
+                market_index: 0,
+                open_orders: 3,
+                open_bids: BASE_PRECISION_I64,
+                ..PerpPosition::default()
+            }),
+            open_orders: 3,
+            has_open_order: true,
+            ..User::default()
+        };
+
+        expire_orders(
+            &mut user,
+            &Pubkey::default(),
+            &market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            now,
+            slot,
+        )
+        .unwrap();
+
+        for order in user.orders[..3].iter() {
+            assert_eq!(order.status, OrderStatus::Canceled);
+            assert_eq!(order.order_group, 0);
+        }
+        assert_eq!(user.perp_positions[0].open_orders, 0);
+        assert_eq!(user.perp_positions[0].open_bids, 0);
+        assert_eq!(user.open_orders, 0);
+    }
+}
+
+mod trigger_source_params {
+    use crate::controller::orders::{
+        merge_modify_order_params_with_existing_order, standardize_trigger_price,
+    };
+    use crate::controller::position::PositionDirection;
+    use crate::state::order_params::ModifyOrderParams;
+    use crate::state::user::{Order, OrderStatus, OrderTriggerSource, OrderType};
+    use crate::PRICE_PRECISION_U64;
+
+    #[test]
+    fn price_thresholds_snap_to_tick_and_rates_are_kept() {
+        let trigger_price = standardize_trigger_price(
+            100 * PRICE_PRECISION_U64 + 1,
+            OrderTriggerSource::MarkTwap,
+            1000,
+            PositionDirection::Long,
+        )
+        .unwrap();
+        assert_eq!(trigger_price, 100 * PRICE_PRECISION_U64);
+
+        let rate = OrderTriggerSource::FundingRate
+            .get_trigger_price(-100)
+            .unwrap();
+        let trigger_price = standardize_trigger_price(
+            rate,
+            OrderTriggerSource::FundingRate,
+            1000,
+            PositionDirection::Long,
+        )
+        .unwrap();
+        assert_eq!(trigger_price, rate);
+        assert_eq!(
+            OrderTriggerSource::FundingRate
+                .get_threshold(trigger_price)
+                .unwrap(),
+            -100
+        );
+
+        assert!(standardize_trigger_price(
+            u32::MAX as u64 + 1,
+            OrderTriggerSource::FundingRate,
+            1000,
+            PositionDirection::Long,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn modify_keeps_trigger_source() {
+        let existing_order = Order {
+            status: OrderStatus::Open,
+            order_type: OrderType::TriggerMarket,
+            base_asset_amount: PRICE_PRECISION_U64,
+            trigger_price: 100 * PRICE_PRECISION_U64,
+            trigger_source: OrderTriggerSource::MarkTwap,
+            ..Order::default()
+        };
+
+        let params = merge_modify_order_params_with_existing_order(
+            &existing_order,
+            &ModifyOrderParams {
+                trigger_price: Some(101 * PRICE_PRECISION_U64),
+                ..ModifyOrderParams::default()
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(params.trigger_price, Some(101 * PRICE_PRECISION_U64));
+        assert_eq!(params.trigger_source, Some(OrderTriggerSource::MarkTwap));
+
+        let params = merge_modify_order_params_with_existing_order(
+            &existing_order,
+            &ModifyOrderParams {
+                trigger_source: Some(OrderTriggerSource::Oracle),
+                ..ModifyOrderParams::default()
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(params.trigger_price, Some(100 * PRICE_PRECISION_U64));
+        assert_eq!(params.trigger_source, Some(OrderTriggerSource::Oracle));
+    }
+}
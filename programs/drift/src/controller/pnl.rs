@@ -592,6 +592,7 @@ pub fn settle_expired_position(
             trigger_price: None,
             builder_idx: None,
             builder_fee: None,
+            replaced_order_id: None,
         };
         emit!(fill_record);
     }
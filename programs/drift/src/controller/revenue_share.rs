@@ -1,11 +1,14 @@
 use anchor_lang::prelude::*;
 
 use crate::controller::spot_balance;
+use crate::math::casting::Cast;
 use crate::math::safe_math::SafeMath;
 use crate::math::spot_balance::get_token_amount;
 use crate::state::events::{emit_stack, RevenueShareSettleRecord};
 use crate::state::perp_market_map::PerpMarketMap;
-use crate::state::revenue_share::{RevenueShareEscrowZeroCopyMut, RevenueShareOrder};
+use crate::state::revenue_share::{
+    RevenueShareEscrowZeroCopyMut, RevenueShareOrder, BUILDER_TIER_LENGTH,
+};
 use crate::state::revenue_share_map::RevenueShareMap;
 use crate::state::spot_market::SpotBalance;
 use crate::state::spot_market_map::SpotMarketMap;
@@ -13,7 +16,8 @@ use crate::state::traits::Size;
 use crate::state::user::MarketType;
 
 /// Runs through the user's RevenueShareEscrow account and sweeps any accrued fees to the corresponding
-/// builders and referrer.
+/// builders and referrer. Builders in a volume tier above the base tier also receive a protocol rebate
+/// from the market's fee pool.
 pub fn sweep_completed_revenue_share_for_market<'a>(
     market_index: u16,
     revenue_share_escrow: &mut RevenueShareEscrowZeroCopyMut,
@@ -23,6 +27,8 @@ pub fn sweep_completed_revenue_share_for_market<'a>(
     now_ts: i64,
     builder_codes_feature_enabled: bool,
     builder_referral_feature_enabled: bool,
+    builder_tier_volume_thresholds: &[u64; BUILDER_TIER_LENGTH],
+    builder_tier_rebate_pcts: &[u8; BUILDER_TIER_LENGTH],
 ) -> crate::error::DriftResult<()> {
     let perp_market = &mut perp_market_map.get_ref_mut(&market_index)?;
     let quote_spot_market = &mut spot_market_map.get_quote_spot_market_mut()?;
@@ -37,6 +43,7 @@ pub fn sweep_completed_revenue_share_for_market<'a>(
             order_market_type,
             order_market_index,
             fees_accrued,
+            volume_accrued,
             builder_idx,
         ) = {
             let ord_ro = match revenue_share_escrow.get_order(i) {
@@ -51,6 +58,7 @@ pub fn sweep_completed_revenue_share_for_market<'a>(
                 ord_ro.market_type,
                 ord_ro.market_index,
                 ord_ro.fees_accrued,
+                ord_ro.volume_accrued,
                 ord_ro.builder_idx,
             )
         };
@@ -126,6 +134,9 @@ pub fn sweep_completed_revenue_share_for_market<'a>(
                                 .total_referrer_rewards,
                             builder_total_builder_rewards: referrer_rev_share.total_builder_rewards,
                             builder_sub_account_id: referrer_user.sub_account_id,
+                            builder_rebate: 0,
+                            builder_rebate_unpaid: 0,
+                            builder_volume_30d: referrer_rev_share.builder_volume_30d,
                         },
                     )?;
 
@@ -162,9 +173,50 @@ pub fn sweep_completed_revenue_share_for_market<'a>(
                     builder_user.get_quote_spot_position_mut(),
                 )?;
 
+                builder_revenue_share.update_builder_volume_30d(volume_accrued, now_ts)?;
+
+                let mut builder_rebate = builder_revenue_share.calculate_builder_rebate(
+                    fees_accrued,
+                    builder_tier_volume_thresholds,
+                    builder_tier_rebate_pcts,
+                )?;
+                let mut builder_rebate_unpaid = 0;
+
+                if builder_rebate > 0 {
+                    let fee_pool_token_amount = get_token_amount(
+                        perp_market.amm.fee_pool.scaled_balance,
+                        quote_spot_market,
+                        perp_market.amm.fee_pool.balance_type(),
+                    )?;
+
+                    if fee_pool_token_amount < builder_rebate as u128 {
+                        msg!(
+                            "market {} fee pool has insufficient balance to pay builder rebate. fee_pool_token_amount: {}, builder_rebate: {}",
+                            market_index,
+                            fee_pool_token_amount,
+                            builder_rebate
+                        );
+                        builder_rebate_unpaid = builder_rebate;
+                        builder_rebate = 0;
+                    } else {
+                        spot_balance::transfer_spot_balances(
+                            builder_rebate as i128,
+                            quote_spot_market,
+                            &mut perp_market.amm.fee_pool,
+                            builder_user.get_quote_spot_position_mut(),
+                        )?;
+
+                        perp_market.amm.total_fee_minus_distributions = perp_market
+                            .amm
+                            .total_fee_minus_distributions
+                            .safe_sub(builder_rebate.cast()?)?;
+                    }
+                }
+
                 builder_revenue_share.total_builder_rewards = builder_revenue_share
                     .total_builder_rewards
-                    .safe_add(fees_accrued as u64)?;
+                    .safe_add(fees_accrued as u64)?
+                    .safe_add(builder_rebate)?;
 
                 emit_stack::<_, { RevenueShareSettleRecord::SIZE }>(RevenueShareSettleRecord {
                     ts: now_ts,
@@ -176,6 +228,9 @@ pub fn sweep_completed_revenue_share_for_market<'a>(
                     builder_total_referrer_rewards: builder_revenue_share.total_referrer_rewards,
                     builder_total_builder_rewards: builder_revenue_share.total_builder_rewards,
                     builder_sub_account_id: builder_user.sub_account_id,
+                    builder_rebate,
+                    builder_rebate_unpaid,
+                    builder_volume_30d: builder_revenue_share.builder_volume_30d,
                 })?;
 
                 // remove order
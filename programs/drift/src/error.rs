@@ -704,6 +704,72 @@ pub enum ErrorCode {
     InvalidPerpMarketConfig,
     #[msg("Insurance fund withdrawal recipient must be the designated treasury address")]
     InvalidInsuranceFundWithdrawalRecipient,
+    #[msg("Max number of order groups")]
+    MaxNumberOfOrderGroups,
+    #[msg("Invalid order group")]
+    InvalidOrderGroup,
+    #[msg("Bracket order waiting on entry order fill")]
+    BracketOrderPending,
+    #[msg("Invalid twap order")]
+    InvalidTwapOrder,
+    #[msg("Invalid iceberg order")]
+    InvalidIcebergOrder,
+    #[msg("Fill or kill order could not be completely filled")]
+    FillOrKillOrderNotFilled,
+    #[msg("Invalid order trigger source")]
+    InvalidOrderTriggerSource,
+    #[msg("Invalid pair order")]
+    InvalidPairOrder,
+    #[msg("Pair order net price breached its limit")]
+    PairOrderNetPriceBreached,
+    #[msg("Invalid scale order size distribution")]
+    InvalidOrderScaleSizeDistribution,
+    #[msg("Invalid delegate permissions")]
+    InvalidDelegatePermissions,
+    #[msg("Delegate not permitted for operation")]
+    DelegatePermissionDenied,
+    #[msg("Could not deserialize delegate permissions")]
+    CouldNotDeserializeDelegatePermissions,
+    #[msg("Invalid withdraw policy")]
+    InvalidWithdrawPolicy,
+    #[msg("Withdraw policy change is timelocked")]
+    WithdrawPolicyTimelocked,
+    #[msg("Withdraw destination not allowed by withdraw policy")]
+    WithdrawDestinationNotAllowed,
+    #[msg("Withdraw policy account missing")]
+    WithdrawPolicyNotFound,
+    #[msg("Could not deserialize withdraw policy")]
+    CouldNotDeserializeWithdrawPolicy,
+    #[msg("Invalid signed msg evm signer")]
+    InvalidSignedMsgEvmSigner,
+    #[msg("Could not deserialize signed msg evm signer")]
+    CouldNotDeserializeSignedMsgEvmSigner,
+    #[msg("Invalid referral campaign")]
+    InvalidReferralCampaign,
+    #[msg("Could not deserialize referral campaign")]
+    CouldNotDeserializeReferralCampaign,
+    #[msg("Invalid portfolio margin mode update")]
+    InvalidPortfolioMarginModeUpdate,
+    #[msg("Invalid margin correlation")]
+    InvalidMarginCorrelation,
+    #[msg("Could not deserialize margin correlations")]
+    CouldNotDeserializeMarginCorrelations,
+    #[msg("Margin correlations account must be passed")]
+    MissingMarginCorrelations,
+    #[msg("Liquidation auctions disabled")]
+    LiquidationAuctionsDisabled,
+    #[msg("Deleverage disabled")]
+    DeleverageDisabled,
+    #[msg("Invalid deleverage")]
+    InvalidDeleverage,
+    #[msg("Invalid auto deleverage")]
+    InvalidAutoDeleverage,
+    #[msg("Too many replace order params")]
+    TooManyReplaceOrderParams,
+    #[msg("User in portfolio margin mode")]
+    UserInPortfolioMarginMode,
+    #[msg("Invalid state resize")]
+    InvalidStateResize,
 }
 
 #[macro_export]
@@ -1,9 +1,12 @@
 use crate::{msg, FeatureBitFlags};
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 use anchor_spl::token_2022::Token2022;
 use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 use phoenix::quantities::WrapperU64;
 use serum_dex::state::ToAlignedBytes;
+use solana_program::program::invoke;
+use solana_program::system_instruction::transfer;
 use std::convert::{identity, TryInto};
 use std::mem::size_of;
 
@@ -21,9 +24,9 @@ use crate::math::constants::{
     AMM_TIMES_PEG_TO_QUOTE_PRECISION_RATIO, DEFAULT_LIQUIDATION_MARGIN_BUFFER_RATIO,
     EPOCH_DURATION, FEE_ADJUSTMENT_MAX, FEE_POOL_TO_REVENUE_POOL_THRESHOLD, GOV_SPOT_MARKET_INDEX,
     IF_FACTOR_PRECISION, INSURANCE_A_MAX, INSURANCE_B_MAX, INSURANCE_C_MAX,
-    INSURANCE_SPECULATIVE_MAX, LIQUIDATION_FEE_PRECISION, MAX_CONCENTRATION_COEFFICIENT,
-    MAX_SQRT_K, MAX_UPDATE_K_PRICE_CHANGE, PERCENTAGE_PRECISION, PERCENTAGE_PRECISION_I64,
-    QUOTE_PRECISION_I64, QUOTE_SPOT_MARKET_INDEX, SPOT_BALANCE_PRECISION,
+    INSURANCE_SPECULATIVE_MAX, LIQUIDATION_FEE_PRECISION, MARGIN_PRECISION,
+    MAX_CONCENTRATION_COEFFICIENT, MAX_SQRT_K, MAX_UPDATE_K_PRICE_CHANGE, PERCENTAGE_PRECISION,
+    PERCENTAGE_PRECISION_I64, QUOTE_PRECISION_I64, QUOTE_SPOT_MARKET_INDEX, SPOT_BALANCE_PRECISION,
     SPOT_CUMULATIVE_INTEREST_PRECISION, SPOT_IMF_PRECISION, SPOT_WEIGHT_PRECISION, THIRTEEN_DAY,
     TWENTY_FOUR_HOUR,
 };
@@ -52,6 +55,9 @@ use crate::state::high_leverage_mode_config::HighLeverageModeConfig;
 use crate::state::if_rebalance_config::{IfRebalanceConfig, IfRebalanceConfigParams};
 use crate::state::insurance_fund_stake::InsuranceFundStake;
 use crate::state::insurance_fund_stake::ProtocolIfSharesTransferConfig;
+use crate::state::margin_correlations::{
+    MarginCorrelationParams, MarginCorrelations, MARGIN_CORRELATIONS_PDA_SEED,
+};
 use crate::state::oracle::get_sb_on_demand_price;
 use crate::state::oracle::{
     get_oracle_price, get_prelaunch_price, get_pyth_price, get_switchboard_price,
@@ -67,18 +73,23 @@ use crate::state::perp_market::{
 use crate::state::perp_market_map::{get_writable_perp_market_set, MarketSet};
 use crate::state::protected_maker_mode_config::ProtectedMakerModeConfig;
 use crate::state::pyth_lazer_oracle::{PythLazerOracle, PYTH_LAZER_ORACLE_SEED};
+use crate::state::referral_campaign::{
+    ReferralCampaign, ReferralCampaignParams, REFERRAL_CAMPAIGN_PDA_SEED,
+};
+use crate::state::revenue_share::DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS;
 use crate::state::spot_market::{
     AssetTier, InsuranceFund, SpotBalanceType, SpotFulfillmentConfigStatus, SpotMarket,
     TokenProgramFlag,
 };
 use crate::state::spot_market_map::get_writable_spot_market_set;
 use crate::state::state::{
-    ExchangeStatus, FeeStructure, LpPoolFeatureBitFlags, OracleGuardRails, State,
+    ExchangeStatus, FeeStructure, LpPoolFeatureBitFlags, MarketFeeOverrideParams, OracleGuardRails,
+    State,
 };
 use crate::state::traits::Size;
-use crate::state::user::{User, UserStats};
+use crate::state::user::{MarketType, User, UserStats};
 use crate::validate;
-use crate::validation::fee_structure::validate_fee_structure;
+use crate::validation::fee_structure::{validate_fee_structure, validate_market_fee_override};
 use crate::validation::margin::{validate_margin, validate_margin_weights};
 use crate::validation::perp_market::validate_perp_market;
 use crate::validation::spot_market::validate_borrow_rate;
@@ -121,7 +132,11 @@ pub fn handle_initialize(ctx: Context<Initialize>) -> Result<()> {
         max_initialize_user_fee: 0,
         feature_bit_flags: 0,
         lp_pool_feature_bit_flags: 0,
-        padding: [0; 8],
+        builder_tier_rebate_pcts: [0; 4],
+        liquidation_auction_duration: 0,
+        deleverage_margin_buffer_ratio: 0,
+        padding: [0; 1],
+        builder_tier_volume_thresholds: DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS,
     };
 
     Ok(())
@@ -355,7 +370,14 @@ pub fn handle_initialize_spot_market(
         fuel_boost_insurance: 0,
         token_program_flag: token_program,
         pool_id: 0,
-        padding: [0; 40],
+        maker_rebate_override_volume_cap: 0,
+        maker_rebate_override_volume: 0,
+        taker_fee_override_multiplier: 0,
+        maker_rebate_override_numerator: 0,
+        fee_override_flags: 0,
+        padding1: 0,
+        portfolio_margin_price_shock: 0,
+        padding: [0; 16],
         insurance_fund: InsuranceFund {
             vault: ctx.accounts.insurance_fund_vault.key(),
             unstaking_period: THIRTEEN_DAY,
@@ -1007,7 +1029,12 @@ pub fn handle_initialize_perp_market(
         last_fill_price: 0,
         lp_pool_id,
         market_config: 0,
-        padding: [0; 22],
+        taker_fee_override_multiplier: 0,
+        maker_rebate_override_numerator: 0,
+        fee_override_flags: 0,
+        padding1: 0,
+        maker_rebate_override_volume_cap: 0,
+        maker_rebate_override_volume: 0,
         amm: AMM {
             oracle: *ctx.accounts.oracle.key,
             oracle_source,
@@ -3644,6 +3671,40 @@ pub fn handle_update_liquidation_duration(
     Ok(())
 }
 
+pub fn handle_update_liquidation_auction_duration(
+    ctx: Context<AdminUpdateState>,
+    liquidation_auction_duration: u8,
+) -> Result<()> {
+    msg!(
+        "liquidation_auction_duration: {} -> {}",
+        ctx.accounts.state.liquidation_auction_duration,
+        liquidation_auction_duration
+    );
+
+    ctx.accounts.state.liquidation_auction_duration = liquidation_auction_duration;
+    Ok(())
+}
+
+pub fn handle_update_deleverage_margin_buffer_ratio(
+    ctx: Context<AdminUpdateState>,
+    deleverage_margin_buffer_ratio: u16,
+) -> Result<()> {
+    validate!(
+        deleverage_margin_buffer_ratio.cast::<u32>()? <= MARGIN_PRECISION,
+        ErrorCode::DefaultError,
+        "deleverage_margin_buffer_ratio must be <= MARGIN_PRECISION"
+    )?;
+
+    msg!(
+        "deleverage_margin_buffer_ratio: {} -> {}",
+        ctx.accounts.state.deleverage_margin_buffer_ratio,
+        deleverage_margin_buffer_ratio
+    );
+
+    ctx.accounts.state.deleverage_margin_buffer_ratio = deleverage_margin_buffer_ratio;
+    Ok(())
+}
+
 pub fn handle_update_liquidation_margin_buffer_ratio(
     ctx: Context<AdminUpdateState>,
     liquidation_margin_buffer_ratio: u32,
@@ -3714,6 +3775,106 @@ pub fn handle_update_state_max_initialize_user_fee(
     Ok(())
 }
 
+pub fn handle_update_state_builder_tier_rebate_pcts(
+    ctx: Context<AdminUpdateState>,
+    builder_tier_rebate_pcts: [u8; 4],
+) -> Result<()> {
+    for (i, rebate_pct) in builder_tier_rebate_pcts.iter().enumerate() {
+        validate!(
+            *rebate_pct <= 100,
+            ErrorCode::InvalidFeeStructure,
+            "builder tier rebate pct must be <= 100"
+        )?;
+
+        if i > 0 {
+            validate!(
+                *rebate_pct >= builder_tier_rebate_pcts[i - 1],
+                ErrorCode::InvalidFeeStructure,
+                "builder tier rebate pcts must not decrease with tier"
+            )?;
+        }
+    }
+
+    msg!(
+        "builder_tier_rebate_pcts: {:?} -> {:?}",
+        ctx.accounts.state.builder_tier_rebate_pcts,
+        builder_tier_rebate_pcts
+    );
+
+    ctx.accounts.state.builder_tier_rebate_pcts = builder_tier_rebate_pcts;
+    Ok(())
+}
+
+pub fn handle_update_state_builder_tier_volume_thresholds(
+    ctx: Context<AdminUpdateState>,
+    builder_tier_volume_thresholds: [u64; 4],
+) -> Result<()> {
+    for (i, threshold) in builder_tier_volume_thresholds.iter().enumerate() {
+        let previous_threshold = if i > 0 {
+            builder_tier_volume_thresholds[i - 1]
+        } else {
+            0
+        };
+
+        validate!(
+            *threshold > previous_threshold,
+            ErrorCode::InvalidFeeStructure,
+            "builder tier volume thresholds must be positive and increase with tier"
+        )?;
+    }
+
+    msg!(
+        "builder_tier_volume_thresholds: {:?} -> {:?}",
+        ctx.accounts.state.builder_tier_volume_thresholds,
+        builder_tier_volume_thresholds
+    );
+
+    ctx.accounts.state.builder_tier_volume_thresholds = builder_tier_volume_thresholds;
+    Ok(())
+}
+
+pub fn handle_resize_state(ctx: Context<ResizeState>) -> Result<()> {
+    let state = &ctx.accounts.state;
+
+    {
+        let data = state.try_borrow_data()?;
+        validate!(
+            data.len() == State::LEGACY_SIZE && data[..8] == State::discriminator(),
+            ErrorCode::InvalidStateResize,
+            "state account is already resized"
+        )?;
+
+        // state can't be deserialized before the resize, so check the admin directly
+        validate!(
+            data[8..40] == ctx.accounts.admin.key().to_bytes(),
+            ErrorCode::Unauthorized,
+            "admin does not match state"
+        )?;
+    }
+
+    let rent_needed = Rent::get()?
+        .minimum_balance(State::SIZE)
+        .saturating_sub(state.lamports());
+    if rent_needed > 0 {
+        invoke(
+            &transfer(&ctx.accounts.admin.key(), &state.key(), rent_needed),
+            &[
+                ctx.accounts.admin.to_account_info(),
+                state.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+        )?;
+    }
+
+    state.realloc(State::SIZE, true)?;
+
+    let mut state: Account<State> = Account::try_from(state)?;
+    state.builder_tier_volume_thresholds = DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS;
+    state.exit(&crate::ID)?;
+
+    Ok(())
+}
+
 #[access_control(
     perp_market_valid(&ctx.accounts.perp_market)
 )]
@@ -4119,6 +4280,47 @@ pub fn handle_update_perp_market_fee_adjustment(
     Ok(())
 }
 
+pub fn handle_update_perp_market_fee_override(
+    ctx: Context<AdminUpdatePerpMarket>,
+    params: MarketFeeOverrideParams,
+) -> Result<()> {
+    let perp_market = &mut load_mut!(ctx.accounts.perp_market)?;
+    msg!("perp market {}", perp_market.market_index);
+
+    validate_market_fee_override(&params)?;
+
+    msg!(
+        "perp_market.taker_fee_override_multiplier: {:?} -> {:?}",
+        perp_market.taker_fee_override_multiplier,
+        params.taker_fee_multiplier
+    );
+    msg!(
+        "perp_market.maker_rebate_override_numerator: {:?} -> {:?}",
+        perp_market.maker_rebate_override_numerator,
+        params.maker_rebate_numerator
+    );
+    msg!(
+        "perp_market.maker_rebate_override_volume_cap: {:?} -> {:?}",
+        perp_market.maker_rebate_override_volume_cap,
+        params.maker_rebate_volume_cap
+    );
+
+    perp_market.fee_override_flags = params.get_flags();
+    perp_market.taker_fee_override_multiplier = params.taker_fee_multiplier.unwrap_or(0);
+    perp_market.maker_rebate_override_numerator = params.maker_rebate_numerator.unwrap_or(0);
+    perp_market.maker_rebate_override_volume_cap = params.maker_rebate_volume_cap;
+
+    if params.reset_maker_rebate_volume {
+        msg!(
+            "perp_market.maker_rebate_override_volume: {:?} -> 0",
+            perp_market.maker_rebate_override_volume
+        );
+        perp_market.maker_rebate_override_volume = 0;
+    }
+
+    Ok(())
+}
+
 pub fn handle_update_perp_market_number_of_users(
     ctx: Context<AdminUpdatePerpMarket>,
     number_of_users: Option<u32>,
@@ -4353,6 +4555,75 @@ pub fn handle_update_spot_market_fee_adjustment(
     Ok(())
 }
 
+pub fn handle_update_spot_market_fee_override(
+    ctx: Context<AdminUpdateSpotMarket>,
+    params: MarketFeeOverrideParams,
+) -> Result<()> {
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+    msg!("spot market {}", spot_market.market_index);
+
+    validate_market_fee_override(&params)?;
+
+    msg!(
+        "spot_market.taker_fee_override_multiplier: {:?} -> {:?}",
+        spot_market.taker_fee_override_multiplier,
+        params.taker_fee_multiplier
+    );
+    msg!(
+        "spot_market.maker_rebate_override_numerator: {:?} -> {:?}",
+        spot_market.maker_rebate_override_numerator,
+        params.maker_rebate_numerator
+    );
+    msg!(
+        "spot_market.maker_rebate_override_volume_cap: {:?} -> {:?}",
+        spot_market.maker_rebate_override_volume_cap,
+        params.maker_rebate_volume_cap
+    );
+
+    spot_market.fee_override_flags = params.get_flags();
+    spot_market.taker_fee_override_multiplier = params.taker_fee_multiplier.unwrap_or(0);
+    spot_market.maker_rebate_override_numerator = params.maker_rebate_numerator.unwrap_or(0);
+    spot_market.maker_rebate_override_volume_cap = params.maker_rebate_volume_cap;
+
+    if params.reset_maker_rebate_volume {
+        msg!(
+            "spot_market.maker_rebate_override_volume: {:?} -> 0",
+            spot_market.maker_rebate_override_volume
+        );
+        spot_market.maker_rebate_override_volume = 0;
+    }
+
+    Ok(())
+}
+
+#[access_control(
+    spot_market_valid(&ctx.accounts.spot_market)
+)]
+pub fn handle_update_spot_market_portfolio_margin_price_shock(
+    ctx: Context<AdminUpdateSpotMarket>,
+    portfolio_margin_price_shock: u16,
+) -> Result<()> {
+    let spot_market = &mut load_mut!(ctx.accounts.spot_market)?;
+    msg!("spot market {}", spot_market.market_index);
+
+    validate!(
+        portfolio_margin_price_shock.cast::<u32>()? <= MARGIN_PRECISION,
+        ErrorCode::DefaultError,
+        "portfolio_margin_price_shock {} must be <= {}",
+        portfolio_margin_price_shock,
+        MARGIN_PRECISION
+    )?;
+
+    msg!(
+        "spot_market.portfolio_margin_price_shock: {:?} -> {:?}",
+        spot_market.portfolio_margin_price_shock,
+        portfolio_margin_price_shock
+    );
+
+    spot_market.portfolio_margin_price_shock = portfolio_margin_price_shock;
+    Ok(())
+}
+
 pub fn handle_update_spot_market_fuel(
     ctx: Context<AdminUpdateSpotMarketFuel>,
     fuel_boost_deposits: Option<u8>,
@@ -4672,6 +4943,8 @@ pub fn handle_settle_expired_market<'c: 'info, 'info>(
         &get_writable_spot_market_set(QUOTE_SPOT_MARKET_INDEX),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     controller::repeg::update_amm(
@@ -4758,6 +5031,121 @@ pub fn handle_update_protected_maker_mode_config(
     Ok(())
 }
 
+pub fn handle_initialize_referral_campaign(
+    ctx: Context<InitializeReferralCampaign>,
+    params: ReferralCampaignParams,
+) -> Result<()> {
+    let mut referrer_stats = load_mut!(ctx.accounts.referrer_stats)?;
+    let mut campaign = ctx.accounts.referral_campaign.load_init()?;
+
+    campaign.referrer = referrer_stats.authority;
+    campaign.second_level_referrer = referrer_stats.referrer;
+    campaign.budget_used = 0;
+    campaign.update(&params)?;
+
+    // fills for the referrer's referees must then include the campaign
+    referrer_stats.update_referral_campaign_status(true);
+
+    msg!(
+        "initialized referral campaign for {} (second level referrer {})",
+        campaign.referrer,
+        campaign.second_level_referrer
+    );
+
+    Ok(())
+}
+
+pub fn handle_update_referral_campaign(
+    ctx: Context<UpdateReferralCampaign>,
+    params: ReferralCampaignParams,
+) -> Result<()> {
+    let mut campaign = load_mut!(ctx.accounts.referral_campaign)?;
+    let mut referrer_stats = load_mut!(ctx.accounts.referrer_stats)?;
+
+    msg!(
+        "campaign.budget: {} -> {} (budget_used {})",
+        campaign.budget,
+        params.budget,
+        campaign.budget_used
+    );
+
+    campaign.update(&params)?;
+
+    // fills clear the status once a campaign ends, so extending it has to set it again
+    referrer_stats.update_referral_campaign_status(true);
+
+    Ok(())
+}
+
+pub fn handle_delete_referral_campaign(ctx: Context<DeleteReferralCampaign>) -> Result<()> {
+    let mut referrer_stats = load_mut!(ctx.accounts.referrer_stats)?;
+    referrer_stats.update_referral_campaign_status(false);
+
+    Ok(())
+}
+
+pub fn handle_initialize_margin_correlations(
+    ctx: Context<InitializeMarginCorrelations>,
+) -> Result<()> {
+    let _margin_correlations = ctx.accounts.margin_correlations.load_init()?;
+
+    let state = &mut ctx.accounts.state;
+    msg!("Setting 6th bit to 1, enabling margin correlations");
+    state.feature_bit_flags = state.feature_bit_flags | (FeatureBitFlags::MarginCorrelations as u8);
+
+    Ok(())
+}
+
+pub fn handle_update_margin_correlation(
+    ctx: Context<UpdateMarginCorrelations>,
+    index: u16,
+    params: MarginCorrelationParams,
+) -> Result<()> {
+    let state = &ctx.accounts.state;
+    let mut margin_correlations = load_mut!(ctx.accounts.margin_correlations)?;
+
+    for (market_type, market_index) in [
+        (params.market_type_a, params.market_index_a),
+        (params.market_type_b, params.market_index_b),
+    ] {
+        let number_of_markets = match market_type {
+            MarketType::Perp => state.number_of_markets,
+            MarketType::Spot => state.number_of_spot_markets,
+        };
+
+        validate!(
+            market_index < number_of_markets,
+            ErrorCode::InvalidMarginCorrelation,
+            "{} market index {} doesnt exist",
+            market_type,
+            market_index
+        )?;
+    }
+
+    msg!(
+        "margin_correlations.correlations[{}]: {:?} -> {:?}",
+        index,
+        margin_correlations
+            .correlations
+            .get(index as usize)
+            .map(|c| c.correlation),
+        params.correlation
+    );
+
+    margin_correlations.update(index, &params)?;
+
+    Ok(())
+}
+
+pub fn handle_delete_margin_correlations(ctx: Context<DeleteMarginCorrelations>) -> Result<()> {
+    let state = &mut ctx.accounts.state;
+    msg!("Setting 6th bit to 0, disabling margin correlations");
+    state.feature_bit_flags =
+        state.feature_bit_flags & !(FeatureBitFlags::MarginCorrelations as u8);
+
+    Ok(())
+}
+
 #[access_control(
     deposit_not_paused(&ctx.accounts.state)
 )]
@@ -4785,6 +5173,8 @@ pub fn handle_admin_deposit<'c: 'info, 'info>(
         &get_writable_spot_market_set(market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mint = get_token_mint(remaining_accounts_iter)?;
@@ -5138,6 +5528,29 @@ pub fn handle_update_feature_bit_flags_builder_referral(
     Ok(())
 }
 
+pub fn handle_update_feature_bit_flags_portfolio_margin(
+    ctx: Context<HotAdminUpdateState>,
+    enable: bool,
+) -> Result<()> {
+    let state = &mut ctx.accounts.state;
+    if enable {
+        validate!(
+            ctx.accounts.admin.key().eq(&state.admin),
+            ErrorCode::DefaultError,
+            "Only state admin can enable feature bit flags"
+        )?;
+
+        msg!("Setting 5th bit to 1, enabling portfolio margin");
+        state.feature_bit_flags =
+            state.feature_bit_flags | (FeatureBitFlags::PortfolioMargin as u8);
+    } else {
+        msg!("Setting 5th bit to 0, disabling portfolio margin");
+        state.feature_bit_flags =
+            state.feature_bit_flags & !(FeatureBitFlags::PortfolioMargin as u8);
+    }
+    Ok(())
+}
+
 pub fn handle_update_feature_bit_flags_settle_lp_pool(
     ctx: Context<HotAdminUpdateState>,
     enable: bool,
@@ -5729,6 +6142,21 @@ pub struct RepegCurve<'info> {
     pub admin: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct ResizeState<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: the legacy state can't be loaded as State, checked in handler
+    #[account(
+        mut,
+        seeds = [b"drift_state".as_ref()],
+        bump,
+        owner = crate::ID,
+    )]
+    pub state: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct AdminUpdateState<'info> {
     pub admin: Signer<'info>,
@@ -6104,6 +6532,125 @@ pub struct UpdateProtectedMakerModeConfig<'info> {
     pub state: Box<Account<'info, State>>,
 }
 
+#[derive(Accounts)]
+pub struct InitializeReferralCampaign<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init,
+        seeds = [REFERRAL_CAMPAIGN_PDA_SEED.as_ref(), referrer_stats.load()?.authority.as_ref()],
+        space = ReferralCampaign::SIZE,
+        bump,
+        payer = admin
+    )]
+    pub referral_campaign: AccountLoader<'info, ReferralCampaign>,
+    #[account(mut)]
+    pub referrer_stats: AccountLoader<'info, UserStats>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateReferralCampaign<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [REFERRAL_CAMPAIGN_PDA_SEED.as_ref(), referral_campaign.load()?.referrer.as_ref()],
+        bump,
+    )]
+    pub referral_campaign: AccountLoader<'info, ReferralCampaign>,
+    #[account(
+        mut,
+        constraint = referrer_stats.load()?.authority.eq(&referral_campaign.load()?.referrer)
+    )]
+    pub referrer_stats: AccountLoader<'info, UserStats>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+}
+
+#[derive(Accounts)]
+pub struct DeleteReferralCampaign<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [REFERRAL_CAMPAIGN_PDA_SEED.as_ref(), referral_campaign.load()?.referrer.as_ref()],
+        bump,
+        close = admin
+    )]
+    pub referral_campaign: AccountLoader<'info, ReferralCampaign>,
+    #[account(
+        mut,
+        constraint = referrer_stats.load()?.authority.eq(&referral_campaign.load()?.referrer)
+    )]
+    pub referrer_stats: AccountLoader<'info, UserStats>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeMarginCorrelations<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init,
+        seeds = [MARGIN_CORRELATIONS_PDA_SEED.as_ref()],
+        space = MarginCorrelations::SIZE,
+        bump,
+        payer = admin
+    )]
+    pub margin_correlations: AccountLoader<'info, MarginCorrelations>,
+    #[account(
+        mut,
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarginCorrelations<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MARGIN_CORRELATIONS_PDA_SEED.as_ref()],
+        bump,
+    )]
+    pub margin_correlations: AccountLoader<'info, MarginCorrelations>,
+    #[account(
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+}
+
+#[derive(Accounts)]
+pub struct DeleteMarginCorrelations<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MARGIN_CORRELATIONS_PDA_SEED.as_ref()],
+        bump,
+        close = admin
+    )]
+    pub margin_correlations: AccountLoader<'info, MarginCorrelations>,
+    #[account(
+        mut,
+        has_one = admin
+    )]
+    pub state: Box<Account<'info, State>>,
+}
+
 #[derive(Accounts)]
 #[instruction(market_index: u16,)]
 pub struct AdminDeposit<'info> {
@@ -6,7 +6,9 @@ use anchor_lang::prelude::{AccountInfo, Pubkey};
 use anchor_spl::token_interface::Mint;
 
 use crate::error::ErrorCode;
+use crate::instructions::optional_accounts::get_delegate_permissions;
 use crate::msg;
+use crate::state::delegate_permissions::DelegatePermissions;
 use crate::state::insurance_fund_stake::InsuranceFundStake;
 use crate::state::perp_market::{MarketStatus, PerpMarket};
 use crate::state::spot_market::SpotMarket;
@@ -21,6 +23,48 @@ pub fn can_sign_for_user(user: &AccountLoader<User>, signer: &Signer) -> anchor_
     })
 }
 
+/// The authority and the user's delegate can sign for every order instruction. Any other signer
+/// needs a DelegatePermissions account for (user, signer) in the remaining accounts with the permissions
+pub fn can_sign_for_user_with_permissions<'a>(
+    user: &AccountLoader<User>,
+    signer: &Signer,
+    remaining_accounts: &'a [AccountInfo<'a>],
+    permissions: u8,
+) -> anchor_lang::Result<()> {
+    if let Some(delegate_permissions) =
+        get_signer_delegate_permissions(user, signer, remaining_accounts)?
+    {
+        validate!(
+            delegate_permissions.has_permissions(permissions),
+            ErrorCode::DelegatePermissionDenied,
+            "delegate {} lacks permissions {:#b} for user",
+            signer.key,
+            permissions
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Returns None if the signer is the authority or the user's delegate
+pub fn get_signer_delegate_permissions<'a>(
+    user: &AccountLoader<User>,
+    signer: &Signer,
+    remaining_accounts: &'a [AccountInfo<'a>],
+) -> anchor_lang::Result<Option<DelegatePermissions>> {
+    if can_sign_for_user(user, signer)? {
+        return Ok(None);
+    }
+
+    match get_delegate_permissions(remaining_accounts, &user.key(), signer.key)? {
+        Some(delegate_permissions) => Ok(Some(delegate_permissions)),
+        None => {
+            msg!("signer {} can not sign for user", signer.key);
+            Err(ErrorCode::DelegatePermissionDenied.into())
+        }
+    }
+}
+
 pub fn is_stats_for_user(
     user: &AccountLoader<User>,
     user_stats: &AccountLoader<UserStats>,
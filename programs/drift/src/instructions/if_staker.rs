@@ -384,6 +384,8 @@ pub fn handle_begin_insurance_fund_swap<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![in_market_index, out_market_index]),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let _token_interface = get_token_interface(remaining_accounts_iter)?;
@@ -615,6 +617,8 @@ pub fn handle_end_insurance_fund_swap<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![in_market_index, out_market_index]),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
     let out_token_program = get_token_interface(remaining_accounts)?;
 
@@ -781,6 +785,8 @@ pub fn handle_transfer_protocol_if_shares_to_revenue_pool<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![market_index]),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mint = get_token_mint(remaining_accounts)?;
@@ -7,6 +7,7 @@ use anchor_spl::associated_token::get_associated_token_address_with_program_id;
 use anchor_spl::token_interface::{TokenAccount, TokenInterface};
 use solana_program::instruction::Instruction;
 use solana_program::pubkey;
+use solana_program::secp256k1_program::ID as SECP256K1_ID;
 use solana_program::sysvar::instructions::{
     self, load_current_index_checked, load_instruction_at_checked, ID as IX_ID,
 };
@@ -22,7 +23,7 @@ use crate::controller::position::get_position_index;
 use crate::controller::position::PositionDirection;
 use crate::controller::spot_balance::update_spot_balances;
 use crate::controller::token::{receive, send_from_program_vault};
-use crate::error::ErrorCode;
+use crate::error::{DriftResult, ErrorCode};
 use crate::get_then_update_id;
 use crate::ids::admin_hot_wallet;
 use crate::ids::{
@@ -30,7 +31,9 @@ use crate::ids::{
     serum_program, titan_mainnet_argos_v1,
 };
 use crate::instructions::constraints::*;
+use crate::instructions::optional_accounts::get_referral_campaign;
 use crate::instructions::optional_accounts::get_revenue_share_escrow_account;
+use crate::instructions::optional_accounts::get_signed_msg_evm_signer;
 use crate::instructions::optional_accounts::{load_maps, AccountMaps};
 use crate::load_mut;
 use crate::math::casting::Cast;
@@ -41,11 +44,13 @@ use crate::math::constants::{
 use crate::math::lp_pool::perp_lp_pool_settlement;
 use crate::math::margin::get_margin_calculation_for_disable_high_leverage_mode;
 use crate::math::margin::{calculate_user_equity, meets_settle_pnl_maintenance_margin_requirement};
+use crate::math::oracle::{is_oracle_valid_for_action, DriftAction};
 use crate::math::orders::{
     estimate_price_from_side, filter_bids_asks_by_oracle_divergence, find_bids_and_asks_from_users,
 };
 use crate::math::position::calculate_base_asset_value_and_pnl_with_oracle_price;
 use crate::math::safe_math::SafeMath;
+use crate::math::safe_unwrap::SafeUnwrap;
 use crate::math::spot_balance::get_token_amount;
 use crate::math::spot_withdraw::validate_spot_market_vault_amount;
 use crate::optional_accounts::{get_token_mint, update_prelaunch_oracle};
@@ -65,7 +70,10 @@ use crate::state::lp_pool::LPPool;
 use crate::state::lp_pool::CONSTITUENT_PDA_SEED;
 use crate::state::lp_pool::SETTLE_AMM_ORACLE_MAX_DELAY;
 use crate::state::oracle_map::OracleMap;
-use crate::state::order_params::{OrderParams, PlaceOrderOptions};
+use crate::state::order_params::{
+    OrderParams, PlaceOrderOptions, SignedMsgCancelOrdersBeforeSlotMessage,
+    SignedMsgCancelOrdersMessage,
+};
 use crate::state::paused_operations::PerpLpOperation;
 use crate::state::paused_operations::{PerpOperation, SpotOperation};
 use crate::state::perp_market::{ContractType, MarketStatus, PerpMarket};
@@ -80,7 +88,7 @@ use crate::state::revenue_share_map::load_revenue_share_map;
 use crate::state::settle_pnl_mode::SettlePnlMode;
 use crate::state::signed_msg_user::{
     SignedMsgOrderId, SignedMsgUserOrdersLoader, SignedMsgUserOrdersZeroCopyMut,
-    SIGNED_MSG_PDA_SEED,
+    SIGNED_MSG_ORDER_SLOT_VALIDITY, SIGNED_MSG_PDA_SEED,
 };
 use crate::state::spot_fulfillment_params::SpotFulfillmentParams;
 use crate::state::spot_market::{SpotBalanceType, SpotMarket};
@@ -95,7 +103,11 @@ use crate::state::user_map::{load_user_map, load_user_maps, UserMap, UserStatsMa
 use crate::state::zero_copy::AccountZeroCopyMut;
 use crate::state::zero_copy::ZeroCopyLoader;
 use crate::validate;
-use crate::validation::sig_verification::verify_and_decode_ed25519_msg;
+use crate::validation::sig_verification::{
+    verify_and_decode_ed25519_msg, verify_and_decode_ed25519_signed_msg,
+    verify_and_decode_secp256k1_msg, SIGNED_MSG_CANCEL_ORDERS_BEFORE_SLOT_MESSAGE_DISCRIMINATOR,
+    SIGNED_MSG_CANCEL_ORDERS_MESSAGE_DISCRIMINATOR,
+};
 use crate::validation::user::{validate_user_deletion, validate_user_is_idle};
 use crate::{controller, load, math, print_error, safe_decrement, OracleSource};
 use crate::{math_error, ID};
@@ -162,6 +174,8 @@ fn fill_order<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let (makers_and_referrer, makers_and_referrer_stats) =
@@ -169,6 +183,15 @@ fn fill_order<'c: 'info, 'info>(
 
     let builder_codes_enabled = state.builder_codes_enabled();
     let builder_referral_enabled = state.builder_referral_enabled();
+    let referral_campaign = get_referral_campaign(
+        ctx.remaining_accounts,
+        &load!(ctx.accounts.user_stats)?.referrer,
+    )?;
+    let mut referral_campaign = referral_campaign
+        .as_ref()
+        .map(|referral_campaign| referral_campaign.load_mut())
+        .transpose()?;
+
     let mut escrow = if builder_codes_enabled || builder_referral_enabled {
         get_revenue_share_escrow_account(
             &mut remaining_accounts_iter,
@@ -203,6 +226,7 @@ fn fill_order<'c: 'info, 'info>(
         FillMode::Fill,
         &mut escrow.as_mut(),
         builder_referral_enabled,
+        &mut referral_campaign.as_deref_mut(),
     )?;
 
     Ok(())
@@ -290,6 +314,8 @@ fn fill_spot_order<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![QUOTE_SPOT_MARKET_INDEX, market_index]),
         Clock::get()?.slot,
         None,
+        ctx.accounts.state.margin_correlations_enabled(),
+        ctx.accounts.state.portfolio_margin_enabled(),
     )?;
 
     let (makers_and_referrer, makers_and_referrer_stats) = match fulfillment_type {
@@ -356,6 +382,7 @@ fn fill_spot_order<'c: 'info, 'info>(
         None,
         &clock,
         fulfillment_params.as_mut(),
+        FillMode::Fill,
     )?;
 
     let base_market = spot_market_map.get_ref(&market_index)?;
@@ -398,6 +425,8 @@ pub fn handle_trigger_order<'c: 'info, 'info>(
         &writeable_spot_markets,
         Clock::get()?.slot,
         None,
+        ctx.accounts.state.margin_correlations_enabled(),
+        ctx.accounts.state.portfolio_margin_enabled(),
     )?;
 
     match market_type {
@@ -442,6 +471,8 @@ pub fn handle_force_cancel_orders<'c: 'info, 'info>(
         &get_writable_spot_market_set(QUOTE_SPOT_MARKET_INDEX),
         Clock::get()?.slot,
         None,
+        ctx.accounts.state.margin_correlations_enabled(),
+        ctx.accounts.state.portfolio_margin_enabled(),
     )?;
 
     controller::orders::force_cancel_orders(
@@ -476,6 +507,8 @@ pub fn handle_update_user_idle<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         None,
+        ctx.accounts.state.margin_correlations_enabled(),
+        ctx.accounts.state.portfolio_margin_enabled(),
     )?;
 
     let (equity, _) =
@@ -510,6 +543,8 @@ pub fn handle_log_user_balances<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         None,
+        ctx.accounts.state.margin_correlations_enabled(),
+        ctx.accounts.state.portfolio_margin_enabled(),
     )?;
 
     let (equity, _) =
@@ -583,6 +618,8 @@ pub fn handle_update_user_fuel_bonus<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         None,
+        ctx.accounts.state.margin_correlations_enabled(),
+        ctx.accounts.state.portfolio_margin_enabled(),
     )?;
 
     let user_margin_calculation =
@@ -653,7 +690,6 @@ pub fn handle_place_signed_msg_taker_order<'c: 'info, 'info>(
     let state = &ctx.accounts.state;
 
     let mut remaining_accounts = ctx.remaining_accounts.iter().peekable();
-    // TODO: generalize to support multiple market types
     let AccountMaps {
         perp_market_map,
         mut spot_market_map,
@@ -664,6 +700,8 @@ pub fn handle_place_signed_msg_taker_order<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let high_leverage_mode_config = get_high_leverage_mode_config(&mut remaining_accounts)?;
@@ -673,6 +711,8 @@ pub fn handle_place_signed_msg_taker_order<'c: 'info, 'info>(
     let mut taker_stats = load_mut!(ctx.accounts.user_stats)?;
     let mut signed_msg_taker = ctx.accounts.signed_msg_user_orders.load_mut()?;
 
+    let evm_address = get_signed_msg_evm_signer(ctx.remaining_accounts, &taker.authority)?;
+
     let escrow = if state.builder_codes_enabled() {
         get_revenue_share_escrow_account(&mut remaining_accounts, &taker.authority)?
     } else {
@@ -691,6 +731,7 @@ pub fn handle_place_signed_msg_taker_order<'c: 'info, 'info>(
         &mut oracle_map,
         high_leverage_mode_config,
         escrow,
+        evm_address,
         state,
         is_delegate_signer,
     )?;
@@ -709,6 +750,7 @@ pub fn place_signed_msg_taker_order<'c: 'info, 'info>(
     oracle_map: &mut OracleMap,
     high_leverage_mode_config: Option<AccountLoader<HighLeverageModeConfig>>,
     escrow: Option<RevenueShareEscrowZeroCopyMut<'info>>,
+    evm_address: Option<[u8; 20]>,
     state: &State,
     is_delegate_signer: bool,
 ) -> Result<()> {
@@ -723,19 +765,38 @@ pub fn place_signed_msg_taker_order<'c: 'info, 'info>(
     // Verify data from verify ix
     let ix: Instruction = load_instruction_at_checked(ix_idx as usize - 1, ix_sysvar)?;
 
-    let signer = if is_delegate_signer {
-        taker.delegate.to_bytes()
+    let verified_message_and_signature = if ix.program_id == SECP256K1_ID {
+        // evm signers are linked to the authority, so they can't sign as the delegate
+        let evm_address = match evm_address {
+            Some(evm_address) if !is_delegate_signer => evm_address,
+            _ => {
+                msg!("SignedMsg secp256k1 order needs the authority's evm signer account");
+                return Err(print_error!(ErrorCode::InvalidSignedMsgEvmSigner)().into());
+            }
+        };
+
+        verify_and_decode_secp256k1_msg(
+            &ix,
+            ix_idx - 1,
+            &evm_address,
+            &taker_order_params_message_bytes[..],
+            is_delegate_signer,
+        )?
     } else {
-        taker.authority.to_bytes()
+        let signer = if is_delegate_signer {
+            taker.delegate.to_bytes()
+        } else {
+            taker.authority.to_bytes()
+        };
+        verify_and_decode_ed25519_msg(
+            &ix,
+            ix_sysvar,
+            ix_idx,
+            &signer,
+            &taker_order_params_message_bytes[..],
+            is_delegate_signer,
+        )?
     };
-    let verified_message_and_signature = verify_and_decode_ed25519_msg(
-        &ix,
-        ix_sysvar,
-        ix_idx,
-        &signer,
-        &taker_order_params_message_bytes[..],
-        is_delegate_signer,
-    )?;
 
     let mut escrow_zc: Option<RevenueShareEscrowZeroCopyMut<'info>> = None;
     let mut builder_fee_bps: Option<u16> = None;
@@ -809,16 +870,22 @@ pub fn place_signed_msg_taker_order<'c: 'info, 'info>(
 
     // First order must be a taker order
     let matching_taker_order_params = &verified_message_and_signature.signed_msg_order_params;
-    if matching_taker_order_params.market_type != MarketType::Perp
-        || !matching_taker_order_params.has_valid_auction_params()?
-    {
-        msg!("First order must be a perp taker order");
+    if !matching_taker_order_params.has_valid_auction_params()? {
+        msg!("First order must be a taker order");
         return Err(print_error!(ErrorCode::InvalidSignedMsgOrderParam)().into());
     }
 
+    let batch_order_params = &verified_message_and_signature.batch_order_params;
+    for params in batch_order_params.iter() {
+        if !params.has_valid_auction_params()? {
+            msg!("Batch orders must be taker orders");
+            return Err(print_error!(ErrorCode::InvalidSignedMsgOrderParam)().into());
+        }
+    }
+
     // Set max slot for the order early so we set correct signed msg order id
     let order_slot = verified_message_and_signature.slot;
-    if order_slot < clock.slot.saturating_sub(500) {
+    if order_slot < clock.slot.saturating_sub(SIGNED_MSG_ORDER_SLOT_VALIDITY) {
         msg!(
             "SignedMsg order slot {} is too old: must be within 500 slots of current slot",
             order_slot
@@ -826,21 +893,11 @@ pub fn place_signed_msg_taker_order<'c: 'info, 'info>(
         return Err(print_error!(ErrorCode::InvalidSignedMsgOrderParam)().into());
     }
     let market_index = matching_taker_order_params.market_index;
-    let max_slot = if matching_taker_order_params.order_type == OrderType::Limit {
-        order_slot.safe_add(
-            matching_taker_order_params
-                .auction_duration
-                .unwrap_or(0)
-                .cast::<u64>()?,
-        )?
-    } else {
-        order_slot.safe_add(
-            matching_taker_order_params
-                .auction_duration
-                .unwrap()
-                .cast::<u64>()?,
-        )?
-    };
+    // a batch is only placed while every order in it can be
+    let mut max_slot = get_signed_msg_order_max_slot(order_slot, matching_taker_order_params)?;
+    for params in batch_order_params.iter() {
+        max_slot = max_slot.min(get_signed_msg_order_max_slot(order_slot, params)?);
+    }
 
     // Dont place order if max slot already passed
     if max_slot < clock.slot {
@@ -863,6 +920,15 @@ pub fn place_signed_msg_taker_order<'c: 'info, 'info>(
         return Ok(());
     }
 
+    if signed_msg_account.is_signed_msg_order_slot_cancelled(order_slot) {
+        msg!(
+            "SignedMsg order slot {} cancelled for taker {:?}",
+            order_slot,
+            taker_key
+        );
+        return Ok(());
+    }
+
     if let Some(max_margin_ratio) = verified_message_and_signature.max_margin_ratio {
         taker.update_perp_position_max_margin_ratio(market_index, max_margin_ratio)?;
     }
@@ -1044,27 +1110,81 @@ pub fn place_signed_msg_taker_order<'c: 'info, 'info>(
         None
     };
 
-    controller::orders::place_perp_order(
-        state,
-        taker,
-        taker_key,
-        perp_market_map,
-        spot_market_map,
-        oracle_map,
-        &high_leverage_mode_config,
-        &clock,
-        *matching_taker_order_params,
-        PlaceOrderOptions {
-            enforce_margin_check: true,
+    // batches only check margin after the last order
+    let options = PlaceOrderOptions {
+        enforce_margin_check: batch_order_params.is_empty(),
+        signed_msg_taker_order_slot: Some(order_slot),
+        ..PlaceOrderOptions::default()
+    };
+
+    match matching_taker_order_params.market_type {
+        MarketType::Perp => controller::orders::place_perp_order(
+            state,
+            taker,
+            taker_key,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            &high_leverage_mode_config,
+            &clock,
+            *matching_taker_order_params,
+            options,
+            &mut builder_order,
+        )?,
+        // perp only params are rejected for spot in deserialize_into_verified_message
+        MarketType::Spot => controller::orders::place_spot_order(
+            state,
+            taker,
+            taker_key,
+            perp_market_map,
+            spot_market_map,
+            oracle_map,
+            &clock,
+            *matching_taker_order_params,
+            options,
+        )?,
+    };
+
+    let num_batch_orders = batch_order_params.len();
+    for (i, params) in batch_order_params.iter().enumerate() {
+        let options = PlaceOrderOptions {
+            enforce_margin_check: i == num_batch_orders - 1,
             signed_msg_taker_order_slot: Some(order_slot),
             ..PlaceOrderOptions::default()
-        },
-        &mut builder_order,
-    )?;
+        };
+
+        match params.market_type {
+            MarketType::Perp => controller::orders::place_perp_order(
+                state,
+                taker,
+                taker_key,
+                perp_market_map,
+                spot_market_map,
+                oracle_map,
+                &high_leverage_mode_config,
+                &clock,
+                *params,
+                options,
+                &mut None,
+            )?,
+            MarketType::Spot => controller::orders::place_spot_order(
+                state,
+                taker,
+                taker_key,
+                perp_market_map,
+                spot_market_map,
+                oracle_map,
+                &clock,
+                *params,
+                options,
+            )?,
+        };
+    }
 
     let order_params_hash =
         base64::encode(solana_program::hash::hash(&signature.try_to_vec().unwrap()).as_ref());
 
+    // batches are recorded under their first order
     emit!(SignedMsgOrderRecord {
         user: taker_key,
         signed_msg_order_max_slot: signed_msg_order_id.max_slot,
@@ -1082,6 +1202,237 @@ pub fn place_signed_msg_taker_order<'c: 'info, 'info>(
     Ok(())
 }
 
+fn get_signed_msg_order_max_slot(order_slot: u64, params: &OrderParams) -> DriftResult<u64> {
+    if params.order_type == OrderType::Limit {
+        order_slot.safe_add(params.auction_duration.unwrap_or(0).cast::<u64>()?)
+    } else {
+        order_slot.safe_add(params.auction_duration.safe_unwrap()?.cast::<u64>()?)
+    }
+}
+
+pub fn handle_cancel_signed_msg_orders<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, CancelSignedMsgOrders<'info>>,
+    signed_msg_cancel_orders_message_bytes: Vec<u8>,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let state = &ctx.accounts.state;
+
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        &mut ctx.remaining_accounts.iter().peekable(),
+        &MarketSet::new(),
+        &MarketSet::new(),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let user_key = ctx.accounts.user.key();
+    let authority = load!(ctx.accounts.user)?.authority;
+
+    let message: SignedMsgCancelOrdersMessage = verify_signed_msg_message(
+        &ctx.accounts.ix_sysvar,
+        &authority,
+        &signed_msg_cancel_orders_message_bytes,
+        SIGNED_MSG_CANCEL_ORDERS_MESSAGE_DISCRIMINATOR,
+    )?;
+    validate_signed_msg_cancel_message(
+        user_key,
+        &authority,
+        message.sub_account_id,
+        message.slot,
+        clock.slot,
+    )?;
+
+    validate!(
+        !message.uuids.is_empty() && message.uuids.len() <= 32,
+        ErrorCode::InvalidSignedMsgOrderParam,
+        "must cancel between 1 and 32 uuids"
+    )?;
+
+    // orders signed before the cancel can't be placed after this
+    let max_slot = message
+        .slot
+        .max(clock.slot)
+        .safe_add(SIGNED_MSG_ORDER_SLOT_VALIDITY)?;
+
+    let mut placed_order_ids = Vec::with_capacity(message.uuids.len());
+    let mut signed_msg_account = ctx.accounts.signed_msg_user_orders.load_mut()?;
+    for uuid in message.uuids.iter() {
+        if let Some(order_id) = signed_msg_account.cancel_signed_msg_order_id(*uuid, max_slot)? {
+            placed_order_ids.push(order_id);
+        }
+    }
+    drop(signed_msg_account);
+
+    // uuids are tracked per authority, so only cancel the user's orders that came from a signed msg
+    let order_ids_to_cancel: Vec<u32> = {
+        let user = load!(ctx.accounts.user)?;
+        placed_order_ids
+            .into_iter()
+            .filter(|order_id| {
+                user.orders.iter().any(|order| {
+                    order.order_id == *order_id
+                        && order.status == OrderStatus::Open
+                        && order.is_signed_msg()
+                })
+            })
+            .collect()
+    };
+
+    for order_id in order_ids_to_cancel {
+        controller::orders::cancel_order_by_order_id(
+            order_id,
+            &ctx.accounts.user,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            &clock,
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn handle_cancel_signed_msg_orders_before_slot<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, CancelSignedMsgOrders<'info>>,
+    signed_msg_cancel_orders_before_slot_message_bytes: Vec<u8>,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let state = &ctx.accounts.state;
+
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        &mut ctx.remaining_accounts.iter().peekable(),
+        &MarketSet::new(),
+        &MarketSet::new(),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let user_key = ctx.accounts.user.key();
+    let authority = load!(ctx.accounts.user)?.authority;
+
+    let message: SignedMsgCancelOrdersBeforeSlotMessage = verify_signed_msg_message(
+        &ctx.accounts.ix_sysvar,
+        &authority,
+        &signed_msg_cancel_orders_before_slot_message_bytes,
+        SIGNED_MSG_CANCEL_ORDERS_BEFORE_SLOT_MESSAGE_DISCRIMINATOR,
+    )?;
+    validate_signed_msg_cancel_message(
+        user_key,
+        &authority,
+        message.sub_account_id,
+        message.slot,
+        clock.slot,
+    )?;
+
+    validate!(
+        message.cancel_before_slot <= message.slot,
+        ErrorCode::InvalidSignedMsgOrderParam,
+        "cancel_before_slot {} can not be after message slot {}",
+        message.cancel_before_slot,
+        message.slot
+    )?;
+
+    ctx.accounts
+        .signed_msg_user_orders
+        .load_mut()?
+        .cancel_signed_msg_orders_before_slot(message.cancel_before_slot)?;
+
+    // signed msg orders keep the slot they were signed at
+    let order_ids_to_cancel: Vec<u32> = load!(ctx.accounts.user)?
+        .orders
+        .iter()
+        .filter(|order| {
+            order.status == OrderStatus::Open
+                && order.is_signed_msg()
+                && order.slot < message.cancel_before_slot
+        })
+        .map(|order| order.order_id)
+        .collect();
+
+    for order_id in order_ids_to_cancel {
+        controller::orders::cancel_order_by_order_id(
+            order_id,
+            &ctx.accounts.user,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            &clock,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn verify_signed_msg_message<T: AnchorDeserialize>(
+    ix_sysvar: &AccountInfo,
+    authority: &Pubkey,
+    message_bytes: &[u8],
+    discriminator: [u8; 8],
+) -> Result<T> {
+    let ix_idx = load_current_index_checked(ix_sysvar)?;
+    validate!(
+        ix_idx > 0,
+        ErrorCode::InvalidVerificationIxIndex,
+        "instruction index must be greater than 0 for one sig verifies"
+    )?;
+
+    let ix: Instruction = load_instruction_at_checked(ix_idx as usize - 1, ix_sysvar)?;
+
+    verify_and_decode_ed25519_signed_msg(
+        &ix,
+        ix_sysvar,
+        ix_idx,
+        &authority.to_bytes(),
+        message_bytes,
+        discriminator,
+    )
+}
+
+fn validate_signed_msg_cancel_message(
+    user_key: Pubkey,
+    authority: &Pubkey,
+    sub_account_id: u16,
+    message_slot: u64,
+    slot: u64,
+) -> Result<()> {
+    let user_pda = Pubkey::find_program_address(
+        &[
+            "user".as_bytes(),
+            &authority.to_bytes(),
+            &sub_account_id.to_le_bytes(),
+        ],
+        &ID,
+    );
+    validate!(
+        user_pda.0 == user_key,
+        ErrorCode::SignedMsgUserContextUserMismatch,
+        "User key does not match pda"
+    )?;
+
+    if message_slot < slot.saturating_sub(SIGNED_MSG_ORDER_SLOT_VALIDITY) {
+        msg!(
+            "SignedMsg cancel slot {} is too old: must be within {} slots of current slot",
+            message_slot,
+            SIGNED_MSG_ORDER_SLOT_VALIDITY
+        );
+        return Err(print_error!(ErrorCode::InvalidSignedMsgOrderParam)().into());
+    }
+
+    Ok(())
+}
+
 #[access_control(
     settle_pnl_not_paused(&ctx.accounts.state)
 )]
@@ -1112,6 +1463,8 @@ pub fn handle_settle_pnl<'c: 'info, 'info>(
         &get_writable_spot_market_set(QUOTE_SPOT_MARKET_INDEX),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let (mut builder_escrow, maybe_rev_share_map) =
@@ -1179,6 +1532,8 @@ pub fn handle_settle_pnl<'c: 'info, 'info>(
                     clock.unix_timestamp,
                     state.builder_codes_enabled(),
                     state.builder_referral_enabled(),
+                    &state.builder_tier_volume_thresholds,
+                    &state.builder_tier_rebate_pcts,
                 )?;
             } else {
                 msg!("Builder Users not provided, but RevenueEscrow was provided");
@@ -1234,6 +1589,8 @@ pub fn handle_settle_multiple_pnls<'c: 'info, 'info>(
         &get_writable_spot_market_set(QUOTE_SPOT_MARKET_INDEX),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let (mut builder_escrow, maybe_rev_share_map) =
@@ -1309,6 +1666,8 @@ pub fn handle_settle_multiple_pnls<'c: 'info, 'info>(
                         clock.unix_timestamp,
                         state.builder_codes_enabled(),
                         state.builder_referral_enabled(),
+                        &state.builder_tier_volume_thresholds,
+                        &state.builder_tier_rebate_pcts,
                     )?;
                 } else {
                     msg!("Builder Users not provided, but RevenueEscrow was provided");
@@ -1340,6 +1699,106 @@ pub fn handle_settle_multiple_pnls<'c: 'info, 'info>(
     Ok(())
 }
 
+/// Sweeps completed builder and referral fees from a user's RevenueShareEscrow without settling
+/// the user's pnl. Fees are taken from the user's perp pnl, so they only reach the market's pnl pool
+/// once the user owes the pool nothing. Markets where the user has negative unsettled pnl are skipped
+/// until settle_pnl realizes it
+#[access_control(
+    settle_pnl_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_sweep_revenue_share_escrow<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, SweepRevenueShareEscrow>,
+    market_indexes: Vec<u16>,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let state = &ctx.accounts.state;
+
+    validate!(
+        state.builder_codes_enabled() || state.builder_referral_enabled(),
+        ErrorCode::DefaultError,
+        "builder codes and builder referral are not enabled"
+    )?;
+
+    let user = &load!(ctx.accounts.user)?;
+
+    let mut remaining_accounts = ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        &mut remaining_accounts,
+        &get_writable_perp_market_set_from_vec(&market_indexes),
+        &get_writable_spot_market_set(QUOTE_SPOT_MARKET_INDEX),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let mut escrow = get_revenue_share_escrow_account(&mut remaining_accounts, &user.authority)?
+        .ok_or(ErrorCode::UnableToLoadRevenueShareAccount)?;
+    let rev_share_map = load_revenue_share_map(&mut remaining_accounts)?;
+
+    escrow.revoke_completed_orders(user)?;
+
+    for market_index in market_indexes.iter() {
+        if let Ok(perp_position) = user.get_perp_position(*market_index) {
+            let perp_market = perp_market_map.get_ref(market_index)?;
+            let (oracle_price_data, oracle_validity) = oracle_map.get_price_data_and_validity(
+                MarketType::Perp,
+                *market_index,
+                &perp_market.oracle_id(),
+                perp_market
+                    .amm
+                    .historical_oracle_data
+                    .last_oracle_price_twap,
+                perp_market.get_max_confidence_interval_multiplier()?,
+                0,
+                0,
+                None,
+            )?;
+            let oracle_price = oracle_price_data.price;
+
+            // same oracle checks as settle_pnl, since the sweep is gated on the user's pnl
+            if !is_oracle_valid_for_action(oracle_validity, Some(DriftAction::SettlePnl))?
+                || !perp_market.is_price_divergence_ok_for_settle_pnl(oracle_price)?
+            {
+                msg!(
+                    "oracle invalid ({}) for market {}, skipping fee sweep",
+                    oracle_validity,
+                    market_index
+                );
+                continue;
+            }
+            drop(perp_market);
+
+            if perp_position.get_unrealized_pnl(oracle_price)? < 0 {
+                msg!(
+                    "user has unsettled losses in market {}, settle pnl to sweep its fees",
+                    market_index
+                );
+                continue;
+            }
+        }
+
+        controller::revenue_share::sweep_completed_revenue_share_for_market(
+            *market_index,
+            &mut escrow,
+            &perp_market_map,
+            &spot_market_map,
+            &rev_share_map,
+            clock.unix_timestamp,
+            state.builder_codes_enabled(),
+            state.builder_referral_enabled(),
+            &state.builder_tier_volume_thresholds,
+            &state.builder_tier_rebate_pcts,
+        )?;
+    }
+
+    Ok(())
+}
+
 #[access_control(
     funding_not_paused(&ctx.accounts.state)
 )]
@@ -1360,6 +1819,8 @@ pub fn handle_settle_funding_payment<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         None,
+        ctx.accounts.state.margin_correlations_enabled(),
+        ctx.accounts.state.portfolio_margin_enabled(),
     )?;
 
     controller::funding::settle_funding_payments(user, &user_key, &perp_market_map, now)?;
@@ -1404,6 +1865,8 @@ pub fn handle_liquidate_perp<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     controller::liquidation::liquidate_perp(
@@ -1456,6 +1919,8 @@ pub fn handle_liquidate_perp_with_fill<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let (makers_and_referrer, makers_and_referrer_stats) =
@@ -1481,6 +1946,99 @@ pub fn handle_liquidate_perp_with_fill<'c: 'info, 'info>(
     Ok(())
 }
 
+#[access_control(
+liq_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_begin_liquidation_auction<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, BeginLiquidationAuction<'info>>,
+    market_index: u16,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let state = &ctx.accounts.state;
+
+    let user_key = ctx.accounts.user.key();
+    let keeper_key = ctx.accounts.keeper.key();
+
+    validate!(user_key != keeper_key, ErrorCode::UserCantLiquidateThemself)?;
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        remaining_accounts_iter,
+        &get_writable_perp_market_set(market_index),
+        &MarketSet::new(),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let user = &mut load_mut!(ctx.accounts.user)?;
+
+    controller::liquidation::begin_perp_liquidation_auction(
+        market_index,
+        user,
+        &user_key,
+        &keeper_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        &clock,
+        state,
+    )?;
+
+    Ok(())
+}
+
+#[access_control(
+    liq_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_deleverage_perp_position<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, DeleveragePerpPosition<'info>>,
+    market_index: u16,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let state = &ctx.accounts.state;
+
+    let user_key = ctx.accounts.user.key();
+    let keeper_key = ctx.accounts.keeper.key();
+
+    validate!(user_key != keeper_key, ErrorCode::UserCantLiquidateThemself)?;
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        remaining_accounts_iter,
+        &get_writable_perp_market_set(market_index),
+        &MarketSet::new(),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let user = &mut load_mut!(ctx.accounts.user)?;
+
+    controller::liquidation::deleverage_perp_position(
+        market_index,
+        user,
+        &user_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        &clock,
+        state,
+    )?;
+
+    Ok(())
+}
+
 #[access_control(
     liq_not_paused(&ctx.accounts.state)
 )]
@@ -1518,6 +2076,8 @@ pub fn handle_liquidate_spot<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![asset_market_index, liability_market_index]),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     controller::liquidation::liquidate_spot(
@@ -1579,6 +2139,8 @@ pub fn handle_liquidate_spot_with_swap_begin<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![asset_market_index, liability_market_index]),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let _token_interface = get_token_interface(remaining_accounts_iter)?;
@@ -1851,7 +2413,10 @@ pub fn handle_liquidate_spot_with_swap_end<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![asset_market_index, liability_market_index]),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
+
     let liability_token_program = get_token_interface(remaining_accounts)?;
 
     let asset_mint = get_token_mint(remaining_accounts)?;
@@ -2042,6 +2607,8 @@ pub fn handle_liquidate_borrow_for_perp_pnl<'c: 'info, 'info>(
         &get_writable_spot_market_set(spot_market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     controller::liquidation::liquidate_borrow_for_perp_pnl(
@@ -2101,6 +2668,8 @@ pub fn handle_liquidate_perp_pnl_for_deposit<'c: 'info, 'info>(
         &get_writable_spot_market_set(spot_market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     controller::liquidation::liquidate_perp_pnl_for_deposit(
@@ -2145,6 +2714,8 @@ pub fn handle_set_user_status_to_being_liquidated<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     controller::liquidation::set_user_status_to_being_liquidated(
@@ -2184,6 +2755,8 @@ pub fn handle_resolve_perp_pnl_deficit<'c: 'info, 'info>(
         &get_writable_spot_market_set(spot_market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mint = get_token_mint(remaining_accounts_iter)?;
@@ -2309,6 +2882,63 @@ pub fn handle_resolve_perp_pnl_deficit<'c: 'info, 'info>(
     Ok(())
 }
 
+#[access_control(
+    liq_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_auto_deleverage_perp_position<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, AutoDeleverage<'info>>,
+    quote_spot_market_index: u16,
+    market_index: u16,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let state = &ctx.accounts.state;
+
+    let user_key = ctx.accounts.user.key();
+    let keeper_key = ctx.accounts.keeper.key();
+
+    validate!(user_key != keeper_key, ErrorCode::UserCantLiquidateThemself)?;
+
+    validate!(
+        quote_spot_market_index == QUOTE_SPOT_MARKET_INDEX,
+        ErrorCode::InvalidSpotMarketAccount
+    )?;
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        remaining_accounts_iter,
+        &get_writable_perp_market_set(market_index),
+        &MarketSet::new(),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let counterparties = load_user_map(remaining_accounts_iter, true)?;
+
+    let user = &mut load_mut!(ctx.accounts.user)?;
+
+    controller::liquidation::auto_deleverage_perp_position(
+        market_index,
+        user,
+        &user_key,
+        &counterparties,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        clock.unix_timestamp,
+        clock.slot,
+        state,
+        ctx.accounts.insurance_fund_vault.amount,
+    )?;
+
+    Ok(())
+}
+
 #[access_control(
     withdraw_not_paused(&ctx.accounts.state)
 )]
@@ -2348,6 +2978,8 @@ pub fn handle_resolve_perp_bankruptcy<'c: 'info, 'info>(
         &get_writable_spot_market_set(quote_spot_market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mint = get_token_mint(remaining_accounts_iter)?;
@@ -2475,6 +3107,8 @@ pub fn handle_resolve_spot_bankruptcy<'c: 'info, 'info>(
         &get_writable_spot_market_set(market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mint = get_token_mint(remaining_accounts_iter)?;
@@ -2928,6 +3562,8 @@ pub fn handle_update_amms<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     controller::repeg::update_amms(&mut perp_market_map, &mut oracle_map, state, &clock)?;
@@ -3055,6 +3691,8 @@ pub fn handle_disable_user_high_leverage_mode<'c: 'info, 'info>(
         &MarketSet::new(),
         slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let in_high_leverage_mode = user.is_high_leverage_mode(MarginRequirementType::Maintenance);
@@ -3190,6 +3828,8 @@ pub fn handle_force_delete_user<'c: 'info, 'info>(
         &get_market_set_for_spot_positions(&user.spot_positions),
         slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     // check the user equity
@@ -3429,6 +4069,8 @@ pub fn handle_settle_perp_to_lp_pool<'c: 'info, 'info>(
         &MarketSet::new(),
         slot,
         None,
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     for (_, perp_market_loader) in perp_market_map.0.iter() {
@@ -3660,6 +4302,8 @@ pub fn handle_update_amm_cache<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         None,
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
     let slot = Clock::get()?.slot;
 
@@ -3884,6 +4528,13 @@ pub struct SettlePNL<'info> {
     pub spot_market_vault: Box<InterfaceAccount<'info, TokenAccount>>,
 }
 
+#[derive(Accounts)]
+pub struct SweepRevenueShareEscrow<'info> {
+    pub state: Box<Account<'info, State>>,
+    pub user: AccountLoader<'info, User>,
+    pub authority: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct PlaceSignedMsgTakerOrder<'info> {
     pub state: Box<Account<'info, State>>,
@@ -3910,6 +4561,27 @@ pub struct PlaceSignedMsgTakerOrder<'info> {
     pub ix_sysvar: AccountInfo<'info>,
 }
 
+#[derive(Accounts)]
+pub struct CancelSignedMsgOrders<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        seeds = [SIGNED_MSG_PDA_SEED.as_ref(), user.load()?.authority.as_ref()],
+        bump,
+    )]
+    /// CHECK: checked in SignedMsgUserOrdersZeroCopy checks
+    pub signed_msg_user_orders: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: The address check is needed because otherwise
+    /// the supplied Sysvar could be anything else.
+    /// The Instruction Sysvar has not been implemented
+    /// in the Anchor framework yet, so this is the safe approach.
+    #[account(address = IX_ID)]
+    pub ix_sysvar: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 pub struct SettleFunding<'info> {
     pub state: Box<Account<'info, State>>,
@@ -3947,6 +4619,30 @@ pub struct LiquidatePerp<'info> {
     pub user_stats: AccountLoader<'info, UserStats>,
 }
 
+#[derive(Accounts)]
+pub struct BeginLiquidationAuction<'info> {
+    pub state: Box<Account<'info, State>>,
+    pub authority: Signer<'info>,
+    #[account(
+        constraint = can_sign_for_user(&keeper, &authority)?
+    )]
+    pub keeper: AccountLoader<'info, User>,
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+}
+
+#[derive(Accounts)]
+pub struct DeleveragePerpPosition<'info> {
+    pub state: Box<Account<'info, State>>,
+    pub authority: Signer<'info>,
+    #[account(
+        constraint = can_sign_for_user(&keeper, &authority)?
+    )]
+    pub keeper: AccountLoader<'info, User>,
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+}
+
 #[derive(Accounts)]
 pub struct LiquidateSpot<'info> {
     pub state: Box<Account<'info, State>>,
@@ -4082,6 +4778,24 @@ pub struct LiquidateSpotWithSwap<'info> {
     pub instructions: UncheckedAccount<'info>,
 }
 
+#[derive(Accounts)]
+#[instruction(spot_market_index: u16,)]
+pub struct AutoDeleverage<'info> {
+    pub state: Box<Account<'info, State>>,
+    pub authority: Signer<'info>,
+    #[account(
+        constraint = can_sign_for_user(&keeper, &authority)?
+    )]
+    pub keeper: AccountLoader<'info, User>,
+    #[account(mut)]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        seeds = [b"insurance_fund_vault".as_ref(), spot_market_index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub insurance_fund_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+}
+
 #[derive(Accounts)]
 #[instruction(spot_market_index: u16,)]
 pub struct ResolveBankruptcy<'info> {
@@ -933,6 +933,8 @@ pub fn handle_update_initial_amm_cache_info<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         None,
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     for (_, perp_market_loader) in perp_market_map.0 {
@@ -171,6 +171,8 @@ pub fn handle_update_lp_pool_aum<'c: 'info, 'info>(
         &MarketSet::new(),
         slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let constituent_map =
@@ -309,6 +311,8 @@ pub fn handle_lp_pool_swap<'c: 'info, 'info>(
         &MarketSet::new(),
         slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let in_spot_market = spot_market_map.get_ref(&in_market_index)?;
@@ -548,6 +552,8 @@ pub fn handle_view_lp_pool_swap_fees<'c: 'info, 'info>(
         &MarketSet::new(),
         slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let in_spot_market = spot_market_map.get_ref(&in_market_index)?;
@@ -676,6 +682,8 @@ pub fn handle_lp_pool_add_liquidity<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![in_market_index]),
         slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let whitelist_mint = &lp_pool.whitelist_mint;
@@ -912,6 +920,8 @@ pub fn handle_view_lp_pool_add_liquidity_fees<'c: 'info, 'info>(
         &MarketSet::new(),
         slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let in_spot_market = spot_market_map.get_ref(&in_market_index)?;
@@ -1051,6 +1061,8 @@ pub fn handle_lp_pool_remove_liquidity<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![out_market_index]),
         slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mut out_spot_market = spot_market_map.get_ref_mut(&out_market_index)?;
@@ -1314,6 +1326,8 @@ pub fn handle_view_lp_pool_remove_liquidity_fees<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![out_market_index]),
         slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let out_spot_market = spot_market_map.get_ref_mut(&out_market_index)?;
@@ -1,5 +1,7 @@
 use crate::error::{DriftResult, ErrorCode};
+use crate::state::delegate_permissions::DelegatePermissions;
 use crate::state::high_leverage_mode_config::HighLeverageModeConfig;
+use crate::state::margin_correlations::MarginCorrelations;
 use crate::state::revenue_share::{
     RevenueShareEscrow, RevenueShareEscrowLoader, RevenueShareEscrowZeroCopyMut,
 };
@@ -14,10 +16,13 @@ use crate::state::oracle::PrelaunchOracle;
 use crate::state::oracle_map::OracleMap;
 use crate::state::perp_market::PerpMarket;
 use crate::state::perp_market_map::{MarketSet, PerpMarketMap};
+use crate::state::referral_campaign::{ReferralCampaign, REFERRAL_CAMPAIGN_PDA_SEED};
+use crate::state::signed_msg_user::{SignedMsgEvmSigner, SIGNED_MSG_EVM_SIGNER_PDA_SEED};
 use crate::state::spot_market_map::SpotMarketMap;
 use crate::state::state::OracleGuardRails;
 use crate::state::traits::Size;
 use crate::state::user::{User, UserStats};
+use crate::state::withdraw_policy::WithdrawPolicy;
 use crate::{validate, OracleSource};
 use anchor_lang::accounts::account::Account;
 use anchor_lang::prelude::{AccountInfo, Interface, Pubkey};
@@ -37,16 +42,28 @@ pub struct AccountMaps<'a> {
     pub oracle_map: OracleMap<'a>,
 }
 
+/// The margin correlations are required once enabled, otherwise a keeper or liquidator could leave
+/// them out to act on a user without their margin credit
 pub fn load_maps<'a, 'b>(
     account_info_iter: &mut Peekable<Iter<'a, AccountInfo<'a>>>,
     writable_perp_markets: &'b MarketSet,
     writable_spot_markets: &'b MarketSet,
     slot: u64,
     oracle_guard_rails: Option<OracleGuardRails>,
+    margin_correlations_required: bool,
+    portfolio_margin_enabled: bool,
 ) -> DriftResult<AccountMaps<'a>> {
-    let oracle_map = OracleMap::load(account_info_iter, slot, oracle_guard_rails)?;
+    let mut oracle_map = OracleMap::load(account_info_iter, slot, oracle_guard_rails)?;
     let spot_market_map = SpotMarketMap::load(writable_spot_markets, account_info_iter)?;
     let perp_market_map = PerpMarketMap::load(writable_perp_markets, account_info_iter)?;
+    oracle_map.margin_correlations = get_margin_correlations(account_info_iter)?;
+    oracle_map.portfolio_margin_enabled = portfolio_margin_enabled;
+
+    validate!(
+        !margin_correlations_required || oracle_map.margin_correlations.is_some(),
+        ErrorCode::MissingMarginCorrelations,
+        "margin correlations must be passed after the perp markets"
+    )?;
 
     for perp_market_index in writable_perp_markets.iter() {
         update_prelaunch_oracle(
@@ -277,6 +294,205 @@ pub fn get_high_leverage_mode_config<'a>(
     Ok(Some(high_leverage_mode_config))
 }
 
+/// Loads the margin correlations if they're passed right after the perp markets
+pub fn get_margin_correlations<'a>(
+    account_info_iter: &mut Peekable<Iter<'a, AccountInfo<'a>>>,
+) -> DriftResult<Option<Box<MarginCorrelations>>> {
+    let margin_correlations_account_info = account_info_iter.peek();
+    if margin_correlations_account_info.is_none() {
+        return Ok(None);
+    }
+
+    let margin_correlations_account_info = margin_correlations_account_info.safe_unwrap()?;
+
+    let data = margin_correlations_account_info
+        .try_borrow_data()
+        .map_err(|e| {
+            msg!("{:?}", e);
+            ErrorCode::CouldNotDeserializeMarginCorrelations
+        })?;
+
+    if data.len() < MarginCorrelations::SIZE {
+        return Ok(None);
+    }
+
+    let margin_correlations_discriminator: [u8; 8] = MarginCorrelations::discriminator();
+    let account_discriminator = array_ref![data, 0, 8];
+    if account_discriminator != &margin_correlations_discriminator {
+        return Ok(None);
+    }
+
+    drop(data);
+
+    let margin_correlations_account_info = account_info_iter.next().safe_unwrap()?;
+
+    let margin_correlations: AccountLoader<MarginCorrelations> =
+        AccountLoader::try_from(margin_correlations_account_info)
+            .or(Err(ErrorCode::CouldNotDeserializeMarginCorrelations))?;
+
+    let margin_correlations = *margin_correlations
+        .load()
+        .or(Err(ErrorCode::CouldNotDeserializeMarginCorrelations))?;
+
+    Ok(Some(Box::new(margin_correlations)))
+}
+
+/// Finds the DelegatePermissions for (user, delegate) anywhere in the remaining accounts. The market
+/// and oracle loaders stop at accounts they don't recognize, so it's passed last
+pub fn get_delegate_permissions<'a>(
+    account_infos: &'a [AccountInfo<'a>],
+    user_key: &Pubkey,
+    delegate: &Pubkey,
+) -> DriftResult<Option<DelegatePermissions>> {
+    let delegate_permissions_discriminator: [u8; 8] = DelegatePermissions::discriminator();
+
+    for account_info in account_infos.iter() {
+        if account_info.data_len() < DelegatePermissions::SIZE {
+            continue;
+        }
+
+        let data = account_info.try_borrow_data().map_err(|e| {
+            msg!("{:?}", e);
+            ErrorCode::CouldNotDeserializeDelegatePermissions
+        })?;
+
+        let account_discriminator = array_ref![data, 0, 8];
+        if account_discriminator != &delegate_permissions_discriminator {
+            continue;
+        }
+
+        drop(data);
+
+        // checks the account is owned by the program
+        let delegate_permissions_loader: AccountLoader<DelegatePermissions> =
+            AccountLoader::try_from(account_info)
+                .or(Err(ErrorCode::CouldNotDeserializeDelegatePermissions))?;
+
+        let delegate_permissions = *delegate_permissions_loader
+            .load()
+            .or(Err(ErrorCode::CouldNotDeserializeDelegatePermissions))?;
+
+        if delegate_permissions.is_for(user_key, delegate) {
+            return Ok(Some(delegate_permissions));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Finds the referrer's ReferralCampaign PDA anywhere in the remaining accounts. Fills update its
+/// budget, so it must be writable. Pass it last so it doesn't interrupt the maker/escrow accounts
+pub fn get_referral_campaign<'a>(
+    account_infos: &'a [AccountInfo<'a>],
+    referrer: &Pubkey,
+) -> DriftResult<Option<AccountLoader<'a, ReferralCampaign>>> {
+    if *referrer == Pubkey::default() {
+        return Ok(None);
+    }
+
+    let (referral_campaign_key, _) = Pubkey::find_program_address(
+        &[REFERRAL_CAMPAIGN_PDA_SEED.as_bytes(), referrer.as_ref()],
+        &crate::id(),
+    );
+
+    let account_info = match account_infos
+        .iter()
+        .find(|account_info| account_info.key == &referral_campaign_key)
+    {
+        Some(account_info) => account_info,
+        None => return Ok(None),
+    };
+
+    validate!(
+        account_info.is_writable,
+        ErrorCode::InvalidReferralCampaign,
+        "referral campaign must be writable"
+    )?;
+
+    // checks the account is owned by the program
+    let referral_campaign_loader: AccountLoader<ReferralCampaign> =
+        AccountLoader::try_from(account_info)
+            .or(Err(ErrorCode::CouldNotDeserializeReferralCampaign))?;
+
+    Ok(Some(referral_campaign_loader))
+}
+
+/// Finds the authority's WithdrawPolicy anywhere in the remaining accounts
+pub fn get_withdraw_policy<'a>(
+    account_infos: &'a [AccountInfo<'a>],
+    authority: &Pubkey,
+) -> DriftResult<Option<WithdrawPolicy>> {
+    let withdraw_policy_discriminator: [u8; 8] = WithdrawPolicy::discriminator();
+
+    for account_info in account_infos.iter() {
+        if account_info.data_len() < WithdrawPolicy::SIZE {
+            continue;
+        }
+
+        let data = account_info.try_borrow_data().map_err(|e| {
+            msg!("{:?}", e);
+            ErrorCode::CouldNotDeserializeWithdrawPolicy
+        })?;
+
+        let account_discriminator = array_ref![data, 0, 8];
+        if account_discriminator != &withdraw_policy_discriminator {
+            continue;
+        }
+
+        drop(data);
+
+        // checks the account is owned by the program
+        let withdraw_policy_loader: AccountLoader<WithdrawPolicy> =
+            AccountLoader::try_from(account_info)
+                .or(Err(ErrorCode::CouldNotDeserializeWithdrawPolicy))?;
+
+        let withdraw_policy = *withdraw_policy_loader
+            .load()
+            .or(Err(ErrorCode::CouldNotDeserializeWithdrawPolicy))?;
+
+        if withdraw_policy.authority.eq(authority) {
+            return Ok(Some(withdraw_policy));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Finds the authority's SignedMsgEvmSigner PDA in the remaining accounts. Only the PDA derived from
+/// the authority is accepted
+pub fn get_signed_msg_evm_signer<'a>(
+    account_infos: &'a [AccountInfo<'a>],
+    authority: &Pubkey,
+) -> DriftResult<Option<[u8; 20]>> {
+    let (signed_msg_evm_signer_key, _) = Pubkey::find_program_address(
+        &[
+            SIGNED_MSG_EVM_SIGNER_PDA_SEED.as_bytes(),
+            authority.as_ref(),
+        ],
+        &crate::id(),
+    );
+
+    let account_info = match account_infos
+        .iter()
+        .find(|account_info| account_info.key == &signed_msg_evm_signer_key)
+    {
+        Some(account_info) => account_info,
+        None => return Ok(None),
+    };
+
+    // checks the account is owned by the program
+    let signed_msg_evm_signer: Account<SignedMsgEvmSigner> = Account::try_from(account_info)
+        .or(Err(ErrorCode::CouldNotDeserializeSignedMsgEvmSigner))?;
+
+    validate!(
+        signed_msg_evm_signer.authority.eq(authority),
+        ErrorCode::InvalidSignedMsgEvmSigner,
+        "evm signer authority mismatch"
+    )?;
+
+    Ok(Some(signed_msg_evm_signer.evm_address))
+}
+
 pub fn get_revenue_share_escrow_account<'a>(
     account_info_iter: &mut Peekable<Iter<'a, AccountInfo<'a>>>,
     expected_authority: &Pubkey,
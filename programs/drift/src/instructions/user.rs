@@ -21,21 +21,25 @@ use crate::controller::spot_position::{
     update_spot_balances_and_cumulative_deposits,
     update_spot_balances_and_cumulative_deposits_with_limits,
 };
-use crate::error::ErrorCode;
+use crate::error::{DriftResult, ErrorCode};
 use crate::get_then_update_id;
 use crate::ids::admin_hot_wallet;
 use crate::ids::{
     lighthouse, marinade_mainnet, WHITELISTED_EXTERNAL_DEPOSITORS, WHITELISTED_SWAP_PROGRAMS,
 };
 use crate::instructions::constraints::*;
+use crate::instructions::optional_accounts::get_referral_campaign;
 use crate::instructions::optional_accounts::get_revenue_share_escrow_account;
 use crate::instructions::optional_accounts::{
-    get_referrer_and_referrer_stats, get_whitelist_token, load_maps, AccountMaps,
+    get_referrer_and_referrer_stats, get_whitelist_token, get_withdraw_policy, load_maps,
+    AccountMaps,
 };
 use crate::instructions::SpotFulfillmentType;
 use crate::load;
 use crate::math::casting::Cast;
-use crate::math::constants::{QUOTE_SPOT_MARKET_INDEX, THIRTEEN_DAY};
+use crate::math::constants::{
+    BASE_PRECISION_U64, MAX_OPEN_ORDERS, QUOTE_SPOT_MARKET_INDEX, THIRTEEN_DAY,
+};
 use crate::math::liquidation::is_cross_margin_being_liquidated;
 use crate::math::margin::calculate_margin_requirement_and_total_collateral_and_liability_info;
 use crate::math::margin::meets_initial_margin_requirement;
@@ -48,11 +52,13 @@ use crate::math::oracle::is_oracle_valid_for_action;
 use crate::math::oracle::DriftAction;
 use crate::math::oracle::LogMode;
 use crate::math::orders::calculate_existing_position_fields_for_order_action;
+use crate::math::orders::calculate_fill_price;
 use crate::math::orders::get_position_delta_for_fill;
 use crate::math::orders::is_multiple_of_step_size;
 use crate::math::orders::standardize_price_i64;
 use crate::math::position::calculate_base_asset_value_with_oracle_price;
 use crate::math::safe_math::SafeMath;
+use crate::math::safe_unwrap::SafeUnwrap;
 use crate::math::spot_balance::get_token_value;
 use crate::math::spot_swap;
 use crate::math::spot_swap::{calculate_swap_price, validate_price_bands_for_swap};
@@ -61,13 +67,17 @@ use crate::optional_accounts::{get_token_interface, get_token_mint};
 use crate::print_error;
 use crate::safe_decrement;
 use crate::safe_increment;
+use crate::state::delegate_permissions::{
+    DelegatePermissionFlag, DelegatePermissions, DelegatePermissionsParams,
+    DELEGATE_PERMISSIONS_PDA_SEED,
+};
 use crate::state::events::emit_stack;
 use crate::state::events::OrderAction;
 use crate::state::events::OrderActionRecord;
 use crate::state::events::OrderRecord;
 use crate::state::events::{
     DepositDirection, DepositExplanation, DepositRecord, FuelSeasonRecord, FuelSweepRecord,
-    NewUserRecord, OrderActionExplanation, SwapRecord,
+    NewUserRecord, OrderActionExplanation, SwapRecord, WithdrawPolicyAction, WithdrawPolicyRecord,
 };
 use crate::state::fill_mode::FillMode;
 use crate::state::fulfillment_params::drift::MatchFulfillmentParams;
@@ -79,11 +89,14 @@ use crate::state::margin_calculation::MarginContext;
 use crate::state::oracle::StrictOraclePrice;
 use crate::state::order_params::{
     parse_optional_params, ModifyOrderParams, OrderParams, PlaceAndTakeOrderSuccessCondition,
-    PlaceOrderOptions, PostOnlyParam,
+    PlaceOrderOptions, PostOnlyParam, ReplaceOrderParams,
 };
+use crate::state::pair_order_params::PairOrderParams;
 use crate::state::paused_operations::{PerpOperation, SpotOperation};
 use crate::state::perp_market::MarketStatus;
-use crate::state::perp_market_map::{get_writable_perp_market_set, MarketSet};
+use crate::state::perp_market_map::{
+    get_writable_perp_market_set, get_writable_perp_market_set_from_vec, MarketSet,
+};
 use crate::state::protected_maker_mode_config::ProtectedMakerModeConfig;
 use crate::state::revenue_share::BuilderInfo;
 use crate::state::revenue_share::RevenueShare;
@@ -96,6 +109,7 @@ use crate::state::signed_msg_user::SignedMsgOrderId;
 use crate::state::signed_msg_user::SignedMsgUserOrdersLoader;
 use crate::state::signed_msg_user::SignedMsgWsDelegates;
 use crate::state::signed_msg_user::SIGNED_MSG_WS_PDA_SEED;
+use crate::state::signed_msg_user::{SignedMsgEvmSigner, SIGNED_MSG_EVM_SIGNER_PDA_SEED};
 use crate::state::signed_msg_user::{SignedMsgUserOrders, SIGNED_MSG_PDA_SEED};
 use crate::state::spot_fulfillment_params::SpotFulfillmentParams;
 use crate::state::spot_market::SpotBalanceType;
@@ -109,10 +123,15 @@ use crate::state::user::Order;
 use crate::state::user::OrderStatus;
 use crate::state::user::ReferrerStatus;
 use crate::state::user::{
-    FuelOverflow, FuelOverflowProvider, MarketType, OrderType, ReferrerName, User, UserStats,
+    FuelOverflow, FuelOverflowProvider, MarginMode, MarketType, OrderType, ReferrerName, User,
+    UserStats,
 };
 use crate::state::user_map::{load_user_maps, UserMap, UserStatsMap};
+use crate::state::withdraw_policy::{
+    WithdrawPolicy, WithdrawPolicyChange, WithdrawPolicyParams, WITHDRAW_POLICY_PDA_SEED,
+};
 use crate::validate;
+use crate::validation::order::validate_order_group;
 use crate::validation::position::validate_perp_position_with_perp_market;
 use crate::validation::user::validate_user_deletion;
 use crate::validation::whitelist::validate_whitelist_token;
@@ -369,6 +388,40 @@ pub fn handle_resize_signed_msg_user_orders<'c: 'info, 'info>(
     Ok(())
 }
 
+pub fn handle_migrate_user_order_layout<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, MigrateUserOrderLayout<'info>>,
+) -> Result<()> {
+    let user = &ctx.accounts.user;
+
+    {
+        let data = user.try_borrow_data()?;
+        validate!(
+            data.len() == User::LEGACY_SIZE && data[..8] == User::discriminator(),
+            ErrorCode::InvalidUserAccount,
+            "user account is already migrated or not a user account"
+        )?;
+    }
+
+    let rent_needed = Rent::get()?
+        .minimum_balance(User::SIZE)
+        .saturating_sub(user.lamports());
+    if rent_needed > 0 {
+        invoke(
+            &transfer(&ctx.accounts.payer.key(), &user.key(), rent_needed),
+            &[
+                ctx.accounts.payer.to_account_info(),
+                user.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+        )?;
+    }
+
+    user.realloc(User::SIZE, false)?;
+    User::migrate_legacy_order_layout(&mut user.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
 pub fn handle_initialize_signed_msg_ws_delegates<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, InitializeSignedMsgWsDelegates<'info>>,
     delegates: Vec<Pubkey>,
@@ -400,6 +453,29 @@ pub fn handle_change_signed_msg_ws_delegate_status<'c: 'info, 'info>(
     Ok(())
 }
 
+pub fn handle_initialize_signed_msg_evm_signer<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, InitializeSignedMsgEvmSigner<'info>>,
+    evm_address: [u8; 20],
+) -> Result<()> {
+    validate!(
+        evm_address != [0; 20],
+        ErrorCode::InvalidSignedMsgEvmSigner,
+        "evm address must be non zero"
+    )?;
+
+    let signed_msg_evm_signer = &mut ctx.accounts.signed_msg_evm_signer;
+    signed_msg_evm_signer.authority = ctx.accounts.authority.key();
+    signed_msg_evm_signer.evm_address = evm_address;
+
+    Ok(())
+}
+
+pub fn handle_delete_signed_msg_evm_signer<'c: 'info, 'info>(
+    _ctx: Context<'_, '_, 'c, 'info, DeleteSignedMsgEvmSigner<'info>>,
+) -> Result<()> {
+    Ok(())
+}
+
 pub fn handle_initialize_fuel_overflow<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, InitializeFuelOverflow<'info>>,
 ) -> Result<()> {
@@ -533,6 +609,8 @@ pub fn handle_initialize_revenue_share<'c: 'info, 'info>(
     revenue_share.authority = ctx.accounts.authority.key();
     revenue_share.total_referrer_rewards = 0;
     revenue_share.total_builder_rewards = 0;
+    revenue_share.builder_volume_30d = 0;
+    revenue_share.last_builder_volume_30d_ts = 0;
     Ok(())
 }
 
@@ -690,6 +768,8 @@ pub fn handle_deposit<'c: 'info, 'info>(
         &get_writable_spot_market_set(market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mint = get_token_mint(remaining_accounts_iter)?;
@@ -791,7 +871,7 @@ pub fn handle_deposit<'c: 'info, 'info>(
         )?;
 
         if !is_being_liquidated {
-            user.exit_cross_margin_liquidation();
+            user.exit_cross_margin_liquidation()?;
         }
     }
 
@@ -891,10 +971,19 @@ pub fn handle_withdraw<'c: 'info, 'info>(
         &get_writable_spot_market_set(market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mint = get_token_mint(remaining_accounts_iter)?;
 
+    validate_withdraw_policy(
+        &user_stats,
+        ctx.remaining_accounts,
+        &ctx.accounts.user_token_account.key(),
+        now,
+    )?;
+
     validate!(!user.is_bankrupt(), ErrorCode::UserBankrupt)?;
 
     let spot_market_is_reduce_only = {
@@ -975,7 +1064,7 @@ pub fn handle_withdraw<'c: 'info, 'info>(
     validate_spot_margin_trading(user, &perp_market_map, &spot_market_map, &mut oracle_map)?;
 
     if user.is_cross_margin_being_liquidated() {
-        user.exit_cross_margin_liquidation();
+        user.exit_cross_margin_liquidation()?;
     }
 
     user.update_last_active_slot(slot);
@@ -1084,6 +1173,8 @@ pub fn handle_transfer_deposit<'c: 'info, 'info>(
         "cant transfer between the same user account"
     )?;
 
+    validate_withdraw_policy(user_stats, ctx.remaining_accounts, &to_user_key, now)?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
@@ -1094,6 +1185,8 @@ pub fn handle_transfer_deposit<'c: 'info, 'info>(
         &get_writable_spot_market_set(market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     {
@@ -1156,7 +1249,7 @@ pub fn handle_transfer_deposit<'c: 'info, 'info>(
     )?;
 
     if from_user.is_cross_margin_being_liquidated() {
-        from_user.exit_cross_margin_liquidation();
+        from_user.exit_cross_margin_liquidation()?;
     }
 
     from_user.update_last_active_slot(slot);
@@ -1319,6 +1412,13 @@ pub fn handle_transfer_pools<'c: 'info, 'info>(
         "cant transfer between the same pool"
     )?;
 
+    validate_withdraw_policy(
+        user_stats,
+        ctx.remaining_accounts,
+        &to_user_key,
+        clock.unix_timestamp,
+    )?;
+
     let AccountMaps {
         perp_market_map,
         spot_market_map,
@@ -1334,6 +1434,8 @@ pub fn handle_transfer_pools<'c: 'info, 'info>(
         ]),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mut deposit_from_spot_market = spot_market_map.get_ref_mut(&deposit_from_market_index)?;
@@ -1657,11 +1759,11 @@ pub fn handle_transfer_pools<'c: 'info, 'info>(
     to_user.update_last_active_slot(slot);
 
     if from_user.is_cross_margin_being_liquidated() {
-        from_user.exit_cross_margin_liquidation();
+        from_user.exit_cross_margin_liquidation()?;
     }
 
     if to_user.is_cross_margin_being_liquidated() {
-        to_user.exit_cross_margin_liquidation();
+        to_user.exit_cross_margin_liquidation()?;
     }
 
     let deposit_from_spot_market = spot_market_map.get_ref(&deposit_from_market_index)?;
@@ -1756,6 +1858,35 @@ pub fn handle_transfer_pools<'c: 'info, 'info>(
     Ok(())
 }
 
+/// Authorities with a withdraw policy can only move funds out of a sub account to its destinations
+fn validate_withdraw_policy<'a>(
+    user_stats: &UserStats,
+    remaining_accounts: &'a [AccountInfo<'a>],
+    destination: &Pubkey,
+    now: i64,
+) -> Result<()> {
+    if !user_stats.has_withdraw_policy() {
+        return Ok(());
+    }
+
+    match get_withdraw_policy(remaining_accounts, &user_stats.authority)? {
+        Some(withdraw_policy) => {
+            validate!(
+                withdraw_policy.is_destination_allowed(destination, now),
+                ErrorCode::WithdrawDestinationNotAllowed,
+                "withdraw to {} blocked by withdraw policy",
+                destination
+            )?;
+        }
+        None => {
+            msg!("withdraw policy for {} not found", user_stats.authority);
+            return Err(ErrorCode::WithdrawPolicyNotFound.into());
+        }
+    }
+
+    Ok(())
+}
+
 #[access_control(
     fill_not_paused(&ctx.accounts.state)
 )]
@@ -1805,6 +1936,8 @@ pub fn handle_transfer_perp_position<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     controller::repeg::update_amm(
@@ -2101,6 +2234,7 @@ pub fn handle_transfer_perp_position<'c: 'info, 'info>(
         trigger_price: None,
         builder_idx: None,
         builder_fee: None,
+        replaced_order_id: None,
     };
 
     emit_stack::<_, { OrderActionRecord::SIZE }>(fill_record)?;
@@ -2136,6 +2270,8 @@ pub fn handle_deposit_into_isolated_perp_position<'c: 'info, 'info>(
         &get_writable_spot_market_set(spot_market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mint = get_token_mint(remaining_accounts_iter)?;
@@ -2218,6 +2354,8 @@ pub fn handle_transfer_isolated_perp_position_deposit<'c: 'info, 'info>(
         &get_writable_spot_market_set(spot_market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     controller::isolated_position::transfer_isolated_perp_position_deposit(
@@ -2270,6 +2408,8 @@ pub fn handle_withdraw_from_isolated_perp_position<'c: 'info, 'info>(
         &get_writable_spot_market_set(spot_market_index),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let mint = get_token_mint(remaining_accounts_iter)?;
@@ -2319,6 +2459,7 @@ pub fn handle_withdraw_from_isolated_perp_position<'c: 'info, 'info>(
 
 #[access_control(
     exchange_not_paused(&ctx.accounts.state)
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::PlaceOrders as u8)
 )]
 pub fn handle_place_perp_order<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, PlaceOrder>,
@@ -2338,6 +2479,8 @@ pub fn handle_place_perp_order<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let high_leverage_mode_config = get_high_leverage_mode_config(&mut remaining_accounts)?;
@@ -2347,6 +2490,12 @@ pub fn handle_place_perp_order<'c: 'info, 'info>(
         return Err(print_error!(ErrorCode::InvalidOrderIOC)().into());
     }
 
+    let delegate_permissions = get_signer_delegate_permissions(
+        &ctx.accounts.user,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+
     let user_key = ctx.accounts.user.key();
     let mut user = load_mut!(ctx.accounts.user)?;
 
@@ -2360,7 +2509,10 @@ pub fn handle_place_perp_order<'c: 'info, 'info>(
         &high_leverage_mode_config,
         clock,
         params,
-        PlaceOrderOptions::default(),
+        PlaceOrderOptions {
+            delegate_permissions,
+            ..PlaceOrderOptions::default()
+        },
         &mut None,
     )?;
 
@@ -2369,6 +2521,7 @@ pub fn handle_place_perp_order<'c: 'info, 'info>(
 
 #[access_control(
     exchange_not_paused(&ctx.accounts.state)
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::CancelOrders as u8)
 )]
 pub fn handle_cancel_order<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, CancelOrder>,
@@ -2387,6 +2540,8 @@ pub fn handle_cancel_order<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let order_id = match order_id {
@@ -2394,6 +2549,17 @@ pub fn handle_cancel_order<'c: 'info, 'info>(
         None => load!(ctx.accounts.user)?.get_last_order_id(),
     };
 
+    let delegate_permissions = get_signer_delegate_permissions(
+        &ctx.accounts.user,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    validate_delegate_can_cancel_order(
+        &ctx.accounts.user,
+        &delegate_permissions,
+        ModifyOrderId::OrderId(order_id),
+    )?;
+
     controller::orders::cancel_order_by_order_id(
         order_id,
         &ctx.accounts.user,
@@ -2408,6 +2574,7 @@ pub fn handle_cancel_order<'c: 'info, 'info>(
 
 #[access_control(
     exchange_not_paused(&ctx.accounts.state)
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::CancelOrders as u8)
 )]
 pub fn handle_cancel_order_by_user_id<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, CancelOrder>,
@@ -2426,6 +2593,19 @@ pub fn handle_cancel_order_by_user_id<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let delegate_permissions = get_signer_delegate_permissions(
+        &ctx.accounts.user,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    validate_delegate_can_cancel_order(
+        &ctx.accounts.user,
+        &delegate_permissions,
+        ModifyOrderId::UserOrderId(user_order_id),
     )?;
 
     controller::orders::cancel_order_by_user_order_id(
@@ -2442,6 +2622,7 @@ pub fn handle_cancel_order_by_user_id<'c: 'info, 'info>(
 
 #[access_control(
     exchange_not_paused(&ctx.accounts.state)
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::CancelOrders as u8)
 )]
 pub fn handle_cancel_orders_by_ids<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, CancelOrder>,
@@ -2460,9 +2641,23 @@ pub fn handle_cancel_orders_by_ids<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let delegate_permissions = get_signer_delegate_permissions(
+        &ctx.accounts.user,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
     )?;
 
     for order_id in order_ids {
+        validate_delegate_can_cancel_order(
+            &ctx.accounts.user,
+            &delegate_permissions,
+            ModifyOrderId::OrderId(order_id),
+        )?;
+
         controller::orders::cancel_order_by_order_id(
             order_id,
             &ctx.accounts.user,
@@ -2478,6 +2673,7 @@ pub fn handle_cancel_orders_by_ids<'c: 'info, 'info>(
 
 #[access_control(
     exchange_not_paused(&ctx.accounts.state)
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::CancelOrders as u8)
 )]
 pub fn handle_cancel_orders<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, CancelOrder<'info>>,
@@ -2498,8 +2694,18 @@ pub fn handle_cancel_orders<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
+    if let Some(delegate_permissions) = get_signer_delegate_permissions(
+        &ctx.accounts.user,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )? {
+        delegate_permissions.validate_cancel_orders_filter(market_type, market_index)?;
+    }
+
     let user_key = ctx.accounts.user.key();
     let mut user = load_mut!(ctx.accounts.user)?;
 
@@ -2524,6 +2730,7 @@ pub fn handle_cancel_orders<'c: 'info, 'info>(
 
 #[access_control(
     exchange_not_paused(&ctx.accounts.state)
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::PlaceOrders as u8 | DelegatePermissionFlag::CancelOrders as u8)
 )]
 pub fn handle_modify_order<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, CancelOrder<'info>>,
@@ -2543,6 +2750,8 @@ pub fn handle_modify_order<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let order_id = match order_id {
@@ -2550,6 +2759,17 @@ pub fn handle_modify_order<'c: 'info, 'info>(
         None => load!(ctx.accounts.user)?.get_last_order_id(),
     };
 
+    let delegate_permissions = get_signer_delegate_permissions(
+        &ctx.accounts.user,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    validate_delegate_can_cancel_order(
+        &ctx.accounts.user,
+        &delegate_permissions,
+        ModifyOrderId::OrderId(order_id),
+    )?;
+
     controller::orders::modify_order(
         ModifyOrderId::OrderId(order_id),
         modify_order_params,
@@ -2559,6 +2779,7 @@ pub fn handle_modify_order<'c: 'info, 'info>(
         &spot_market_map,
         &mut oracle_map,
         clock,
+        delegate_permissions,
     )?;
 
     Ok(())
@@ -2566,6 +2787,7 @@ pub fn handle_modify_order<'c: 'info, 'info>(
 
 #[access_control(
     exchange_not_paused(&ctx.accounts.state)
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::PlaceOrders as u8 | DelegatePermissionFlag::CancelOrders as u8)
 )]
 pub fn handle_modify_order_by_user_order_id<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, CancelOrder<'info>>,
@@ -2585,6 +2807,19 @@ pub fn handle_modify_order_by_user_order_id<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let delegate_permissions = get_signer_delegate_permissions(
+        &ctx.accounts.user,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    validate_delegate_can_cancel_order(
+        &ctx.accounts.user,
+        &delegate_permissions,
+        ModifyOrderId::UserOrderId(user_order_id),
     )?;
 
     controller::orders::modify_order(
@@ -2596,13 +2831,40 @@ pub fn handle_modify_order_by_user_order_id<'c: 'info, 'info>(
         &spot_market_map,
         &mut oracle_map,
         clock,
+        delegate_permissions,
     )?;
 
     Ok(())
 }
 
+/// Scoped delegates can only cancel (and modify) orders in their markets
+fn validate_delegate_can_cancel_order(
+    user: &AccountLoader<User>,
+    delegate_permissions: &Option<DelegatePermissions>,
+    order_id: ModifyOrderId,
+) -> DriftResult {
+    if let Some(delegate_permissions) = delegate_permissions {
+        let user = load!(user)?;
+        let order_index = match order_id {
+            ModifyOrderId::OrderId(order_id) => user.get_order_index(order_id),
+            ModifyOrderId::UserOrderId(user_order_id) => {
+                user.get_order_index_by_user_order_id(user_order_id)
+            }
+        };
+
+        // missing orders are handled by the cancel or modify itself
+        if let Ok(order_index) = order_index {
+            let order = &user.orders[order_index];
+            delegate_permissions.validate_market(order.market_type, order.market_index)?;
+        }
+    }
+
+    Ok(())
+}
+
 #[access_control(
     exchange_not_paused(&ctx.accounts.state)
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::PlaceOrders as u8)
 )]
 pub fn handle_place_orders<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, PlaceOrder>,
@@ -2613,6 +2875,7 @@ pub fn handle_place_orders<'c: 'info, 'info>(
 
 #[access_control(
     exchange_not_paused(&ctx.accounts.state)
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::PlaceOrders as u8)
 )]
 pub fn handle_place_scale_orders<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, PlaceOrder>,
@@ -2647,6 +2910,8 @@ fn place_orders<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let high_leverage_mode_config = get_high_leverage_mode_config(&mut remaining_accounts)?;
@@ -2681,9 +2946,22 @@ fn place_orders<'c: 'info, 'info>(
         "max 32 order params"
     )?;
 
+    let delegate_permissions = get_signer_delegate_permissions(
+        &ctx.accounts.user,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+
     let user_key = ctx.accounts.user.key();
     let mut user = load_mut!(ctx.accounts.user)?;
 
+    let order_group = if validate_order_group(&order_params)? {
+        user.get_next_order_group()?
+    } else {
+        0
+    };
+    let has_bracket_entry = order_params.iter().any(|params| params.is_bracket_entry());
+
     let num_orders = order_params.len();
     for (i, params) in order_params.iter().enumerate() {
         validate!(
@@ -2692,6 +2970,8 @@ fn place_orders<'c: 'info, 'info>(
             "immediate_or_cancel order must be in place_and_make or place_and_take"
         )?;
 
+        let is_grouped = params.is_order_group();
+
         // only enforce margin on last order and only try to expire on first order
         let options = PlaceOrderOptions {
             signed_msg_taker_order_slot: None,
@@ -2700,6 +2980,9 @@ fn place_orders<'c: 'info, 'info>(
             risk_increasing: false,
             explanation: OrderActionExplanation::None,
             existing_position_direction_override: None,
+            order_group: if is_grouped { order_group } else { 0 },
+            bracket_pending: is_grouped && has_bracket_entry && !params.is_bracket_entry(),
+            delegate_permissions,
         };
 
         if params.market_type == MarketType::Perp {
@@ -2734,6 +3017,188 @@ fn place_orders<'c: 'info, 'info>(
     Ok(())
 }
 
+#[access_control(
+    exchange_not_paused(&ctx.accounts.state)
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::PlaceOrders as u8 | DelegatePermissionFlag::CancelOrders as u8)
+)]
+pub fn handle_replace_orders<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, PlaceOrder>,
+    params: Vec<ReplaceOrderParams>,
+) -> Result<()> {
+    let clock = &Clock::get()?;
+    let state = &ctx.accounts.state;
+
+    let mut remaining_accounts = ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        &mut remaining_accounts,
+        &MarketSet::new(),
+        &MarketSet::new(),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let high_leverage_mode_config = get_high_leverage_mode_config(&mut remaining_accounts)?;
+
+    validate!(
+        params.len() <= MAX_OPEN_ORDERS as usize,
+        ErrorCode::TooManyReplaceOrderParams,
+        "max {} replace order params",
+        MAX_OPEN_ORDERS
+    )?;
+
+    let delegate_permissions = get_signer_delegate_permissions(
+        &ctx.accounts.user,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+
+    let user_key = ctx.accounts.user.key();
+    let mut user = load_mut!(ctx.accounts.user)?;
+
+    let num_orders = params.len();
+    for (i, replace_params) in params.iter().enumerate() {
+        let user_order_id = replace_params.user_order_id;
+        let mut order_params = replace_params.order_params;
+        order_params.user_order_id = user_order_id;
+
+        validate!(
+            user_order_id != 0,
+            ErrorCode::InvalidOrder,
+            "user_order_id must be set to replace an order"
+        )?;
+
+        validate!(
+            !order_params.is_immediate_or_cancel(),
+            ErrorCode::InvalidOrderIOC,
+            "immediate_or_cancel order must be in place_and_make or place_and_take"
+        )?;
+
+        validate!(
+            !order_params.is_order_group() && !order_params.is_bracket_entry(),
+            ErrorCode::InvalidOrder,
+            "replacement orders can not be grouped"
+        )?;
+
+        let old_order_index = user.orders.iter().position(|order| {
+            order.user_order_id == user_order_id && order.status == OrderStatus::Open
+        });
+
+        // cancel first so the replacement can reuse the slot
+        let old_order = match old_order_index {
+            Some(order_index) => {
+                let old_order = user.orders[order_index];
+
+                if let Some(delegate_permissions) = &delegate_permissions {
+                    delegate_permissions
+                        .validate_market(old_order.market_type, old_order.market_index)?;
+                }
+
+                validate!(
+                    old_order.order_group == 0,
+                    ErrorCode::InvalidOrder,
+                    "order {} is part of an order group and can not be replaced",
+                    old_order.order_id
+                )?;
+
+                controller::orders::cancel_order(
+                    order_index,
+                    &mut user,
+                    &user_key,
+                    &perp_market_map,
+                    &spot_market_map,
+                    &mut oracle_map,
+                    clock.unix_timestamp,
+                    clock.slot,
+                    OrderActionExplanation::OrderReplaced,
+                    None,
+                    0,
+                    false,
+                )?;
+
+                Some(old_order)
+            }
+            None => {
+                msg!(
+                    "no open order with user order id {}, placing without replacing",
+                    user_order_id
+                );
+                None
+            }
+        };
+
+        // only enforce margin on last order and only try to expire on first order
+        let options = PlaceOrderOptions {
+            enforce_margin_check: i == num_orders - 1,
+            try_expire_orders: i == 0,
+            delegate_permissions,
+            ..PlaceOrderOptions::default()
+        };
+
+        let (new_order_id, oracle_id) = if order_params.market_type == MarketType::Perp {
+            let new_order_id = controller::orders::place_perp_order(
+                state,
+                &mut user,
+                user_key,
+                &perp_market_map,
+                &spot_market_map,
+                &mut oracle_map,
+                &high_leverage_mode_config,
+                clock,
+                order_params,
+                options,
+                &mut None,
+            )?;
+
+            (
+                new_order_id,
+                perp_market_map
+                    .get_ref(&order_params.market_index)?
+                    .oracle_id(),
+            )
+        } else {
+            let new_order_id = controller::orders::place_spot_order(
+                state,
+                &mut user,
+                user_key,
+                &perp_market_map,
+                &spot_market_map,
+                &mut oracle_map,
+                clock,
+                order_params,
+                options,
+            )?;
+
+            (
+                new_order_id,
+                spot_market_map
+                    .get_ref(&order_params.market_index)?
+                    .oracle_id(),
+            )
+        };
+
+        // the old order stays cancelled if the replacement was skipped, e.g. a failed try post only
+        if let (Some(old_order), Some(new_order_id)) = (old_order, new_order_id) {
+            let new_order_index = user.get_order_index(new_order_id)?;
+
+            controller::orders::emit_replace_order_record(
+                clock.unix_timestamp,
+                &user_key,
+                &old_order,
+                &user.orders[new_order_index],
+                oracle_map.get_price_data(&oracle_id)?.price,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
 #[access_control(
     fill_not_paused(&ctx.accounts.state)
 )]
@@ -2756,6 +3221,8 @@ pub fn handle_place_and_take_perp_order<'c: 'info, 'info>(
         &MarketSet::new(),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     if params.post_only != PostOnlyParam::None {
@@ -2769,6 +3236,7 @@ pub fn handle_place_and_take_perp_order<'c: 'info, 'info>(
     let high_leverage_mode_config = get_high_leverage_mode_config(remaining_accounts_iter)?;
 
     let is_immediate_or_cancel = params.is_immediate_or_cancel();
+    let time_in_force = params.get_time_in_force();
 
     controller::repeg::update_amm(
         params.market_index,
@@ -2805,6 +3273,15 @@ pub fn handle_place_and_take_perp_order<'c: 'info, 'info>(
 
     let builder_referral_enabled = state.builder_referral_enabled();
     let builder_codes_enabled = state.builder_codes_enabled();
+    let referral_campaign = get_referral_campaign(
+        ctx.remaining_accounts,
+        &load!(ctx.accounts.user_stats)?.referrer,
+    )?;
+    let mut referral_campaign = referral_campaign
+        .as_ref()
+        .map(|referral_campaign| referral_campaign.load_mut())
+        .transpose()?;
+
     let mut escrow = if builder_codes_enabled || builder_referral_enabled {
         get_revenue_share_escrow_account(remaining_accounts_iter, &load!(user)?.authority)?
     } else {
@@ -2825,12 +3302,10 @@ pub fn handle_place_and_take_perp_order<'c: 'info, 'info>(
         &makers_and_referrer_stats,
         None,
         &Clock::get()?,
-        FillMode::PlaceAndTake(
-            is_immediate_or_cancel || optional_params.is_some(),
-            auction_duration_percentage,
-        ),
+        FillMode::PlaceAndTake(time_in_force, auction_duration_percentage),
         &mut escrow.as_mut(),
         builder_referral_enabled,
+        &mut referral_campaign.as_deref_mut(),
     )?;
 
     let order_unfilled = load!(ctx.accounts.user)?
@@ -2838,6 +3313,11 @@ pub fn handle_place_and_take_perp_order<'c: 'info, 'info>(
         .iter()
         .any(|order| order.order_id == order_id && order.status == OrderStatus::Open);
 
+    let order_filled = load!(ctx.accounts.user)?
+        .orders
+        .iter()
+        .any(|order| order.order_id == order_id && order.status == OrderStatus::Filled);
+
     if is_immediate_or_cancel && order_unfilled {
         controller::orders::cancel_order_by_order_id(
             order_id,
@@ -2857,7 +3337,7 @@ pub fn handle_place_and_take_perp_order<'c: 'info, 'info>(
         )?;
     } else if success_condition == PlaceAndTakeOrderSuccessCondition::FullFill as u8 {
         validate!(
-            base_asset_amount_filled > 0 && !order_unfilled,
+            base_asset_amount_filled > 0 && order_filled,
             ErrorCode::PlaceAndTakeOrderSuccessConditionFailed,
             "no full fill"
         )?;
@@ -2888,9 +3368,12 @@ pub fn handle_place_and_make_perp_order<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     if !params.is_immediate_or_cancel()
+        || params.is_fill_or_kill()
         || params.post_only == PostOnlyParam::None
         || params.order_type != OrderType::Limit
     {
@@ -2934,6 +3417,15 @@ pub fn handle_place_and_make_perp_order<'c: 'info, 'info>(
 
     let builder_referral_enabled = state.builder_referral_enabled();
     let builder_codes_enabled = state.builder_codes_enabled();
+    let referral_campaign = get_referral_campaign(
+        ctx.remaining_accounts,
+        &load!(ctx.accounts.taker_stats)?.referrer,
+    )?;
+    let mut referral_campaign = referral_campaign
+        .as_ref()
+        .map(|referral_campaign| referral_campaign.load_mut())
+        .transpose()?;
+
     let mut escrow = if builder_codes_enabled || builder_referral_enabled {
         get_revenue_share_escrow_account(
             remaining_accounts_iter,
@@ -2960,6 +3452,7 @@ pub fn handle_place_and_make_perp_order<'c: 'info, 'info>(
         FillMode::PlaceAndMake,
         &mut escrow.as_mut(),
         builder_referral_enabled,
+        &mut referral_campaign.as_deref_mut(),
     )?;
 
     let order_exists = load!(ctx.accounts.user)?
@@ -3003,9 +3496,12 @@ pub fn handle_place_and_make_signed_msg_perp_order<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     if !params.is_immediate_or_cancel()
+        || params.is_fill_or_kill()
         || params.post_only == PostOnlyParam::None
         || params.order_type != OrderType::Limit
     {
@@ -3049,6 +3545,15 @@ pub fn handle_place_and_make_signed_msg_perp_order<'c: 'info, 'info>(
 
     let builder_referral_enabled = state.builder_referral_enabled();
     let builder_codes_enabled = state.builder_codes_enabled();
+    let referral_campaign = get_referral_campaign(
+        ctx.remaining_accounts,
+        &load!(ctx.accounts.taker_stats)?.referrer,
+    )?;
+    let mut referral_campaign = referral_campaign
+        .as_ref()
+        .map(|referral_campaign| referral_campaign.load_mut())
+        .transpose()?;
+
     let mut escrow = if builder_codes_enabled || builder_referral_enabled {
         get_revenue_share_escrow_account(
             remaining_accounts_iter,
@@ -3082,6 +3587,7 @@ pub fn handle_place_and_make_signed_msg_perp_order<'c: 'info, 'info>(
         FillMode::PlaceAndMake,
         &mut escrow.as_mut(),
         builder_referral_enabled,
+        &mut referral_campaign.as_deref_mut(),
     )?;
 
     let order_exists = load!(ctx.accounts.user)?
@@ -3103,6 +3609,9 @@ pub fn handle_place_and_make_signed_msg_perp_order<'c: 'info, 'info>(
     Ok(())
 }
 
+#[access_control(
+    can_sign_for_user_with_permissions(&ctx.accounts.user, &ctx.accounts.authority, ctx.remaining_accounts, DelegatePermissionFlag::PlaceOrders as u8)
+)]
 pub fn handle_place_spot_order<'c: 'info, 'info>(
     ctx: Context<'_, '_, 'c, 'info, PlaceOrder>,
     params: OrderParams,
@@ -3117,6 +3626,8 @@ pub fn handle_place_spot_order<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         None,
+        ctx.accounts.state.margin_correlations_enabled(),
+        ctx.accounts.state.portfolio_margin_enabled(),
     )?;
 
     if params.is_immediate_or_cancel() {
@@ -3124,6 +3635,12 @@ pub fn handle_place_spot_order<'c: 'info, 'info>(
         return Err(print_error!(ErrorCode::InvalidOrderIOC)().into());
     }
 
+    let delegate_permissions = get_signer_delegate_permissions(
+        &ctx.accounts.user,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+
     let user_key = ctx.accounts.user.key();
     let mut user = load_mut!(ctx.accounts.user)?;
 
@@ -3136,7 +3653,10 @@ pub fn handle_place_spot_order<'c: 'info, 'info>(
         &mut oracle_map,
         &Clock::get()?,
         params,
-        PlaceOrderOptions::default(),
+        PlaceOrderOptions {
+            delegate_permissions,
+            ..PlaceOrderOptions::default()
+        },
     )?;
 
     Ok(())
@@ -3165,6 +3685,8 @@ pub fn handle_place_and_take_spot_order<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![QUOTE_SPOT_MARKET_INDEX, market_index]),
         clock.slot,
         None,
+        ctx.accounts.state.margin_correlations_enabled(),
+        ctx.accounts.state.portfolio_margin_enabled(),
     )?;
 
     if params.post_only != PostOnlyParam::None {
@@ -3178,6 +3700,7 @@ pub fn handle_place_and_take_spot_order<'c: 'info, 'info>(
     };
 
     let is_immediate_or_cancel = params.is_immediate_or_cancel();
+    let time_in_force = params.get_time_in_force();
 
     let mut fulfillment_params: Box<dyn SpotFulfillmentParams> = match fulfillment_type {
         SpotFulfillmentType::SerumV3 => {
@@ -3265,6 +3788,7 @@ pub fn handle_place_and_take_spot_order<'c: 'info, 'info>(
         None,
         &clock,
         fulfillment_params.as_mut(),
+        FillMode::PlaceAndTake(time_in_force, 100),
     )?;
 
     let order_unfilled = load!(ctx.accounts.user)?
@@ -3293,15 +3817,27 @@ pub fn handle_place_and_take_spot_order<'c: 'info, 'info>(
 #[access_control(
     fill_not_paused(&ctx.accounts.state)
 )]
-pub fn handle_place_and_make_spot_order<'c: 'info, 'info>(
-    ctx: Context<'_, '_, 'c, 'info, PlaceAndMake<'info>>,
-    params: OrderParams,
-    taker_order_id: u32,
-    fulfillment_type: SpotFulfillmentType,
+pub fn handle_place_and_take_pair_orders<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, PlaceAndTake<'info>>,
+    params: PairOrderParams,
 ) -> Result<()> {
-    let clock = &Clock::get()?;
+    let clock = Clock::get()?;
     let state = &ctx.accounts.state;
 
+    params.validate()?;
+    let legs = params.get_leg_order_params();
+
+    let perp_market_indexes = legs
+        .iter()
+        .filter(|leg| leg.market_type == MarketType::Perp)
+        .map(|leg| leg.market_index)
+        .collect::<Vec<u16>>();
+    let spot_leg = legs.iter().find(|leg| leg.market_type == MarketType::Spot);
+    let mut spot_market_indexes = vec![QUOTE_SPOT_MARKET_INDEX];
+    if let Some(spot_leg) = spot_leg {
+        spot_market_indexes.push(spot_leg.market_index);
+    }
+
     let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
     let AccountMaps {
         perp_market_map,
@@ -3309,15 +3845,392 @@ pub fn handle_place_and_make_spot_order<'c: 'info, 'info>(
         mut oracle_map,
     } = load_maps(
         remaining_accounts_iter,
-        &MarketSet::new(),
-        &get_writable_spot_market_set_from_many(vec![QUOTE_SPOT_MARKET_INDEX, params.market_index]),
-        Clock::get()?.slot,
-        None,
+        &get_writable_perp_market_set_from_vec(&perp_market_indexes),
+        &get_writable_spot_market_set_from_many(spot_market_indexes),
+        clock.slot,
+        Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
-    let (_referrer, _referrer_stats) = get_referrer_and_referrer_stats(remaining_accounts_iter)?;
-
+    let (makers_and_referrer, makers_and_referrer_stats) =
+        load_user_maps(remaining_accounts_iter, true)?;
+
+    let high_leverage_mode_config = get_high_leverage_mode_config(remaining_accounts_iter)?;
+
+    let builder_referral_enabled = state.builder_referral_enabled();
+    let builder_codes_enabled = state.builder_codes_enabled();
+    let referral_campaign = get_referral_campaign(
+        ctx.remaining_accounts,
+        &load!(ctx.accounts.user_stats)?.referrer,
+    )?;
+    let mut referral_campaign = referral_campaign
+        .as_ref()
+        .map(|referral_campaign| referral_campaign.load_mut())
+        .transpose()?;
+
+    let mut escrow = if builder_codes_enabled || builder_referral_enabled {
+        get_revenue_share_escrow_account(
+            remaining_accounts_iter,
+            &load!(ctx.accounts.user)?.authority,
+        )?
+    } else {
+        None
+    };
+
+    // spot legs only fill against drift makers, so the market vaults are the last remaining accounts
+    let mut fulfillment_params: Option<Box<dyn SpotFulfillmentParams>> = match spot_leg {
+        Some(spot_leg) => {
+            let base_market = spot_market_map.get_ref(&spot_leg.market_index)?;
+            let quote_market = spot_market_map.get_quote_spot_market()?;
+            Some(Box::new(MatchFulfillmentParams::new(
+                remaining_accounts_iter,
+                &base_market,
+                &quote_market,
+            )?))
+        }
+        None => None,
+    };
+
+    // margin is only checked once both legs have been placed and filled
+    let leg_place_options = PlaceOrderOptions {
+        enforce_margin_check: false,
+        ..PlaceOrderOptions::default()
+    };
+
+    let user_key = ctx.accounts.user.key();
+    let mut leg_fill_prices = [0_u64; 2];
+    for (i, leg) in legs.iter().enumerate() {
+        if leg.market_type == MarketType::Perp {
+            controller::repeg::update_amm(
+                leg.market_index,
+                &perp_market_map,
+                &mut oracle_map,
+                state,
+                &clock,
+            )?;
+        }
+
+        let mut user = load_mut!(ctx.accounts.user)?;
+        let order_id_before = user.get_last_order_id();
+
+        match leg.market_type {
+            MarketType::Perp => controller::orders::place_perp_order(
+                state,
+                &mut user,
+                user_key,
+                &perp_market_map,
+                &spot_market_map,
+                &mut oracle_map,
+                &high_leverage_mode_config,
+                &clock,
+                *leg,
+                leg_place_options.clone(),
+                &mut None,
+            )?,
+            MarketType::Spot => controller::orders::place_spot_order(
+                state,
+                &mut user,
+                user_key,
+                &perp_market_map,
+                &spot_market_map,
+                &mut oracle_map,
+                &clock,
+                *leg,
+                leg_place_options.clone(),
+            )?,
+        };
+
+        let order_id = user.get_last_order_id();
+        drop(user);
+
+        if order_id == order_id_before {
+            msg!("pair order leg {} failed to be placed", i);
+            return Err(print_error!(ErrorCode::InvalidPairOrder)().into());
+        }
+
+        // fill or kill, so either leg failing to completely fill reverts both
+        let user = &mut ctx.accounts.user;
+        match leg.market_type {
+            MarketType::Perp => {
+                controller::orders::fill_perp_order(
+                    order_id,
+                    state,
+                    user,
+                    &ctx.accounts.user_stats,
+                    &spot_market_map,
+                    &perp_market_map,
+                    &mut oracle_map,
+                    &user.clone(),
+                    &ctx.accounts.user_stats.clone(),
+                    &makers_and_referrer,
+                    &makers_and_referrer_stats,
+                    None,
+                    &clock,
+                    FillMode::PairLeg,
+                    &mut escrow.as_mut(),
+                    builder_referral_enabled,
+                    &mut referral_campaign.as_deref_mut(),
+                )?;
+            }
+            MarketType::Spot => {
+                controller::orders::fill_spot_order(
+                    order_id,
+                    state,
+                    user,
+                    &ctx.accounts.user_stats,
+                    &spot_market_map,
+                    &perp_market_map,
+                    &mut oracle_map,
+                    &user.clone(),
+                    &ctx.accounts.user_stats.clone(),
+                    &makers_and_referrer,
+                    &makers_and_referrer_stats,
+                    None,
+                    &clock,
+                    fulfillment_params.as_deref_mut().safe_unwrap()?,
+                    FillMode::PairLeg,
+                )?;
+            }
+        }
+
+        // read the fill price before the next leg can reuse the filled order's slot
+        let user = load!(ctx.accounts.user)?;
+        let order = user
+            .orders
+            .iter()
+            .find(|order| order.order_id == order_id)
+            .safe_unwrap()?;
+        let base_precision = match leg.market_type {
+            MarketType::Perp => BASE_PRECISION_U64,
+            MarketType::Spot => spot_market_map.get_ref(&leg.market_index)?.get_precision(),
+        };
+        leg_fill_prices[i] = calculate_fill_price(
+            order.quote_asset_amount_filled,
+            order.base_asset_amount_filled,
+            base_precision,
+        )?;
+    }
+
+    params.validate_net_price(leg_fill_prices[0], leg_fill_prices[1])?;
+
+    let mut user = load_mut!(ctx.accounts.user)?;
+    let margin_calculation = calculate_margin_requirement_and_total_collateral_and_liability_info(
+        &user,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        MarginContext::standard(MarginRequirementType::Fill)
+            .strict(true)
+            .fuel_numerator(&user, clock.unix_timestamp),
+    )?;
+
+    load_mut!(ctx.accounts.user_stats)?.update_fuel_bonus(
+        &mut user,
+        margin_calculation.fuel_deposits,
+        margin_calculation.fuel_borrows,
+        margin_calculation.fuel_positions,
+        clock.unix_timestamp,
+    )?;
+
+    validate!(
+        margin_calculation.meets_margin_requirement(),
+        ErrorCode::InsufficientCollateral,
+        "pair order breaches fill margin requirement: {:?}",
+        margin_calculation
+    )?;
+    drop(user);
+
+    if let (Some(spot_leg), Some(fulfillment_params)) = (spot_leg, fulfillment_params.as_ref()) {
+        let base_market = spot_market_map.get_ref(&spot_leg.market_index)?;
+        let quote_market = spot_market_map.get_quote_spot_market()?;
+        fulfillment_params.validate_vault_amounts(&base_market, &quote_market)?;
+    }
+
+    Ok(())
+}
+
+#[access_control(
+    fill_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_place_and_make_spot_order<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, PlaceAndMake<'info>>,
+    params: OrderParams,
+    taker_order_id: u32,
+    fulfillment_type: SpotFulfillmentType,
+) -> Result<()> {
+    let clock = &Clock::get()?;
+    let state = &ctx.accounts.state;
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        remaining_accounts_iter,
+        &MarketSet::new(),
+        &get_writable_spot_market_set_from_many(vec![QUOTE_SPOT_MARKET_INDEX, params.market_index]),
+        Clock::get()?.slot,
+        None,
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let (_referrer, _referrer_stats) = get_referrer_and_referrer_stats(remaining_accounts_iter)?;
+
+    if !params.is_immediate_or_cancel()
+        || params.is_fill_or_kill()
+        || params.post_only == PostOnlyParam::None
+        || params.order_type != OrderType::Limit
+    {
+        msg!("place_and_make must use IOC post only limit order");
+        return Err(print_error!(ErrorCode::InvalidOrderIOCPostOnly)().into());
+    }
+
+    let market_index = params.market_index;
+
+    let mut fulfillment_params: Box<dyn SpotFulfillmentParams> = match fulfillment_type {
+        SpotFulfillmentType::SerumV3 => {
+            let base_market = spot_market_map.get_ref(&market_index)?;
+            let quote_market = spot_market_map.get_quote_spot_market()?;
+            Box::new(SerumFulfillmentParams::new(
+                remaining_accounts_iter,
+                &ctx.accounts.state,
+                &base_market,
+                &quote_market,
+                clock.unix_timestamp,
+            )?)
+        }
+        SpotFulfillmentType::PhoenixV1 => {
+            let base_market = spot_market_map.get_ref(&market_index)?;
+            let quote_market = spot_market_map.get_quote_spot_market()?;
+            Box::new(PhoenixFulfillmentParams::new(
+                remaining_accounts_iter,
+                &ctx.accounts.state,
+                &base_market,
+                &quote_market,
+            )?)
+        }
+        SpotFulfillmentType::OpenbookV2 => {
+            let base_market = spot_market_map.get_ref(&market_index)?;
+            let quote_market = spot_market_map.get_quote_spot_market()?;
+            Box::new(OpenbookV2FulfillmentParams::new(
+                remaining_accounts_iter,
+                &ctx.accounts.state,
+                &base_market,
+                &quote_market,
+                clock.unix_timestamp,
+            )?)
+        }
+        SpotFulfillmentType::Match => {
+            let base_market = spot_market_map.get_ref(&market_index)?;
+            let quote_market = spot_market_map.get_quote_spot_market()?;
+            Box::new(MatchFulfillmentParams::new(
+                remaining_accounts_iter,
+                &base_market,
+                &quote_market,
+            )?)
+        }
+    };
+
+    let user_key = ctx.accounts.user.key();
+    let mut user = load_mut!(ctx.accounts.user)?;
+    let authority = user.authority;
+
+    controller::orders::place_spot_order(
+        state,
+        &mut user,
+        user_key,
+        &perp_market_map,
+        &spot_market_map,
+        &mut oracle_map,
+        clock,
+        params,
+        PlaceOrderOptions::default(),
+    )?;
+
+    drop(user);
+
+    let order_id = load!(ctx.accounts.user)?.get_last_order_id();
+
+    let mut makers_and_referrer = UserMap::empty();
+    let mut makers_and_referrer_stats = UserStatsMap::empty();
+    makers_and_referrer.insert(ctx.accounts.user.key(), ctx.accounts.user.clone())?;
+    makers_and_referrer_stats.insert(authority, ctx.accounts.user_stats.clone())?;
+
+    controller::orders::fill_spot_order(
+        taker_order_id,
+        state,
+        &ctx.accounts.taker,
+        &ctx.accounts.taker_stats,
+        &spot_market_map,
+        &perp_market_map,
+        &mut oracle_map,
+        &ctx.accounts.user.clone(),
+        &ctx.accounts.user_stats.clone(),
+        &makers_and_referrer,
+        &makers_and_referrer_stats,
+        Some(order_id),
+        clock,
+        fulfillment_params.as_mut(),
+        FillMode::PlaceAndMake,
+    )?;
+
+    let order_exists = load!(ctx.accounts.user)?
+        .orders
+        .iter()
+        .any(|order| order.order_id == order_id && order.status == OrderStatus::Open);
+
+    if order_exists {
+        controller::orders::cancel_order_by_order_id(
+            order_id,
+            &ctx.accounts.user,
+            &perp_market_map,
+            &spot_market_map,
+            &mut oracle_map,
+            clock,
+        )?;
+    }
+
+    let base_market = spot_market_map.get_ref(&market_index)?;
+    let quote_market = spot_market_map.get_quote_spot_market()?;
+    fulfillment_params.validate_vault_amounts(&base_market, &quote_market)?;
+
+    Ok(())
+}
+
+#[access_control(
+    fill_not_paused(&ctx.accounts.state)
+)]
+pub fn handle_place_and_make_signed_msg_spot_order<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, PlaceAndMakeSignedMsg<'info>>,
+    params: OrderParams,
+    signed_msg_order_uuid: [u8; 8],
+    fulfillment_type: SpotFulfillmentType,
+) -> Result<()> {
+    let clock = &Clock::get()?;
+    let state = &ctx.accounts.state;
+
+    let remaining_accounts_iter = &mut ctx.remaining_accounts.iter().peekable();
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        remaining_accounts_iter,
+        &MarketSet::new(),
+        &get_writable_spot_market_set_from_many(vec![QUOTE_SPOT_MARKET_INDEX, params.market_index]),
+        Clock::get()?.slot,
+        None,
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    let (_referrer, _referrer_stats) = get_referrer_and_referrer_stats(remaining_accounts_iter)?;
+
     if !params.is_immediate_or_cancel()
+        || params.is_fill_or_kill()
         || params.post_only == PostOnlyParam::None
         || params.order_type != OrderType::Limit
     {
@@ -3391,6 +4304,15 @@ pub fn handle_place_and_make_spot_order<'c: 'info, 'info>(
 
     let order_id = load!(ctx.accounts.user)?.get_last_order_id();
 
+    let taker_order_id = ctx
+        .accounts
+        .taker_signed_msg_user_orders
+        .load()?
+        .iter()
+        .find(|signed_msg_order_id| signed_msg_order_id.uuid == signed_msg_order_uuid)
+        .ok_or(ErrorCode::SignedMsgOrderDoesNotExist)?
+        .order_id;
+
     let mut makers_and_referrer = UserMap::empty();
     let mut makers_and_referrer_stats = UserStatsMap::empty();
     makers_and_referrer.insert(ctx.accounts.user.key(), ctx.accounts.user.clone())?;
@@ -3411,6 +4333,7 @@ pub fn handle_place_and_make_spot_order<'c: 'info, 'info>(
         Some(order_id),
         clock,
         fulfillment_params.as_mut(),
+        FillMode::PlaceAndMake,
     )?;
 
     let order_exists = load!(ctx.accounts.user)?
@@ -3486,6 +4409,8 @@ pub fn handle_update_user_margin_trading_enabled<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         None,
+        false,
+        false,
     )?;
 
     let mut user = load_mut!(ctx.accounts.user)?;
@@ -3514,6 +4439,8 @@ pub fn handle_update_user_pool_id<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         None,
+        false,
+        false,
     )?;
 
     let mut user = load_mut!(ctx.accounts.user)?;
@@ -3535,6 +4462,44 @@ pub fn handle_update_user_delegate(
     Ok(())
 }
 
+pub fn handle_initialize_delegate_permissions(
+    ctx: Context<InitializeDelegatePermissions>,
+    _sub_account_id: u16,
+    delegate: Pubkey,
+    params: DelegatePermissionsParams,
+) -> Result<()> {
+    validate!(
+        delegate != ctx.accounts.authority.key() && delegate != Pubkey::default(),
+        ErrorCode::InvalidDelegatePermissions,
+        "delegate can not be the authority or default pubkey"
+    )?;
+
+    let mut delegate_permissions = ctx.accounts.delegate_permissions.load_init()?;
+    delegate_permissions.user = ctx.accounts.user.key();
+    delegate_permissions.delegate = delegate;
+    delegate_permissions.update(&params)?;
+
+    Ok(())
+}
+
+pub fn handle_update_delegate_permissions(
+    ctx: Context<UpdateDelegatePermissions>,
+    _sub_account_id: u16,
+    params: DelegatePermissionsParams,
+) -> Result<()> {
+    let mut delegate_permissions = load_mut!(ctx.accounts.delegate_permissions)?;
+    delegate_permissions.update(&params)?;
+
+    Ok(())
+}
+
+pub fn handle_delete_delegate_permissions(
+    _ctx: Context<DeleteDelegatePermissions>,
+    _sub_account_id: u16,
+) -> Result<()> {
+    Ok(())
+}
+
 pub fn handle_update_user_reduce_only(
     ctx: Context<UpdateUser>,
     _sub_account_id: u16,
@@ -3602,6 +4567,137 @@ pub fn handle_update_user_protected_maker_orders(
     Ok(())
 }
 
+pub fn handle_initialize_withdraw_policy(
+    ctx: Context<InitializeWithdrawPolicy>,
+    params: WithdrawPolicyParams,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+
+    let mut withdraw_policy = ctx.accounts.withdraw_policy.load_init()?;
+    withdraw_policy.initialize(ctx.accounts.authority.key(), &params)?;
+
+    let mut user_stats = load_mut!(ctx.accounts.user_stats)?;
+    user_stats.update_withdraw_policy_status(true);
+
+    emit_withdraw_policy_record(&withdraw_policy, WithdrawPolicyAction::Initialize, now)?;
+
+    Ok(())
+}
+
+pub fn handle_update_withdraw_policy(
+    ctx: Context<UpdateWithdrawPolicy>,
+    params: WithdrawPolicyParams,
+) -> Result<()> {
+    request_withdraw_policy_change(&ctx, WithdrawPolicyChange::Update, &params)
+}
+
+pub fn handle_request_delete_withdraw_policy(ctx: Context<UpdateWithdrawPolicy>) -> Result<()> {
+    request_withdraw_policy_change(
+        &ctx,
+        WithdrawPolicyChange::Delete,
+        &WithdrawPolicyParams::default(),
+    )
+}
+
+fn request_withdraw_policy_change(
+    ctx: &Context<UpdateWithdrawPolicy>,
+    change: WithdrawPolicyChange,
+    params: &WithdrawPolicyParams,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let mut withdraw_policy = load_mut!(ctx.accounts.withdraw_policy)?;
+
+    if withdraw_policy.apply_pending_update(now)? {
+        emit_withdraw_policy_record(&withdraw_policy, WithdrawPolicyAction::ApplyUpdate, now)?;
+    }
+
+    withdraw_policy.request_change(change, params, now)?;
+
+    let action = match change {
+        WithdrawPolicyChange::Delete => WithdrawPolicyAction::RequestDelete,
+        _ => WithdrawPolicyAction::RequestUpdate,
+    };
+    emit_withdraw_policy_record(&withdraw_policy, action, now)?;
+
+    Ok(())
+}
+
+/// Canceling a pending change takes effect immediately since it keeps the current policy
+pub fn handle_cancel_withdraw_policy_change(ctx: Context<UpdateWithdrawPolicy>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let mut withdraw_policy = load_mut!(ctx.accounts.withdraw_policy)?;
+
+    if withdraw_policy.apply_pending_update(now)? {
+        emit_withdraw_policy_record(&withdraw_policy, WithdrawPolicyAction::ApplyUpdate, now)?;
+        return Ok(());
+    }
+
+    validate!(
+        withdraw_policy.has_pending_change(),
+        ErrorCode::InvalidWithdrawPolicy,
+        "no pending withdraw policy change"
+    )?;
+
+    withdraw_policy.clear_pending_change();
+
+    emit_withdraw_policy_record(&withdraw_policy, WithdrawPolicyAction::CancelChange, now)?;
+
+    Ok(())
+}
+
+pub fn handle_delete_withdraw_policy(ctx: Context<DeleteWithdrawPolicy>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+
+    let withdraw_policy = load!(ctx.accounts.withdraw_policy)?;
+    withdraw_policy.validate_delete(now)?;
+
+    let mut user_stats = load_mut!(ctx.accounts.user_stats)?;
+    user_stats.update_withdraw_policy_status(false);
+
+    emit!(WithdrawPolicyRecord {
+        ts: now,
+        authority: withdraw_policy.authority,
+        action: WithdrawPolicyAction::Delete,
+        destinations: vec![],
+        timelock: 0,
+        effective_ts: now,
+    });
+
+    Ok(())
+}
+
+fn emit_withdraw_policy_record(
+    withdraw_policy: &WithdrawPolicy,
+    action: WithdrawPolicyAction,
+    now: i64,
+) -> Result<()> {
+    let (destinations, timelock, effective_ts) = match action {
+        WithdrawPolicyAction::RequestUpdate | WithdrawPolicyAction::RequestDelete => (
+            withdraw_policy.pending_destinations
+                [..withdraw_policy.num_pending_destinations as usize]
+                .to_vec(),
+            withdraw_policy.pending_timelock,
+            withdraw_policy.pending_ts,
+        ),
+        _ => (
+            withdraw_policy.get_destinations(now).to_vec(),
+            withdraw_policy.timelock,
+            now,
+        ),
+    };
+
+    emit!(WithdrawPolicyRecord {
+        ts: now,
+        authority: withdraw_policy.authority,
+        action,
+        destinations,
+        timelock,
+        effective_ts,
+    });
+
+    Ok(())
+}
+
 pub fn handle_delete_user(ctx: Context<DeleteUser>) -> Result<()> {
     let user = &load!(ctx.accounts.user)?;
     let user_stats = &mut load_mut!(ctx.accounts.user_stats)?;
@@ -3738,6 +4834,8 @@ pub fn handle_enable_user_high_leverage_mode<'c: 'info, 'info>(
         &MarketSet::new(),
         Clock::get()?.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     validate_user_can_enable_high_leverage_mode(
@@ -3754,6 +4852,68 @@ pub fn handle_enable_user_high_leverage_mode<'c: 'info, 'info>(
     Ok(())
 }
 
+pub fn handle_update_user_portfolio_margin_mode<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, UpdateUserPortfolioMarginMode<'info>>,
+    _sub_account_id: u16,
+    enable: bool,
+) -> Result<()> {
+    let state = &ctx.accounts.state;
+    let mut user = load_mut!(ctx.accounts.user)?;
+
+    let AccountMaps {
+        perp_market_map,
+        spot_market_map,
+        mut oracle_map,
+    } = load_maps(
+        &mut ctx.remaining_accounts.iter().peekable(),
+        &MarketSet::new(),
+        &MarketSet::new(),
+        Clock::get()?.slot,
+        Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
+    )?;
+
+    if enable {
+        validate!(
+            state.portfolio_margin_enabled(),
+            ErrorCode::InvalidPortfolioMarginModeUpdate,
+            "portfolio margin is not enabled"
+        )?;
+
+        validate!(
+            user.margin_mode == MarginMode::Default,
+            ErrorCode::InvalidPortfolioMarginModeUpdate,
+            "user margin mode must be default, currently {:?}",
+            user.margin_mode
+        )?;
+
+        user.margin_mode = MarginMode::Portfolio;
+    } else {
+        validate!(
+            user.is_portfolio_margin_mode(),
+            ErrorCode::InvalidPortfolioMarginModeUpdate,
+            "user is not in portfolio margin mode"
+        )?;
+
+        user.margin_mode = MarginMode::Default;
+
+        // hedged positions lose their offsets, so the standard requirement must still be met
+        validate!(
+            meets_initial_margin_requirement(
+                &user,
+                &perp_market_map,
+                &spot_market_map,
+                &mut oracle_map
+            )?,
+            ErrorCode::InsufficientCollateral,
+            "user does not meet initial margin requirement without portfolio margin"
+        )?;
+    }
+
+    Ok(())
+}
+
 /// Checks if an instruction is a SPL Token CloseAccount targeting
 /// one of the swap's token accounts.
 fn is_token_close_account_for_swap_ix(
@@ -3806,6 +4966,8 @@ pub fn handle_begin_swap<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![in_market_index, out_market_index]),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
 
     let _token_interface = get_token_interface(remaining_accounts_iter)?;
@@ -3816,6 +4978,15 @@ pub fn handle_begin_swap<'c: 'info, 'info>(
 
     validate!(!user.is_bankrupt(), ErrorCode::UserBankrupt)?;
 
+    // the swapped out tokens go to the authority's in token account, end_swap only needs enough
+    // back to keep the user above margin
+    validate_withdraw_policy(
+        &*load!(ctx.accounts.user_stats)?,
+        ctx.remaining_accounts,
+        &ctx.accounts.in_token_account.key(),
+        now,
+    )?;
+
     math::liquidation::validate_user_not_being_liquidated(
         &mut user,
         &perp_market_map,
@@ -4098,6 +5269,8 @@ pub fn handle_end_swap<'c: 'info, 'info>(
         &get_writable_spot_market_set_from_many(vec![in_market_index, out_market_index]),
         clock.slot,
         Some(state.oracle_guard_rails),
+        state.margin_correlations_enabled(),
+        state.portfolio_margin_enabled(),
     )?;
     let out_token_program = get_token_interface(remaining_accounts)?;
 
@@ -4515,6 +5688,58 @@ pub struct InitializeUserStats<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct InitializeWithdrawPolicy<'info> {
+    #[account(
+        init,
+        seeds = [WITHDRAW_POLICY_PDA_SEED.as_ref(), authority.key.as_ref()],
+        space = WithdrawPolicy::SIZE,
+        bump,
+        payer = authority
+    )]
+    pub withdraw_policy: AccountLoader<'info, WithdrawPolicy>,
+    #[account(
+        mut,
+        has_one = authority
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateWithdrawPolicy<'info> {
+    #[account(
+        mut,
+        seeds = [WITHDRAW_POLICY_PDA_SEED.as_ref(), authority.key.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub withdraw_policy: AccountLoader<'info, WithdrawPolicy>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DeleteWithdrawPolicy<'info> {
+    #[account(
+        mut,
+        seeds = [WITHDRAW_POLICY_PDA_SEED.as_ref(), authority.key.as_ref()],
+        bump,
+        has_one = authority,
+        close = authority
+    )]
+    pub withdraw_policy: AccountLoader<'info, WithdrawPolicy>,
+    #[account(
+        mut,
+        has_one = authority
+    )]
+    pub user_stats: AccountLoader<'info, UserStats>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
 #[derive(Accounts)]
 #[instruction(num_orders: u16)]
 pub struct InitializeSignedMsgUserOrders<'info> {
@@ -4557,6 +5782,19 @@ pub struct ResizeSignedMsgUserOrders<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct MigrateUserOrderLayout<'info> {
+    /// CHECK: legacy user accounts can't be loaded as User, checked in handler
+    #[account(
+        mut,
+        owner = crate::ID,
+    )]
+    pub user: AccountInfo<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 #[instruction(delegates: Vec<Pubkey>)]
 pub struct InitializeSignedMsgWsDelegates<'info> {
@@ -4591,6 +5829,36 @@ pub struct ChangeSignedMsgWsDelegateStatus<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct InitializeSignedMsgEvmSigner<'info> {
+    #[account(
+        init,
+        seeds = [SIGNED_MSG_EVM_SIGNER_PDA_SEED.as_ref(), authority.key().as_ref()],
+        space = SignedMsgEvmSigner::SIZE,
+        bump,
+        payer = authority
+    )]
+    pub signed_msg_evm_signer: Account<'info, SignedMsgEvmSigner>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DeleteSignedMsgEvmSigner<'info> {
+    #[account(
+        mut,
+        seeds = [SIGNED_MSG_EVM_SIGNER_PDA_SEED.as_ref(), authority.key().as_ref()],
+        bump,
+        has_one = authority,
+        close = authority
+    )]
+    pub signed_msg_evm_signer: Account<'info, SignedMsgEvmSigner>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct InitializeFuelOverflow<'info> {
     #[account(
@@ -4864,10 +6132,9 @@ pub struct TransferPerpPosition<'info> {
 #[derive(Accounts)]
 pub struct PlaceOrder<'info> {
     pub state: Box<Account<'info, State>>,
-    #[account(
-        mut,
-        constraint = can_sign_for_user(&user, &authority)?
-    )]
+    /// Signer checked by can_sign_for_user_with_permissions since scoped delegates pass their
+    /// DelegatePermissions in the remaining accounts
+    #[account(mut)]
     pub user: AccountLoader<'info, User>,
     pub authority: Signer<'info>,
 }
@@ -4875,10 +6142,9 @@ pub struct PlaceOrder<'info> {
 #[derive(Accounts)]
 pub struct CancelOrder<'info> {
     pub state: Box<Account<'info, State>>,
-    #[account(
-        mut,
-        constraint = can_sign_for_user(&user, &authority)?
-    )]
+    /// Signer checked by can_sign_for_user_with_permissions since scoped delegates pass their
+    /// DelegatePermissions in the remaining accounts
+    #[account(mut)]
     pub user: AccountLoader<'info, User>,
     pub authority: Signer<'info>,
 }
@@ -5099,6 +6365,62 @@ pub struct DeleteUser<'info> {
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+#[instruction(sub_account_id: u16, delegate: Pubkey)]
+pub struct InitializeDelegatePermissions<'info> {
+    #[account(
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        init,
+        seeds = [DELEGATE_PERMISSIONS_PDA_SEED.as_ref(), user.key().as_ref(), delegate.as_ref()],
+        space = DelegatePermissions::SIZE,
+        bump,
+        payer = authority
+    )]
+    pub delegate_permissions: AccountLoader<'info, DelegatePermissions>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(sub_account_id: u16)]
+pub struct UpdateDelegatePermissions<'info> {
+    #[account(
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        has_one = user
+    )]
+    pub delegate_permissions: AccountLoader<'info, DelegatePermissions>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(sub_account_id: u16)]
+pub struct DeleteDelegatePermissions<'info> {
+    #[account(
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub user: AccountLoader<'info, User>,
+    #[account(
+        mut,
+        has_one = user,
+        close = authority
+    )]
+    pub delegate_permissions: AccountLoader<'info, DelegatePermissions>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct DeleteSignedMsgUserOrders<'info> {
     #[account(
@@ -5197,6 +6519,21 @@ pub struct EnableUserHighLeverageMode<'info> {
     pub high_leverage_mode_config: AccountLoader<'info, HighLeverageModeConfig>,
 }
 
+#[derive(Accounts)]
+#[instruction(
+    sub_account_id: u16,
+)]
+pub struct UpdateUserPortfolioMarginMode<'info> {
+    pub state: Box<Account<'info, State>>,
+    #[account(
+        mut,
+        seeds = [b"user", authority.key.as_ref(), sub_account_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub user: AccountLoader<'info, User>,
+    pub authority: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct UpdateUserProtectedMakerMode<'info> {
     pub state: Box<Account<'info, State>>,
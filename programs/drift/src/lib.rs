@@ -10,9 +10,11 @@ use math::{bn, constants::*};
 use state::oracle::OracleSource;
 
 use crate::controller::position::PositionDirection;
+use crate::state::delegate_permissions::DelegatePermissionsParams;
 use crate::state::if_rebalance_config::IfRebalanceConfigParams;
 use crate::state::oracle::PrelaunchOracleParams;
-use crate::state::order_params::{ModifyOrderParams, OrderParams};
+use crate::state::order_params::{ModifyOrderParams, OrderParams, ReplaceOrderParams};
+use crate::state::pair_order_params::PairOrderParams;
 use crate::state::perp_market::{ContractTier, MarketStatus};
 use crate::state::scale_order_params::ScaleOrderParams;
 use crate::state::settle_pnl_mode::SettlePnlMode;
@@ -21,6 +23,7 @@ use crate::state::spot_market::SpotFulfillmentConfigStatus;
 use crate::state::state::FeeStructure;
 use crate::state::state::*;
 use crate::state::user::MarketType;
+use crate::state::withdraw_policy::WithdrawPolicyParams;
 
 pub mod controller;
 pub mod error;
@@ -101,6 +104,12 @@ pub mod drift {
     //     handle_resize_signed_msg_user_orders(ctx, num_orders)
     // }
 
+    // pub fn migrate_user_order_layout<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, MigrateUserOrderLayout<'info>>,
+    // ) -> Result<()> {
+    //     handle_migrate_user_order_layout(ctx)
+    // }
+
     // pub fn initialize_signed_msg_ws_delegates<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, InitializeSignedMsgWsDelegates<'info>>,
     //     delegates: Vec<Pubkey>,
@@ -116,6 +125,19 @@ pub mod drift {
     //     handle_change_signed_msg_ws_delegate_status(ctx, delegate, add)
     // }
 
+    // pub fn initialize_signed_msg_evm_signer<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, InitializeSignedMsgEvmSigner<'info>>,
+    //     evm_address: [u8; 20],
+    // ) -> Result<()> {
+    //     handle_initialize_signed_msg_evm_signer(ctx, evm_address)
+    // }
+
+    // pub fn delete_signed_msg_evm_signer<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, DeleteSignedMsgEvmSigner<'info>>,
+    // ) -> Result<()> {
+    //     handle_delete_signed_msg_evm_signer(ctx)
+    // }
+
     // pub fn initialize_fuel_overflow<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, InitializeFuelOverflow<'info>>,
     // ) -> Result<()> {
@@ -314,6 +336,20 @@ pub mod drift {
     //     handle_place_and_make_signed_msg_perp_order(ctx, params, signed_msg_order_uuid)
     // }
 
+    // pub fn place_and_make_signed_msg_spot_order<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, PlaceAndMakeSignedMsg<'info>>,
+    //     params: OrderParams,
+    //     signed_msg_order_uuid: [u8; 8],
+    //     fulfillment_type: Option<SpotFulfillmentType>,
+    // ) -> Result<()> {
+    //     handle_place_and_make_signed_msg_spot_order(
+    //         ctx,
+    //         params,
+    //         signed_msg_order_uuid,
+    //         fulfillment_type.unwrap_or(SpotFulfillmentType::Match),
+    //     )
+    // }
+
     // pub fn place_signed_msg_taker_order<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, PlaceSignedMsgTakerOrder<'info>>,
     //     signed_msg_order_params_message_bytes: Vec<u8>,
@@ -326,6 +362,23 @@ pub mod drift {
     //     )
     // }
 
+    // pub fn cancel_signed_msg_orders<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, CancelSignedMsgOrders<'info>>,
+    //     signed_msg_cancel_orders_message_bytes: Vec<u8>,
+    // ) -> Result<()> {
+    //     handle_cancel_signed_msg_orders(ctx, signed_msg_cancel_orders_message_bytes)
+    // }
+
+    // pub fn cancel_signed_msg_orders_before_slot<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, CancelSignedMsgOrders<'info>>,
+    //     signed_msg_cancel_orders_before_slot_message_bytes: Vec<u8>,
+    // ) -> Result<()> {
+    //     handle_cancel_signed_msg_orders_before_slot(
+    //         ctx,
+    //         signed_msg_cancel_orders_before_slot_message_bytes,
+    //     )
+    // }
+
     // pub fn place_spot_order<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, PlaceOrder>,
     //     params: OrderParams,
@@ -347,6 +400,13 @@ pub mod drift {
     //     )
     // }
 
+    // pub fn place_and_take_pair_orders<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, PlaceAndTake<'info>>,
+    //     params: PairOrderParams,
+    // ) -> Result<()> {
+    //     handle_place_and_take_pair_orders(ctx, params)
+    // }
+
     // pub fn place_and_make_spot_order<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, PlaceAndMake<'info>>,
     //     params: OrderParams,
@@ -375,6 +435,13 @@ pub mod drift {
     //     handle_place_scale_orders(ctx, params)
     // }
 
+    // pub fn replace_orders<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, PlaceOrder>,
+    //     params: Vec<ReplaceOrderParams>,
+    // ) -> Result<()> {
+    //     handle_replace_orders(ctx, params)
+    // }
+
     // pub fn begin_swap<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, Swap<'info>>,
     //     in_market_index: u16,
@@ -454,6 +521,30 @@ pub mod drift {
     //     handle_update_user_delegate(ctx, _sub_account_id, delegate)
     // }
 
+    // pub fn initialize_delegate_permissions(
+    //     ctx: Context<InitializeDelegatePermissions>,
+    //     _sub_account_id: u16,
+    //     delegate: Pubkey,
+    //     params: DelegatePermissionsParams,
+    // ) -> Result<()> {
+    //     handle_initialize_delegate_permissions(ctx, _sub_account_id, delegate, params)
+    // }
+
+    // pub fn update_delegate_permissions(
+    //     ctx: Context<UpdateDelegatePermissions>,
+    //     _sub_account_id: u16,
+    //     params: DelegatePermissionsParams,
+    // ) -> Result<()> {
+    //     handle_update_delegate_permissions(ctx, _sub_account_id, params)
+    // }
+
+    // pub fn delete_delegate_permissions(
+    //     ctx: Context<DeleteDelegatePermissions>,
+    //     _sub_account_id: u16,
+    // ) -> Result<()> {
+    //     handle_delete_delegate_permissions(ctx, _sub_account_id)
+    // }
+
     // pub fn update_user_reduce_only(
     //     ctx: Context<UpdateUser>,
     //     _sub_account_id: u16,
@@ -478,6 +569,32 @@ pub mod drift {
     //     handle_update_user_protected_maker_orders(ctx, _sub_account_id, protected_maker_orders)
     // }
 
+    // pub fn initialize_withdraw_policy(
+    //     ctx: Context<InitializeWithdrawPolicy>,
+    //     params: WithdrawPolicyParams,
+    // ) -> Result<()> {
+    //     handle_initialize_withdraw_policy(ctx, params)
+    // }
+
+    // pub fn update_withdraw_policy(
+    //     ctx: Context<UpdateWithdrawPolicy>,
+    //     params: WithdrawPolicyParams,
+    // ) -> Result<()> {
+    //     handle_update_withdraw_policy(ctx, params)
+    // }
+
+    // pub fn request_delete_withdraw_policy(ctx: Context<UpdateWithdrawPolicy>) -> Result<()> {
+    //     handle_request_delete_withdraw_policy(ctx)
+    // }
+
+    // pub fn cancel_withdraw_policy_change(ctx: Context<UpdateWithdrawPolicy>) -> Result<()> {
+    //     handle_cancel_withdraw_policy_change(ctx)
+    // }
+
+    // pub fn delete_withdraw_policy(ctx: Context<DeleteWithdrawPolicy>) -> Result<()> {
+    //     handle_delete_withdraw_policy(ctx)
+    // }
+
     // pub fn delete_user<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, DeleteUser>,
     // ) -> Result<()> {
@@ -507,6 +624,14 @@ pub mod drift {
     //     handle_enable_user_high_leverage_mode(ctx, sub_account_id)
     // }
 
+    // pub fn update_user_portfolio_margin_mode<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, UpdateUserPortfolioMarginMode<'info>>,
+    //     sub_account_id: u16,
+    //     enable: bool,
+    // ) -> Result<()> {
+    //     handle_update_user_portfolio_margin_mode(ctx, sub_account_id, enable)
+    // }
+
     // // Keeper Instructions
 
     // pub fn fill_perp_order<'c: 'info, 'info>(
@@ -592,6 +717,13 @@ pub mod drift {
     //     handle_settle_pnl(ctx, market_index)
     // }
 
+    // pub fn sweep_revenue_share_escrow<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, SweepRevenueShareEscrow>,
+    //     market_indexes: Vec<u16>,
+    // ) -> Result<()> {
+    //     handle_sweep_revenue_share_escrow(ctx, market_indexes)
+    // }
+
     // pub fn settle_multiple_pnls<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, SettlePNL>,
     //     market_indexes: Vec<u16>,
@@ -634,6 +766,20 @@ pub mod drift {
     //     handle_liquidate_perp_with_fill(ctx, market_index)
     // }
 
+    // pub fn begin_liquidation_auction<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, BeginLiquidationAuction<'info>>,
+    //     market_index: u16,
+    // ) -> Result<()> {
+    //     handle_begin_liquidation_auction(ctx, market_index)
+    // }
+
+    // pub fn deleverage_perp_position<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, DeleveragePerpPosition<'info>>,
+    //     market_index: u16,
+    // ) -> Result<()> {
+    //     handle_deleverage_perp_position(ctx, market_index)
+    // }
+
     // pub fn liquidate_spot<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, LiquidateSpot<'info>>,
     //     asset_market_index: u16,
@@ -726,6 +872,14 @@ pub mod drift {
     //     handle_resolve_perp_bankruptcy(ctx, quote_spot_market_index, market_index)
     // }
 
+    // pub fn auto_deleverage_perp_position<'c: 'info, 'info>(
+    //     ctx: Context<'_, '_, 'c, 'info, AutoDeleverage<'info>>,
+    //     quote_spot_market_index: u16,
+    //     market_index: u16,
+    // ) -> Result<()> {
+    //     handle_auto_deleverage_perp_position(ctx, quote_spot_market_index, market_index)
+    // }
+
     // pub fn resolve_spot_bankruptcy<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, ResolveBankruptcy<'info>>,
     //     market_index: u16,
@@ -1531,6 +1685,20 @@ pub mod drift {
     //     handle_update_liquidation_duration(ctx, liquidation_duration)
     // }
 
+    // pub fn update_liquidation_auction_duration(
+    //     ctx: Context<AdminUpdateState>,
+    //     liquidation_auction_duration: u8,
+    // ) -> Result<()> {
+    //     handle_update_liquidation_auction_duration(ctx, liquidation_auction_duration)
+    // }
+
+    // pub fn update_deleverage_margin_buffer_ratio(
+    //     ctx: Context<AdminUpdateState>,
+    //     deleverage_margin_buffer_ratio: u16,
+    // ) -> Result<()> {
+    //     handle_update_deleverage_margin_buffer_ratio(ctx, deleverage_margin_buffer_ratio)
+    // }
+
     // pub fn update_liquidation_margin_buffer_ratio(
     //     ctx: Context<AdminUpdateState>,
     //     liquidation_margin_buffer_ratio: u32,
@@ -1566,6 +1734,24 @@ pub mod drift {
     //     handle_update_state_max_initialize_user_fee(ctx, max_initialize_user_fee)
     // }
 
+    // pub fn update_state_builder_tier_rebate_pcts(
+    //     ctx: Context<AdminUpdateState>,
+    //     builder_tier_rebate_pcts: [u8; 4],
+    // ) -> Result<()> {
+    //     handle_update_state_builder_tier_rebate_pcts(ctx, builder_tier_rebate_pcts)
+    // }
+
+    // pub fn update_state_builder_tier_volume_thresholds(
+    //     ctx: Context<AdminUpdateState>,
+    //     builder_tier_volume_thresholds: [u64; 4],
+    // ) -> Result<()> {
+    //     handle_update_state_builder_tier_volume_thresholds(ctx, builder_tier_volume_thresholds)
+    // }
+
+    // pub fn resize_state(ctx: Context<ResizeState>) -> Result<()> {
+    //     handle_resize_state(ctx)
+    // }
+
     // pub fn update_perp_market_oracle(
     //     ctx: Context<AdminUpdatePerpMarketOracle>,
     //     oracle: Pubkey,
@@ -1661,6 +1847,30 @@ pub mod drift {
     //     handle_update_spot_market_fee_adjustment(ctx, fee_adjustment)
     // }
 
+    // pub fn update_perp_market_fee_override(
+    //     ctx: Context<AdminUpdatePerpMarket>,
+    //     params: MarketFeeOverrideParams,
+    // ) -> Result<()> {
+    //     handle_update_perp_market_fee_override(ctx, params)
+    // }
+
+    // pub fn update_spot_market_fee_override(
+    //     ctx: Context<AdminUpdateSpotMarket>,
+    //     params: MarketFeeOverrideParams,
+    // ) -> Result<()> {
+    //     handle_update_spot_market_fee_override(ctx, params)
+    // }
+
+    // pub fn update_spot_market_portfolio_margin_price_shock(
+    //     ctx: Context<AdminUpdateSpotMarket>,
+    //     portfolio_margin_price_shock: u16,
+    // ) -> Result<()> {
+    //     handle_update_spot_market_portfolio_margin_price_shock(
+    //         ctx,
+    //         portfolio_margin_price_shock,
+    //     )
+    // }
+
     // // pub fn update_perp_market_fuel(
     // //     ctx: Context<HotAdminUpdatePerpMarket>,
     // //     fuel_boost_taker: Option<u8>,
@@ -1873,6 +2083,42 @@ pub mod drift {
     //     handle_update_protected_maker_mode_config(ctx, max_users, reduce_only, current_users)
     // }
 
+    // pub fn initialize_referral_campaign(
+    //     ctx: Context<InitializeReferralCampaign>,
+    //     params: ReferralCampaignParams,
+    // ) -> Result<()> {
+    //     handle_initialize_referral_campaign(ctx, params)
+    // }
+
+    // pub fn update_referral_campaign(
+    //     ctx: Context<UpdateReferralCampaign>,
+    //     params: ReferralCampaignParams,
+    // ) -> Result<()> {
+    //     handle_update_referral_campaign(ctx, params)
+    // }
+
+    // pub fn delete_referral_campaign(ctx: Context<DeleteReferralCampaign>) -> Result<()> {
+    //     handle_delete_referral_campaign(ctx)
+    // }
+
+    // pub fn initialize_margin_correlations(
+    //     ctx: Context<InitializeMarginCorrelations>,
+    // ) -> Result<()> {
+    //     handle_initialize_margin_correlations(ctx)
+    // }
+
+    // pub fn update_margin_correlation(
+    //     ctx: Context<UpdateMarginCorrelations>,
+    //     index: u16,
+    //     params: MarginCorrelationParams,
+    // ) -> Result<()> {
+    //     handle_update_margin_correlation(ctx, index, params)
+    // }
+
+    // pub fn delete_margin_correlations(ctx: Context<DeleteMarginCorrelations>) -> Result<()> {
+    //     handle_delete_margin_correlations(ctx)
+    // }
+
     // pub fn admin_deposit<'c: 'info, 'info>(
     //     ctx: Context<'_, '_, 'c, 'info, AdminDeposit<'info>>,
     //     market_index: u16,
@@ -1920,6 +2166,13 @@ pub mod drift {
     // //     handle_update_feature_bit_flags_builder_referral(ctx, enable)
     // // }
 
+    // pub fn update_feature_bit_flags_portfolio_margin(
+    //     ctx: Context<HotAdminUpdateState>,
+    //     enable: bool,
+    // ) -> Result<()> {
+    //     handle_update_feature_bit_flags_portfolio_margin(ctx, enable)
+    // }
+
     // pub fn update_feature_bit_flags_builder_codes(
     //     ctx: Context<HotAdminUpdateState>,
     //     enable: bool,
@@ -89,7 +89,9 @@ pub fn calculate_auction_price(
     is_prediction_market: bool,
 ) -> DriftResult<u64> {
     match order.order_type {
-        OrderType::TriggerMarket if order.is_bit_flag_set(OrderBitFlag::OracleTriggerMarket) => {
+        OrderType::TriggerMarket | OrderType::TrailingStop
+            if order.is_bit_flag_set(OrderBitFlag::OracleTriggerMarket) =>
+        {
             calculate_auction_price_for_oracle_offset_auction(
                 order,
                 slot,
@@ -98,10 +100,13 @@ pub fn calculate_auction_price(
                 is_prediction_market,
             )
         }
-        OrderType::Market | OrderType::TriggerMarket | OrderType::TriggerLimit => {
+        OrderType::Market
+        | OrderType::TriggerMarket
+        | OrderType::TriggerLimit
+        | OrderType::TrailingStop => {
             calculate_auction_price_for_fixed_auction(order, slot, tick_size)
         }
-        OrderType::Limit => {
+        OrderType::Limit | OrderType::Twap => {
             if order.has_oracle_price_offset() {
                 calculate_auction_price_for_oracle_offset_auction(
                     order,
@@ -250,8 +255,8 @@ pub fn calculate_auction_params_for_trigger_order(
             -3_500
         };
 
-        let (auction_start_price, auction_end_price, derived_auction_duration) =
-            if matches!(order.order_type, OrderType::TriggerMarket) {
+        let (mut auction_start_price, mut auction_end_price, derived_auction_duration) =
+            if matches!(order.order_type, OrderType::TriggerMarket) || order.is_trailing_stop() {
                 OrderParams::derive_oracle_order_auction_params(
                     perp_market,
                     order.direction,
@@ -259,6 +264,14 @@ pub fn calculate_auction_params_for_trigger_order(
                     None,
                     auction_start_buffer,
                 )?
+            } else if order.is_twap() && order.has_oracle_price_offset() {
+                OrderParams::derive_oracle_order_auction_params(
+                    perp_market,
+                    order.direction,
+                    oracle_price_data.price,
+                    Some(order.oracle_price_offset),
+                    auction_start_buffer,
+                )?
             } else {
                 OrderParams::derive_market_order_auction_params(
                     perp_market,
@@ -269,6 +282,18 @@ pub fn calculate_auction_params_for_trigger_order(
                 )?
             };
 
+        // twap slices can't cross their oracle offset limit, even with the crossing start buffer
+        if order.is_twap() && order.has_oracle_price_offset() {
+            let oracle_price_offset = order.oracle_price_offset.cast::<i64>()?;
+            if order.direction == PositionDirection::Long {
+                auction_start_price = auction_start_price.min(oracle_price_offset);
+                auction_end_price = auction_end_price.min(oracle_price_offset);
+            } else {
+                auction_start_price = auction_start_price.max(oracle_price_offset);
+                auction_end_price = auction_end_price.max(oracle_price_offset);
+            }
+        }
+
         let auction_duration = auction_duration.max(derived_auction_duration);
 
         Ok((auction_duration, auction_start_price, auction_end_price))
@@ -13,10 +13,11 @@ use crate::math::constants::{
 use crate::math::helpers::get_proportion_u128;
 use crate::math::safe_math::SafeMath;
 
-use crate::state::state::{FeeStructure, FeeTier, OrderFillerRewardStructure};
+use crate::state::referral_campaign::ReferralCampaign;
+use crate::state::state::{FeeStructure, FeeTier, MarketFeeOverride, OrderFillerRewardStructure};
 use crate::state::user::{MarketType, UserStats};
 
-use crate::math::constants::{FEE_ADJUSTMENT_MAX, QUOTE_PRECISION_U64};
+use crate::math::constants::{FEE_ADJUSTMENT_MAX, FEE_PERCENTAGE_DENOMINATOR, QUOTE_PRECISION_U64};
 use crate::msg;
 
 #[cfg(test)]
@@ -29,6 +30,7 @@ pub struct FillFees {
     pub fee_to_market_for_lp: i64,
     pub filler_reward: u64,
     pub referrer_reward: u64,
+    pub second_level_referrer_reward: u64,
     pub referee_discount: u64,
     pub builder_fee: Option<u64>,
 }
@@ -47,17 +49,25 @@ pub fn calculate_fee_for_fulfillment_with_amm(
     fee_adjustment: i16,
     user_high_leverage_mode: bool,
     builder_fee_bps: Option<u16>,
+    referral_campaign: Option<&ReferralCampaign>,
+    fee_override: MarketFeeOverride,
 ) -> DriftResult<FillFees> {
-    let fee_tier = determine_user_fee_tier(
+    let fee_tier = fee_override.apply(determine_user_fee_tier(
         user_stats,
         fee_structure,
         &MarketType::Perp,
         user_high_leverage_mode,
-    )?;
+    )?)?;
 
     // if there was a quote_asset_amount_surplus, the order was a maker order and fee_to_market comes from surplus
     if is_post_only {
-        let maker_rebate = calculate_maker_rebate(quote_asset_amount, &fee_tier, fee_adjustment)?;
+        let mut maker_rebate =
+            calculate_maker_rebate(quote_asset_amount, &fee_tier, fee_adjustment)?;
+
+        // an overridden rebate is capped by what the fill leaves the market
+        if fee_override.overrides_maker_rebate() {
+            maker_rebate = maker_rebate.min(quote_asset_amount_surplus.max(0).cast()?);
+        }
 
         let fee = quote_asset_amount_surplus
             .cast::<u64>()?
@@ -93,6 +103,7 @@ pub fn calculate_fee_for_fulfillment_with_amm(
             fee_to_market_for_lp: 0,
             filler_reward,
             referrer_reward: 0,
+            second_level_referrer_reward: 0,
             referee_discount: 0,
             builder_fee: None,
         })
@@ -103,16 +114,18 @@ pub fn calculate_fee_for_fulfillment_with_amm(
             fee = fee.safe_mul(2)?;
         }
 
-        let (fee, referee_discount, referrer_reward) = if reward_referrer {
-            calculate_referee_fee_and_referrer_reward(
-                fee,
-                &fee_tier,
-                fee_structure.referrer_reward_epoch_upper_bound,
-                referrer_stats,
-            )?
-        } else {
-            (fee, 0, 0)
-        };
+        let (fee, referee_discount, referrer_reward, second_level_referrer_reward) =
+            if reward_referrer {
+                calculate_referee_fee_and_referrer_reward(
+                    fee,
+                    &fee_tier,
+                    fee_structure.referrer_reward_epoch_upper_bound,
+                    referrer_stats,
+                    referral_campaign,
+                )?
+            } else {
+                (fee, 0, 0, 0)
+            };
 
         let filler_reward = if !reward_filler {
             0_u64
@@ -129,6 +142,7 @@ pub fn calculate_fee_for_fulfillment_with_amm(
         let fee_to_market = fee
             .safe_sub(filler_reward)?
             .safe_sub(referrer_reward)?
+            .safe_sub(second_level_referrer_reward)?
             .cast::<i64>()?
             .safe_add(quote_asset_amount_surplus)?;
 
@@ -152,6 +166,7 @@ pub fn calculate_fee_for_fulfillment_with_amm(
             fee_to_market_for_lp,
             filler_reward,
             referrer_reward,
+            second_level_referrer_reward,
             referee_discount,
             builder_fee,
         })
@@ -219,7 +234,12 @@ fn calculate_referee_fee_and_referrer_reward(
     fee_tier: &FeeTier,
     referrer_reward_epoch_upper_bound: u64,
     referrer_stats: &Option<&mut UserStats>,
-) -> DriftResult<(u64, u64, u64)> {
+    referral_campaign: Option<&ReferralCampaign>,
+) -> DriftResult<(u64, u64, u64, u64)> {
+    if let Some(referral_campaign) = referral_campaign {
+        return calculate_referee_fee_and_referrer_reward_for_campaign(fee, referral_campaign);
+    }
+
     let referee_discount = get_proportion_u128(
         fee as u128,
         fee_tier.referee_fee_numerator as u128,
@@ -244,7 +264,53 @@ fn calculate_referee_fee_and_referrer_reward(
         }
         None => max_referrer_reward_from_fee,
     };
-    Ok((referee_fee, referee_discount, referrer_reward))
+    Ok((referee_fee, referee_discount, referrer_reward, 0))
+}
+
+/// Campaign rewards replace the fee tier's and aren't limited by the referrer's epoch cap, only by
+/// the campaign's remaining budget. The referee discount is paid out of the budget first
+fn calculate_referee_fee_and_referrer_reward_for_campaign(
+    fee: u64,
+    referral_campaign: &ReferralCampaign,
+) -> DriftResult<(u64, u64, u64, u64)> {
+    let remaining_budget = referral_campaign.remaining_budget();
+
+    let referee_discount = get_proportion_u128(
+        fee as u128,
+        referral_campaign.referee_fee_numerator as u128,
+        FEE_PERCENTAGE_DENOMINATOR as u128,
+    )?
+    .cast::<u64>()?
+    .min(remaining_budget);
+
+    let total_referrer_reward = get_proportion_u128(
+        fee as u128,
+        referral_campaign.referrer_reward_numerator as u128,
+        FEE_PERCENTAGE_DENOMINATOR as u128,
+    )?
+    .cast::<u64>()?
+    .min(remaining_budget.safe_sub(referee_discount)?);
+
+    let second_level_referrer_reward = if referral_campaign.has_second_level_referrer() {
+        get_proportion_u128(
+            total_referrer_reward as u128,
+            referral_campaign.second_level_reward_numerator as u128,
+            FEE_PERCENTAGE_DENOMINATOR as u128,
+        )?
+        .cast::<u64>()?
+    } else {
+        0
+    };
+
+    let referee_fee = fee.safe_sub(referee_discount)?;
+    let referrer_reward = total_referrer_reward.safe_sub(second_level_referrer_reward)?;
+
+    Ok((
+        referee_fee,
+        referee_discount,
+        referrer_reward,
+        second_level_referrer_reward,
+    ))
 }
 
 fn calculate_filler_reward(
@@ -301,18 +367,20 @@ pub fn calculate_fee_for_fulfillment_with_match(
     fee_adjustment: i16,
     user_high_leverage_mode: bool,
     builder_fee_bps: Option<u16>,
+    referral_campaign: Option<&ReferralCampaign>,
+    fee_override: MarketFeeOverride,
 ) -> DriftResult<FillFees> {
-    let taker_fee_tier = determine_user_fee_tier(
+    let taker_fee_tier = fee_override.apply(determine_user_fee_tier(
         taker_stats,
         fee_structure,
         market_type,
         user_high_leverage_mode,
-    )?;
-    let maker_fee_tier = if let Some(maker_stats) = maker_stats {
+    )?)?;
+    let maker_fee_tier = fee_override.apply(if let Some(maker_stats) = maker_stats {
         determine_user_fee_tier(maker_stats, fee_structure, market_type, false)?
     } else {
         determine_user_fee_tier(taker_stats, fee_structure, market_type, false)?
-    };
+    })?;
 
     let mut taker_fee = calculate_taker_fee(quote_asset_amount, &taker_fee_tier, fee_adjustment)?;
 
@@ -320,18 +388,21 @@ pub fn calculate_fee_for_fulfillment_with_match(
         taker_fee = taker_fee.safe_mul(2)?;
     }
 
-    let (taker_fee, referee_discount, referrer_reward) = if reward_referrer {
-        calculate_referee_fee_and_referrer_reward(
-            taker_fee,
-            &taker_fee_tier,
-            fee_structure.referrer_reward_epoch_upper_bound,
-            referrer_stats,
-        )?
-    } else {
-        (taker_fee, 0, 0)
-    };
+    let (taker_fee, referee_discount, referrer_reward, second_level_referrer_reward) =
+        if reward_referrer {
+            calculate_referee_fee_and_referrer_reward(
+                taker_fee,
+                &taker_fee_tier,
+                fee_structure.referrer_reward_epoch_upper_bound,
+                referrer_stats,
+                referral_campaign,
+            )?
+        } else {
+            (taker_fee, 0, 0, 0)
+        };
 
-    let maker_rebate = calculate_maker_rebate(quote_asset_amount, &maker_fee_tier, fee_adjustment)?;
+    let mut maker_rebate =
+        calculate_maker_rebate(quote_asset_amount, &maker_fee_tier, fee_adjustment)?;
 
     let filler_reward = if filler_multiplier == 0 {
         0_u64
@@ -345,10 +416,21 @@ pub fn calculate_fee_for_fulfillment_with_match(
         )?
     };
 
+    // an overridden rebate is capped by what the taker fee leaves the market
+    if fee_override.overrides_maker_rebate() {
+        maker_rebate = maker_rebate.min(
+            taker_fee
+                .saturating_sub(filler_reward)
+                .saturating_sub(referrer_reward)
+                .saturating_sub(second_level_referrer_reward),
+        );
+    }
+
     // must be non-negative
     let fee_to_market = taker_fee
         .safe_sub(filler_reward)?
         .safe_sub(referrer_reward)?
+        .safe_sub(second_level_referrer_reward)?
         .safe_sub(maker_rebate)?
         .cast::<i64>()?;
 
@@ -368,6 +450,7 @@ pub fn calculate_fee_for_fulfillment_with_match(
         fee_to_market,
         filler_reward,
         referrer_reward,
+        second_level_referrer_reward,
         fee_to_market_for_lp: 0,
         referee_discount,
         builder_fee,
@@ -392,9 +475,14 @@ pub fn calculate_fee_for_fulfillment_with_external_market(
     unsettled_referrer_rebate: u64,
     fee_pool_amount: u64,
     fee_adjustment: i16,
+    fee_override: MarketFeeOverride,
 ) -> DriftResult<ExternalFillFees> {
-    let taker_fee_tier =
-        determine_user_fee_tier(user_stats, fee_structure, &MarketType::Spot, false)?;
+    let taker_fee_tier = fee_override.apply(determine_user_fee_tier(
+        user_stats,
+        fee_structure,
+        &MarketType::Spot,
+        false,
+    )?)?;
 
     let fee = calculate_taker_fee(quote_asset_amount, &taker_fee_tier, fee_adjustment)?;
 
@@ -1,7 +1,7 @@
 mod calculate_fee_for_taker_and_maker {
     use crate::math::constants::QUOTE_PRECISION_U64;
     use crate::math::fees::{calculate_fee_for_fulfillment_with_match, FillFees};
-    use crate::state::state::FeeStructure;
+    use crate::state::state::{FeeStructure, MarketFeeOverride};
     use crate::state::user::{MarketType, UserStats};
 
     #[test]
@@ -32,6 +32,8 @@ mod calculate_fee_for_taker_and_maker {
             0,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -77,6 +79,8 @@ mod calculate_fee_for_taker_and_maker {
             0,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -121,6 +125,8 @@ mod calculate_fee_for_taker_and_maker {
             0,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -165,6 +171,8 @@ mod calculate_fee_for_taker_and_maker {
             0,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -207,6 +215,8 @@ mod calculate_fee_for_taker_and_maker {
             0,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -246,6 +256,8 @@ mod calculate_fee_for_taker_and_maker {
             -50,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -278,6 +290,8 @@ mod calculate_fee_for_taker_and_maker {
             50,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -311,6 +325,8 @@ mod calculate_fee_for_taker_and_maker {
             -50,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -344,6 +360,8 @@ mod calculate_fee_for_taker_and_maker {
             -50,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -383,6 +401,8 @@ mod calculate_fee_for_taker_and_maker {
             -100,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -415,6 +435,8 @@ mod calculate_fee_for_taker_and_maker {
             -100,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -448,6 +470,8 @@ mod calculate_fee_for_taker_and_maker {
             -100,
             true,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -481,6 +505,8 @@ mod calculate_fee_for_taker_and_maker {
             -100,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -514,6 +540,8 @@ mod calculate_fee_for_taker_and_maker {
             -100,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -553,6 +581,8 @@ mod calculate_fee_for_taker_and_maker {
             -50,
             true,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -563,13 +593,89 @@ mod calculate_fee_for_taker_and_maker {
         assert_eq!(referrer_reward, 0);
         assert_eq!(referee_discount, 0);
     }
+
+    #[test]
+    fn market_fee_override() {
+        let quote_asset_amount = 100 * QUOTE_PRECISION_U64;
+        let taker_stats = UserStats::default();
+        let mut maker_stats = UserStats::default();
+
+        let fee_override = MarketFeeOverride {
+            taker_fee_multiplier: Some(50),   // half the tier's 10 bps
+            maker_rebate_numerator: Some(30), // 3 bps
+        };
+
+        let FillFees {
+            user_fee: taker_fee,
+            maker_rebate,
+            fee_to_market,
+            ..
+        } = calculate_fee_for_fulfillment_with_match(
+            &taker_stats,
+            &Some(&mut maker_stats),
+            quote_asset_amount,
+            &FeeStructure::test_default(),
+            0,
+            0,
+            0,
+            false,
+            &None,
+            &MarketType::Perp,
+            0,
+            false,
+            None,
+            None,
+            fee_override,
+        )
+        .unwrap();
+
+        assert_eq!(taker_fee, 50000);
+        assert_eq!(maker_rebate, 30000);
+        assert_eq!(fee_to_market, 20000);
+
+        // rebate larger than the taker fee is capped so fee_to_market stays non-negative
+        let fee_override = MarketFeeOverride {
+            taker_fee_multiplier: None,
+            maker_rebate_numerator: Some(150),
+        };
+
+        let FillFees {
+            user_fee: taker_fee,
+            maker_rebate,
+            fee_to_market,
+            ..
+        } = calculate_fee_for_fulfillment_with_match(
+            &taker_stats,
+            &Some(&mut maker_stats),
+            quote_asset_amount,
+            &FeeStructure::test_default(),
+            0,
+            0,
+            0,
+            false,
+            &None,
+            &MarketType::Perp,
+            0,
+            false,
+            None,
+            None,
+            fee_override,
+        )
+        .unwrap();
+
+        assert_eq!(taker_fee, 100000);
+        assert_eq!(maker_rebate, 100000);
+        assert_eq!(fee_to_market, 0);
+    }
 }
 
 mod calculate_fee_for_order_fulfill_against_amm {
     use crate::math::constants::QUOTE_PRECISION_U64;
     use crate::math::fees::{calculate_fee_for_fulfillment_with_amm, FillFees};
-    use crate::state::state::FeeStructure;
+    use crate::state::referral_campaign::ReferralCampaign;
+    use crate::state::state::{FeeStructure, MarketFeeOverride};
     use crate::state::user::UserStats;
+    use anchor_lang::prelude::Pubkey;
 
     #[test]
     fn referrer() {
@@ -599,6 +705,8 @@ mod calculate_fee_for_order_fulfill_against_amm {
             0,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -637,6 +745,8 @@ mod calculate_fee_for_order_fulfill_against_amm {
             -50,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -667,6 +777,8 @@ mod calculate_fee_for_order_fulfill_against_amm {
             50,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -698,6 +810,8 @@ mod calculate_fee_for_order_fulfill_against_amm {
             -50,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -729,6 +843,8 @@ mod calculate_fee_for_order_fulfill_against_amm {
             -50,
             false,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -767,6 +883,8 @@ mod calculate_fee_for_order_fulfill_against_amm {
             -50,
             true,
             None,
+            None,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -776,12 +894,98 @@ mod calculate_fee_for_order_fulfill_against_amm {
         assert_eq!(referrer_reward, 0);
         assert_eq!(referee_discount, 0);
     }
+
+    #[test]
+    fn referral_campaign() {
+        let quote_asset_amount = 100 * QUOTE_PRECISION_U64;
+
+        let taker_stats = UserStats::default();
+        let fee_structure = FeeStructure::test_default();
+
+        let mut referral_campaign = ReferralCampaign {
+            second_level_referrer: Pubkey::new_unique(),
+            start_ts: 0,
+            end_ts: 100,
+            budget: 100 * QUOTE_PRECISION_U64,
+            referrer_reward_numerator: 20,
+            referee_fee_numerator: 10,
+            second_level_reward_numerator: 25,
+            ..ReferralCampaign::default()
+        };
+
+        let FillFees {
+            user_fee,
+            fee_to_market,
+            referee_discount,
+            referrer_reward,
+            second_level_referrer_reward,
+            ..
+        } = calculate_fee_for_fulfillment_with_amm(
+            &taker_stats,
+            quote_asset_amount,
+            &fee_structure,
+            0,
+            60,
+            false,
+            true,
+            &None,
+            0,
+            false,
+            0,
+            false,
+            None,
+            Some(&referral_campaign),
+            MarketFeeOverride::default(),
+        )
+        .unwrap();
+
+        assert_eq!(user_fee, 90000);
+        assert_eq!(referee_discount, 10000);
+        assert_eq!(referrer_reward, 15000);
+        assert_eq!(second_level_referrer_reward, 5000);
+        assert_eq!(fee_to_market, 70000);
+
+        // budget only covers the discount and part of the referrer reward
+        referral_campaign.budget = 15000;
+
+        let FillFees {
+            user_fee,
+            fee_to_market,
+            referee_discount,
+            referrer_reward,
+            second_level_referrer_reward,
+            ..
+        } = calculate_fee_for_fulfillment_with_amm(
+            &taker_stats,
+            quote_asset_amount,
+            &fee_structure,
+            0,
+            60,
+            false,
+            true,
+            &None,
+            0,
+            false,
+            0,
+            false,
+            None,
+            Some(&referral_campaign),
+            MarketFeeOverride::default(),
+        )
+        .unwrap();
+
+        assert_eq!(user_fee, 90000);
+        assert_eq!(referee_discount, 10000);
+        assert_eq!(referrer_reward, 3750);
+        assert_eq!(second_level_referrer_reward, 1250);
+        assert_eq!(fee_to_market, 85000);
+    }
 }
 
 mod calculate_fee_for_fulfillment_with_serum {
     use crate::math::constants::QUOTE_PRECISION_U64;
     use crate::math::fees::{calculate_fee_for_fulfillment_with_external_market, ExternalFillFees};
-    use crate::state::state::FeeStructure;
+    use crate::state::state::{FeeStructure, MarketFeeOverride};
     use crate::state::user::UserStats;
 
     #[test]
@@ -813,6 +1017,7 @@ mod calculate_fee_for_fulfillment_with_serum {
             serum_referrer_rebate,
             fee_pool_token_amount,
             0,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -851,6 +1056,7 @@ mod calculate_fee_for_fulfillment_with_serum {
             serum_referrer_rebate,
             fee_pool_token_amount,
             0,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -890,6 +1096,7 @@ mod calculate_fee_for_fulfillment_with_serum {
             serum_referrer_rebate,
             fee_pool_token_amount,
             0,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -929,6 +1136,7 @@ mod calculate_fee_for_fulfillment_with_serum {
             serum_referrer_rebate,
             fee_pool_token_amount,
             0,
+            MarketFeeOverride::default(),
         )
         .unwrap();
 
@@ -5,10 +5,14 @@ use crate::math::casting::Cast;
 use crate::math::constants::{
     AMM_RESERVE_PRECISION_I128, FUNDING_RATE_TO_QUOTE_PRECISION_PRECISION_RATIO,
     LIQUIDATION_FEE_PRECISION, LIQUIDATION_FEE_PRECISION_U128,
-    LIQUIDATION_FEE_TO_MARGIN_PRECISION_RATIO, LIQUIDATION_PCT_PRECISION, PRICE_PRECISION,
+    LIQUIDATION_FEE_TO_MARGIN_PRECISION_RATIO, LIQUIDATION_PCT_PRECISION, MARGIN_PRECISION_U128,
+    PERCENTAGE_PRECISION, PERCENTAGE_PRECISION_I128, PRICE_PRECISION,
     PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO, QUOTE_PRECISION, SPOT_WEIGHT_PRECISION_U128,
 };
-use crate::math::margin::calculate_margin_requirement_and_total_collateral_and_liability_info;
+use crate::math::margin::{
+    calculate_margin_requirement_and_total_collateral_and_liability_info, MarginRequirementType,
+};
+use crate::math::position::calculate_base_asset_value_with_oracle_price;
 use crate::math::safe_math::SafeMath;
 use crate::math::spot_balance::get_token_amount;
 
@@ -62,6 +66,34 @@ pub fn calculate_base_asset_amount_to_cover_margin_shortage(
         .cast()
 }
 
+/// Inverse of calculate_base_asset_amount_to_cover_margin_shortage without an if fee, the margin
+/// shortage covered by closing `base_asset_amount` at `liquidation_fee` from the oracle
+pub fn calculate_margin_shortage_covered_by_base_asset_amount(
+    base_asset_amount: u64,
+    margin_ratio: u32,
+    liquidation_fee: u32,
+    oracle_price: i64,
+    quote_oracle_price: i64,
+) -> DriftResult<u128> {
+    let margin_ratio = margin_ratio.safe_mul(LIQUIDATION_FEE_TO_MARGIN_PRECISION_RATIO)?;
+
+    if margin_ratio <= liquidation_fee {
+        return Ok(0);
+    }
+
+    base_asset_amount
+        .cast::<u128>()?
+        .safe_mul(
+            oracle_price
+                .cast::<u128>()?
+                .safe_mul(quote_oracle_price.cast()?)?
+                .safe_div(PRICE_PRECISION)?,
+        )?
+        .safe_mul(margin_ratio.safe_sub(liquidation_fee)?.cast()?)?
+        .safe_div(LIQUIDATION_FEE_PRECISION_U128)?
+        .safe_div(PRICE_TIMES_AMM_TO_QUOTE_PRECISION_RATIO)
+}
+
 pub fn calculate_liability_transfer_to_cover_margin_shortage(
     margin_shortage: u128,
     asset_weight: u32,
@@ -237,7 +269,7 @@ pub fn validate_user_not_being_liquidated(
 
     if user.is_cross_margin_being_liquidated() {
         if margin_calculation.can_exit_cross_margin_liquidation()? {
-            user.exit_cross_margin_liquidation();
+            user.exit_cross_margin_liquidation()?;
         } else {
             return Err(ErrorCode::UserIsBeingLiquidated);
         }
@@ -371,13 +403,49 @@ pub fn calculate_max_pct_to_liquidate(
     slot: u64,
     initial_pct_to_liquidate: u128,
     liquidation_duration: u128,
+) -> DriftResult<u128> {
+    calculate_max_pct_to_free(
+        margin_shortage,
+        user.last_active_slot,
+        user.liquidation_margin_freed,
+        slot,
+        initial_pct_to_liquidate,
+        liquidation_duration,
+    )
+}
+
+/// Paces deleveraging like liquidation, from the slot the user was first deleveraged in
+pub fn calculate_max_pct_to_deleverage(
+    user: &User,
+    margin_shortage: u128,
+    slot: u64,
+    initial_pct_to_liquidate: u128,
+    liquidation_duration: u128,
+) -> DriftResult<u128> {
+    calculate_max_pct_to_free(
+        margin_shortage,
+        user.deleverage_start_slot,
+        user.deleverage_margin_freed,
+        slot,
+        initial_pct_to_liquidate,
+        liquidation_duration,
+    )
+}
+
+fn calculate_max_pct_to_free(
+    margin_shortage: u128,
+    start_slot: u64,
+    margin_freed: u64,
+    slot: u64,
+    initial_pct_to_liquidate: u128,
+    liquidation_duration: u128,
 ) -> DriftResult<u128> {
     // if margin shortage is tiny, accelerate liquidation
     if margin_shortage < 50 * QUOTE_PRECISION {
         return Ok(LIQUIDATION_PCT_PRECISION);
     }
 
-    let slots_elapsed = slot.safe_sub(user.last_active_slot)?;
+    let slots_elapsed = slot.safe_sub(start_slot)?;
 
     let pct_freeable = slots_elapsed
         .cast::<u128>()?
@@ -387,11 +455,11 @@ pub fn calculate_max_pct_to_liquidate(
         .safe_add(initial_pct_to_liquidate)?
         .min(LIQUIDATION_PCT_PRECISION);
 
-    let total_margin_shortage = margin_shortage.safe_add(user.liquidation_margin_freed.cast()?)?;
+    let total_margin_shortage = margin_shortage.safe_add(margin_freed.cast()?)?;
     let max_margin_freed = total_margin_shortage
         .safe_mul(pct_freeable)?
         .safe_div(LIQUIDATION_PCT_PRECISION)?;
-    let margin_freeable = max_margin_freed.saturating_sub(user.liquidation_margin_freed.cast()?);
+    let margin_freeable = max_margin_freed.saturating_sub(margin_freed.cast()?);
 
     margin_freeable
         .safe_mul(LIQUIDATION_PCT_PRECISION)?
@@ -532,6 +600,199 @@ pub fn get_liquidation_order_params(
     Ok(order_params)
 }
 
+/// Reduce only oracle order whose price starts at the oracle and decays to the max liquidation fee
+/// over the auction, the price improvement vs the max fee stays with the liquidatee. Expires
+/// `auction_duration` seconds after `now`, which is never before the auction's last slot
+pub fn get_liquidation_auction_order_params(
+    market_index: u16,
+    existing_direction: PositionDirection,
+    base_asset_amount: u64,
+    oracle_price: i64,
+    max_liquidation_fee: u32,
+    auction_duration: u8,
+    now: i64,
+) -> DriftResult<OrderParams> {
+    let direction = existing_direction.opposite();
+
+    let max_discount = oracle_price
+        .abs()
+        .cast::<u128>()?
+        .safe_mul(max_liquidation_fee.cast()?)?
+        .safe_div(LIQUIDATION_FEE_PRECISION_U128)?
+        .cast::<i64>()?;
+
+    let auction_end_price_offset = match direction {
+        PositionDirection::Long => max_discount,
+        PositionDirection::Short => -max_discount,
+    };
+
+    let order_params = OrderParams {
+        market_index,
+        direction,
+        order_type: OrderType::Oracle,
+        market_type: MarketType::Perp,
+        base_asset_amount,
+        reduce_only: true,
+        oracle_price_offset: Some(auction_end_price_offset.cast()?),
+        auction_duration: Some(auction_duration),
+        auction_start_price: Some(0),
+        auction_end_price: Some(auction_end_price_offset),
+        max_ts: Some(now.safe_add(auction_duration.cast()?)?),
+        ..OrderParams::default()
+    };
+
+    Ok(order_params)
+}
+
+/// Reduce only oracle order for deleveraging a position, priced at most the liquidator fee away
+/// from the oracle. Expires `duration` seconds after `now` so an unfilled order doesn't outlive
+/// the pacing it was sized with
+pub fn get_deleverage_order_params(
+    market_index: u16,
+    existing_direction: PositionDirection,
+    base_asset_amount: u64,
+    oracle_price: i64,
+    liquidator_fee: u32,
+    duration: u8,
+    now: i64,
+) -> DriftResult<OrderParams> {
+    let direction = existing_direction.opposite();
+
+    let max_discount = oracle_price
+        .abs()
+        .cast::<u128>()?
+        .safe_mul(liquidator_fee.cast()?)?
+        .safe_div(LIQUIDATION_FEE_PRECISION_U128)?
+        .cast::<i32>()?;
+
+    let oracle_price_offset = match direction {
+        PositionDirection::Long => max_discount,
+        PositionDirection::Short => -max_discount,
+    };
+
+    let order_params = OrderParams {
+        market_index,
+        direction,
+        order_type: OrderType::Oracle,
+        market_type: MarketType::Perp,
+        base_asset_amount,
+        reduce_only: true,
+        oracle_price_offset: Some(oracle_price_offset),
+        max_ts: Some(now.safe_add(duration.cast()?)?),
+        ..OrderParams::default()
+    };
+
+    Ok(order_params)
+}
+
+/// Cross margin perp position with the largest maintenance margin requirement at the oracle price
+pub fn get_largest_risk_cross_perp_market_index(
+    user: &User,
+    perp_market_map: &PerpMarketMap,
+    oracle_map: &mut OracleMap,
+) -> DriftResult<Option<u16>> {
+    let mut largest_risk: Option<(u16, u128)> = None;
+    for perp_position in user.perp_positions.iter() {
+        if perp_position.base_asset_amount == 0 || perp_position.is_isolated() {
+            continue;
+        }
+
+        let market = perp_market_map.get_ref(&perp_position.market_index)?;
+        let oracle_price = oracle_map.get_price_data(&market.oracle_id())?.price;
+
+        let base_asset_value = calculate_base_asset_value_with_oracle_price(
+            perp_position.base_asset_amount.cast()?,
+            oracle_price,
+        )?;
+        let margin_ratio = market.get_margin_ratio(
+            perp_position.base_asset_amount.unsigned_abs().cast()?,
+            MarginRequirementType::Maintenance,
+            user.is_high_leverage_mode(MarginRequirementType::Maintenance),
+        )?;
+        let margin_requirement = base_asset_value
+            .safe_mul(margin_ratio.cast()?)?
+            .safe_div(MARGIN_PRECISION_U128)?;
+
+        let is_largest = match largest_risk {
+            Some((_, largest_margin_requirement)) => {
+                margin_requirement > largest_margin_requirement
+            }
+            None => true,
+        };
+
+        if is_largest {
+            largest_risk = Some((perp_position.market_index, margin_requirement));
+        }
+    }
+
+    Ok(largest_risk.map(|(market_index, _)| market_index))
+}
+
+/// Max the insurance fund can pay towards a perp market bankruptcy. Leaves 1 in the vault so it
+/// always remains >= 1
+pub fn calculate_max_perp_if_payment(
+    perp_market: &PerpMarket,
+    insurance_fund_vault_balance: u64,
+) -> DriftResult<u128> {
+    let max_insurance_withdraw = perp_market
+        .insurance_claim
+        .quote_max_insurance
+        .safe_sub(perp_market.insurance_claim.quote_settled_insurance)?
+        .cast::<u128>()?;
+
+    Ok(insurance_fund_vault_balance
+        .saturating_sub(1)
+        .cast::<u128>()?
+        .min(max_insurance_withdraw))
+}
+
+/// Auto deleverage queue score, the position's pnl as a share of its entry value times the
+/// account's leverage. Positions without profit or accounts without collateral score 0
+/// precision: PERCENTAGE_PRECISION
+pub fn calculate_auto_deleverage_score(
+    unrealized_pnl: i128,
+    quote_entry_amount: i64,
+    base_asset_value: u128,
+    total_collateral: i128,
+) -> DriftResult<u128> {
+    if unrealized_pnl <= 0 || total_collateral <= 0 {
+        return Ok(0);
+    }
+
+    let pnl_pct = unrealized_pnl
+        .unsigned_abs()
+        .safe_mul(PERCENTAGE_PRECISION)?
+        .safe_div(quote_entry_amount.unsigned_abs().max(1).cast()?)?;
+
+    let leverage = base_asset_value
+        .safe_mul(PERCENTAGE_PRECISION)?
+        .safe_div(total_collateral.unsigned_abs())?;
+
+    pnl_pct.safe_mul(leverage)?.safe_div(PERCENTAGE_PRECISION)
+}
+
+/// Pnl against the entry value as a share of the entry value. Applied to a market side's totals it
+/// is the bound keeper submitted positions must meet to enter the auto deleverage queue
+/// precision: PERCENTAGE_PRECISION
+pub fn calculate_entry_pnl_pct(
+    base_asset_amount: i128,
+    quote_entry_amount: i128,
+    oracle_price: i64,
+) -> DriftResult<i128> {
+    let base_asset_value =
+        calculate_base_asset_value_with_oracle_price(base_asset_amount, oracle_price)?
+            .cast::<i128>()?;
+
+    let pnl = if base_asset_amount < 0 {
+        quote_entry_amount.safe_sub(base_asset_value)?
+    } else {
+        quote_entry_amount.safe_add(base_asset_value)?
+    };
+
+    pnl.safe_mul(PERCENTAGE_PRECISION_I128)?
+        .safe_div(quote_entry_amount.unsigned_abs().max(1).cast()?)
+}
+
 pub fn get_liquidation_fee(
     base_liquidation_fee: u32,
     max_liquidation_fee: u32,
@@ -905,6 +905,117 @@ mod calculate_max_pct_to_liquidate {
     }
 }
 
+mod calculate_max_pct_to_deleverage {
+    use crate::math::liquidation::calculate_max_pct_to_deleverage;
+    use crate::state::user::User;
+    use crate::{LIQUIDATION_PCT_PRECISION, QUOTE_PRECISION};
+
+    #[test]
+    fn paced_from_deleverage_start() {
+        // idle user, last active long before the deleverage started
+        let user = User {
+            last_active_slot: 1,
+            deleverage_start_slot: 1000,
+            ..User::default()
+        };
+        let margin_shortage = 100 * QUOTE_PRECISION;
+
+        let pct = calculate_max_pct_to_deleverage(
+            &user,
+            margin_shortage,
+            1000,
+            LIQUIDATION_PCT_PRECISION / 10,
+            10,
+        )
+        .unwrap();
+        assert_eq!(pct, LIQUIDATION_PCT_PRECISION / 10);
+
+        let pct = calculate_max_pct_to_deleverage(
+            &user,
+            margin_shortage,
+            1005,
+            LIQUIDATION_PCT_PRECISION / 10,
+            10,
+        )
+        .unwrap();
+        assert_eq!(pct, LIQUIDATION_PCT_PRECISION * 6 / 10);
+    }
+
+    #[test]
+    fn margin_freed() {
+        let user = User {
+            deleverage_start_slot: 1000,
+            deleverage_margin_freed: 50 * QUOTE_PRECISION as u64,
+            ..User::default()
+        };
+        let margin_shortage = 150 * QUOTE_PRECISION;
+
+        // 60% of the 200 total shortage is freeable, 50 has been freed already
+        let pct = calculate_max_pct_to_deleverage(
+            &user,
+            margin_shortage,
+            1005,
+            LIQUIDATION_PCT_PRECISION / 10,
+            10,
+        )
+        .unwrap();
+        assert_eq!(pct, 70 * LIQUIDATION_PCT_PRECISION / 150);
+    }
+}
+
+mod calculate_margin_shortage_covered_by_base_asset_amount {
+    use crate::math::constants::{
+        BASE_PRECISION_U64, LIQUIDATION_FEE_PRECISION, MARGIN_PRECISION, PRICE_PRECISION_I64,
+        QUOTE_PRECISION,
+    };
+    use crate::math::liquidation::{
+        calculate_base_asset_amount_to_cover_margin_shortage,
+        calculate_margin_shortage_covered_by_base_asset_amount,
+    };
+
+    #[test]
+    fn inverse_of_base_asset_amount_to_cover() {
+        let margin_shortage = 10 * QUOTE_PRECISION;
+        let margin_ratio = MARGIN_PRECISION / 10;
+        let liquidation_fee = LIQUIDATION_FEE_PRECISION / 100;
+        let oracle_price = 100 * PRICE_PRECISION_I64;
+
+        let base_asset_amount = calculate_base_asset_amount_to_cover_margin_shortage(
+            margin_shortage,
+            margin_ratio,
+            liquidation_fee,
+            0,
+            oracle_price,
+            PRICE_PRECISION_I64,
+        )
+        .unwrap();
+        assert_eq!(base_asset_amount, BASE_PRECISION_U64 * 10 / 9);
+
+        let margin_shortage_covered = calculate_margin_shortage_covered_by_base_asset_amount(
+            base_asset_amount,
+            margin_ratio,
+            liquidation_fee,
+            oracle_price,
+            PRICE_PRECISION_I64,
+        )
+        .unwrap();
+        assert_eq!(margin_shortage_covered, margin_shortage - 1);
+    }
+
+    #[test]
+    fn fee_above_margin_ratio() {
+        let margin_shortage_covered = calculate_margin_shortage_covered_by_base_asset_amount(
+            BASE_PRECISION_U64,
+            MARGIN_PRECISION / 100,
+            LIQUIDATION_FEE_PRECISION / 50,
+            100 * PRICE_PRECISION_I64,
+            PRICE_PRECISION_I64,
+        )
+        .unwrap();
+        assert_eq!(margin_shortage_covered, 0);
+    }
+}
+
 mod get_liquidation_fee {
     use crate::math::liquidation::get_liquidation_fee;
     use crate::LIQUIDATION_FEE_PRECISION;
@@ -933,6 +1044,255 @@ mod get_liquidation_fee {
     }
 }
 
+mod get_liquidation_auction_order_params {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::{BASE_PRECISION_U64, PRICE_PRECISION_I64};
+    use crate::math::liquidation::get_liquidation_auction_order_params;
+    use crate::state::user::{MarketType, OrderType};
+    use crate::LIQUIDATION_FEE_PRECISION;
+
+    #[test]
+    fn long_position() {
+        let max_liq_fee: u32 = 5 * LIQUIDATION_FEE_PRECISION / 100;
+        let oracle_price = 100 * PRICE_PRECISION_I64;
+
+        let params = get_liquidation_auction_order_params(
+            0,
+            PositionDirection::Long,
+            BASE_PRECISION_U64,
+            oracle_price,
+            max_liq_fee,
+            20,
+            1000,
+        )
+        .unwrap();
+
+        assert_eq!(params.direction, PositionDirection::Short);
+        assert_eq!(params.order_type, OrderType::Oracle);
+        assert_eq!(params.market_type, MarketType::Perp);
+        assert_eq!(params.base_asset_amount, BASE_PRECISION_U64);
+        assert!(params.reduce_only);
+        assert_eq!(params.auction_duration, Some(20));
+        assert_eq!(params.max_ts, Some(1020));
+        assert_eq!(params.auction_start_price, Some(0));
+        assert_eq!(params.auction_end_price, Some(-5 * PRICE_PRECISION_I64));
+        assert_eq!(
+            params.oracle_price_offset,
+            Some(-5 * PRICE_PRECISION_I64 as i32)
+        );
+    }
+
+    #[test]
+    fn short_position() {
+        let max_liq_fee: u32 = 5 * LIQUIDATION_FEE_PRECISION / 100;
+        let oracle_price = 100 * PRICE_PRECISION_I64;
+
+        let params = get_liquidation_auction_order_params(
+            0,
+            PositionDirection::Short,
+            BASE_PRECISION_U64,
+            oracle_price,
+            max_liq_fee,
+            20,
+            1000,
+        )
+        .unwrap();
+
+        assert_eq!(params.direction, PositionDirection::Long);
+        assert_eq!(params.max_ts, Some(1020));
+        assert_eq!(params.auction_start_price, Some(0));
+        assert_eq!(params.auction_end_price, Some(5 * PRICE_PRECISION_I64));
+        assert_eq!(
+            params.oracle_price_offset,
+            Some(5 * PRICE_PRECISION_I64 as i32)
+        );
+    }
+}
+
+mod get_deleverage_order_params {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::{BASE_PRECISION_U64, PRICE_PRECISION_I64};
+    use crate::math::liquidation::get_deleverage_order_params;
+    use crate::state::user::{MarketType, OrderType};
+    use crate::LIQUIDATION_FEE_PRECISION;
+
+    #[test]
+    fn long_position() {
+        let liquidator_fee: u32 = LIQUIDATION_FEE_PRECISION / 100;
+        let oracle_price = 100 * PRICE_PRECISION_I64;
+
+        let params = get_deleverage_order_params(
+            0,
+            PositionDirection::Long,
+            BASE_PRECISION_U64,
+            oracle_price,
+            liquidator_fee,
+            150,
+            1000,
+        )
+        .unwrap();
+
+        assert_eq!(params.direction, PositionDirection::Short);
+        assert_eq!(params.order_type, OrderType::Oracle);
+        assert_eq!(params.market_type, MarketType::Perp);
+        assert_eq!(params.base_asset_amount, BASE_PRECISION_U64);
+        assert!(params.reduce_only);
+        assert_eq!(
+            params.oracle_price_offset,
+            Some(-PRICE_PRECISION_I64 as i32)
+        );
+        assert_eq!(params.auction_duration, None);
+        assert_eq!(params.max_ts, Some(1150));
+    }
+
+    #[test]
+    fn short_position() {
+        let liquidator_fee: u32 = LIQUIDATION_FEE_PRECISION / 100;
+        let oracle_price = 100 * PRICE_PRECISION_I64;
+
+        let params = get_deleverage_order_params(
+            0,
+            PositionDirection::Short,
+            BASE_PRECISION_U64,
+            oracle_price,
+            liquidator_fee,
+            150,
+            1000,
+        )
+        .unwrap();
+
+        assert_eq!(params.direction, PositionDirection::Long);
+        assert_eq!(params.oracle_price_offset, Some(PRICE_PRECISION_I64 as i32));
+    }
+}
+
+mod calculate_auto_deleverage_score {
+    use crate::math::constants::{PERCENTAGE_PRECISION, QUOTE_PRECISION_I128, QUOTE_PRECISION_I64};
+    use crate::math::liquidation::calculate_auto_deleverage_score;
+    use crate::QUOTE_PRECISION;
+
+    #[test]
+    fn no_profit() {
+        let score = calculate_auto_deleverage_score(
+            -10 * QUOTE_PRECISION_I128,
+            -100 * QUOTE_PRECISION_I64,
+            90 * QUOTE_PRECISION,
+            100 * QUOTE_PRECISION_I128,
+        )
+        .unwrap();
+        assert_eq!(score, 0);
+
+        let score = calculate_auto_deleverage_score(
+            10 * QUOTE_PRECISION_I128,
+            -100 * QUOTE_PRECISION_I64,
+            110 * QUOTE_PRECISION,
+            -QUOTE_PRECISION_I128,
+        )
+        .unwrap();
+        assert_eq!(score, 0);
+    }
+
+    #[test]
+    fn ranks_by_pnl_and_leverage() {
+        // 10% pnl at 1.1x leverage
+        let score = calculate_auto_deleverage_score(
+            10 * QUOTE_PRECISION_I128,
+            -100 * QUOTE_PRECISION_I64,
+            110 * QUOTE_PRECISION,
+            100 * QUOTE_PRECISION_I128,
+        )
+        .unwrap();
+        assert_eq!(score, PERCENTAGE_PRECISION * 11 / 100);
+
+        // 10% pnl at 11x leverage
+        let levered_score = calculate_auto_deleverage_score(
+            10 * QUOTE_PRECISION_I128,
+            -100 * QUOTE_PRECISION_I64,
+            110 * QUOTE_PRECISION,
+            10 * QUOTE_PRECISION_I128,
+        )
+        .unwrap();
+        assert_eq!(levered_score, PERCENTAGE_PRECISION * 11 / 10);
+
+        // 50% pnl at 1.1x leverage
+        let profitable_score = calculate_auto_deleverage_score(
+            50 * QUOTE_PRECISION_I128,
+            100 * QUOTE_PRECISION_I64,
+            55 * QUOTE_PRECISION,
+            50 * QUOTE_PRECISION_I128,
+        )
+        .unwrap();
+        assert_eq!(profitable_score, PERCENTAGE_PRECISION * 55 / 100);
+
+        assert!(levered_score > profitable_score);
+        assert!(profitable_score > score);
+    }
+}
+
+mod calculate_entry_pnl_pct {
+    use crate::math::constants::{
+        BASE_PRECISION_I128, PERCENTAGE_PRECISION_I128, PRICE_PRECISION_I64, QUOTE_PRECISION_I128,
+    };
+    use crate::math::liquidation::calculate_entry_pnl_pct;
+
+    #[test]
+    fn test() {
+        let oracle_price = 100 * PRICE_PRECISION_I64;
+
+        // shorts 12 for 1780, worth 1200
+        let pnl_pct = calculate_entry_pnl_pct(
+            -12 * BASE_PRECISION_I128,
+            1780 * QUOTE_PRECISION_I128,
+            oracle_price,
+        )
+        .unwrap();
+        assert_eq!(pnl_pct, 580 * PERCENTAGE_PRECISION_I128 / 1780);
+
+        // long 10 for 1500, worth 1000
+        let pnl_pct = calculate_entry_pnl_pct(
+            10 * BASE_PRECISION_I128,
+            -1500 * QUOTE_PRECISION_I128,
+            oracle_price,
+        )
+        .unwrap();
+        assert_eq!(pnl_pct, -PERCENTAGE_PRECISION_I128 / 3);
+
+        let pnl_pct = calculate_entry_pnl_pct(0, 0, oracle_price).unwrap();
+        assert_eq!(pnl_pct, 0);
+    }
+}
+
+mod calculate_max_perp_if_payment {
+    use crate::math::liquidation::calculate_max_perp_if_payment;
+    use crate::state::perp_market::{InsuranceClaim, PerpMarket};
+    use crate::QUOTE_PRECISION_U64;
+
+    #[test]
+    fn test() {
+        let market = PerpMarket {
+            insurance_claim: InsuranceClaim {
+                quote_max_insurance: 100 * QUOTE_PRECISION_U64,
+                quote_settled_insurance: 40 * QUOTE_PRECISION_U64,
+                ..InsuranceClaim::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        // capped by the market's remaining insurance
+        let max_if_payment =
+            calculate_max_perp_if_payment(&market, 1000 * QUOTE_PRECISION_U64).unwrap();
+        assert_eq!(max_if_payment, 60 * QUOTE_PRECISION_U64 as u128);
+
+        // capped by the vault, which keeps 1
+        let max_if_payment =
+            calculate_max_perp_if_payment(&market, 10 * QUOTE_PRECISION_U64).unwrap();
+        assert_eq!(max_if_payment, 10 * QUOTE_PRECISION_U64 as u128 - 1);
+
+        let max_if_payment = calculate_max_perp_if_payment(&market, 0).unwrap();
+        assert_eq!(max_if_payment, 0);
+    }
+}
+
 mod validate_swap_within_liquidation_boundaries {
     use crate::math::liquidation::validate_swap_within_liquidation_boundaries;
     use crate::{LIQUIDATION_FEE_PRECISION, PRICE_PRECISION_I64};
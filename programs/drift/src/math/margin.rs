@@ -17,6 +17,8 @@ use crate::math::funding::calculate_funding_payment;
 use crate::math::oracle::{is_oracle_valid_for_action, DriftAction};
 
 use crate::math::helpers::get_proportion_u128;
+use crate::math::margin_correlations::MarginCorrelationExposures;
+use crate::math::portfolio_margin::PortfolioMarginGroups;
 use crate::math::safe_math::SafeMath;
 use crate::math::spot_balance::{get_strict_token_value, get_token_value};
 use crate::msg;
@@ -283,6 +285,15 @@ pub fn calculate_margin_requirement_and_total_collateral_and_liability_info(
 
     let user_pool_id = user.pool_id;
 
+    let mut portfolio_margin_groups = PortfolioMarginGroups::new(
+        user,
+        perp_market_map,
+        spot_market_map,
+        oracle_map.portfolio_margin_enabled,
+    )?;
+
+    let mut margin_correlation_exposures = MarginCorrelationExposures::default();
+
     for spot_position in user.spot_positions.iter() {
         validation::position::validate_spot_position(spot_position)?;
 
@@ -429,6 +440,22 @@ pub fn calculate_margin_requirement_and_total_collateral_and_liability_info(
                 )?;
             }
 
+            let is_margin_correlation_exposure =
+                !portfolio_margin_groups.contains_spot_market(spot_market.market_index);
+
+            if let Some(portfolio_margin_group) =
+                portfolio_margin_groups.get_spot_group_mut(spot_market.market_index)
+            {
+                if !(calculation.context.ignore_invalid_deposit_oracles && !oracle_valid) {
+                    portfolio_margin_group.add_spot_position(
+                        worst_case_token_value,
+                        worst_case_token_value
+                            .safe_sub(worst_case_weighted_token_value)?
+                            .unsigned_abs(),
+                    )?;
+                }
+            }
+
             calculation.add_cross_margin_margin_requirement(
                 spot_position.margin_requirement_for_open_orders()?,
                 0,
@@ -443,6 +470,14 @@ pub fn calculate_margin_requirement_and_total_collateral_and_liability_info(
                             spot_market.market_index
                         );
                         worst_case_weighted_token_value = 0;
+                    } else if is_margin_correlation_exposure {
+                        margin_correlation_exposures.add_exposure(
+                            MarketIdentifier::spot(spot_market.market_index),
+                            true,
+                            worst_case_token_value
+                                .safe_sub(worst_case_weighted_token_value)?
+                                .unsigned_abs(),
+                        );
                     }
 
                     calculation.add_cross_margin_total_collateral(
@@ -476,6 +511,16 @@ pub fn calculate_margin_requirement_and_total_collateral_and_liability_info(
                         MarketIdentifier::spot(spot_market.market_index),
                     )?;
 
+                    if is_margin_correlation_exposure {
+                        margin_correlation_exposures.add_exposure(
+                            MarketIdentifier::spot(spot_market.market_index),
+                            false,
+                            worst_case_weighted_token_value
+                                .unsigned_abs()
+                                .safe_sub(worst_case_token_value.unsigned_abs())?,
+                        );
+                    }
+
                     calculation.add_spot_liability()?;
                     calculation.update_with_spot_isolated_liability(
                         spot_market.asset_tier == AssetTier::Isolated,
@@ -629,6 +674,31 @@ pub fn calculate_margin_requirement_and_total_collateral_and_liability_info(
             oracle_price_data.price,
         )?;
 
+        let is_margin_correlation_exposure =
+            !portfolio_margin_groups.contains_perp_market(market.market_index);
+
+        if let Some(portfolio_margin_group) =
+            portfolio_margin_groups.get_perp_group_mut(market.market_index)
+        {
+            let (worst_case_base_asset_amount, _) = market_position
+                .worst_case_liability_value(oracle_price_data.price, market.contract_type)?;
+
+            let margin_ratio = perp_position_custom_margin_ratio.max(market.get_margin_ratio(
+                worst_case_base_asset_amount.unsigned_abs(),
+                position_margin_type,
+                perp_position_user_high_leverage_mode,
+            )?);
+
+            portfolio_margin_group.add_perp_position(
+                market.market_index,
+                worst_case_base_asset_amount,
+                worst_case_liability_value,
+                perp_margin_requirement
+                    .safe_sub(market_position.margin_requirement_for_open_orders()?)?,
+                margin_ratio,
+            )?;
+        }
+
         if market_position.is_isolated() {
             let quote_spot_market = spot_market_map.get_ref(&market.quote_spot_market_index)?;
             let quote_token_amount = get_token_amount(
@@ -662,6 +732,14 @@ pub fn calculate_margin_requirement_and_total_collateral_and_liability_info(
                 MarketIdentifier::perp(market.market_index),
             )?;
 
+            if is_margin_correlation_exposure && market_position.base_asset_amount != 0 {
+                margin_correlation_exposures.add_exposure(
+                    MarketIdentifier::perp(market.market_index),
+                    market_position.base_asset_amount > 0,
+                    perp_margin_requirement,
+                );
+            }
+
             calculation.add_cross_margin_total_collateral(weighted_pnl)?;
         }
 
@@ -693,6 +771,28 @@ pub fn calculate_margin_requirement_and_total_collateral_and_liability_info(
         }
     }
 
+    for portfolio_margin_group in portfolio_margin_groups.iter() {
+        let portfolio_margin_offset = portfolio_margin_group.calculate_offset()?;
+
+        calculation.add_cross_margin_margin_requirement(
+            portfolio_margin_offset.margin_requirement,
+            0,
+            MarketIdentifier::perp(portfolio_margin_group.perp_market_index),
+        )?;
+        calculation.add_cross_margin_total_collateral(
+            portfolio_margin_offset.total_collateral_credit.cast()?,
+        )?;
+        calculation.add_cross_margin_correlation_credit(
+            portfolio_margin_offset.margin_requirement_credit,
+        )?;
+    }
+
+    if let Some(margin_correlations) = oracle_map.margin_correlations.as_deref() {
+        calculation.add_cross_margin_correlation_credit(
+            margin_correlation_exposures.calculate_margin_credit(margin_correlations)?,
+        )?;
+    }
+
     calculation.validate_num_spot_liabilities()?;
 
     // update fuel to account for spot market deltas where there is no spot position
@@ -872,6 +972,12 @@ pub fn validate_user_can_enable_high_leverage_mode(
         "user already in high leverage mode"
     )?;
 
+    validate!(
+        !user.is_portfolio_margin_mode(),
+        ErrorCode::UserInPortfolioMarginMode,
+        "user in portfolio margin mode"
+    )?;
+
     let meets_maintenance_requirement =
         meets_maintenance_margin_requirement(user, perp_market_map, spot_market_map, oracle_map)?;
 
@@ -0,0 +1,94 @@
+use crate::error::DriftResult;
+use crate::math::casting::Cast;
+use crate::math::constants::PERCENTAGE_PRECISION;
+use crate::math::safe_math::SafeMath;
+use crate::state::margin_calculation::MarketIdentifier;
+use crate::state::margin_correlations::MarginCorrelations;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MarginCorrelationExposure {
+    pub market: MarketIdentifier,
+    pub is_long: bool,
+    /// Requirement attributable to the position's price risk. For spot deposits it's the haircut
+    /// taken off the collateral
+    /// precision: QUOTE_PRECISION
+    pub margin_requirement: u128,
+}
+
+/// Cross margin positions that can be offset against each other with the margin correlations
+#[derive(Default, Debug)]
+pub struct MarginCorrelationExposures {
+    exposures: Vec<MarginCorrelationExposure>,
+}
+
+impl MarginCorrelationExposures {
+    pub fn add_exposure(
+        &mut self,
+        market: MarketIdentifier,
+        is_long: bool,
+        margin_requirement: u128,
+    ) {
+        if margin_requirement == 0 {
+            return;
+        }
+
+        self.exposures.push(MarginCorrelationExposure {
+            market,
+            is_long,
+            margin_requirement,
+        });
+    }
+
+    /// Credits each correlated pair of opposite positions with the correlation times the smaller of
+    /// their requirements. A requirement that's been offset can't be offset again by a later pair
+    pub fn calculate_margin_credit(
+        &self,
+        margin_correlations: &MarginCorrelations,
+    ) -> DriftResult<u128> {
+        let mut remaining_margin_requirements: Vec<u128> = self
+            .exposures
+            .iter()
+            .map(|exposure| exposure.margin_requirement)
+            .collect();
+
+        let mut margin_credit = 0_u128;
+        for margin_correlation in margin_correlations.active_correlations() {
+            let index_a = self.find_exposure(margin_correlation.market_a());
+            let index_b = self.find_exposure(margin_correlation.market_b());
+
+            let (index_a, index_b) = match (index_a, index_b) {
+                (Some(index_a), Some(index_b)) => (index_a, index_b),
+                _ => continue,
+            };
+
+            if self.exposures[index_a].is_long == self.exposures[index_b].is_long {
+                continue;
+            }
+
+            let offset_margin_requirement =
+                remaining_margin_requirements[index_a].min(remaining_margin_requirements[index_b]);
+
+            remaining_margin_requirements[index_a] =
+                remaining_margin_requirements[index_a].safe_sub(offset_margin_requirement)?;
+            remaining_margin_requirements[index_b] =
+                remaining_margin_requirements[index_b].safe_sub(offset_margin_requirement)?;
+
+            margin_credit = margin_credit.safe_add(
+                offset_margin_requirement
+                    .safe_mul(margin_correlation.correlation.cast()?)?
+                    .safe_div(PERCENTAGE_PRECISION)?,
+            )?;
+        }
+
+        Ok(margin_credit)
+    }
+
+    fn find_exposure(&self, market: MarketIdentifier) -> Option<usize> {
+        self.exposures
+            .iter()
+            .position(|exposure| exposure.market == market)
+    }
+}
@@ -0,0 +1,74 @@
+use crate::math::constants::QUOTE_PRECISION;
+use crate::math::margin_correlations::MarginCorrelationExposures;
+use crate::state::margin_calculation::MarketIdentifier;
+use crate::state::margin_correlations::{MarginCorrelationParams, MarginCorrelations};
+use crate::state::user::MarketType;
+
+fn margin_correlations(params: &[MarginCorrelationParams]) -> MarginCorrelations {
+    let mut margin_correlations = MarginCorrelations::default();
+    for (i, params) in params.iter().enumerate() {
+        margin_correlations.update(i as u16, params).unwrap();
+    }
+    margin_correlations
+}
+
+#[test]
+fn opposite_perps() {
+    let margin_correlations = margin_correlations(&[MarginCorrelationParams {
+        market_type_a: MarketType::Perp,
+        market_index_a: 1,
+        market_type_b: MarketType::Perp,
+        market_index_b: 2,
+        correlation: 800_000,
+    }]);
+
+    let mut exposures = MarginCorrelationExposures::default();
+    exposures.add_exposure(MarketIdentifier::perp(1), true, 1000 * QUOTE_PRECISION);
+    exposures.add_exposure(MarketIdentifier::perp(2), false, 500 * QUOTE_PRECISION);
+
+    let credit = exposures
+        .calculate_margin_credit(&margin_correlations)
+        .unwrap();
+    assert_eq!(credit, 400 * QUOTE_PRECISION);
+
+    // same direction isn't a hedge
+    let mut exposures = MarginCorrelationExposures::default();
+    exposures.add_exposure(MarketIdentifier::perp(1), true, 1000 * QUOTE_PRECISION);
+    exposures.add_exposure(MarketIdentifier::perp(2), true, 500 * QUOTE_PRECISION);
+
+    let credit = exposures
+        .calculate_margin_credit(&margin_correlations)
+        .unwrap();
+    assert_eq!(credit, 0);
+}
+
+#[test]
+fn requirement_only_offset_once() {
+    let margin_correlations = margin_correlations(&[
+        MarginCorrelationParams {
+            market_type_a: MarketType::Spot,
+            market_index_a: 2,
+            market_type_b: MarketType::Perp,
+            market_index_b: 0,
+            correlation: 900_000,
+        },
+        MarginCorrelationParams {
+            market_type_a: MarketType::Perp,
+            market_index_a: 0,
+            market_type_b: MarketType::Perp,
+            market_index_b: 1,
+            correlation: 500_000,
+        },
+    ]);
+
+    let mut exposures = MarginCorrelationExposures::default();
+    exposures.add_exposure(MarketIdentifier::spot(2), true, 300 * QUOTE_PRECISION);
+    exposures.add_exposure(MarketIdentifier::perp(0), false, 500 * QUOTE_PRECISION);
+    exposures.add_exposure(MarketIdentifier::perp(1), true, 1000 * QUOTE_PRECISION);
+
+    // 300 of sol-perp offset by the lst deposit, the remaining 200 by btc-perp
+    let credit = exposures
+        .calculate_margin_credit(&margin_correlations)
+        .unwrap();
+    assert_eq!(credit, 270 * QUOTE_PRECISION + 100 * QUOTE_PRECISION);
+}
@@ -18,10 +18,12 @@ pub mod insurance;
 pub mod liquidation;
 pub mod lp_pool;
 pub mod margin;
+pub mod margin_correlations;
 pub mod matching;
 pub mod oracle;
 pub mod orders;
 pub mod pnl;
+pub mod portfolio_margin;
 pub mod position;
 pub mod quote_asset;
 pub mod repeg;
@@ -9,10 +9,11 @@ use crate::error::{DriftResult, ErrorCode};
 use crate::math::amm::calculate_amm_available_liquidity;
 use crate::math::casting::Cast;
 use crate::math::constants::{
-    BASE_PRECISION_I128, FEE_ADJUSTMENT_MAX, MARGIN_PRECISION_I128, MARGIN_PRECISION_U128,
-    MAX_PREDICTION_MARKET_PRICE, MAX_PREDICTION_MARKET_PRICE_I64, OPEN_ORDER_MARGIN_REQUIREMENT,
-    PERCENTAGE_PRECISION_I128, PERCENTAGE_PRECISION_U64, PRICE_PRECISION_I128, PRICE_PRECISION_U64,
-    QUOTE_PRECISION_I128, SPOT_WEIGHT_PRECISION, SPOT_WEIGHT_PRECISION_I128,
+    BASE_PRECISION_I128, FEE_ADJUSTMENT_MAX, FUNDING_RATE_BUFFER_I128, MARGIN_PRECISION_I128,
+    MARGIN_PRECISION_U128, MAX_PREDICTION_MARKET_PRICE, MAX_PREDICTION_MARKET_PRICE_I64,
+    OPEN_ORDER_MARGIN_REQUIREMENT, PERCENTAGE_PRECISION_I128, PERCENTAGE_PRECISION_U64,
+    PRICE_PRECISION_I128, PRICE_PRECISION_U64, QUOTE_PRECISION_I128, SPOT_WEIGHT_PRECISION,
+    SPOT_WEIGHT_PRECISION_I128,
 };
 use crate::state::protected_maker_mode_config::ProtectedMakerParams;
 use crate::state::user::OrderBitFlag;
@@ -35,7 +36,8 @@ use crate::state::perp_market_map::PerpMarketMap;
 use crate::state::spot_market::SpotMarket;
 use crate::state::spot_market_map::SpotMarketMap;
 use crate::state::user::{
-    MarketType, Order, OrderFillSimulation, OrderStatus, OrderTriggerCondition, PerpPosition, User,
+    MarketType, Order, OrderFillSimulation, OrderStatus, OrderTriggerCondition, OrderTriggerSource,
+    PerpPosition, User,
 };
 use crate::state::user_map::UserMap;
 use crate::validate;
@@ -355,13 +357,21 @@ pub fn get_position_delta_for_fill(
 }
 
 #[inline(always)]
-pub fn should_expire_order(user: &User, user_order_index: usize, now: i64) -> DriftResult<bool> {
+pub fn should_expire_order(
+    user: &User,
+    user_order_index: usize,
+    now: i64,
+    slot: u64,
+) -> DriftResult<bool> {
     let order = &user.orders[user_order_index];
-    if order.status != OrderStatus::Open || order.max_ts == 0 || order.must_be_triggered() {
+    if order.status != OrderStatus::Open
+        || (order.max_ts == 0 && order.max_slot == 0)
+        || order.must_be_triggered()
+    {
         return Ok(false);
     }
 
-    Ok(now > order.max_ts)
+    Ok(order.is_expired(now, slot))
 }
 
 pub fn should_cancel_reduce_only_order(
@@ -566,14 +576,144 @@ pub fn is_oracle_too_divergent_with_twap_5min(
     Ok(too_divergent)
 }
 
-pub fn order_satisfies_trigger_condition(order: &Order, oracle_price: u64) -> DriftResult<bool> {
+pub fn order_satisfies_trigger_condition(order: &Order, trigger_value: i64) -> DriftResult<bool> {
+    let threshold = order.get_trigger_threshold()?;
     match order.trigger_condition {
-        OrderTriggerCondition::Above => Ok(oracle_price > order.trigger_price),
-        OrderTriggerCondition::Below => Ok(oracle_price < order.trigger_price),
+        OrderTriggerCondition::Above => Ok(trigger_value > threshold),
+        OrderTriggerCondition::Below => Ok(trigger_value < threshold),
         _ => Err(print_error!(ErrorCode::InvalidTriggerOrderCondition)()),
     }
 }
 
+/// The value a perp trigger order's threshold is compared against. Oracle triggers use the
+/// (optionally median) trigger price, the rest read the amm's twaps and funding
+pub fn calculate_perp_trigger_value(
+    trigger_source: OrderTriggerSource,
+    market: &PerpMarket,
+    oracle_trigger_price: u64,
+) -> DriftResult<i64> {
+    match trigger_source {
+        OrderTriggerSource::Oracle => oracle_trigger_price.cast(),
+        OrderTriggerSource::MarkTwap => market.amm.last_mark_price_twap_5min.cast(),
+        OrderTriggerSource::FundingRate => {
+            let oracle_twap = market.amm.historical_oracle_data.last_oracle_price_twap;
+            validate!(
+                oracle_twap > 0,
+                ErrorCode::InvalidOracle,
+                "oracle twap must be positive to trigger off funding rate"
+            )?;
+
+            market
+                .amm
+                .last_funding_rate
+                .cast::<i128>()?
+                .safe_mul(PERCENTAGE_PRECISION_I128)?
+                .safe_div(
+                    oracle_twap
+                        .cast::<i128>()?
+                        .safe_mul(FUNDING_RATE_BUFFER_I128)?,
+                )?
+                .cast()
+        }
+        OrderTriggerSource::OracleMarkDivergence => {
+            let oracle_twap_5min = market
+                .amm
+                .historical_oracle_data
+                .last_oracle_price_twap_5min;
+            validate!(
+                oracle_twap_5min > 0,
+                ErrorCode::InvalidOracle,
+                "oracle twap must be positive to trigger off mark divergence"
+            )?;
+
+            market
+                .amm
+                .last_mark_price_twap_5min
+                .cast::<i128>()?
+                .safe_sub(oracle_twap_5min.cast()?)?
+                .safe_mul(PERCENTAGE_PRECISION_I128)?
+                .safe_div(oracle_twap_5min.cast()?)?
+                .cast()
+        }
+    }
+}
+
+/// Ratchets a trailing stop's trigger price towards the oracle price. A below (sell) stop can only
+/// move up and an above (buy) stop can only move down, so the trigger never gives back ground
+pub fn calculate_trailing_stop_trigger_price(
+    order: &Order,
+    oracle_price: u64,
+    tick_size: u64,
+) -> DriftResult<u64> {
+    validate!(
+        order.is_trailing_stop(),
+        ErrorCode::InvalidOrder,
+        "order is not a trailing stop"
+    )?;
+
+    let trail_offset = order.trailing_stop_distance.cast::<u64>()?;
+    let trail_offset = if order.is_bit_flag_set(OrderBitFlag::TrailingStopBps) {
+        oracle_price.safe_mul(trail_offset)?.safe_div(10_000)?
+    } else {
+        trail_offset
+    };
+
+    let trigger_price = match order.trigger_condition {
+        OrderTriggerCondition::Below => {
+            let trailing_price = oracle_price.saturating_sub(trail_offset);
+            order.trigger_price.max(trailing_price)
+        }
+        OrderTriggerCondition::Above => {
+            let trailing_price = oracle_price.safe_add(trail_offset)?;
+            if order.trigger_price == 0 {
+                trailing_price
+            } else {
+                order.trigger_price.min(trailing_price)
+            }
+        }
+        _ => return Err(print_error!(ErrorCode::InvalidTriggerOrderCondition)()),
+    };
+
+    standardize_price(trigger_price, tick_size, order.direction)
+}
+
+/// Number of slices a twap order should have released by now. The first slice is due at
+/// max_ts - num_slices * slice_interval and each following slice one interval later
+pub fn calculate_twap_slices_due(order: &Order, now: i64) -> DriftResult<u16> {
+    validate!(
+        order.is_twap(),
+        ErrorCode::InvalidOrder,
+        "order is not a twap"
+    )?;
+
+    let slice_interval = order.get_twap_slice_interval().cast::<i64>()?;
+    let num_slices = order.get_twap_num_slices();
+
+    validate!(
+        slice_interval > 0 && num_slices > 0,
+        ErrorCode::InvalidTwapOrder,
+        "twap order slice_interval={} num_slices={}",
+        slice_interval,
+        num_slices
+    )?;
+
+    let start_ts = order
+        .max_ts
+        .safe_sub(slice_interval.safe_mul(num_slices.cast()?)?)?;
+
+    if now < start_ts {
+        return Ok(0);
+    }
+
+    let slices_due = now
+        .safe_sub(start_ts)?
+        .safe_div(slice_interval)?
+        .safe_add(1)?
+        .min(num_slices.cast()?);
+
+    slices_due.cast()
+}
+
 pub fn is_new_order_risk_increasing(
     order: &Order,
     position_base_asset_amount: i64,
@@ -721,6 +861,8 @@ fn get_max_fill_amounts_for_market(
     get_max_withdraw_for_market_with_token_amount(market, token_amount, is_leaving_drift)
 }
 
+/// Returns the maker's matchable orders and their limit prices. The size each order can match comes from
+/// get_base_asset_amount_unfilled, which only exposes an iceberg's current clip
 pub fn find_maker_orders(
     user: &User,
     direction: &PositionDirection,
@@ -816,6 +958,7 @@ pub fn calculate_max_perp_order_size(
         );
     drop(quote_spot_market);
 
+    // open bids/asks hold the full size of open orders, so iceberg reserves count towards margin
     let perp_position: &PerpPosition = &user.perp_positions[position_index];
     let (worst_case_base_asset_amount, worst_case_liability_value) = perp_position
         .worst_case_liability_value(oracle_price_data_price, perp_market.contract_type)?;
@@ -1271,7 +1414,7 @@ pub fn find_bids_and_asks_from_users(
                 continue;
             }
 
-            if now > order.max_ts && order.max_ts != 0 {
+            if order.is_expired(now, slot) {
                 continue;
             }
 
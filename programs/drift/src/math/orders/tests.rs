@@ -350,7 +350,7 @@ mod should_expire_order {
 
         let now = 100;
 
-        let is_expired = should_expire_order(&user, 0, now).unwrap();
+        let is_expired = should_expire_order(&user, 0, now, 0).unwrap();
 
         assert!(!is_expired);
     }
@@ -369,7 +369,7 @@ mod should_expire_order {
 
         let now = 100;
 
-        let is_expired = should_expire_order(&user, 0, now).unwrap();
+        let is_expired = should_expire_order(&user, 0, now, 0).unwrap();
 
         assert!(!is_expired);
     }
@@ -388,7 +388,7 @@ mod should_expire_order {
 
         let now = 100;
 
-        let is_expired = should_expire_order(&user, 0, now).unwrap();
+        let is_expired = should_expire_order(&user, 0, now, 0).unwrap();
 
         assert!(is_expired);
     }
@@ -407,7 +407,7 @@ mod should_expire_order {
 
         let now = 100;
 
-        let is_expired = should_expire_order(&user, 0, now).unwrap();
+        let is_expired = should_expire_order(&user, 0, now, 0).unwrap();
 
         assert!(!is_expired);
     }
@@ -426,7 +426,7 @@ mod should_expire_order {
 
         let now = 100;
 
-        let is_expired = should_expire_order(&user, 0, now).unwrap();
+        let is_expired = should_expire_order(&user, 0, now, 0).unwrap();
 
         assert!(!is_expired);
     }
@@ -445,10 +445,28 @@ mod should_expire_order {
 
         let now = 100;
 
-        let is_expired = should_expire_order(&user, 0, now).unwrap();
+        let is_expired = should_expire_order(&user, 0, now, 0).unwrap();
 
         assert!(!is_expired);
     }
+
+    #[test]
+    fn good_til_slot() {
+        let user = User {
+            orders: get_orders(Order {
+                status: OrderStatus::Open,
+                order_type: OrderType::Limit,
+                max_slot: 200,
+                ..Order::default()
+            }),
+            ..User::default()
+        };
+
+        let now = 100;
+
+        assert!(!should_expire_order(&user, 0, now, 200).unwrap());
+        assert!(should_expire_order(&user, 0, now, 201).unwrap());
+    }
 }
 
 mod get_max_fill_amounts {
@@ -4845,3 +4863,266 @@ mod order_bit_flags {
         assert_eq!(flags, 8);
     }
 }
+
+mod calculate_trailing_stop_trigger_price {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::PRICE_PRECISION_U64;
+    use crate::math::orders::calculate_trailing_stop_trigger_price;
+    use crate::state::user::{Order, OrderBitFlag, OrderTriggerCondition, OrderType};
+
+    #[test]
+    fn below_only_ratchets_up() {
+        let mut order = Order {
+            order_type: OrderType::TrailingStop,
+            direction: PositionDirection::Short,
+            trigger_condition: OrderTriggerCondition::Below,
+            trigger_price: 95 * PRICE_PRECISION_U64,
+            trailing_stop_distance: 5 * PRICE_PRECISION_U64 as u32,
+            ..Order::default()
+        };
+
+        let trigger_price =
+            calculate_trailing_stop_trigger_price(&order, 102 * PRICE_PRECISION_U64, 1).unwrap();
+        assert_eq!(trigger_price, 97 * PRICE_PRECISION_U64);
+
+        order.trigger_price = trigger_price;
+        let trigger_price =
+            calculate_trailing_stop_trigger_price(&order, 99 * PRICE_PRECISION_U64, 1).unwrap();
+        assert_eq!(trigger_price, 97 * PRICE_PRECISION_U64);
+    }
+
+    #[test]
+    fn above_only_ratchets_down() {
+        let mut order = Order {
+            order_type: OrderType::TrailingStop,
+            direction: PositionDirection::Long,
+            trigger_condition: OrderTriggerCondition::Above,
+            trigger_price: 0,
+            trailing_stop_distance: 5 * PRICE_PRECISION_U64 as u32,
+            ..Order::default()
+        };
+
+        let trigger_price =
+            calculate_trailing_stop_trigger_price(&order, 100 * PRICE_PRECISION_U64, 1).unwrap();
+        assert_eq!(trigger_price, 105 * PRICE_PRECISION_U64);
+
+        order.trigger_price = trigger_price;
+        let trigger_price =
+            calculate_trailing_stop_trigger_price(&order, 98 * PRICE_PRECISION_U64, 1).unwrap();
+        assert_eq!(trigger_price, 103 * PRICE_PRECISION_U64);
+
+        order.trigger_price = trigger_price;
+        let trigger_price =
+            calculate_trailing_stop_trigger_price(&order, 101 * PRICE_PRECISION_U64, 1).unwrap();
+        assert_eq!(trigger_price, 103 * PRICE_PRECISION_U64);
+    }
+
+    #[test]
+    fn bps_offset() {
+        let mut order = Order {
+            order_type: OrderType::TrailingStop,
+            direction: PositionDirection::Short,
+            trigger_condition: OrderTriggerCondition::Below,
+            trailing_stop_distance: 100, // 1%
+            ..Order::default()
+        };
+        order.add_bit_flag(OrderBitFlag::TrailingStopBps);
+
+        let trigger_price =
+            calculate_trailing_stop_trigger_price(&order, 200 * PRICE_PRECISION_U64, 1).unwrap();
+        assert_eq!(trigger_price, 198 * PRICE_PRECISION_U64);
+    }
+
+    #[test]
+    fn not_trailing_stop() {
+        let order = Order {
+            order_type: OrderType::TriggerMarket,
+            trigger_condition: OrderTriggerCondition::Below,
+            ..Order::default()
+        };
+
+        assert!(calculate_trailing_stop_trigger_price(&order, PRICE_PRECISION_U64, 1).is_err());
+    }
+}
+
+mod calculate_twap_slices_due {
+    use crate::math::constants::BASE_PRECISION_U64;
+    use crate::math::orders::calculate_twap_slices_due;
+    use crate::state::user::{Order, OrderType};
+
+    #[test]
+    fn releases_one_slice_per_interval() {
+        let now = 1_000;
+        let mut order = Order {
+            order_type: OrderType::Twap,
+            base_asset_amount: 10 * BASE_PRECISION_U64,
+            max_ts: now + 4 * 60,
+            ..Order::default()
+        };
+        order.set_twap_params(60, 4, 0);
+
+        assert_eq!(calculate_twap_slices_due(&order, now).unwrap(), 1);
+        assert_eq!(calculate_twap_slices_due(&order, now + 59).unwrap(), 1);
+        assert_eq!(calculate_twap_slices_due(&order, now + 60).unwrap(), 2);
+        assert_eq!(calculate_twap_slices_due(&order, now + 185).unwrap(), 4);
+        assert_eq!(calculate_twap_slices_due(&order, now + 1_000).unwrap(), 4);
+
+        order.set_twap_params(60, 4, 3);
+        assert_eq!(order.get_twap_slice_interval(), 60);
+        assert_eq!(order.get_twap_num_slices(), 4);
+        assert_eq!(order.get_twap_slices_released(), 3);
+        assert_eq!(
+            order.get_twap_base_asset_amount_released().unwrap(),
+            7_500_000_000
+        );
+        assert_eq!(
+            order.get_base_asset_amount_unfilled(None).unwrap(),
+            10 * BASE_PRECISION_U64
+        );
+        assert_eq!(
+            order.get_base_asset_amount_unfilled(Some(0)).unwrap(),
+            7_500_000_000
+        );
+
+        order.base_asset_amount_filled = 8 * BASE_PRECISION_U64;
+        assert_eq!(order.get_base_asset_amount_unfilled(Some(0)).unwrap(), 0);
+
+        order.set_twap_params(60, 4, 4);
+        assert_eq!(
+            order.get_base_asset_amount_unfilled(Some(0)).unwrap(),
+            2 * BASE_PRECISION_U64
+        );
+    }
+
+    #[test]
+    fn not_twap() {
+        let order = Order {
+            order_type: OrderType::Limit,
+            ..Order::default()
+        };
+
+        assert!(calculate_twap_slices_due(&order, 0).is_err());
+    }
+}
+
+mod order_satisfies_trigger_condition {
+    use crate::math::constants::{PRICE_PRECISION_I64, PRICE_PRECISION_U64};
+    use crate::math::orders::{calculate_perp_trigger_value, order_satisfies_trigger_condition};
+    use crate::state::oracle::HistoricalOracleData;
+    use crate::state::perp_market::{PerpMarket, AMM};
+    use crate::state::user::{Order, OrderTriggerCondition, OrderTriggerSource, OrderType};
+
+    #[test]
+    fn oracle_source() {
+        let order = Order {
+            order_type: OrderType::TriggerMarket,
+            trigger_price: 100 * PRICE_PRECISION_U64,
+            trigger_condition: OrderTriggerCondition::Above,
+            ..Order::default()
+        };
+
+        assert_eq!(order.trigger_source, OrderTriggerSource::Oracle);
+
+        let market = PerpMarket::default();
+        let trigger_value = calculate_perp_trigger_value(
+            OrderTriggerSource::Oracle,
+            &market,
+            101 * PRICE_PRECISION_U64,
+        )
+        .unwrap();
+        assert!(order_satisfies_trigger_condition(&order, trigger_value).unwrap());
+        assert!(!order_satisfies_trigger_condition(&order, 100 * PRICE_PRECISION_I64).unwrap());
+    }
+
+    #[test]
+    fn mark_twap_source() {
+        let order = Order {
+            order_type: OrderType::TriggerMarket,
+            trigger_price: 100 * PRICE_PRECISION_U64,
+            trigger_source: OrderTriggerSource::MarkTwap,
+            trigger_condition: OrderTriggerCondition::Below,
+            ..Order::default()
+        };
+
+        let market = PerpMarket {
+            amm: AMM {
+                last_mark_price_twap_5min: 99 * PRICE_PRECISION_U64,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        // oracle is above the threshold but the mark twap isn't
+        let trigger_value =
+            calculate_perp_trigger_value(order.trigger_source, &market, 101 * PRICE_PRECISION_U64)
+                .unwrap();
+        assert_eq!(trigger_value, 99 * PRICE_PRECISION_I64);
+        assert!(order_satisfies_trigger_condition(&order, trigger_value).unwrap());
+    }
+
+    #[test]
+    fn funding_rate_source() {
+        // trigger once hourly funding drops below -0.01%
+        let order = Order {
+            order_type: OrderType::TriggerMarket,
+            trigger_price: OrderTriggerSource::FundingRate
+                .get_trigger_price(-100)
+                .unwrap(),
+            trigger_source: OrderTriggerSource::FundingRate,
+            trigger_condition: OrderTriggerCondition::Below,
+            ..Order::default()
+        };
+        assert_eq!(order.get_trigger_threshold().unwrap(), -100);
+
+        let mut market = PerpMarket {
+            amm: AMM {
+                last_funding_rate: 10_000_000, // 0.01% of $100
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price_twap: 100 * PRICE_PRECISION_I64,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        let trigger_source = order.trigger_source;
+        let trigger_value = calculate_perp_trigger_value(trigger_source, &market, 0).unwrap();
+        assert_eq!(trigger_value, 100);
+        assert!(!order_satisfies_trigger_condition(&order, trigger_value).unwrap());
+
+        market.amm.last_funding_rate = -20_000_000;
+        let trigger_value = calculate_perp_trigger_value(trigger_source, &market, 0).unwrap();
+        assert_eq!(trigger_value, -200);
+        assert!(order_satisfies_trigger_condition(&order, trigger_value).unwrap());
+    }
+
+    #[test]
+    fn oracle_mark_divergence_source() {
+        let order = Order {
+            order_type: OrderType::TriggerMarket,
+            trigger_price: 5_000,
+            trigger_source: OrderTriggerSource::OracleMarkDivergence,
+            trigger_condition: OrderTriggerCondition::Above,
+            ..Order::default()
+        };
+
+        let market = PerpMarket {
+            amm: AMM {
+                last_mark_price_twap_5min: 101 * PRICE_PRECISION_U64,
+                historical_oracle_data: HistoricalOracleData {
+                    last_oracle_price_twap_5min: 100 * PRICE_PRECISION_I64,
+                    ..HistoricalOracleData::default()
+                },
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+
+        let trigger_value =
+            calculate_perp_trigger_value(order.trigger_source, &market, 100 * PRICE_PRECISION_U64)
+                .unwrap();
+        assert_eq!(trigger_value, 10_000); // mark twap 1% above oracle twap
+        assert!(order_satisfies_trigger_condition(&order, trigger_value).unwrap());
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::BTreeMap;
+
+use anchor_lang::prelude::Pubkey;
+
+use crate::error::DriftResult;
+use crate::math::casting::Cast;
+use crate::math::constants::{
+    MARGIN_PRECISION, MARGIN_PRECISION_I128, MARGIN_PRECISION_U128, QUOTE_SPOT_MARKET_INDEX,
+};
+use crate::math::safe_math::SafeMath;
+use crate::state::perp_market::MarketStatus;
+use crate::state::perp_market_map::PerpMarketMap;
+use crate::state::spot_market_map::SpotMarketMap;
+use crate::state::user::User;
+
+#[cfg(test)]
+mod tests;
+
+/// Oracle price moves applied to each underlying, as a fraction of the underlying's shock size
+/// precision: PORTFOLIO_MARGIN_SCENARIO_PRECISION
+pub const PORTFOLIO_MARGIN_SCENARIOS: [i64; 7] = [-100, -66, -33, 0, 33, 66, 100];
+pub const PORTFOLIO_MARGIN_SCENARIO_PRECISION: i64 = 100;
+/// Requirement kept on a group's hedged notional whatever the scenarios return, covers the basis
+/// and funding risk between the legs
+/// precision: MARGIN_PRECISION
+pub const PORTFOLIO_MARGIN_MIN_MARGIN_RATIO: u32 = MARGIN_PRECISION / 100; // 1%
+
+/// Spot and perp positions of a portfolio margin user that share an underlying. Each position is
+/// margined with the standard weights, then the notional hedged between the spot and perp legs is
+/// swapped to the group's scenario requirement
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PortfolioMarginGroup {
+    /// Signed worst case value of the group's spot positions
+    /// precision: QUOTE_PRECISION
+    pub spot_value: i128,
+    /// precision: QUOTE_PRECISION
+    pub spot_token_value: u128,
+    /// Standard requirement on the spot positions' price risk. For deposits it's the haircut taken
+    /// off the collateral
+    /// precision: QUOTE_PRECISION
+    pub spot_margin_requirement: u128,
+    /// Signed worst case value of the group's perp positions
+    /// precision: QUOTE_PRECISION
+    pub perp_value: i128,
+    /// precision: QUOTE_PRECISION
+    pub perp_liability_value: u128,
+    /// Standard requirement on the perp positions, incl the imf premium and custom margin ratios
+    /// precision: QUOTE_PRECISION
+    pub perp_margin_requirement: u128,
+    /// Max of the group's perp margin ratios
+    /// precision: MARGIN_PRECISION
+    pub perp_margin_ratio: u32,
+    pub perp_market_index: u16,
+    /// Admin configured shock of the group's spot market, 0 to derive it from the margin ratios
+    /// precision: MARGIN_PRECISION
+    pub price_shock: u32,
+}
+
+/// Adjustments to the standard margin calculation for a group's hedged notional
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PortfolioMarginOffset {
+    /// Spot deposit haircut released on the hedged notional
+    /// precision: QUOTE_PRECISION
+    pub total_collateral_credit: u128,
+    /// Spot borrow and perp requirements released on the hedged notional
+    /// precision: QUOTE_PRECISION
+    pub margin_requirement_credit: u128,
+    /// Scenario requirement on the hedged notional
+    /// precision: QUOTE_PRECISION
+    pub margin_requirement: u128,
+}
+
+impl PortfolioMarginGroup {
+    pub fn add_spot_position(
+        &mut self,
+        token_value: i128,
+        margin_requirement: u128,
+    ) -> DriftResult {
+        self.spot_value = self.spot_value.safe_add(token_value)?;
+        self.spot_token_value = self.spot_token_value.safe_add(token_value.unsigned_abs())?;
+        self.spot_margin_requirement = self.spot_margin_requirement.safe_add(margin_requirement)?;
+
+        Ok(())
+    }
+
+    pub fn add_perp_position(
+        &mut self,
+        market_index: u16,
+        base_asset_amount: i128,
+        liability_value: u128,
+        margin_requirement: u128,
+        margin_ratio: u32,
+    ) -> DriftResult {
+        let base_asset_value = if base_asset_amount < 0 {
+            -liability_value.cast::<i128>()?
+        } else {
+            liability_value.cast::<i128>()?
+        };
+
+        self.perp_value = self.perp_value.safe_add(base_asset_value)?;
+        self.perp_liability_value = self.perp_liability_value.safe_add(liability_value)?;
+        self.perp_margin_requirement = self.perp_margin_requirement.safe_add(margin_requirement)?;
+
+        if margin_ratio >= self.perp_margin_ratio {
+            self.perp_margin_ratio = margin_ratio;
+            self.perp_market_index = market_index;
+        }
+
+        Ok(())
+    }
+
+    /// Notional of the spot leg offset by an opposite perp leg
+    /// precision: QUOTE_PRECISION
+    pub fn calculate_hedged_value(&self) -> u128 {
+        if self.spot_value.signum() * self.perp_value.signum() < 0 {
+            self.spot_value
+                .unsigned_abs()
+                .min(self.perp_value.unsigned_abs())
+        } else {
+            0
+        }
+    }
+
+    /// Share of the spot value the standard weights charge, the haircut for deposits
+    /// precision: MARGIN_PRECISION
+    pub fn calculate_spot_margin_ratio(&self) -> DriftResult<u128> {
+        if self.spot_token_value == 0 {
+            return Ok(0);
+        }
+
+        self.spot_margin_requirement
+            .safe_mul(MARGIN_PRECISION_U128)?
+            .safe_div(self.spot_token_value)
+    }
+
+    /// Releases the standard requirements on the hedged notional and charges its worst loss across
+    /// the price scenarios instead. The underlying is shocked by the spot market's configured price
+    /// shock, or the larger of the spot haircut and the perp margin ratio when it isn't set, and
+    /// gains on the spot leg only count after the haircut. The unhedged remainder keeps the standard
+    /// requirements
+    pub fn calculate_offset(&self) -> DriftResult<PortfolioMarginOffset> {
+        let hedged_value = self.calculate_hedged_value();
+        if hedged_value == 0 {
+            return Ok(PortfolioMarginOffset::default());
+        }
+
+        let spot_margin_requirement_released = self
+            .spot_margin_requirement
+            .safe_mul(hedged_value)?
+            .safe_div(self.spot_token_value)?;
+
+        let perp_margin_requirement_released = self
+            .perp_margin_requirement
+            .safe_mul(hedged_value)?
+            .safe_div(self.perp_liability_value)?;
+
+        let spot_margin_ratio = self.calculate_spot_margin_ratio()?;
+        let shock_margin_ratio = if self.price_shock > 0 {
+            self.price_shock.cast::<u128>()?
+        } else {
+            spot_margin_ratio.max(self.perp_margin_ratio.cast()?)
+        };
+        let spot_gain_weight = MARGIN_PRECISION_U128.saturating_sub(spot_margin_ratio);
+
+        let mut worst_case_loss = 0_u128;
+        for scenario in PORTFOLIO_MARGIN_SCENARIOS.iter() {
+            let price_move_value = hedged_value
+                .cast::<i128>()?
+                .safe_mul(shock_margin_ratio.cast()?)?
+                .safe_mul(scenario.cast()?)?
+                .safe_div(MARGIN_PRECISION_I128)?
+                .safe_div(PORTFOLIO_MARGIN_SCENARIO_PRECISION.cast()?)?;
+
+            let spot_pnl = price_move_value.safe_mul(self.spot_value.signum())?;
+            let perp_pnl = -spot_pnl;
+
+            let weighted_spot_pnl = if spot_pnl > 0 {
+                spot_pnl
+                    .safe_mul(spot_gain_weight.cast()?)?
+                    .safe_div(MARGIN_PRECISION_I128)?
+            } else {
+                spot_pnl
+            };
+
+            let scenario_pnl = weighted_spot_pnl.safe_add(perp_pnl)?;
+            if scenario_pnl < 0 {
+                worst_case_loss = worst_case_loss.max(scenario_pnl.unsigned_abs());
+            }
+        }
+
+        let min_margin_requirement = hedged_value
+            .safe_mul(PORTFOLIO_MARGIN_MIN_MARGIN_RATIO.cast()?)?
+            .safe_div(MARGIN_PRECISION_U128)?;
+
+        let (total_collateral_credit, spot_margin_requirement_credit) = if self.spot_value > 0 {
+            (spot_margin_requirement_released, 0)
+        } else {
+            (0, spot_margin_requirement_released)
+        };
+
+        Ok(PortfolioMarginOffset {
+            total_collateral_credit,
+            margin_requirement_credit: spot_margin_requirement_credit
+                .safe_add(perp_margin_requirement_released)?,
+            margin_requirement: worst_case_loss.max(min_margin_requirement),
+        })
+    }
+}
+
+/// Groups a portfolio margin user's positions by underlying, identified by the market oracle
+#[derive(Default, Debug)]
+pub struct PortfolioMarginGroups {
+    groups: BTreeMap<Pubkey, PortfolioMarginGroup>,
+    spot_market_oracles: BTreeMap<u16, Pubkey>,
+    perp_market_oracles: BTreeMap<u16, Pubkey>,
+}
+
+impl PortfolioMarginGroups {
+    /// Pairs the user's cross perp positions with the non-quote spot positions that share their
+    /// oracle. Underlyings with only spot or only perp exposure keep the standard treatment, as does
+    /// every position while portfolio margin is disabled in the state
+    pub fn new(
+        user: &User,
+        perp_market_map: &PerpMarketMap,
+        spot_market_map: &SpotMarketMap,
+        portfolio_margin_enabled: bool,
+    ) -> DriftResult<Self> {
+        let mut portfolio_margin_groups = PortfolioMarginGroups::default();
+
+        if !portfolio_margin_enabled || !user.is_portfolio_margin_mode() {
+            return Ok(portfolio_margin_groups);
+        }
+
+        let mut price_shocks: BTreeMap<Pubkey, u32> = BTreeMap::new();
+        for spot_position in user.spot_positions.iter() {
+            if spot_position.is_available() || spot_position.market_index == QUOTE_SPOT_MARKET_INDEX
+            {
+                continue;
+            }
+
+            let spot_market = spot_market_map.get_ref(&spot_position.market_index)?;
+            portfolio_margin_groups
+                .spot_market_oracles
+                .insert(spot_market.market_index, spot_market.oracle);
+
+            let price_shock = price_shocks.entry(spot_market.oracle).or_insert(0);
+            *price_shock = (*price_shock).max(spot_market.portfolio_margin_price_shock.cast()?);
+        }
+
+        for perp_position in user.perp_positions.iter() {
+            if perp_position.is_available() || perp_position.is_isolated() {
+                continue;
+            }
+
+            let perp_market = perp_market_map.get_ref(&perp_position.market_index)?;
+            if perp_market.status == MarketStatus::Settlement {
+                continue;
+            }
+
+            portfolio_margin_groups
+                .perp_market_oracles
+                .insert(perp_market.market_index, perp_market.amm.oracle);
+        }
+
+        let PortfolioMarginGroups {
+            groups,
+            spot_market_oracles,
+            perp_market_oracles,
+        } = &mut portfolio_margin_groups;
+
+        for oracle in perp_market_oracles.values() {
+            if spot_market_oracles.values().any(|o| o == oracle) {
+                groups.insert(
+                    *oracle,
+                    PortfolioMarginGroup {
+                        price_shock: price_shocks.get(oracle).copied().unwrap_or(0),
+                        ..PortfolioMarginGroup::default()
+                    },
+                );
+            }
+        }
+
+        spot_market_oracles.retain(|_, oracle| groups.contains_key(oracle));
+        perp_market_oracles.retain(|_, oracle| groups.contains_key(oracle));
+
+        Ok(portfolio_margin_groups)
+    }
+
+    pub fn get_spot_group_mut(&mut self, market_index: u16) -> Option<&mut PortfolioMarginGroup> {
+        let oracle = self.spot_market_oracles.get(&market_index)?;
+        self.groups.get_mut(oracle)
+    }
+
+    pub fn get_perp_group_mut(&mut self, market_index: u16) -> Option<&mut PortfolioMarginGroup> {
+        let oracle = self.perp_market_oracles.get(&market_index)?;
+        self.groups.get_mut(oracle)
+    }
+
+    pub fn contains_spot_market(&self, market_index: u16) -> bool {
+        self.spot_market_oracles.contains_key(&market_index)
+    }
+
+    pub fn contains_perp_market(&self, market_index: u16) -> bool {
+        self.perp_market_oracles.contains_key(&market_index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PortfolioMarginGroup> {
+        self.groups.values()
+    }
+}
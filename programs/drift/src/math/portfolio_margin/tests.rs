@@ -0,0 +1,135 @@
+use crate::math::constants::{MARGIN_PRECISION, QUOTE_PRECISION, QUOTE_PRECISION_I128};
+use crate::math::portfolio_margin::{PortfolioMarginGroup, PortfolioMarginOffset};
+
+#[test]
+fn hedged_group() {
+    let mut portfolio_margin_group = PortfolioMarginGroup::default();
+
+    // 10k spot deposit with a 20% haircut
+    portfolio_margin_group
+        .add_spot_position(10000 * QUOTE_PRECISION_I128, 2000 * QUOTE_PRECISION)
+        .unwrap();
+    portfolio_margin_group
+        .add_perp_position(
+            0,
+            -1,
+            10000 * QUOTE_PRECISION,
+            1000 * QUOTE_PRECISION,
+            MARGIN_PRECISION / 10,
+        )
+        .unwrap();
+
+    assert_eq!(
+        portfolio_margin_group.calculate_hedged_value(),
+        10000 * QUOTE_PRECISION
+    );
+
+    // shocked by the 20% haircut, the spot gain only counts at 80%
+    assert_eq!(
+        portfolio_margin_group.calculate_offset().unwrap(),
+        PortfolioMarginOffset {
+            total_collateral_credit: 2000 * QUOTE_PRECISION,
+            margin_requirement_credit: 1000 * QUOTE_PRECISION,
+            margin_requirement: 400 * QUOTE_PRECISION,
+        }
+    );
+}
+
+#[test]
+fn partially_hedged_group() {
+    let mut portfolio_margin_group = PortfolioMarginGroup::default();
+
+    // 10k spot borrow with a 10% liability premium
+    portfolio_margin_group
+        .add_spot_position(-10000 * QUOTE_PRECISION_I128, 1000 * QUOTE_PRECISION)
+        .unwrap();
+    portfolio_margin_group
+        .add_perp_position(
+            1,
+            1,
+            2000 * QUOTE_PRECISION,
+            100 * QUOTE_PRECISION,
+            MARGIN_PRECISION / 20,
+        )
+        .unwrap();
+    portfolio_margin_group
+        .add_perp_position(
+            2,
+            1,
+            2000 * QUOTE_PRECISION,
+            400 * QUOTE_PRECISION,
+            MARGIN_PRECISION / 5,
+        )
+        .unwrap();
+
+    assert_eq!(portfolio_margin_group.perp_market_index, 2);
+    assert_eq!(
+        portfolio_margin_group.calculate_hedged_value(),
+        4000 * QUOTE_PRECISION
+    );
+
+    // only the hedged 4k is released, shocked by the 20% perp margin ratio
+    assert_eq!(
+        portfolio_margin_group.calculate_offset().unwrap(),
+        PortfolioMarginOffset {
+            total_collateral_credit: 0,
+            margin_requirement_credit: 900 * QUOTE_PRECISION,
+            margin_requirement: 80 * QUOTE_PRECISION,
+        }
+    );
+}
+
+#[test]
+fn unhedged_group() {
+    let mut portfolio_margin_group = PortfolioMarginGroup::default();
+
+    portfolio_margin_group
+        .add_spot_position(10000 * QUOTE_PRECISION_I128, 2000 * QUOTE_PRECISION)
+        .unwrap();
+    portfolio_margin_group
+        .add_perp_position(
+            0,
+            1,
+            10000 * QUOTE_PRECISION,
+            1000 * QUOTE_PRECISION,
+            MARGIN_PRECISION / 10,
+        )
+        .unwrap();
+
+    assert_eq!(portfolio_margin_group.calculate_hedged_value(), 0);
+    assert_eq!(
+        portfolio_margin_group.calculate_offset().unwrap(),
+        PortfolioMarginOffset::default()
+    );
+}
+
+#[test]
+fn configured_price_shock() {
+    let mut portfolio_margin_group = PortfolioMarginGroup {
+        price_shock: MARGIN_PRECISION / 10,
+        ..PortfolioMarginGroup::default()
+    };
+
+    portfolio_margin_group
+        .add_spot_position(10000 * QUOTE_PRECISION_I128, 2000 * QUOTE_PRECISION)
+        .unwrap();
+    portfolio_margin_group
+        .add_perp_position(
+            0,
+            -1,
+            10000 * QUOTE_PRECISION,
+            1000 * QUOTE_PRECISION,
+            MARGIN_PRECISION / 10,
+        )
+        .unwrap();
+
+    // shocked by the configured 10% instead of the 20% haircut
+    assert_eq!(
+        portfolio_margin_group.calculate_offset().unwrap(),
+        PortfolioMarginOffset {
+            total_collateral_credit: 2000 * QUOTE_PRECISION,
+            margin_requirement_credit: 1000 * QUOTE_PRECISION,
+            margin_requirement: 200 * QUOTE_PRECISION,
+        }
+    );
+}
@@ -0,0 +1,240 @@
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::casting::Cast;
+use crate::math::position::calculate_base_asset_value_with_oracle_price;
+use crate::math::safe_math::SafeMath;
+use crate::math::spot_balance::get_token_value;
+use crate::state::perp_market::PerpMarket;
+use crate::state::spot_market::SpotMarket;
+use crate::state::traits::Size;
+use crate::state::user::{MarketType, Order, PerpPosition};
+use crate::validate;
+use anchor_lang::prelude::*;
+
+#[cfg(test)]
+mod tests;
+
+pub const DELEGATE_PERMISSIONS_PDA_SEED: &str = "DELEGATE_PERMISSIONS";
+pub const MAX_DELEGATE_MARKETS: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum DelegatePermissionFlag {
+    PlaceOrders = 0b00000001,
+    CancelOrders = 0b00000010,
+    /// Orders placed by the delegate must be post only
+    PostOnly = 0b00000100,
+    /// Orders placed by the delegate must be reduce only
+    ReduceOnly = 0b00001000,
+}
+
+/// Scoped permissions for a delegate of a single user (sub account). Unlike User::delegate, a user
+/// can have any number of these, each limited to the markets, order types and size it needs
+#[account(zero_copy(unsafe))]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct DelegatePermissions {
+    /// The user account the delegate can act for
+    pub user: Pubkey,
+    /// The delegate's signer
+    pub delegate: Pubkey,
+    /// Max notional of a single order placed by the delegate. 0 means no cap
+    /// precision: QUOTE_PRECISION
+    pub max_order_notional: u64,
+    /// Max notional of the worst case perp position (position plus open orders) after the delegate
+    /// places an order. 0 means no cap
+    /// precision: QUOTE_PRECISION
+    pub max_position_notional: u64,
+    /// Perp markets the delegate can place and cancel orders in. No markets means every perp market
+    pub perp_market_indexes: [u16; 8],
+    /// Spot markets the delegate can place and cancel orders in. No markets means every spot market
+    pub spot_market_indexes: [u16; 8],
+    /// Bitmask of DelegatePermissionFlag
+    pub permissions: u8,
+    pub num_perp_markets: u8,
+    pub num_spot_markets: u8,
+    pub padding: [u8; 13],
+}
+
+impl Size for DelegatePermissions {
+    const SIZE: usize = 136;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Eq, PartialEq, Debug)]
+pub struct DelegatePermissionsParams {
+    pub permissions: u8,
+    pub max_order_notional: u64,
+    pub max_position_notional: u64,
+    pub perp_market_indexes: Vec<u16>,
+    pub spot_market_indexes: Vec<u16>,
+}
+
+impl DelegatePermissions {
+    pub fn update(&mut self, params: &DelegatePermissionsParams) -> DriftResult {
+        validate!(
+            params.perp_market_indexes.len() <= MAX_DELEGATE_MARKETS
+                && params.spot_market_indexes.len() <= MAX_DELEGATE_MARKETS,
+            ErrorCode::InvalidDelegatePermissions,
+            "delegate can be limited to at most {} perp and {} spot markets",
+            MAX_DELEGATE_MARKETS,
+            MAX_DELEGATE_MARKETS
+        )?;
+
+        validate!(
+            params.permissions
+                & (DelegatePermissionFlag::PlaceOrders as u8
+                    | DelegatePermissionFlag::CancelOrders as u8)
+                != 0,
+            ErrorCode::InvalidDelegatePermissions,
+            "delegate must be able to place or cancel orders"
+        )?;
+
+        self.permissions = params.permissions;
+        self.max_order_notional = params.max_order_notional;
+        self.max_position_notional = params.max_position_notional;
+
+        self.perp_market_indexes = [0; MAX_DELEGATE_MARKETS];
+        self.perp_market_indexes[..params.perp_market_indexes.len()]
+            .copy_from_slice(&params.perp_market_indexes);
+        self.num_perp_markets = params.perp_market_indexes.len().cast()?;
+
+        self.spot_market_indexes = [0; MAX_DELEGATE_MARKETS];
+        self.spot_market_indexes[..params.spot_market_indexes.len()]
+            .copy_from_slice(&params.spot_market_indexes);
+        self.num_spot_markets = params.spot_market_indexes.len().cast()?;
+
+        Ok(())
+    }
+
+    pub fn has_permissions(&self, permissions: u8) -> bool {
+        self.permissions & permissions == permissions
+    }
+
+    pub fn validate_market(&self, market_type: MarketType, market_index: u16) -> DriftResult {
+        let market_indexes = match market_type {
+            MarketType::Perp => &self.perp_market_indexes[..self.num_perp_markets as usize],
+            MarketType::Spot => &self.spot_market_indexes[..self.num_spot_markets as usize],
+        };
+
+        validate!(
+            market_indexes.is_empty() || market_indexes.contains(&market_index),
+            ErrorCode::DelegatePermissionDenied,
+            "delegate can not act in {:?} market {}",
+            market_type,
+            market_index
+        )?;
+
+        Ok(())
+    }
+
+    /// Scoped delegates cancelling in bulk must name a market type and market index when they're
+    /// limited to a set of markets
+    pub fn validate_cancel_orders_filter(
+        &self,
+        market_type: Option<MarketType>,
+        market_index: Option<u16>,
+    ) -> DriftResult {
+        if self.num_perp_markets == 0 && self.num_spot_markets == 0 {
+            return Ok(());
+        }
+
+        match (market_type, market_index) {
+            (Some(market_type), Some(market_index)) => {
+                self.validate_market(market_type, market_index)
+            }
+            _ => {
+                msg!("delegate limited to markets must cancel orders by market");
+                Err(ErrorCode::DelegatePermissionDenied)
+            }
+        }
+    }
+
+    fn validate_order(&self, order: &Order) -> DriftResult {
+        self.validate_market(order.market_type, order.market_index)?;
+
+        validate!(
+            order.post_only || !self.has_permissions(DelegatePermissionFlag::PostOnly as u8),
+            ErrorCode::DelegatePermissionDenied,
+            "delegate can only place post only orders"
+        )?;
+
+        validate!(
+            order.reduce_only || !self.has_permissions(DelegatePermissionFlag::ReduceOnly as u8),
+            ErrorCode::DelegatePermissionDenied,
+            "delegate can only place reduce only orders"
+        )?;
+
+        Ok(())
+    }
+
+    fn validate_order_notional(&self, order_notional: u128) -> DriftResult {
+        validate!(
+            self.max_order_notional == 0 || order_notional <= self.max_order_notional.cast()?,
+            ErrorCode::DelegatePermissionDenied,
+            "order notional {} above delegate max order notional {}",
+            order_notional,
+            self.max_order_notional
+        )?;
+
+        Ok(())
+    }
+
+    /// Called once the order is on the user's position so the position cap includes it. The order
+    /// notional counts the whole unfilled size, including twap slices and iceberg reserve not yet released
+    pub fn validate_perp_order(
+        &self,
+        order: &Order,
+        position: &PerpPosition,
+        market: &PerpMarket,
+        oracle_price: i64,
+    ) -> DriftResult {
+        self.validate_order(order)?;
+
+        let order_notional = calculate_base_asset_value_with_oracle_price(
+            order
+                .base_asset_amount
+                .safe_sub(order.base_asset_amount_filled)?
+                .cast()?,
+            oracle_price,
+        )?;
+        self.validate_order_notional(order_notional)?;
+
+        let (_, position_notional) =
+            position.worst_case_liability_value(oracle_price, market.contract_type)?;
+
+        validate!(
+            self.max_position_notional == 0
+                || position_notional <= self.max_position_notional.cast()?,
+            ErrorCode::DelegatePermissionDenied,
+            "worst case position notional {} above delegate max position notional {}",
+            position_notional,
+            self.max_position_notional
+        )?;
+
+        Ok(())
+    }
+
+    pub fn validate_spot_order(
+        &self,
+        order: &Order,
+        spot_market: &SpotMarket,
+        oracle_price: i64,
+    ) -> DriftResult {
+        self.validate_order(order)?;
+
+        let order_notional = get_token_value(
+            order
+                .base_asset_amount
+                .safe_sub(order.base_asset_amount_filled)?
+                .cast()?,
+            spot_market.decimals,
+            oracle_price,
+        )?
+        .unsigned_abs();
+        self.validate_order_notional(order_notional)?;
+
+        Ok(())
+    }
+
+    pub fn is_for(&self, user: &Pubkey, delegate: &Pubkey) -> bool {
+        self.user.eq(user) && self.delegate.eq(delegate)
+    }
+}
@@ -0,0 +1,242 @@
+use crate::error::ErrorCode;
+use crate::math::constants::{
+    BASE_PRECISION_I64, BASE_PRECISION_U64, PRICE_PRECISION_I64, QUOTE_PRECISION_U64,
+};
+use crate::state::delegate_permissions::{
+    DelegatePermissionFlag, DelegatePermissions, DelegatePermissionsParams,
+};
+use crate::state::perp_market::PerpMarket;
+use crate::state::spot_market::SpotMarket;
+use crate::state::user::{MarketType, Order, OrderType, PerpPosition};
+
+fn get_delegate_permissions(params: DelegatePermissionsParams) -> DelegatePermissions {
+    let mut delegate_permissions = DelegatePermissions::default();
+    delegate_permissions.update(&params).unwrap();
+    delegate_permissions
+}
+
+#[test]
+fn update() {
+    let mut delegate_permissions = DelegatePermissions::default();
+
+    let params = DelegatePermissionsParams {
+        permissions: DelegatePermissionFlag::PostOnly as u8,
+        ..DelegatePermissionsParams::default()
+    };
+    assert_eq!(
+        delegate_permissions.update(&params),
+        Err(ErrorCode::InvalidDelegatePermissions)
+    );
+
+    let params = DelegatePermissionsParams {
+        permissions: DelegatePermissionFlag::PlaceOrders as u8,
+        perp_market_indexes: vec![0; 9],
+        ..DelegatePermissionsParams::default()
+    };
+    assert_eq!(
+        delegate_permissions.update(&params),
+        Err(ErrorCode::InvalidDelegatePermissions)
+    );
+
+    let params = DelegatePermissionsParams {
+        permissions: DelegatePermissionFlag::PlaceOrders as u8
+            | DelegatePermissionFlag::CancelOrders as u8,
+        perp_market_indexes: vec![0, 1],
+        spot_market_indexes: vec![1],
+        ..DelegatePermissionsParams::default()
+    };
+    delegate_permissions.update(&params).unwrap();
+    assert_eq!(delegate_permissions.num_perp_markets, 2);
+    assert_eq!(delegate_permissions.num_spot_markets, 1);
+    assert!(delegate_permissions.has_permissions(
+        DelegatePermissionFlag::PlaceOrders as u8 | DelegatePermissionFlag::CancelOrders as u8
+    ));
+
+    // shrinking the allow-list clears the old markets
+    let params = DelegatePermissionsParams {
+        permissions: DelegatePermissionFlag::CancelOrders as u8,
+        perp_market_indexes: vec![1],
+        ..DelegatePermissionsParams::default()
+    };
+    delegate_permissions.update(&params).unwrap();
+    assert_eq!(
+        delegate_permissions.perp_market_indexes,
+        [1, 0, 0, 0, 0, 0, 0, 0]
+    );
+    assert_eq!(delegate_permissions.num_spot_markets, 0);
+    assert!(!delegate_permissions.has_permissions(DelegatePermissionFlag::PlaceOrders as u8));
+}
+
+#[test]
+fn validate_market() {
+    let delegate_permissions = get_delegate_permissions(DelegatePermissionsParams {
+        permissions: DelegatePermissionFlag::PlaceOrders as u8,
+        perp_market_indexes: vec![0, 2],
+        ..DelegatePermissionsParams::default()
+    });
+
+    assert!(delegate_permissions
+        .validate_market(MarketType::Perp, 2)
+        .is_ok());
+    assert_eq!(
+        delegate_permissions.validate_market(MarketType::Perp, 1),
+        Err(ErrorCode::DelegatePermissionDenied)
+    );
+    // no spot markets listed means every spot market
+    assert!(delegate_permissions
+        .validate_market(MarketType::Spot, 5)
+        .is_ok());
+
+    assert!(delegate_permissions
+        .validate_cancel_orders_filter(Some(MarketType::Perp), Some(0))
+        .is_ok());
+    assert_eq!(
+        delegate_permissions.validate_cancel_orders_filter(None, None),
+        Err(ErrorCode::DelegatePermissionDenied)
+    );
+
+    let unrestricted = get_delegate_permissions(DelegatePermissionsParams {
+        permissions: DelegatePermissionFlag::CancelOrders as u8,
+        ..DelegatePermissionsParams::default()
+    });
+    assert!(unrestricted
+        .validate_cancel_orders_filter(None, None)
+        .is_ok());
+}
+
+#[test]
+fn validate_perp_order() {
+    let delegate_permissions = get_delegate_permissions(DelegatePermissionsParams {
+        permissions: DelegatePermissionFlag::PlaceOrders as u8
+            | DelegatePermissionFlag::PostOnly as u8,
+        max_order_notional: 1000 * QUOTE_PRECISION_U64,
+        max_position_notional: 2000 * QUOTE_PRECISION_U64,
+        ..DelegatePermissionsParams::default()
+    });
+
+    let market = PerpMarket::default();
+    let oracle_price = 100 * PRICE_PRECISION_I64;
+
+    let order = Order {
+        market_type: MarketType::Perp,
+        base_asset_amount: 10 * BASE_PRECISION_U64,
+        post_only: true,
+        ..Order::default()
+    };
+    let position = PerpPosition {
+        base_asset_amount: 5 * BASE_PRECISION_I64,
+        open_bids: 10 * BASE_PRECISION_I64,
+        ..PerpPosition::default()
+    };
+    assert!(delegate_permissions
+        .validate_perp_order(&order, &position, &market, oracle_price)
+        .is_ok());
+
+    let not_post_only = Order {
+        post_only: false,
+        ..order
+    };
+    assert_eq!(
+        delegate_permissions.validate_perp_order(&not_post_only, &position, &market, oracle_price),
+        Err(ErrorCode::DelegatePermissionDenied)
+    );
+
+    let too_big = Order {
+        base_asset_amount: 11 * BASE_PRECISION_U64,
+        ..order
+    };
+    assert_eq!(
+        delegate_permissions.validate_perp_order(&too_big, &position, &market, oracle_price),
+        Err(ErrorCode::DelegatePermissionDenied)
+    );
+
+    // a twap is capped on its whole size, not the slices released so far
+    let twap = Order {
+        order_type: OrderType::Twap,
+        base_asset_amount: 11 * BASE_PRECISION_U64,
+        base_asset_amount_filled: BASE_PRECISION_U64 / 2,
+        twap_slice_interval: 60,
+        twap_num_slices: 11,
+        twap_slices_released: 1,
+        ..order
+    };
+    assert_eq!(
+        delegate_permissions.validate_perp_order(&twap, &position, &market, oracle_price),
+        Err(ErrorCode::DelegatePermissionDenied)
+    );
+
+    let twap = Order {
+        base_asset_amount_filled: BASE_PRECISION_U64,
+        ..twap
+    };
+    assert!(delegate_permissions
+        .validate_perp_order(&twap, &position, &market, oracle_price)
+        .is_ok());
+
+    // worst case position is 15 + 10 = 25 base, $2500 notional
+    let position = PerpPosition {
+        base_asset_amount: 15 * BASE_PRECISION_I64,
+        open_bids: 10 * BASE_PRECISION_I64,
+        ..PerpPosition::default()
+    };
+    assert_eq!(
+        delegate_permissions.validate_perp_order(&order, &position, &market, oracle_price),
+        Err(ErrorCode::DelegatePermissionDenied)
+    );
+}
+
+#[test]
+fn validate_spot_order() {
+    let delegate_permissions = get_delegate_permissions(DelegatePermissionsParams {
+        permissions: DelegatePermissionFlag::PlaceOrders as u8
+            | DelegatePermissionFlag::ReduceOnly as u8,
+        max_order_notional: 1000 * QUOTE_PRECISION_U64,
+        spot_market_indexes: vec![1],
+        ..DelegatePermissionsParams::default()
+    });
+
+    let spot_market = SpotMarket {
+        market_index: 1,
+        decimals: 9,
+        ..SpotMarket::default()
+    };
+    let oracle_price = 100 * PRICE_PRECISION_I64;
+
+    let order = Order {
+        market_type: MarketType::Spot,
+        market_index: 1,
+        base_asset_amount: 10 * BASE_PRECISION_U64,
+        reduce_only: true,
+        ..Order::default()
+    };
+    assert!(delegate_permissions
+        .validate_spot_order(&order, &spot_market, oracle_price)
+        .is_ok());
+
+    let too_big = Order {
+        base_asset_amount: 11 * BASE_PRECISION_U64,
+        ..order
+    };
+    assert_eq!(
+        delegate_permissions.validate_spot_order(&too_big, &spot_market, oracle_price),
+        Err(ErrorCode::DelegatePermissionDenied)
+    );
+
+    let not_reduce_only = Order {
+        reduce_only: false,
+        ..order
+    };
+    assert_eq!(
+        delegate_permissions.validate_spot_order(&not_reduce_only, &spot_market, oracle_price),
+        Err(ErrorCode::DelegatePermissionDenied)
+    );
+
+    let other_market = Order {
+        market_index: 2,
+        ..order
+    };
+    assert_eq!(
+        delegate_permissions.validate_spot_order(&other_market, &spot_market, oracle_price),
+        Err(ErrorCode::DelegatePermissionDenied)
+    );
+}
@@ -188,7 +188,7 @@ pub struct OrderRecord {
 }
 
 impl Size for OrderRecord {
-    const SIZE: usize = 192;
+    const SIZE: usize = 248;
 }
 
 #[event]
@@ -263,6 +263,8 @@ pub struct OrderActionRecord {
     pub builder_idx: Option<u8>,
     /// precision: QUOTE_PRECISION builder fee paid by the taker
     pub builder_fee: Option<u64>,
+    /// For replace actions, the id of the order the taker order replaced
+    pub replaced_order_id: Option<u32>,
 }
 
 impl Size for OrderActionRecord {
@@ -352,6 +354,7 @@ pub fn get_order_action_record(
         trigger_price,
         builder_idx,
         builder_fee,
+        replaced_order_id: None,
     })
 }
 
@@ -363,6 +366,12 @@ pub enum OrderAction {
     Fill,
     Trigger,
     Expire,
+    /// Trailing stop trigger price ratcheted without triggering
+    UpdateTrigger,
+    /// Twap order released its next slice and restarted its auction
+    ReleaseSlice,
+    /// Open order canceled and replaced. taker_order is the replaced order and maker_order the new one
+    Replace,
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
@@ -388,6 +397,9 @@ pub enum OrderActionExplanation {
     DeriskLp,
     OrderFilledWithOpenbookV2,
     TransferPerpPosition,
+    OrderGroupSiblingFilled,
+    OrderReplaced,
+    Deleverage,
 }
 
 #[event]
@@ -452,6 +464,7 @@ pub enum LiquidationType {
     LiquidatePerpPnlForDeposit,
     PerpBankruptcy,
     SpotBankruptcy,
+    PerpAutoDeleverage,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
@@ -525,6 +538,7 @@ pub struct SpotBankruptcyRecord {
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
 pub enum LiquidationBitFlag {
     IsolatedPosition = 0b00000001,
+    LiquidationAuction = 0b00000010,
 }
 
 #[event]
@@ -695,6 +709,29 @@ pub struct DeleteUserRecord {
     pub keeper: Option<Pubkey>,
 }
 
+#[event]
+pub struct WithdrawPolicyRecord {
+    /// unix_timestamp of action
+    pub ts: i64,
+    pub authority: Pubkey,
+    pub action: WithdrawPolicyAction,
+    /// The destinations after the action. For requested updates, the pending destinations
+    pub destinations: Vec<Pubkey>,
+    pub timelock: u32,
+    /// When the destinations and timelock take effect
+    pub effective_ts: i64,
+}
+
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Eq, Debug)]
+pub enum WithdrawPolicyAction {
+    Initialize,
+    RequestUpdate,
+    ApplyUpdate,
+    RequestDelete,
+    CancelChange,
+    Delete,
+}
+
 #[event]
 pub struct FuelSweepRecord {
     pub ts: i64,
@@ -739,12 +776,30 @@ pub struct RevenueShareSettleRecord {
     pub builder_sub_account_id: u16,
     pub builder_total_referrer_rewards: u64,
     pub builder_total_builder_rewards: u64,
+    /// protocol rebate paid from the market fee pool on top of fee_settled
+    pub builder_rebate: u64,
+    /// rebate owed but not paid because the market fee pool was short
+    pub builder_rebate_unpaid: u64,
+    pub builder_volume_30d: u64,
 }
 
 impl Size for RevenueShareSettleRecord {
     const SIZE: usize = 140;
 }
 
+/// Emitted by the fill that uses up the rest of a referral campaign's budget
+#[event]
+pub struct ReferralCampaignBudgetDepletedRecord {
+    pub ts: i64,
+    pub referrer: Pubkey,
+    pub budget: u64,
+    pub budget_used: u64,
+}
+
+impl Size for ReferralCampaignBudgetDepletedRecord {
+    const SIZE: usize = 72;
+}
+
 pub fn emit_stack<T: AnchorSerialize + Discriminator, const N: usize>(event: T) -> DriftResult {
     #[cfg(not(feature = "drift-rs"))]
     {
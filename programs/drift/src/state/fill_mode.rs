@@ -2,6 +2,7 @@ use crate::error::DriftResult;
 use crate::math::auction::calculate_auction_price;
 use crate::math::casting::Cast;
 use crate::math::safe_math::SafeMath;
+use crate::state::order_params::TimeInForce;
 use crate::state::user::Order;
 
 #[cfg(test)]
@@ -11,8 +12,10 @@ mod tests;
 pub enum FillMode {
     Fill,
     PlaceAndMake,
-    PlaceAndTake(bool, u8),
+    PlaceAndTake(TimeInForce, u8),
     Liquidation,
+    /// fill or kill leg of a pair order; margin is checked once after both legs fill
+    PairLeg,
 }
 
 impl FillMode {
@@ -34,11 +37,17 @@ impl FillMode {
                     is_prediction_market,
                     None,
                 ),
-            FillMode::PlaceAndTake(_, auction_duration_percentage) => {
+            FillMode::PlaceAndTake(_, _) | FillMode::PairLeg => {
+                let auction_duration_percentage = match self {
+                    FillMode::PlaceAndTake(_, auction_duration_percentage) => {
+                        *auction_duration_percentage
+                    }
+                    _ => 100,
+                };
                 let auction_duration = order
                     .auction_duration
                     .cast::<u64>()?
-                    .safe_mul(auction_duration_percentage.min(&100).cast()?)?
+                    .safe_mul(auction_duration_percentage.min(100).cast()?)?
                     .safe_div(100)?
                     .cast::<u64>()?;
 
@@ -70,6 +79,21 @@ impl FillMode {
     }
 
     pub fn is_ioc(&self) -> bool {
-        matches!(self, FillMode::PlaceAndTake(true, _))
+        matches!(
+            self,
+            FillMode::PlaceAndTake(TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill, _)
+                | FillMode::PairLeg
+        )
+    }
+
+    pub fn is_fill_or_kill(&self) -> bool {
+        matches!(
+            self,
+            FillMode::PlaceAndTake(TimeInForce::FillOrKill, _) | FillMode::PairLeg
+        )
+    }
+
+    pub fn is_pair_leg(&self) -> bool {
+        self == &FillMode::PairLeg
     }
 }
@@ -1,4 +1,5 @@
 use crate::state::fill_mode::FillMode;
+use crate::state::order_params::TimeInForce;
 use crate::state::user::{Order, OrderType};
 use crate::{PositionDirection, PRICE_PRECISION_I64, PRICE_PRECISION_U64};
 
@@ -27,7 +28,7 @@ fn test() {
 
     assert_eq!(limit_price, Some(100 * PRICE_PRECISION_U64));
 
-    let place_and_take_mode = FillMode::PlaceAndTake(false, 100);
+    let place_and_take_mode = FillMode::PlaceAndTake(TimeInForce::GoodTilCanceled, 100);
 
     let limit_price = place_and_take_mode
         .get_limit_price(&market_order, oracle_price, slot, tick_size, false)
@@ -49,3 +50,29 @@ fn test() {
 
     assert_eq!(limit_price, Some(120 * PRICE_PRECISION_U64));
 }
+
+#[test]
+fn pair_leg() {
+    let market_order = Order {
+        order_type: OrderType::Market,
+        direction: PositionDirection::Long,
+        auction_start_price: 100 * PRICE_PRECISION_I64,
+        auction_end_price: 110 * PRICE_PRECISION_I64,
+        price: 120 * PRICE_PRECISION_U64,
+        slot: 0,
+        auction_duration: 10,
+        ..Order::default()
+    };
+
+    let fill_mode = FillMode::PairLeg;
+
+    let limit_price = fill_mode
+        .get_limit_price(&market_order, Some(100 * PRICE_PRECISION_I64), 0, 1, false)
+        .unwrap();
+
+    assert_eq!(limit_price, Some(110 * PRICE_PRECISION_U64));
+    assert!(fill_mode.is_ioc());
+    assert!(fill_mode.is_fill_or_kill());
+    assert!(fill_mode.is_pair_leg());
+    assert!(!fill_mode.is_liquidation());
+}
@@ -9,7 +9,10 @@ use crate::{
     math::constants::{LIQUIDATION_PCT_PRECISION, QUOTE_SPOT_MARKET_INDEX},
     math::{
         bankruptcy::{is_cross_margin_bankrupt, is_isolated_margin_bankrupt},
-        liquidation::calculate_max_pct_to_liquidate,
+        liquidation::{
+            calculate_max_pct_to_deleverage, calculate_max_pct_to_liquidate,
+            get_largest_risk_cross_perp_market_index,
+        },
         margin::calculate_user_safest_position_tiers,
         safe_unwrap::SafeUnwrap,
     },
@@ -19,6 +22,7 @@ use crate::{
 
 use super::{
     events::LiquidationBitFlag,
+    oracle_map::OracleMap,
     perp_market::ContractTier,
     perp_market_map::PerpMarketMap,
     spot_market::{AssetTier, SpotBalanceType, SpotMarket},
@@ -51,6 +55,15 @@ pub trait LiquidatePerpMode {
         liquidation_duration: u128,
     ) -> DriftResult<u128>;
 
+    fn calculate_max_pct_to_deleverage(
+        &self,
+        user: &User,
+        margin_shortage: u128,
+        slot: u64,
+        initial_pct_to_liquidate: u128,
+        liquidation_duration: u128,
+    ) -> DriftResult<u128>;
+
     fn increment_free_margin(&self, user: &mut User, amount: u64) -> DriftResult<()>;
 
     fn is_user_bankrupt(&self, user: &User) -> DriftResult<bool>;
@@ -86,6 +99,19 @@ pub trait LiquidatePerpMode {
     ) -> DriftResult<()>;
 
     fn margin_shortage(&self, margin_calculation: &MarginCalculation) -> DriftResult<u128>;
+
+    /// Whether the user meets maintenance but not the buffer the margin calculation was made with
+    fn is_in_deleverage_band(&self, margin_calculation: &MarginCalculation) -> DriftResult<bool> {
+        Ok(self.meets_margin_requirements(margin_calculation)?
+            && !self.can_exit_liquidation(margin_calculation)?)
+    }
+
+    fn get_deleverage_market_index(
+        &self,
+        user: &User,
+        perp_market_map: &PerpMarketMap,
+        oracle_map: &mut OracleMap,
+    ) -> DriftResult<Option<u16>>;
 }
 
 pub fn get_perp_liquidation_mode(
@@ -139,7 +165,7 @@ impl LiquidatePerpMode for CrossMarginLiquidatePerpMode {
     }
 
     fn exit_liquidation(&self, user: &mut User) -> DriftResult<()> {
-        Ok(user.exit_cross_margin_liquidation())
+        user.exit_cross_margin_liquidation()
     }
 
     fn get_cancel_orders_params(&self) -> (Option<MarketType>, Option<u16>) {
@@ -163,6 +189,23 @@ impl LiquidatePerpMode for CrossMarginLiquidatePerpMode {
         )
     }
 
+    fn calculate_max_pct_to_deleverage(
+        &self,
+        user: &User,
+        margin_shortage: u128,
+        slot: u64,
+        initial_pct_to_liquidate: u128,
+        liquidation_duration: u128,
+    ) -> DriftResult<u128> {
+        calculate_max_pct_to_deleverage(
+            user,
+            margin_shortage,
+            slot,
+            initial_pct_to_liquidate,
+            liquidation_duration,
+        )
+    }
+
     fn increment_free_margin(&self, user: &mut User, amount: u64) -> DriftResult<()> {
         user.increment_margin_freed(amount)
     }
@@ -261,6 +304,15 @@ impl LiquidatePerpMode for CrossMarginLiquidatePerpMode {
     fn margin_shortage(&self, margin_calculation: &MarginCalculation) -> DriftResult<u128> {
         margin_calculation.cross_margin_margin_shortage()
     }
+
+    fn get_deleverage_market_index(
+        &self,
+        user: &User,
+        perp_market_map: &PerpMarketMap,
+        oracle_map: &mut OracleMap,
+    ) -> DriftResult<Option<u16>> {
+        get_largest_risk_cross_perp_market_index(user, perp_market_map, oracle_map)
+    }
 }
 
 pub struct IsolatedMarginLiquidatePerpMode {
@@ -312,6 +364,17 @@ impl LiquidatePerpMode for IsolatedMarginLiquidatePerpMode {
         Ok(LIQUIDATION_PCT_PRECISION)
     }
 
+    fn calculate_max_pct_to_deleverage(
+        &self,
+        _user: &User,
+        _margin_shortage: u128,
+        _slot: u64,
+        _initial_pct_to_liquidate: u128,
+        _liquidation_duration: u128,
+    ) -> DriftResult<u128> {
+        Ok(LIQUIDATION_PCT_PRECISION)
+    }
+
     fn increment_free_margin(&self, _user: &mut User, _amount: u64) -> DriftResult<()> {
         Ok(())
     }
@@ -404,4 +467,15 @@ impl LiquidatePerpMode for IsolatedMarginLiquidatePerpMode {
     fn margin_shortage(&self, margin_calculation: &MarginCalculation) -> DriftResult<u128> {
         margin_calculation.isolated_margin_shortage(self.market_index)
     }
+
+    fn get_deleverage_market_index(
+        &self,
+        user: &User,
+        _perp_market_map: &PerpMarketMap,
+        _oracle_map: &mut OracleMap,
+    ) -> DriftResult<Option<u16>> {
+        let perp_position = user.get_isolated_perp_position(self.market_index)?;
+
+        Ok((perp_position.base_asset_amount != 0).then_some(self.market_index))
+    }
 }
@@ -304,6 +304,24 @@ impl MarginCalculation {
         Ok(())
     }
 
+    /// Credit for offsetting positions in correlated markets or on the same underlying, bounded by
+    /// the requirements it offsets
+    pub fn add_cross_margin_correlation_credit(&mut self, margin_credit: u128) -> DriftResult {
+        if margin_credit == 0 {
+            return Ok(());
+        }
+
+        self.margin_requirement = self.margin_requirement.safe_sub(margin_credit)?;
+
+        if self.context.margin_buffer > 0 {
+            self.margin_requirement_plus_buffer = self
+                .margin_requirement_plus_buffer
+                .safe_sub(margin_credit)?;
+        }
+
+        Ok(())
+    }
+
     pub fn add_isolated_margin_calculation(
         &mut self,
         market_index: u16,
@@ -0,0 +1,171 @@
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::casting::Cast;
+use crate::math::constants::PERCENTAGE_PRECISION;
+use crate::state::margin_calculation::MarketIdentifier;
+use crate::state::traits::Size;
+use crate::state::user::MarketType;
+use crate::validate;
+use anchor_lang::prelude::*;
+
+#[cfg(test)]
+mod tests;
+
+pub const MARGIN_CORRELATIONS_PDA_SEED: &str = "margin_correlations";
+pub const MAX_MARGIN_CORRELATIONS: usize = 32;
+
+/// Admin configured market pairs whose offsetting cross margin positions receive a margin credit
+#[account(zero_copy(unsafe))]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct MarginCorrelations {
+    /// Pairs are offset in order, so a position is credited against the earlier pairs first
+    pub correlations: [MarginCorrelation; MAX_MARGIN_CORRELATIONS],
+    pub padding: [u8; 64],
+}
+
+impl Size for MarginCorrelations {
+    const SIZE: usize = 456;
+}
+
+#[zero_copy(unsafe)]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct MarginCorrelation {
+    /// Share of the smaller of the two requirements that is credited back, 0 if the slot is unused
+    /// precision: PERCENTAGE_PRECISION
+    pub correlation: u32,
+    pub market_index_a: u16,
+    pub market_index_b: u16,
+    pub market_type_a: MarketType,
+    pub market_type_b: MarketType,
+    pub padding: [u8; 2],
+}
+
+impl MarginCorrelation {
+    pub fn is_active(&self) -> bool {
+        self.correlation > 0
+    }
+
+    pub fn market_a(&self) -> MarketIdentifier {
+        MarketIdentifier {
+            market_type: self.market_type_a,
+            market_index: self.market_index_a,
+        }
+    }
+
+    pub fn market_b(&self) -> MarketIdentifier {
+        MarketIdentifier {
+            market_type: self.market_type_b,
+            market_index: self.market_index_b,
+        }
+    }
+
+    pub fn is_for_markets(&self, market_a: MarketIdentifier, market_b: MarketIdentifier) -> bool {
+        let (a, b) = (self.market_a(), self.market_b());
+        (a == market_a && b == market_b) || (a == market_b && b == market_a)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub struct MarginCorrelationParams {
+    pub market_type_a: MarketType,
+    pub market_index_a: u16,
+    pub market_type_b: MarketType,
+    pub market_index_b: u16,
+    pub correlation: u32,
+}
+
+impl MarginCorrelationParams {
+    pub fn validate(&self) -> DriftResult {
+        validate!(
+            self.correlation.cast::<u128>()? <= PERCENTAGE_PRECISION,
+            ErrorCode::InvalidMarginCorrelation,
+            "correlation ({}) must be <= PERCENTAGE_PRECISION",
+            self.correlation
+        )?;
+
+        validate!(
+            self.market_type_a == MarketType::Perp || self.market_type_b == MarketType::Perp,
+            ErrorCode::InvalidMarginCorrelation,
+            "margin correlations must include a perp market"
+        )?;
+
+        validate!(
+            self.market_type_a != self.market_type_b || self.market_index_a != self.market_index_b,
+            ErrorCode::InvalidMarginCorrelation,
+            "margin correlation markets must be different"
+        )?;
+
+        Ok(())
+    }
+}
+
+impl MarginCorrelations {
+    pub fn get_correlation(
+        &self,
+        market_a: MarketIdentifier,
+        market_b: MarketIdentifier,
+    ) -> Option<u32> {
+        self.correlations
+            .iter()
+            .find(|c| c.is_active() && c.is_for_markets(market_a, market_b))
+            .map(|c| c.correlation)
+    }
+
+    pub fn active_correlations(&self) -> impl Iterator<Item = &MarginCorrelation> {
+        self.correlations.iter().filter(|c| c.is_active())
+    }
+
+    /// Sets the pair at `index`, a zero correlation clears the slot
+    pub fn update(&mut self, index: u16, params: &MarginCorrelationParams) -> DriftResult {
+        params.validate()?;
+
+        let index = index as usize;
+        validate!(
+            index < MAX_MARGIN_CORRELATIONS,
+            ErrorCode::InvalidMarginCorrelation,
+            "index ({}) must be < {}",
+            index,
+            MAX_MARGIN_CORRELATIONS
+        )?;
+
+        let market_a = MarketIdentifier {
+            market_type: params.market_type_a,
+            market_index: params.market_index_a,
+        };
+        let market_b = MarketIdentifier {
+            market_type: params.market_type_b,
+            market_index: params.market_index_b,
+        };
+
+        if params.correlation > 0 {
+            let duplicate =
+                self.correlations.iter().enumerate().any(|(i, c)| {
+                    i != index && c.is_active() && c.is_for_markets(market_a, market_b)
+                });
+
+            validate!(
+                !duplicate,
+                ErrorCode::InvalidMarginCorrelation,
+                "margin correlation already set for markets {:?} and {:?}",
+                market_a,
+                market_b
+            )?;
+        }
+
+        self.correlations[index] = if params.correlation > 0 {
+            MarginCorrelation {
+                correlation: params.correlation,
+                market_index_a: params.market_index_a,
+                market_index_b: params.market_index_b,
+                market_type_a: params.market_type_a,
+                market_type_b: params.market_type_b,
+                padding: [0; 2],
+            }
+        } else {
+            MarginCorrelation::default()
+        };
+
+        Ok(())
+    }
+}
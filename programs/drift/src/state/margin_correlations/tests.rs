@@ -0,0 +1,86 @@
+use crate::state::margin_calculation::MarketIdentifier;
+use crate::state::margin_correlations::{MarginCorrelationParams, MarginCorrelations};
+use crate::state::user::MarketType;
+
+#[test]
+fn validate_params() {
+    let params = MarginCorrelationParams {
+        market_type_a: MarketType::Perp,
+        market_index_a: 1,
+        market_type_b: MarketType::Perp,
+        market_index_b: 2,
+        correlation: 800_000,
+    };
+    assert!(params.validate().is_ok());
+
+    let params = MarginCorrelationParams {
+        correlation: 1_000_001,
+        ..params
+    };
+    assert!(params.validate().is_err());
+
+    let params = MarginCorrelationParams {
+        correlation: 800_000,
+        market_index_b: 1,
+        ..params
+    };
+    assert!(params.validate().is_err());
+
+    let params = MarginCorrelationParams {
+        market_type_a: MarketType::Spot,
+        market_type_b: MarketType::Spot,
+        market_index_b: 2,
+        ..params
+    };
+    assert!(params.validate().is_err());
+
+    let params = MarginCorrelationParams {
+        market_type_b: MarketType::Perp,
+        market_index_b: 1,
+        ..params
+    };
+    assert!(params.validate().is_ok());
+}
+
+#[test]
+fn update() {
+    let mut margin_correlations = MarginCorrelations::default();
+
+    let params = MarginCorrelationParams {
+        market_type_a: MarketType::Perp,
+        market_index_a: 1,
+        market_type_b: MarketType::Perp,
+        market_index_b: 2,
+        correlation: 800_000,
+    };
+    margin_correlations.update(0, &params).unwrap();
+
+    assert_eq!(
+        margin_correlations.get_correlation(MarketIdentifier::perp(2), MarketIdentifier::perp(1)),
+        Some(800_000)
+    );
+    assert_eq!(
+        margin_correlations.get_correlation(MarketIdentifier::perp(1), MarketIdentifier::spot(2)),
+        None
+    );
+
+    // same pair in another slot
+    let reversed_params = MarginCorrelationParams {
+        market_index_a: 2,
+        market_index_b: 1,
+        ..params
+    };
+    assert!(margin_correlations.update(1, &reversed_params).is_err());
+
+    // updating the same slot is fine
+    margin_correlations.update(0, &reversed_params).unwrap();
+
+    assert!(margin_correlations.update(32, &params).is_err());
+
+    let params = MarginCorrelationParams {
+        correlation: 0,
+        ..params
+    };
+    margin_correlations.update(0, &params).unwrap();
+    assert_eq!(margin_correlations.active_correlations().count(), 0);
+}
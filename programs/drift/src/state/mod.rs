@@ -1,5 +1,6 @@
 pub mod amm_cache;
 pub mod constituent_map;
+pub mod delegate_permissions;
 pub mod events;
 pub mod fill_mode;
 pub mod fulfillment;
@@ -11,14 +12,17 @@ pub mod liquidation_mode;
 pub mod load_ref;
 pub mod lp_pool;
 pub mod margin_calculation;
+pub mod margin_correlations;
 pub mod oracle;
 pub mod oracle_map;
 pub mod order_params;
+pub mod pair_order_params;
 pub mod paused_operations;
 pub mod perp_market;
 pub mod perp_market_map;
 pub mod protected_maker_mode_config;
 pub mod pyth_lazer_oracle;
+pub mod referral_campaign;
 pub mod revenue_share;
 pub mod revenue_share_map;
 pub mod scale_order_params;
@@ -32,4 +36,5 @@ pub mod state;
 pub mod traits;
 pub mod user;
 pub mod user_map;
+pub mod withdraw_policy;
 pub mod zero_copy;
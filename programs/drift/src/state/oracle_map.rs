@@ -6,6 +6,7 @@ use crate::ids::{
 use crate::math::constants::PRICE_PRECISION_I64;
 use crate::math::oracle::{oracle_validity, LogMode, OracleValidity};
 use crate::msg;
+use crate::state::margin_correlations::MarginCorrelations;
 use crate::state::oracle::{get_oracle_price, OraclePriceData, OracleSource, PrelaunchOracle};
 use crate::state::state::OracleGuardRails;
 use crate::state::user::MarketType;
@@ -39,6 +40,11 @@ pub struct OracleMap<'a> {
     pub slot: u64,
     pub oracle_guard_rails: OracleGuardRails,
     pub quote_asset_price_data: OraclePriceData,
+    /// Correlated market pairs credited in margin calculations, set when the account is passed
+    pub margin_correlations: Option<Box<MarginCorrelations>>,
+    /// Mirrors the state's portfolio margin flag, portfolio margin users get the standard treatment
+    /// while it's off
+    pub portfolio_margin_enabled: bool,
 }
 
 impl<'a> OracleMap<'a> {
@@ -257,6 +263,8 @@ impl<'a> OracleMap<'a> {
                 has_sufficient_number_of_data_points: true,
                 sequence_id: None,
             },
+            margin_correlations: None,
+            portfolio_margin_enabled: false,
         })
     }
 
@@ -321,6 +329,8 @@ impl<'a> OracleMap<'a> {
                 has_sufficient_number_of_data_points: true,
                 sequence_id: None,
             },
+            margin_correlations: None,
+            portfolio_margin_enabled: false,
         })
     }
 
@@ -353,6 +363,8 @@ impl<'a> OracleMap<'a> {
                 has_sufficient_number_of_data_points: true,
                 sequence_id: None,
             },
+            margin_correlations: None,
+            portfolio_margin_enabled: false,
         }
     }
 }
@@ -7,9 +7,10 @@ use crate::math::constants::{
 };
 use crate::math::safe_math::SafeMath;
 use crate::math::safe_unwrap::SafeUnwrap;
+use crate::state::delegate_permissions::DelegatePermissions;
 use crate::state::events::OrderActionExplanation;
 use crate::state::perp_market::{ContractTier, PerpMarket};
-use crate::state::user::{MarketType, OrderTriggerCondition, OrderType};
+use crate::state::user::{MarketType, OrderTriggerCondition, OrderTriggerSource, OrderType};
 use anchor_lang::prelude::*;
 use borsh::{BorshDeserialize, BorshSerialize};
 use std::ops::Div;
@@ -30,18 +31,27 @@ pub struct OrderParams {
     pub post_only: PostOnlyParam,
     pub bit_flags: u8,
     pub max_ts: Option<i64>,
-    pub trigger_price: Option<u64>,
+    pub trigger_price: Option<u64>, // rate trigger sources pass the signed threshold's u32 bits
     pub trigger_condition: OrderTriggerCondition,
     pub oracle_price_offset: Option<i32>, // price offset from oracle for order (~ +/- 2147 max)
     pub auction_duration: Option<u8>,     // specified in slots
     pub auction_start_price: Option<i64>, // specified in price or oracle_price_offset
     pub auction_end_price: Option<i64>,   // specified in price or oracle_price_offset
+    pub trailing_stop_distance: Option<u32>, // trailing stop orders only, in price or bps with TrailingStopBps
+    pub twap_slice_interval: Option<u32>,    // twap orders only, seconds between slices
+    pub iceberg_clip_size: Option<u64>, // limit orders only, base shown per clip. makes the order an iceberg
+    pub trigger_source: Option<OrderTriggerSource>, // trigger orders only, defaults to the oracle
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
 pub enum OrderParamsBitFlag {
     ImmediateOrCancel = 0b00000001,
     UpdateHighLeverageMode = 0b00000010,
+    TrailingStopBps = 0b00000100,
+    OrderGroup = 0b00001000,
+    BracketEntry = 0b00010000,
+    /// With ImmediateOrCancel set the order is fill or kill, on its own the order is good til slot
+    ExtendedTimeInForce = 0b01000000,
 }
 
 impl OrderParams {
@@ -847,8 +857,34 @@ impl OrderParams {
         Ok(params)
     }
 
+    pub fn get_time_in_force(&self) -> TimeInForce {
+        let immediate_or_cancel = self.bit_flags & OrderParamsBitFlag::ImmediateOrCancel as u8 != 0;
+        let extended_time_in_force =
+            self.bit_flags & OrderParamsBitFlag::ExtendedTimeInForce as u8 != 0;
+
+        match (immediate_or_cancel, extended_time_in_force) {
+            (true, true) => TimeInForce::FillOrKill,
+            (true, false) => TimeInForce::ImmediateOrCancel,
+            (false, true) => TimeInForce::GoodTilSlot,
+            (false, false) => TimeInForce::GoodTilCanceled,
+        }
+    }
+
+    /// Fill or kill orders are immediate or cancel orders that must fill completely
     pub fn is_immediate_or_cancel(&self) -> bool {
-        self.bit_flags & OrderParamsBitFlag::ImmediateOrCancel as u8 != 0
+        matches!(
+            self.get_time_in_force(),
+            TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill
+        )
+    }
+
+    pub fn is_fill_or_kill(&self) -> bool {
+        self.get_time_in_force() == TimeInForce::FillOrKill
+    }
+
+    /// Good til slot orders pass the last slot the order can be filled in as max_ts
+    pub fn is_good_til_slot(&self) -> bool {
+        self.get_time_in_force() == TimeInForce::GoodTilSlot
     }
 
     pub fn is_update_high_leverage_mode(&self) -> bool {
@@ -859,8 +895,27 @@ impl OrderParams {
         self.base_asset_amount == u64::MAX
     }
 
+    pub fn is_trailing_stop_bps(&self) -> bool {
+        self.bit_flags & OrderParamsBitFlag::TrailingStopBps as u8 != 0
+    }
+
+    pub fn is_order_group(&self) -> bool {
+        self.bit_flags & OrderParamsBitFlag::OrderGroup as u8 != 0
+    }
+
+    pub fn is_bracket_entry(&self) -> bool {
+        self.bit_flags & OrderParamsBitFlag::BracketEntry as u8 != 0
+    }
+
+    pub fn is_iceberg(&self) -> bool {
+        self.iceberg_clip_size.is_some()
+    }
+
     pub fn is_trigger_order(&self) -> bool {
-        self.order_type == OrderType::TriggerMarket || self.order_type == OrderType::TriggerLimit
+        matches!(
+            self.order_type,
+            OrderType::TriggerMarket | OrderType::TriggerLimit | OrderType::TrailingStop
+        )
     }
 }
 
@@ -892,6 +947,40 @@ pub struct SignedMsgOrderParamsDelegateMessage {
     pub isolated_position_deposit: Option<u64>,
 }
 
+/// Places a batch of taker orders under one uuid with a single margin check after the last order
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Eq, PartialEq, Debug)]
+pub struct SignedMsgBatchOrderParamsMessage {
+    pub signed_msg_order_params: Vec<OrderParams>,
+    pub sub_account_id: u16,
+    pub slot: u64,
+    pub uuid: [u8; 8],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Eq, PartialEq, Debug)]
+pub struct SignedMsgBatchOrderParamsDelegateMessage {
+    pub signed_msg_order_params: Vec<OrderParams>,
+    pub taker_pubkey: Pubkey,
+    pub slot: u64,
+    pub uuid: [u8; 8],
+}
+
+/// Cancels signed msg orders by uuid, including ones that were already placed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Eq, PartialEq, Debug)]
+pub struct SignedMsgCancelOrdersMessage {
+    pub sub_account_id: u16,
+    pub slot: u64,
+    pub uuids: Vec<[u8; 8]>,
+}
+
+/// Cancels every signed msg order signed before cancel_before_slot, for all of the authority's
+/// sub accounts. Orders that were already placed are only cancelled for sub_account_id
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Eq, PartialEq, Debug)]
+pub struct SignedMsgCancelOrdersBeforeSlotMessage {
+    pub sub_account_id: u16,
+    pub slot: u64,
+    pub cancel_before_slot: u64,
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Eq, PartialEq, Debug)]
 pub struct SignedMsgTriggerOrderParams {
     pub trigger_price: u64,
@@ -926,6 +1015,15 @@ pub enum PostOnlyParam {
     Slide,        // Modify price to be post only if can't be post only
 }
 
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq, Default)]
+pub enum TimeInForce {
+    #[default]
+    GoodTilCanceled, // Rests until filled, canceled or max_ts
+    GoodTilSlot,       // Rests until filled, canceled or the slot in max_ts
+    ImmediateOrCancel, // Fills what it can immediately and cancels the rest
+    FillOrKill,        // Fills completely and immediately or the whole fill is rolled back
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
 pub struct ModifyOrderParams {
     pub direction: Option<PositionDirection>,
@@ -942,6 +1040,8 @@ pub struct ModifyOrderParams {
     pub auction_start_price: Option<i64>,
     pub auction_end_price: Option<i64>,
     pub policy: Option<u8>,
+    pub trailing_stop_distance: Option<u32>,
+    pub trigger_source: Option<OrderTriggerSource>,
 }
 
 impl ModifyOrderParams {
@@ -954,6 +1054,14 @@ impl ModifyOrderParams {
     }
 }
 
+/// Cancels the open order with user_order_id (if any) and places order_params in its place,
+/// keeping the same user_order_id
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub struct ReplaceOrderParams {
+    pub user_order_id: u8,
+    pub order_params: OrderParams,
+}
+
 pub enum ModifyOrderPolicy {
     MustModify = 1,
     ExcludePreviousFill = 2,
@@ -967,6 +1075,10 @@ pub struct PlaceOrderOptions {
     pub risk_increasing: bool,
     pub explanation: OrderActionExplanation,
     pub existing_position_direction_override: Option<PositionDirection>,
+    pub order_group: u8,
+    pub bracket_pending: bool,
+    /// Set when a scoped delegate places the order
+    pub delegate_permissions: Option<DelegatePermissions>,
 }
 
 impl Default for PlaceOrderOptions {
@@ -978,6 +1090,9 @@ impl Default for PlaceOrderOptions {
             risk_increasing: false,
             explanation: OrderActionExplanation::None,
             existing_position_direction_override: None,
+            order_group: 0,
+            bracket_pending: false,
+            delegate_permissions: None,
         }
     }
 }
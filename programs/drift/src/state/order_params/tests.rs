@@ -1666,7 +1666,7 @@ mod get_close_perp_params {
             max_ts: 100,
             posted_slot_tail: get_posted_slot_from_clock_slot(slot),
             bit_flags: 0,
-            padding: [0; 1],
+            ..Order::default()
         }
     }
 
@@ -1738,3 +1738,26 @@ fn test_parse_optional_params() {
     assert_eq!(success_condition, 0x34);
     assert_eq!(auction_duration_percentage, 0x12);
 }
+
+#[test]
+fn test_get_time_in_force() {
+    use crate::state::order_params::{OrderParams, OrderParamsBitFlag, TimeInForce};
+
+    let mut params = OrderParams::default();
+    assert_eq!(params.get_time_in_force(), TimeInForce::GoodTilCanceled);
+
+    params.bit_flags = OrderParamsBitFlag::ExtendedTimeInForce as u8;
+    assert_eq!(params.get_time_in_force(), TimeInForce::GoodTilSlot);
+    assert!(!params.is_immediate_or_cancel());
+
+    params.bit_flags = OrderParamsBitFlag::ImmediateOrCancel as u8;
+    assert_eq!(params.get_time_in_force(), TimeInForce::ImmediateOrCancel);
+    assert!(params.is_immediate_or_cancel());
+    assert!(!params.is_fill_or_kill());
+
+    params.bit_flags =
+        OrderParamsBitFlag::ExtendedTimeInForce as u8 | OrderParamsBitFlag::ImmediateOrCancel as u8;
+    assert_eq!(params.get_time_in_force(), TimeInForce::FillOrKill);
+    assert!(params.is_immediate_or_cancel());
+    assert!(params.is_fill_or_kill());
+}
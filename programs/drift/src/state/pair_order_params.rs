@@ -0,0 +1,122 @@
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::casting::Cast;
+use crate::math::constants::QUOTE_SPOT_MARKET_INDEX;
+use crate::math::matching::do_orders_cross;
+use crate::math::safe_math::SafeMath;
+use crate::state::order_params::{OrderParams, OrderParamsBitFlag, PostOnlyParam};
+use crate::state::user::{MarketType, OrderType};
+use crate::validate;
+use anchor_lang::prelude::*;
+
+#[cfg(test)]
+mod tests;
+
+/// Parameters for a two leg spread order (e.g. spot vs perp basis or future vs perp). Both legs are
+/// taken in the same instruction and either both fill completely within the net price limit or
+/// the instruction fails and neither leg is filled
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub struct PairOrderParams {
+    /// The legs, in opposite directions and on different markets. At most one leg can be spot
+    pub legs: [OrderParams; 2],
+    /// Limit on the first leg's average fill price minus the second leg's. Going long the first leg,
+    /// the spread paid can be at most this. Going short, the spread received must be at least this
+    /// precision: PRICE_PRECISION
+    pub net_price_limit: i64,
+}
+
+impl PairOrderParams {
+    pub fn validate(&self) -> DriftResult {
+        let [first_leg, second_leg] = &self.legs;
+
+        validate!(
+            first_leg.market_type != second_leg.market_type
+                || first_leg.market_index != second_leg.market_index,
+            ErrorCode::InvalidPairOrder,
+            "pair order legs must be on different markets"
+        )?;
+
+        validate!(
+            first_leg.direction != second_leg.direction,
+            ErrorCode::InvalidPairOrder,
+            "pair order legs must be in opposite directions"
+        )?;
+
+        validate!(
+            first_leg.market_type == MarketType::Perp || second_leg.market_type == MarketType::Perp,
+            ErrorCode::InvalidPairOrder,
+            "pair order needs at least one perp leg"
+        )?;
+
+        for leg in self.legs.iter() {
+            validate!(
+                leg.order_type == OrderType::Limit,
+                ErrorCode::InvalidPairOrder,
+                "pair order legs must be limit orders"
+            )?;
+
+            validate!(
+                leg.post_only == PostOnlyParam::None,
+                ErrorCode::InvalidOrderPostOnly,
+                "pair order legs can not be post only"
+            )?;
+
+            validate!(
+                leg.trigger_price.unwrap_or(0) == 0
+                    && leg.iceberg_clip_size.is_none()
+                    && leg.bit_flags
+                        & (OrderParamsBitFlag::OrderGroup as u8
+                            | OrderParamsBitFlag::BracketEntry as u8
+                            | OrderParamsBitFlag::ExtendedTimeInForce as u8)
+                        == 0,
+                ErrorCode::InvalidPairOrder,
+                "pair order legs can not be icebergs, good til slot or grouped"
+            )?;
+
+            validate!(
+                leg.market_type == MarketType::Perp || leg.market_index != QUOTE_SPOT_MARKET_INDEX,
+                ErrorCode::InvalidPairOrder,
+                "spot leg can not be the quote market"
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Legs are filled as fill or kill takers so a partial fill on either leg fails the whole pair
+    pub fn get_leg_order_params(&self) -> [OrderParams; 2] {
+        self.legs.map(|mut leg| {
+            leg.bit_flags |= OrderParamsBitFlag::ImmediateOrCancel as u8
+                | OrderParamsBitFlag::ExtendedTimeInForce as u8;
+            leg
+        })
+    }
+
+    /// The first leg crosses if its fill price is within net_price_limit of the second leg's fill price
+    pub fn validate_net_price(
+        &self,
+        first_leg_fill_price: u64,
+        second_leg_fill_price: u64,
+    ) -> DriftResult {
+        let first_leg_limit_price = second_leg_fill_price
+            .cast::<i64>()?
+            .safe_add(self.net_price_limit)?
+            .max(0)
+            .unsigned_abs();
+
+        let direction = self.legs[0].direction;
+        let net_price = first_leg_fill_price
+            .cast::<i64>()?
+            .safe_sub(second_leg_fill_price.cast()?)?;
+
+        validate!(
+            do_orders_cross(direction, first_leg_limit_price, first_leg_fill_price),
+            ErrorCode::PairOrderNetPriceBreached,
+            "pair order net price {} breaches limit {} (going {:?} the first leg)",
+            net_price,
+            self.net_price_limit,
+            direction
+        )?;
+
+        Ok(())
+    }
+}
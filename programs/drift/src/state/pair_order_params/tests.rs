@@ -0,0 +1,105 @@
+use crate::state::order_params::{OrderParams, PostOnlyParam};
+use crate::state::pair_order_params::PairOrderParams;
+use crate::state::user::{MarketType, OrderType};
+use crate::{PositionDirection, BASE_PRECISION_U64, PRICE_PRECISION_I64, PRICE_PRECISION_U64};
+
+fn get_basis_params(net_price_limit: i64) -> PairOrderParams {
+    PairOrderParams {
+        legs: [
+            OrderParams {
+                order_type: OrderType::Limit,
+                market_type: MarketType::Spot,
+                market_index: 1,
+                direction: PositionDirection::Long,
+                base_asset_amount: BASE_PRECISION_U64,
+                price: 101 * PRICE_PRECISION_U64,
+                ..OrderParams::default()
+            },
+            OrderParams {
+                order_type: OrderType::Limit,
+                market_type: MarketType::Perp,
+                market_index: 0,
+                direction: PositionDirection::Short,
+                base_asset_amount: BASE_PRECISION_U64,
+                price: 100 * PRICE_PRECISION_U64,
+                ..OrderParams::default()
+            },
+        ],
+        net_price_limit,
+    }
+}
+
+#[test]
+fn test_validate() {
+    let params = get_basis_params(PRICE_PRECISION_I64);
+    assert!(params.validate().is_ok());
+
+    let mut same_direction = params;
+    same_direction.legs[1].direction = PositionDirection::Long;
+    assert!(same_direction.validate().is_err());
+
+    let mut same_market = params;
+    same_market.legs[0].market_type = MarketType::Perp;
+    assert!(same_market.validate().is_err());
+
+    let mut two_spot_legs = params;
+    two_spot_legs.legs[1].market_type = MarketType::Spot;
+    two_spot_legs.legs[1].market_index = 2;
+    assert!(two_spot_legs.validate().is_err());
+
+    let mut market_leg = params;
+    market_leg.legs[1].order_type = OrderType::Market;
+    assert!(market_leg.validate().is_err());
+
+    let mut post_only_leg = params;
+    post_only_leg.legs[0].post_only = PostOnlyParam::MustPostOnly;
+    assert!(post_only_leg.validate().is_err());
+
+    let mut quote_leg = params;
+    quote_leg.legs[0].market_index = 0;
+    assert!(quote_leg.validate().is_err());
+
+    let mut iceberg_leg = params;
+    iceberg_leg.legs[1].iceberg_clip_size = Some(BASE_PRECISION_U64 / 2);
+    assert!(iceberg_leg.validate().is_err());
+}
+
+#[test]
+fn test_leg_order_params_are_fill_or_kill() {
+    let params = get_basis_params(PRICE_PRECISION_I64);
+    for leg in params.get_leg_order_params().iter() {
+        assert!(leg.is_fill_or_kill());
+    }
+}
+
+#[test]
+fn test_validate_net_price() {
+    // long spot / short perp, paying at most $1 of basis
+    let params = get_basis_params(PRICE_PRECISION_I64);
+    assert!(params
+        .validate_net_price(101 * PRICE_PRECISION_U64, 100 * PRICE_PRECISION_U64)
+        .is_ok());
+    assert!(params
+        .validate_net_price(101 * PRICE_PRECISION_U64 + 1, 100 * PRICE_PRECISION_U64)
+        .is_err());
+
+    // short spot / long perp, receiving at least $1 of basis
+    let mut params = get_basis_params(PRICE_PRECISION_I64);
+    params.legs[0].direction = PositionDirection::Short;
+    params.legs[1].direction = PositionDirection::Long;
+    assert!(params
+        .validate_net_price(101 * PRICE_PRECISION_U64, 100 * PRICE_PRECISION_U64)
+        .is_ok());
+    assert!(params
+        .validate_net_price(101 * PRICE_PRECISION_U64 - 1, 100 * PRICE_PRECISION_U64)
+        .is_err());
+
+    // negative limit, first leg must fill below the second
+    let params = get_basis_params(-PRICE_PRECISION_I64);
+    assert!(params
+        .validate_net_price(99 * PRICE_PRECISION_U64, 100 * PRICE_PRECISION_U64)
+        .is_ok());
+    assert!(params
+        .validate_net_price(100 * PRICE_PRECISION_U64, 100 * PRICE_PRECISION_U64)
+        .is_err());
+}
@@ -4,7 +4,7 @@ use crate::state::pyth_lazer_oracle::PythLazerOracle;
 use crate::state::user::{MarketType, Order};
 use anchor_lang::prelude::*;
 
-use crate::state::state::{State, ValidityGuardRails};
+use crate::state::state::{FeeOverrideFlag, MarketFeeOverride, State, ValidityGuardRails};
 use std::cmp::max;
 
 use crate::controller::position::PositionDirection;
@@ -258,7 +258,21 @@ pub struct PerpMarket {
     pub last_fill_price: u64,
     pub lp_pool_id: u8,
     pub market_config: u8,
-    pub padding: [u8; 22],
+    /// Scales the fee tier's taker fee when the TakerFee override flag is set, so volume tiers still
+    /// apply. 100 keeps the tier's fee
+    /// precision: FEE_PERCENTAGE_DENOMINATOR
+    pub taker_fee_override_multiplier: u16,
+    /// Replaces the fee tier's maker rebate when the MakerRebate override flag is set
+    /// precision: FEE_DENOMINATOR
+    pub maker_rebate_override_numerator: u16,
+    pub fee_override_flags: u8,
+    pub padding1: u8,
+    /// Maker volume the maker rebate override applies to, 0 for no cap
+    /// precision: QUOTE_PRECISION
+    pub maker_rebate_override_volume_cap: u64,
+    /// Maker volume that has received the maker rebate override
+    /// precision: QUOTE_PRECISION
+    pub maker_rebate_override_volume: u64,
 }
 
 impl Default for PerpMarket {
@@ -307,7 +321,12 @@ impl Default for PerpMarket {
             last_fill_price: 0,
             lp_pool_id: 0,
             market_config: 0,
-            padding: [0; 22],
+            taker_fee_override_multiplier: 0,
+            maker_rebate_override_numerator: 0,
+            fee_override_flags: 0,
+            padding1: 0,
+            maker_rebate_override_volume_cap: 0,
+            maker_rebate_override_volume: 0,
         }
     }
 }
@@ -329,6 +348,38 @@ impl PerpMarket {
         self.market_config & flag as u8 != 0
     }
 
+    pub fn has_fee_override_flag(&self, flag: FeeOverrideFlag) -> bool {
+        self.fee_override_flags & flag as u8 != 0
+    }
+
+    pub fn get_fee_override(&self) -> MarketFeeOverride {
+        let maker_rebate_override_active = self.has_fee_override_flag(FeeOverrideFlag::MakerRebate)
+            && (self.maker_rebate_override_volume_cap == 0
+                || self.maker_rebate_override_volume < self.maker_rebate_override_volume_cap);
+
+        MarketFeeOverride {
+            taker_fee_multiplier: self
+                .has_fee_override_flag(FeeOverrideFlag::TakerFee)
+                .then(|| self.taker_fee_override_multiplier as u32),
+            maker_rebate_numerator: maker_rebate_override_active
+                .then(|| self.maker_rebate_override_numerator as u32),
+        }
+    }
+
+    pub fn update_maker_rebate_override_volume(
+        &mut self,
+        fee_override: &MarketFeeOverride,
+        maker_quote_asset_amount: u64,
+    ) -> DriftResult {
+        if fee_override.overrides_maker_rebate() {
+            self.maker_rebate_override_volume = self
+                .maker_rebate_override_volume
+                .safe_add(maker_quote_asset_amount)?;
+        }
+
+        Ok(())
+    }
+
     pub fn is_in_settlement(&self, now: i64) -> bool {
         let in_settlement = matches!(
             self.status,
@@ -731,3 +731,59 @@ mod amm_can_fill_order_tests {
         assert!(!can_fill);
     }
 }
+
+mod get_fee_override {
+    use crate::math::constants::QUOTE_PRECISION_U64;
+    use crate::state::perp_market::PerpMarket;
+    use crate::state::state::{FeeOverrideFlag, MarketFeeOverride};
+
+    #[test]
+    fn maker_rebate_volume_cap() {
+        let mut market = PerpMarket {
+            taker_fee_override_multiplier: 50,
+            maker_rebate_override_numerator: 30,
+            fee_override_flags: FeeOverrideFlag::MakerRebate as u8,
+            maker_rebate_override_volume_cap: 100 * QUOTE_PRECISION_U64,
+            ..PerpMarket::default()
+        };
+
+        let fee_override = market.get_fee_override();
+        assert_eq!(
+            fee_override,
+            MarketFeeOverride {
+                taker_fee_multiplier: None,
+                maker_rebate_numerator: Some(30),
+            }
+        );
+
+        market
+            .update_maker_rebate_override_volume(&fee_override, 100 * QUOTE_PRECISION_U64)
+            .unwrap();
+        assert_eq!(
+            { market.maker_rebate_override_volume },
+            100 * QUOTE_PRECISION_U64
+        );
+
+        // cap reached, falls back to the fee tier
+        let fee_override = market.get_fee_override();
+        assert_eq!(fee_override, MarketFeeOverride::default());
+
+        market
+            .update_maker_rebate_override_volume(&fee_override, 100 * QUOTE_PRECISION_U64)
+            .unwrap();
+        assert_eq!(
+            { market.maker_rebate_override_volume },
+            100 * QUOTE_PRECISION_U64
+        );
+
+        market.fee_override_flags |= FeeOverrideFlag::TakerFee as u8;
+        market.maker_rebate_override_volume_cap = 0;
+        assert_eq!(
+            market.get_fee_override(),
+            MarketFeeOverride {
+                taker_fee_multiplier: Some(50),
+                maker_rebate_numerator: Some(30),
+            }
+        );
+    }
+}
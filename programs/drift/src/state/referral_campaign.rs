@@ -0,0 +1,125 @@
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::constants::FEE_PERCENTAGE_DENOMINATOR;
+use crate::math::safe_math::SafeMath;
+use crate::state::traits::Size;
+use crate::validate;
+use anchor_lang::prelude::*;
+
+#[cfg(test)]
+mod tests;
+
+pub const REFERRAL_CAMPAIGN_PDA_SEED: &str = "REFERRAL_CAMPAIGN";
+/// Max referrer reward plus referee discount, over FEE_PERCENTAGE_DENOMINATOR. The rest of the fee
+/// has to cover the filler reward and maker rebate
+pub const MAX_REFERRAL_CAMPAIGN_FEE_SHARE_NUMERATOR: u32 = FEE_PERCENTAGE_DENOMINATOR / 2;
+
+/// Overrides the fee tier's referral reward and discount for the referees of `referrer` while the
+/// campaign is live and has budget left
+#[account(zero_copy(unsafe))]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct ReferralCampaign {
+    pub referrer: Pubkey,
+    /// The referrer's own referrer, receives a share of the referrer reward. Default if there is none
+    pub second_level_referrer: Pubkey,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    /// Max referrer rewards, second level rewards and referee discounts paid out by the campaign
+    pub budget: u64,
+    pub budget_used: u64,
+    /// Share of the referee's fee paid to referrers, over FEE_PERCENTAGE_DENOMINATOR
+    pub referrer_reward_numerator: u32,
+    /// Share of the referee's fee that is discounted, over FEE_PERCENTAGE_DENOMINATOR
+    pub referee_fee_numerator: u32,
+    /// Share of the referrer reward paid to the second level referrer, over FEE_PERCENTAGE_DENOMINATOR
+    pub second_level_reward_numerator: u32,
+    pub padding: [u8; 36],
+}
+
+impl Size for ReferralCampaign {
+    const SIZE: usize = 152;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub struct ReferralCampaignParams {
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub budget: u64,
+    pub referrer_reward_numerator: u32,
+    pub referee_fee_numerator: u32,
+    pub second_level_reward_numerator: u32,
+}
+
+impl ReferralCampaignParams {
+    pub fn validate(&self) -> DriftResult {
+        validate!(
+            self.start_ts < self.end_ts,
+            ErrorCode::InvalidReferralCampaign,
+            "start_ts ({}) must be before end_ts ({})",
+            self.start_ts,
+            self.end_ts
+        )?;
+
+        validate!(
+            self.referrer_reward_numerator
+                .safe_add(self.referee_fee_numerator)?
+                <= MAX_REFERRAL_CAMPAIGN_FEE_SHARE_NUMERATOR,
+            ErrorCode::InvalidReferralCampaign,
+            "referrer reward ({}) and referee discount ({}) exceed max fee share ({})",
+            self.referrer_reward_numerator,
+            self.referee_fee_numerator,
+            MAX_REFERRAL_CAMPAIGN_FEE_SHARE_NUMERATOR
+        )?;
+
+        validate!(
+            self.second_level_reward_numerator <= FEE_PERCENTAGE_DENOMINATOR,
+            ErrorCode::InvalidReferralCampaign,
+            "second level reward ({}) exceeds the referrer reward",
+            self.second_level_reward_numerator
+        )?;
+
+        Ok(())
+    }
+}
+
+impl ReferralCampaign {
+    pub fn update(&mut self, params: &ReferralCampaignParams) -> DriftResult {
+        params.validate()?;
+
+        self.start_ts = params.start_ts;
+        self.end_ts = params.end_ts;
+        self.budget = params.budget;
+        self.referrer_reward_numerator = params.referrer_reward_numerator;
+        self.referee_fee_numerator = params.referee_fee_numerator;
+        self.second_level_reward_numerator = params.second_level_reward_numerator;
+
+        Ok(())
+    }
+
+    pub fn is_active(&self, now: i64) -> bool {
+        self.start_ts <= now && now < self.end_ts && self.remaining_budget() > 0
+    }
+
+    pub fn has_ended(&self, now: i64) -> bool {
+        now >= self.end_ts
+    }
+
+    pub fn remaining_budget(&self) -> u64 {
+        self.budget.saturating_sub(self.budget_used)
+    }
+
+    pub fn has_second_level_referrer(&self) -> bool {
+        self.second_level_referrer != Pubkey::default()
+    }
+
+    /// Returns true if the amount used up the rest of the budget
+    pub fn increment_budget_used(&mut self, amount: u64) -> DriftResult<bool> {
+        if amount == 0 {
+            return Ok(false);
+        }
+
+        self.budget_used = self.budget_used.safe_add(amount)?;
+
+        Ok(self.remaining_budget() == 0)
+    }
+}
@@ -0,0 +1,95 @@
+use crate::state::referral_campaign::{ReferralCampaign, ReferralCampaignParams};
+
+#[test]
+fn validate_params() {
+    let params = ReferralCampaignParams {
+        start_ts: 0,
+        end_ts: 100,
+        budget: 1_000_000,
+        referrer_reward_numerator: 20,
+        referee_fee_numerator: 10,
+        second_level_reward_numerator: 25,
+    };
+    assert!(params.validate().is_ok());
+
+    let params = ReferralCampaignParams {
+        end_ts: 0,
+        ..params
+    };
+    assert!(params.validate().is_err());
+
+    let params = ReferralCampaignParams {
+        end_ts: 100,
+        referrer_reward_numerator: 60,
+        referee_fee_numerator: 50,
+        ..params
+    };
+    assert!(params.validate().is_err());
+
+    // the whole fee leaves nothing for the filler reward and maker rebate
+    let params = ReferralCampaignParams {
+        referrer_reward_numerator: 60,
+        referee_fee_numerator: 40,
+        ..params
+    };
+    assert!(params.validate().is_err());
+
+    let params = ReferralCampaignParams {
+        referrer_reward_numerator: 30,
+        referee_fee_numerator: 21,
+        ..params
+    };
+    assert!(params.validate().is_err());
+
+    let params = ReferralCampaignParams {
+        referrer_reward_numerator: 30,
+        referee_fee_numerator: 20,
+        ..params
+    };
+    assert!(params.validate().is_ok());
+
+    let params = ReferralCampaignParams {
+        referrer_reward_numerator: 20,
+        referee_fee_numerator: 10,
+        second_level_reward_numerator: 101,
+        ..params
+    };
+    assert!(params.validate().is_err());
+}
+
+#[test]
+fn is_active() {
+    let mut referral_campaign = ReferralCampaign {
+        start_ts: 10,
+        end_ts: 100,
+        budget: 1_000_000,
+        ..ReferralCampaign::default()
+    };
+
+    assert!(!referral_campaign.is_active(9));
+    assert!(referral_campaign.is_active(10));
+    assert!(referral_campaign.is_active(99));
+    assert!(!referral_campaign.is_active(100));
+
+    referral_campaign.budget_used = 1_000_000;
+    assert!(!referral_campaign.is_active(50));
+
+    // a spent budget can be topped up, only end_ts ends the campaign
+    assert!(!referral_campaign.has_ended(99));
+    assert!(referral_campaign.has_ended(100));
+}
+
+#[test]
+fn increment_budget_used() {
+    let mut referral_campaign = ReferralCampaign {
+        budget: 1_000_000,
+        ..ReferralCampaign::default()
+    };
+
+    assert!(!referral_campaign.increment_budget_used(0).unwrap());
+    assert!(!referral_campaign.increment_budget_used(400_000).unwrap());
+    assert_eq!(referral_campaign.remaining_budget(), 600_000);
+
+    assert!(referral_campaign.increment_budget_used(600_000).unwrap());
+    assert_eq!(referral_campaign.remaining_budget(), 0);
+}
@@ -8,14 +8,32 @@ use prelude::AccountInfo;
 
 use crate::error::{DriftResult, ErrorCode};
 use crate::math::casting::Cast;
+use crate::math::constants::{
+    FIFTY_MILLION_QUOTE, ONE_HUNDRED_MILLION_QUOTE, TEN_MILLION_QUOTE, THIRTY_DAY,
+};
+use crate::math::safe_math::SafeMath;
 use crate::math::safe_unwrap::SafeUnwrap;
+use crate::math::stats::calculate_rolling_sum;
 use crate::state::user::{MarketType, OrderStatus, User};
 use crate::validate;
 use crate::{msg, ID};
 
+#[cfg(test)]
+mod tests;
+
 pub const REVENUE_SHARE_PDA_SEED: &str = "REV_SHARE";
 pub const REVENUE_SHARE_ESCROW_PDA_SEED: &str = "REV_ESCROW";
 
+pub const BUILDER_TIER_LENGTH: usize = 4;
+/// 30d builder-attributed volume required to reach each builder tier above the base tier, used until
+/// the admin configures State.builder_tier_volume_thresholds
+pub const DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS: [u64; BUILDER_TIER_LENGTH] = [
+    TEN_MILLION_QUOTE,
+    FIFTY_MILLION_QUOTE,
+    ONE_HUNDRED_MILLION_QUOTE,
+    ONE_HUNDRED_MILLION_QUOTE * 5,
+];
+
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq, Default)]
 pub enum RevenueShareOrderBitFlag {
     #[default]
@@ -32,12 +50,57 @@ pub struct RevenueShare {
     pub authority: Pubkey,
     pub total_referrer_rewards: u64,
     pub total_builder_rewards: u64,
-    pub padding: [u8; 18],
+    /// rolling 30d volume of fills that paid this builder a fee
+    pub builder_volume_30d: u64,
+    pub last_builder_volume_30d_ts: i64,
+    pub padding: [u8; 2],
 }
 
 impl RevenueShare {
     pub fn space() -> usize {
-        8 + 32 + 8 + 8 + 18
+        8 + 32 + 8 + 8 + 8 + 8 + 2
+    }
+
+    pub fn update_builder_volume_30d(&mut self, quote_asset_amount: u64, now: i64) -> DriftResult {
+        let since_last = now.safe_sub(self.last_builder_volume_30d_ts)?.max(1);
+
+        self.builder_volume_30d = calculate_rolling_sum(
+            self.builder_volume_30d,
+            quote_asset_amount,
+            since_last,
+            THIRTY_DAY,
+        )?;
+        self.last_builder_volume_30d_ts = now;
+
+        Ok(())
+    }
+
+    /// 0 is the base tier, tier n is reached once builder_volume_30d >= builder_tier_volume_thresholds[n - 1]
+    pub fn get_builder_tier(
+        &self,
+        builder_tier_volume_thresholds: &[u64; BUILDER_TIER_LENGTH],
+    ) -> usize {
+        let builder_volume_30d = self.builder_volume_30d;
+        builder_tier_volume_thresholds
+            .iter()
+            .take_while(|threshold| builder_volume_30d >= **threshold)
+            .count()
+    }
+
+    /// Protocol rebate paid on top of the builder fee, as a percentage of the fee settled
+    pub fn calculate_builder_rebate(
+        &self,
+        fee_settled: u64,
+        builder_tier_volume_thresholds: &[u64; BUILDER_TIER_LENGTH],
+        builder_tier_rebate_pcts: &[u8; BUILDER_TIER_LENGTH],
+    ) -> DriftResult<u64> {
+        let tier = self.get_builder_tier(builder_tier_volume_thresholds);
+        if tier == 0 {
+            return Ok(0);
+        }
+
+        let rebate_pct = builder_tier_rebate_pcts[tier - 1].cast::<u64>()?;
+        fee_settled.safe_mul(rebate_pct)?.safe_div(100)
     }
 }
 
@@ -69,7 +132,9 @@ pub struct RevenueShareOrder {
     /// the index into the User's orders list when this RevenueShareOrder was created, make sure to verify that order_id matches.
     pub user_order_index: u8,
     pub market_type: MarketType,
-    pub padding: [u8; 10],
+    pub padding: [u8; 2],
+    /// quote volume of the fills that accrued `fees_accrued`, counted towards the builder's tier on settlement
+    pub volume_accrued: u64,
 }
 
 impl RevenueShareOrder {
@@ -93,7 +158,8 @@ impl RevenueShareOrder {
             bit_flags,
             sub_account_id,
             user_order_index,
-            padding: [0; 10],
+            padding: [0; 2],
+            volume_accrued: 0,
         }
     }
 
@@ -151,6 +217,10 @@ impl RevenueShareOrder {
             .fees_accrued
             .checked_add(other.fees_accrued)
             .ok_or(ErrorCode::MathError)?;
+        self.volume_accrued = self
+            .volume_accrued
+            .checked_add(other.volume_accrued)
+            .ok_or(ErrorCode::MathError)?;
         Ok(self)
     }
 }
@@ -0,0 +1,136 @@
+use crate::math::constants::{ONE_MILLION_QUOTE, TEN_MILLION_QUOTE, THIRTY_DAY};
+use crate::state::revenue_share::{
+    RevenueShare, RevenueShareOrder, BUILDER_TIER_LENGTH, DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS,
+};
+use crate::state::user::MarketType;
+
+#[test]
+fn builder_tier() {
+    let mut revenue_share = RevenueShare::default();
+    assert_eq!(
+        revenue_share.get_builder_tier(&DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS),
+        0
+    );
+
+    revenue_share.builder_volume_30d = TEN_MILLION_QUOTE - 1;
+    assert_eq!(
+        revenue_share.get_builder_tier(&DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS),
+        0
+    );
+
+    revenue_share.builder_volume_30d = TEN_MILLION_QUOTE;
+    assert_eq!(
+        revenue_share.get_builder_tier(&DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS),
+        1
+    );
+
+    revenue_share.builder_volume_30d = u64::MAX;
+    assert_eq!(
+        revenue_share.get_builder_tier(&DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS),
+        BUILDER_TIER_LENGTH
+    );
+
+    // admin configured thresholds
+    let builder_tier_volume_thresholds = [
+        ONE_MILLION_QUOTE,
+        2 * ONE_MILLION_QUOTE,
+        TEN_MILLION_QUOTE,
+        2 * TEN_MILLION_QUOTE,
+    ];
+    revenue_share.builder_volume_30d = TEN_MILLION_QUOTE;
+    assert_eq!(
+        revenue_share.get_builder_tier(&builder_tier_volume_thresholds),
+        3
+    );
+}
+
+#[test]
+fn calculate_builder_rebate() {
+    let builder_tier_rebate_pcts = [10, 20, 30, 50];
+    let fee_settled = 1_000_000;
+
+    let mut revenue_share = RevenueShare::default();
+    assert_eq!(
+        revenue_share
+            .calculate_builder_rebate(
+                fee_settled,
+                &DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS,
+                &builder_tier_rebate_pcts
+            )
+            .unwrap(),
+        0
+    );
+
+    revenue_share.builder_volume_30d = TEN_MILLION_QUOTE;
+    assert_eq!(
+        revenue_share
+            .calculate_builder_rebate(
+                fee_settled,
+                &DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS,
+                &builder_tier_rebate_pcts
+            )
+            .unwrap(),
+        100_000
+    );
+
+    revenue_share.builder_volume_30d = u64::MAX;
+    assert_eq!(
+        revenue_share
+            .calculate_builder_rebate(
+                fee_settled,
+                &DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS,
+                &builder_tier_rebate_pcts
+            )
+            .unwrap(),
+        500_000
+    );
+
+    // tiers are configured with no rebate
+    assert_eq!(
+        revenue_share
+            .calculate_builder_rebate(
+                fee_settled,
+                &DEFAULT_BUILDER_TIER_VOLUME_THRESHOLDS,
+                &[0; BUILDER_TIER_LENGTH]
+            )
+            .unwrap(),
+        0
+    );
+}
+
+#[test]
+fn update_builder_volume_30d() {
+    let mut revenue_share = RevenueShare::default();
+    let now = THIRTY_DAY * 2;
+
+    revenue_share
+        .update_builder_volume_30d(ONE_MILLION_QUOTE, now)
+        .unwrap();
+    assert_eq!({ revenue_share.builder_volume_30d }, ONE_MILLION_QUOTE);
+    assert_eq!({ revenue_share.last_builder_volume_30d_ts }, now);
+
+    // half of the window has passed, half of the previous volume rolls off
+    revenue_share
+        .update_builder_volume_30d(ONE_MILLION_QUOTE, now + THIRTY_DAY / 2)
+        .unwrap();
+    assert_eq!(
+        { revenue_share.builder_volume_30d },
+        ONE_MILLION_QUOTE * 3 / 2
+    );
+}
+
+#[test]
+fn merge_accumulates_volume() {
+    let mut order = RevenueShareOrder::new(0, 0, 1, 10, MarketType::Perp, 0, 0, 0);
+    order.fees_accrued = 100;
+    order.volume_accrued = 1_000;
+
+    let mut other = RevenueShareOrder::new(0, 0, 2, 10, MarketType::Perp, 0, 0, 0);
+    other.add_bit_flag(crate::state::revenue_share::RevenueShareOrderBitFlag::Completed);
+    other.fees_accrued = 50;
+    other.volume_accrued = 500;
+
+    let merged = order.merge(&other).unwrap();
+    assert_eq!(merged.fees_accrued, 150);
+    assert_eq!(merged.volume_accrued, 1_500);
+}
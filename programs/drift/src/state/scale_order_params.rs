@@ -1,7 +1,9 @@
 use crate::controller::position::PositionDirection;
 use crate::error::{DriftResult, ErrorCode};
-use crate::math::constants::MAX_OPEN_ORDERS;
+use crate::math::casting::Cast;
+use crate::math::constants::{MAX_OPEN_ORDERS, PERCENTAGE_PRECISION_U64};
 use crate::math::safe_math::SafeMath;
+use crate::math::safe_unwrap::SafeUnwrap;
 use crate::state::order_params::{OrderParams, PostOnlyParam};
 use crate::state::user::{MarketType, OrderTriggerCondition, OrderType};
 use crate::validate;
@@ -14,6 +16,13 @@ mod tests;
 pub const MIN_SCALE_ORDER_COUNT: u8 = 2;
 /// Maximum number of orders allowed in a single scale order instruction
 pub const MAX_SCALE_ORDER_COUNT: u8 = MAX_OPEN_ORDERS;
+/// Bounds on the exponential size ratio between consecutive orders (0.5x to 2x)
+pub const MIN_SCALE_ORDER_SIZE_RATIO: u32 = PERCENTAGE_PRECISION_U64 as u32 / 2;
+pub const MAX_SCALE_ORDER_SIZE_RATIO: u32 = PERCENTAGE_PRECISION_U64 as u32 * 2;
+/// Precision of the price ratio between consecutive geometric scale orders
+const GEOMETRIC_RATIO_PRECISION: u128 = 1_000_000_000_000;
+/// Weight of the first order for exponential size distribution
+const EXPONENTIAL_WEIGHT_PRECISION: u128 = 1_000_000_000;
 
 /// How to distribute order sizes across scale orders
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Eq, PartialEq, Debug)]
@@ -25,6 +34,20 @@ pub enum SizeDistribution {
     Ascending,
     /// Largest orders at start price, smallest at end price
     Descending,
+    /// Each order size_ratio times the size of the previous one
+    Exponential,
+    /// Sizes proportional to size_weights
+    Custom,
+}
+
+/// How to space prices across scale orders
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub enum PriceDistribution {
+    /// Equal price steps between orders
+    #[default]
+    Linear,
+    /// Equal percentage steps between orders, tighter towards the lower price
+    Geometric,
 }
 
 /// Parameters for placing scale orders - multiple limit orders distributed across a price range
@@ -51,6 +74,17 @@ pub struct ScaleOrderParams {
     pub bit_flags: u8,
     /// Maximum timestamp for orders to be valid
     pub max_ts: Option<i64>,
+    /// How to space prices between start and end price. Defaults to linear
+    pub price_distribution: Option<PriceDistribution>,
+    /// Size of each order relative to the previous one for exponential distribution (in PERCENTAGE_PRECISION)
+    pub size_ratio: Option<u32>,
+    /// Relative size of each order for custom distribution, one weight per order
+    pub size_weights: Option<Vec<u16>>,
+    /// Starting oracle price offset (in PRICE_PRECISION). Set with end_oracle_price_offset and
+    /// start_price and end_price left at 0 to place a ladder of oracle offset limit orders
+    pub start_oracle_price_offset: Option<i32>,
+    /// Ending oracle price offset (in PRICE_PRECISION)
+    pub end_oracle_price_offset: Option<i32>,
 }
 
 impl ScaleOrderParams {
@@ -70,6 +104,77 @@ impl ScaleOrderParams {
             MAX_SCALE_ORDER_COUNT
         )?;
 
+        if self.is_oracle_price_offset() {
+            self.validate_oracle_price_offsets()?;
+        } else {
+            self.validate_prices()?;
+        }
+
+        // Validate that total size can be distributed among all orders meeting minimum step size
+        let min_total_size = order_step_size.safe_mul(self.order_count as u64)?;
+        validate!(
+            self.total_base_asset_amount >= min_total_size,
+            ErrorCode::OrderAmountTooSmall,
+            "total_base_asset_amount must be at least {} (order_step_size * order_count)",
+            min_total_size
+        )?;
+
+        match self.size_distribution {
+            SizeDistribution::Exponential => {
+                validate!(
+                    self.size_ratio.map_or(false, |size_ratio| {
+                        (MIN_SCALE_ORDER_SIZE_RATIO..=MAX_SCALE_ORDER_SIZE_RATIO)
+                            .contains(&size_ratio)
+                    }),
+                    ErrorCode::InvalidOrderScaleSizeDistribution,
+                    "size_ratio must be between {} and {}",
+                    MIN_SCALE_ORDER_SIZE_RATIO,
+                    MAX_SCALE_ORDER_SIZE_RATIO
+                )?;
+            }
+            SizeDistribution::Custom => {
+                let size_weights = self.get_size_weights();
+                validate!(
+                    size_weights.len() == self.order_count as usize,
+                    ErrorCode::InvalidOrderScaleSizeDistribution,
+                    "size_weights must have one weight per order"
+                )?;
+
+                validate!(
+                    size_weights.iter().all(|weight| *weight > 0),
+                    ErrorCode::InvalidOrderScaleSizeDistribution,
+                    "size_weights must be greater than 0"
+                )?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn is_oracle_price_offset(&self) -> bool {
+        self.start_oracle_price_offset.is_some() || self.end_oracle_price_offset.is_some()
+    }
+
+    pub fn get_price_distribution(&self) -> PriceDistribution {
+        self.price_distribution.unwrap_or_default()
+    }
+
+    fn get_size_weights(&self) -> &[u16] {
+        self.size_weights.as_deref().unwrap_or_default()
+    }
+
+    fn get_oracle_price_offsets(&self) -> DriftResult<(i32, i32)> {
+        match (self.start_oracle_price_offset, self.end_oracle_price_offset) {
+            (Some(start_offset), Some(end_offset)) => Ok((start_offset, end_offset)),
+            _ => {
+                msg!("start_oracle_price_offset and end_oracle_price_offset must both be set");
+                Err(ErrorCode::InvalidOrderScalePriceRange)
+            }
+        }
+    }
+
+    fn validate_prices(&self) -> DriftResult<()> {
         validate!(
             self.start_price != self.end_price,
             ErrorCode::InvalidOrderScalePriceRange,
@@ -95,19 +200,92 @@ impl ScaleOrderParams {
             }
         }
 
-        // Validate that total size can be distributed among all orders meeting minimum step size
-        let min_total_size = order_step_size.safe_mul(self.order_count as u64)?;
+        if self.get_price_distribution() == PriceDistribution::Geometric {
+            validate!(
+                self.start_price.min(self.end_price) > 0,
+                ErrorCode::InvalidOrderScalePriceRange,
+                "geometric scale orders need positive prices"
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_oracle_price_offsets(&self) -> DriftResult<()> {
+        let (start_offset, end_offset) = self.get_oracle_price_offsets()?;
+
+        // like an oracle offset limit order, the ladder leaves its prices at 0
         validate!(
-            self.total_base_asset_amount >= min_total_size,
-            ErrorCode::OrderAmountTooSmall,
-            "total_base_asset_amount must be at least {} (order_step_size * order_count)",
-            min_total_size
+            self.start_price == 0 && self.end_price == 0,
+            ErrorCode::InvalidOrderScalePriceRange,
+            "oracle offset scale orders must have start_price and end_price of 0"
+        )?;
+
+        validate!(
+            start_offset != end_offset,
+            ErrorCode::InvalidOrderScalePriceRange,
+            "start_oracle_price_offset and end_oracle_price_offset cannot be equal"
+        )?;
+
+        // Same as prices, longs start closest to the oracle and scale down, shorts scale up
+        match self.direction {
+            PositionDirection::Long => {
+                validate!(
+                    start_offset > end_offset,
+                    ErrorCode::InvalidOrderScalePriceRange,
+                    "for long scale orders, start_oracle_price_offset must be greater than end_oracle_price_offset"
+                )?;
+            }
+            PositionDirection::Short => {
+                validate!(
+                    start_offset < end_offset,
+                    ErrorCode::InvalidOrderScalePriceRange,
+                    "for short scale orders, start_oracle_price_offset must be less than end_oracle_price_offset"
+                )?;
+            }
+        }
+
+        validate!(
+            self.get_price_distribution() == PriceDistribution::Linear,
+            ErrorCode::InvalidOrderScalePriceRange,
+            "oracle offset scale orders must be linearly spaced"
         )?;
 
         Ok(())
     }
 
-    /// Calculate evenly distributed prices between start and end price
+    /// Calculate evenly distributed oracle price offsets between the start and end offset
+    pub fn calculate_oracle_price_offset_distribution(&self) -> DriftResult<Vec<i32>> {
+        let order_count = self.order_count as usize;
+        let (start_offset, end_offset) = self.get_oracle_price_offsets()?;
+
+        if order_count == 1 {
+            return Ok(vec![start_offset]);
+        }
+
+        let start_offset = start_offset as i64;
+        let end_offset = end_offset as i64;
+
+        let offset_step = end_offset
+            .safe_sub(start_offset)?
+            .safe_div((order_count - 1) as i64)?;
+
+        let mut offsets = Vec::with_capacity(order_count);
+        for i in 0..order_count {
+            // Use exact end offset for the last order to avoid rounding errors
+            let offset = if i == order_count - 1 {
+                end_offset
+            } else {
+                start_offset.safe_add(offset_step.safe_mul(i as i64)?)?
+            };
+            offsets.push(offset.cast::<i32>()?);
+        }
+
+        Ok(offsets)
+    }
+
+    /// Calculate prices between start and end price. Prices are standardized to the tick size
+    /// when the orders are placed
     pub fn calculate_price_distribution(&self) -> DriftResult<Vec<u64>> {
         let order_count = self.order_count as usize;
 
@@ -119,6 +297,16 @@ impl ScaleOrderParams {
             return Ok(vec![self.start_price, self.end_price]);
         }
 
+        match self.get_price_distribution() {
+            PriceDistribution::Linear => self.calculate_linear_prices(),
+            PriceDistribution::Geometric => self.calculate_geometric_prices(),
+        }
+    }
+
+    /// Calculate evenly distributed prices between start and end price
+    fn calculate_linear_prices(&self) -> DriftResult<Vec<u64>> {
+        let order_count = self.order_count as usize;
+
         let (min_price, max_price) = if self.start_price < self.end_price {
             (self.start_price, self.end_price)
         } else {
@@ -145,15 +333,130 @@ impl ScaleOrderParams {
         Ok(prices)
     }
 
+    /// Calculate prices with a constant ratio between neighbouring orders. The ratio is the
+    /// (order_count - 1)th root of max_price / min_price, found by binary search
+    fn calculate_geometric_prices(&self) -> DriftResult<Vec<u64>> {
+        let order_count = self.order_count as usize;
+        let num_steps = order_count - 1;
+
+        let (min_price, max_price) = if self.start_price < self.end_price {
+            (self.start_price, self.end_price)
+        } else {
+            (self.end_price, self.start_price)
+        };
+
+        // smallest ratio that gets from min_price to max_price in num_steps
+        let mut low = GEOMETRIC_RATIO_PRECISION;
+        let mut high = GEOMETRIC_RATIO_PRECISION
+            .safe_mul(max_price.cast()?)?
+            .safe_div(min_price.cast()?)?
+            .safe_add(1)?;
+        while low < high {
+            let mid = low.safe_add(high.safe_sub(low)?.safe_div(2)?)?;
+            if apply_geometric_ratio(min_price, mid, num_steps)? >= max_price {
+                high = mid;
+            } else {
+                low = mid.safe_add(1)?;
+            }
+        }
+
+        let mut prices = Vec::with_capacity(order_count);
+        for i in 0..order_count {
+            // Use exact max_price for the last order to avoid rounding errors
+            let price = if i == num_steps {
+                max_price
+            } else {
+                apply_geometric_ratio(min_price, low, i)?
+            };
+            prices.push(price);
+        }
+
+        if self.start_price > self.end_price {
+            prices.reverse();
+        }
+
+        Ok(prices)
+    }
+
     /// Calculate order sizes based on size distribution strategy
     pub fn calculate_size_distribution(&self, order_step_size: u64) -> DriftResult<Vec<u64>> {
         match self.size_distribution {
             SizeDistribution::Flat => self.calculate_flat_sizes(order_step_size),
             SizeDistribution::Ascending => self.calculate_scaled_sizes(order_step_size, false),
             SizeDistribution::Descending => self.calculate_scaled_sizes(order_step_size, true),
+            SizeDistribution::Exponential => {
+                let size_ratio = self.size_ratio.safe_unwrap()?;
+                let mut weights = Vec::with_capacity(self.order_count as usize);
+                let mut weight = EXPONENTIAL_WEIGHT_PRECISION;
+                for _ in 0..self.order_count {
+                    weights.push(weight);
+                    weight = weight
+                        .safe_mul(size_ratio.cast()?)?
+                        .safe_div(PERCENTAGE_PRECISION_U64.cast()?)?;
+                }
+                self.calculate_weighted_sizes(order_step_size, &weights)
+            }
+            SizeDistribution::Custom => {
+                let weights = self
+                    .get_size_weights()
+                    .iter()
+                    .map(|weight| *weight as u128)
+                    .collect::<Vec<u128>>();
+                self.calculate_weighted_sizes(order_step_size, &weights)
+            }
         }
     }
 
+    /// Calculate sizes proportional to weights. Sizes are rounded down to the step size (at least
+    /// one step) and the rounding is settled on the last order, or the largest if sizes overshoot
+    fn calculate_weighted_sizes(
+        &self,
+        order_step_size: u64,
+        weights: &[u128],
+    ) -> DriftResult<Vec<u64>> {
+        let weight_sum = weights
+            .iter()
+            .try_fold(0_u128, |sum, weight| sum.safe_add(*weight))?;
+
+        validate!(
+            weight_sum > 0,
+            ErrorCode::InvalidOrderScaleSizeDistribution,
+            "size weights sum to 0"
+        )?;
+
+        let mut sizes = Vec::with_capacity(weights.len());
+        let mut total = 0u64;
+
+        for weight in weights.iter() {
+            let raw_size = self
+                .total_base_asset_amount
+                .cast::<u128>()?
+                .safe_mul(*weight)?
+                .safe_div(weight_sum)?
+                .cast::<u64>()?;
+            let rounded_size = raw_size
+                .safe_div(order_step_size)?
+                .safe_mul(order_step_size)?
+                .max(order_step_size);
+            sizes.push(rounded_size);
+            total = total.safe_add(rounded_size)?;
+        }
+
+        if total < self.total_base_asset_amount {
+            let diff = self.total_base_asset_amount.safe_sub(total)?;
+            if let Some(last) = sizes.last_mut() {
+                *last = last.safe_add(diff)?;
+            }
+        } else if total > self.total_base_asset_amount {
+            let diff = total.safe_sub(self.total_base_asset_amount)?;
+            if let Some(largest) = sizes.iter_mut().max() {
+                *largest = largest.safe_sub(diff)?;
+            }
+        }
+
+        Ok(sizes)
+    }
+
     /// Calculate flat (equal) distribution of sizes
     fn calculate_flat_sizes(&self, order_step_size: u64) -> DriftResult<Vec<u64>> {
         let order_count = self.order_count as u64;
@@ -238,12 +541,28 @@ impl ScaleOrderParams {
     pub fn expand_to_order_params(&self, order_step_size: u64) -> DriftResult<Vec<OrderParams>> {
         self.validate(order_step_size)?;
 
-        let prices = self.calculate_price_distribution()?;
+        // oracle offset ladders place 0 price limit orders that follow the oracle
+        let (prices, oracle_price_offsets) = if self.is_oracle_price_offset() {
+            let offsets = self.calculate_oracle_price_offset_distribution()?;
+            (
+                vec![0; offsets.len()],
+                offsets.into_iter().map(Some).collect::<Vec<_>>(),
+            )
+        } else {
+            let prices = self.calculate_price_distribution()?;
+            let offsets = vec![None; prices.len()];
+            (prices, offsets)
+        };
         let sizes = self.calculate_size_distribution(order_step_size)?;
 
         let mut order_params = Vec::with_capacity(self.order_count as usize);
 
-        for (i, (price, size)) in prices.iter().zip(sizes.iter()).enumerate() {
+        for (i, ((price, size), oracle_price_offset)) in prices
+            .iter()
+            .zip(sizes.iter())
+            .zip(oracle_price_offsets.iter())
+            .enumerate()
+        {
             order_params.push(OrderParams {
                 order_type: OrderType::Limit,
                 market_type: self.market_type,
@@ -258,13 +577,34 @@ impl ScaleOrderParams {
                 max_ts: self.max_ts,
                 trigger_price: None,
                 trigger_condition: OrderTriggerCondition::Above,
-                oracle_price_offset: None,
+                oracle_price_offset: *oracle_price_offset,
                 auction_duration: None,
                 auction_start_price: None,
                 auction_end_price: None,
+                trailing_stop_distance: None,
+                twap_slice_interval: None,
+                iceberg_clip_size: None,
+                trigger_source: None,
             });
         }
 
         Ok(order_params)
     }
 }
+
+/// price * (ratio / GEOMETRIC_RATIO_PRECISION)^steps, saturating at u64::MAX
+fn apply_geometric_ratio(price: u64, ratio: u128, steps: usize) -> DriftResult<u64> {
+    let mut result = price.cast::<u128>()?;
+    for _ in 0..steps {
+        result = match result.checked_mul(ratio) {
+            Some(product) => product.safe_div(GEOMETRIC_RATIO_PRECISION)?,
+            None => return Ok(u64::MAX),
+        };
+
+        if result > u64::MAX as u128 {
+            return Ok(u64::MAX);
+        }
+    }
+
+    result.cast()
+}
@@ -1,6 +1,6 @@
 use crate::state::order_params::PostOnlyParam;
-use crate::state::scale_order_params::{ScaleOrderParams, SizeDistribution};
-use crate::state::user::MarketType;
+use crate::state::scale_order_params::{PriceDistribution, ScaleOrderParams, SizeDistribution};
+use crate::state::user::{MarketType, OrderType};
 use crate::{PositionDirection, BASE_PRECISION_U64, PRICE_PRECISION_U64};
 
 #[test]
@@ -22,6 +22,11 @@ fn test_validate_order_count_bounds() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
     assert!(params.validate(step_size).is_err());
 
@@ -58,6 +63,11 @@ fn test_validate_price_range() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
     assert!(params.validate(step_size).is_err());
 
@@ -105,6 +115,11 @@ fn test_price_distribution_long() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let prices = params.calculate_price_distribution().unwrap();
@@ -132,6 +147,11 @@ fn test_price_distribution_short() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let prices = params.calculate_price_distribution().unwrap();
@@ -161,6 +181,11 @@ fn test_flat_size_distribution() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let sizes = params.calculate_size_distribution(step_size).unwrap();
@@ -204,6 +229,11 @@ fn test_ascending_size_distribution() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let sizes = params.calculate_size_distribution(step_size).unwrap();
@@ -261,6 +291,11 @@ fn test_descending_size_distribution() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let sizes = params.calculate_size_distribution(step_size).unwrap();
@@ -318,6 +353,11 @@ fn test_ascending_size_distribution_3_orders() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let sizes = params.calculate_size_distribution(step_size).unwrap();
@@ -365,6 +405,11 @@ fn test_flat_distribution_with_remainder() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let sizes = params.calculate_size_distribution(step_size).unwrap();
@@ -403,6 +448,11 @@ fn test_expand_to_order_params_perp() {
         post_only: PostOnlyParam::MustPostOnly,
         bit_flags: 2, // High leverage mode
         max_ts: Some(12345),
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let order_params = params.expand_to_order_params(step_size).unwrap();
@@ -452,6 +502,11 @@ fn test_expand_to_order_params_spot() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let order_params = params.expand_to_order_params(step_size).unwrap();
@@ -492,6 +547,11 @@ fn test_spot_short_scale_orders() {
         post_only: PostOnlyParam::MustPostOnly,
         bit_flags: 0,
         max_ts: Some(99999),
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let order_params = params.expand_to_order_params(step_size).unwrap();
@@ -535,6 +595,11 @@ fn test_two_orders_price_distribution() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     let prices = params.calculate_price_distribution().unwrap();
@@ -562,7 +627,241 @@ fn test_validate_min_total_size() {
         post_only: PostOnlyParam::None,
         bit_flags: 0,
         max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
     };
 
     assert!(params.validate(step_size).is_err());
 }
+
+#[test]
+fn test_geometric_price_distribution() {
+    // Short: start low, end high, each price 10% above the previous one
+    let params = ScaleOrderParams {
+        market_type: MarketType::Perp,
+        direction: PositionDirection::Short,
+        market_index: 0,
+        total_base_asset_amount: BASE_PRECISION_U64,
+        start_price: 100 * PRICE_PRECISION_U64,
+        end_price: 133_100_000,
+        order_count: 4,
+        size_distribution: SizeDistribution::Flat,
+        reduce_only: false,
+        post_only: PostOnlyParam::None,
+        bit_flags: 0,
+        max_ts: None,
+        price_distribution: Some(PriceDistribution::Geometric),
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
+    };
+
+    let prices = params.calculate_price_distribution().unwrap();
+    assert_eq!(
+        prices,
+        vec![100_000_000, 110_000_000, 121_000_000, 133_100_000]
+    );
+
+    // Long: same prices from the top down
+    let params = ScaleOrderParams {
+        direction: PositionDirection::Long,
+        start_price: 133_100_000,
+        end_price: 100 * PRICE_PRECISION_U64,
+        ..params
+    };
+
+    let prices = params.calculate_price_distribution().unwrap();
+    assert_eq!(
+        prices,
+        vec![133_100_000, 121_000_000, 110_000_000, 100_000_000]
+    );
+}
+
+#[test]
+fn test_exponential_size_distribution() {
+    let step_size = BASE_PRECISION_U64 / 1000;
+
+    // each order twice the size of the previous one
+    let params = ScaleOrderParams {
+        market_type: MarketType::Perp,
+        direction: PositionDirection::Long,
+        market_index: 0,
+        total_base_asset_amount: 15 * BASE_PRECISION_U64,
+        start_price: 110 * PRICE_PRECISION_U64,
+        end_price: 100 * PRICE_PRECISION_U64,
+        order_count: 4,
+        size_distribution: SizeDistribution::Exponential,
+        reduce_only: false,
+        post_only: PostOnlyParam::None,
+        bit_flags: 0,
+        max_ts: None,
+        price_distribution: None,
+        size_ratio: Some(2_000_000),
+        size_weights: None,
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
+    };
+
+    params.validate(step_size).unwrap();
+    let sizes = params.calculate_size_distribution(step_size).unwrap();
+    assert_eq!(
+        sizes,
+        vec![
+            BASE_PRECISION_U64,
+            2 * BASE_PRECISION_U64,
+            4 * BASE_PRECISION_U64,
+            8 * BASE_PRECISION_U64
+        ]
+    );
+
+    let params = ScaleOrderParams {
+        size_ratio: Some(3_000_000),
+        ..params
+    };
+    assert!(params.validate(step_size).is_err());
+}
+
+#[test]
+fn test_custom_size_distribution() {
+    let step_size = BASE_PRECISION_U64 / 10;
+
+    let params = ScaleOrderParams {
+        market_type: MarketType::Spot,
+        direction: PositionDirection::Short,
+        market_index: 1,
+        total_base_asset_amount: 10 * BASE_PRECISION_U64,
+        start_price: 100 * PRICE_PRECISION_U64,
+        end_price: 110 * PRICE_PRECISION_U64,
+        order_count: 3,
+        size_distribution: SizeDistribution::Custom,
+        reduce_only: false,
+        post_only: PostOnlyParam::None,
+        bit_flags: 0,
+        max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: Some(vec![1, 1, 1]),
+        start_oracle_price_offset: None,
+        end_oracle_price_offset: None,
+    };
+
+    params.validate(step_size).unwrap();
+    let sizes = params.calculate_size_distribution(step_size).unwrap();
+    // 3.333 rounds down to 3.3, the remainder goes to the last order
+    assert_eq!(sizes, vec![3_300_000_000, 3_300_000_000, 3_400_000_000]);
+    assert_eq!(sizes.iter().sum::<u64>(), 10 * BASE_PRECISION_U64);
+
+    let params = ScaleOrderParams {
+        size_weights: Some(vec![5, 3, 2]),
+        ..params
+    };
+    let sizes = params.calculate_size_distribution(step_size).unwrap();
+    assert_eq!(
+        sizes,
+        vec![
+            5 * BASE_PRECISION_U64,
+            3 * BASE_PRECISION_U64,
+            2 * BASE_PRECISION_U64
+        ]
+    );
+
+    // one weight per order, none zero
+    let params = ScaleOrderParams {
+        size_weights: Some(vec![1, 2]),
+        ..params
+    };
+    assert!(params.validate(step_size).is_err());
+
+    let params = ScaleOrderParams {
+        size_weights: Some(vec![1, 0, 2]),
+        ..params
+    };
+    assert!(params.validate(step_size).is_err());
+}
+
+#[test]
+fn test_oracle_price_offset_scale_orders() {
+    let step_size = BASE_PRECISION_U64 / 1000;
+
+    // Long: bids from $0.10 to $0.50 below the oracle
+    let params = ScaleOrderParams {
+        market_type: MarketType::Perp,
+        direction: PositionDirection::Long,
+        market_index: 0,
+        total_base_asset_amount: 5 * BASE_PRECISION_U64,
+        start_price: 0,
+        end_price: 0,
+        order_count: 5,
+        size_distribution: SizeDistribution::Flat,
+        reduce_only: false,
+        post_only: PostOnlyParam::MustPostOnly,
+        bit_flags: 0,
+        max_ts: None,
+        price_distribution: None,
+        size_ratio: None,
+        size_weights: None,
+        start_oracle_price_offset: Some(-(PRICE_PRECISION_U64 as i32) / 10),
+        end_oracle_price_offset: Some(-(PRICE_PRECISION_U64 as i32) / 2),
+    };
+
+    assert!(params.is_oracle_price_offset());
+
+    let order_params = params.expand_to_order_params(step_size).unwrap();
+    assert_eq!(order_params.len(), 5);
+
+    let offsets: Vec<i32> = order_params
+        .iter()
+        .map(|op| op.oracle_price_offset.unwrap())
+        .collect();
+    assert_eq!(
+        offsets,
+        vec![-100_000, -200_000, -300_000, -400_000, -500_000]
+    );
+
+    for op in order_params.iter() {
+        assert_eq!(op.order_type, OrderType::Limit);
+        assert_eq!(op.price, 0);
+        assert_eq!(op.base_asset_amount, BASE_PRECISION_U64);
+    }
+
+    // Long offsets must scale down
+    let params = ScaleOrderParams {
+        start_oracle_price_offset: Some(-500_000),
+        end_oracle_price_offset: Some(-100_000),
+        ..params
+    };
+    assert!(params.validate(step_size).is_err());
+
+    // Short offsets must scale up and can't be geometric
+    let params = ScaleOrderParams {
+        direction: PositionDirection::Short,
+        ..params
+    };
+    assert!(params.validate(step_size).is_ok());
+
+    let params = ScaleOrderParams {
+        price_distribution: Some(PriceDistribution::Geometric),
+        ..params
+    };
+    assert!(params.validate(step_size).is_err());
+
+    // both offsets are needed and prices stay at 0
+    let params = ScaleOrderParams {
+        price_distribution: None,
+        end_oracle_price_offset: None,
+        ..params
+    };
+    assert!(params.validate(step_size).is_err());
+
+    let params = ScaleOrderParams {
+        start_price: 100 * PRICE_PRECISION_U64,
+        end_price: 110 * PRICE_PRECISION_U64,
+        end_oracle_price_offset: Some(-100_000),
+        ..params
+    };
+    assert!(params.validate(step_size).is_err());
+}
@@ -1,6 +1,7 @@
 use std::cell::{Ref, RefMut};
 
 use crate::error::{DriftResult, ErrorCode};
+use crate::math::safe_math::SafeMath;
 use crate::math::safe_unwrap::SafeUnwrap;
 use crate::msg;
 use crate::{validate, ID};
@@ -14,7 +15,11 @@ use crate::state::traits::Size;
 
 pub const SIGNED_MSG_PDA_SEED: &str = "SIGNED_MSG";
 pub const SIGNED_MSG_WS_PDA_SEED: &str = "SIGNED_MSG_WS";
+pub const SIGNED_MSG_EVM_SIGNER_PDA_SEED: &str = "SIGNED_MSG_EVM_SIGNER";
 pub const SIGNED_MSG_SLOT_EVICTION_BUFFER: u64 = 10;
+/// Signed msg orders can't be placed more than this many slots after the slot they were signed at,
+/// so cancels only need to be kept for that long
+pub const SIGNED_MSG_ORDER_SLOT_VALIDITY: u64 = 500;
 
 mod tests;
 
@@ -36,6 +41,12 @@ impl SignedMsgOrderId {
             padding: 0,
         }
     }
+
+    /// Cancels every order signed before max_slot - SIGNED_MSG_ORDER_SLOT_VALIDITY. Uses the empty
+    /// uuid, which placed orders can't have
+    pub fn is_cancel_before_slot(&self) -> bool {
+        self.uuid == [0; 8] && self.max_slot != 0
+    }
 }
 
 impl Size for SignedMsgUserOrders {
@@ -157,6 +168,65 @@ impl<'a> SignedMsgUserOrdersZeroCopyMut<'a> {
             return Err(ErrorCode::InvalidSignedMsgOrderId.into());
         }
 
+        self.insert_signed_msg_order_id(signed_msg_order_id)
+    }
+
+    /// Tombstones the uuid until max_slot so the order can't be placed. Returns the order id if the
+    /// order was already placed
+    pub fn cancel_signed_msg_order_id(
+        &mut self,
+        uuid: [u8; 8],
+        max_slot: u64,
+    ) -> DriftResult<Option<u32>> {
+        if uuid == [0; 8] || max_slot == 0 {
+            return Err(ErrorCode::InvalidSignedMsgOrderId.into());
+        }
+
+        for i in 0..self.len() {
+            let existing_signed_msg_order_id = self.get_mut(i);
+            if existing_signed_msg_order_id.uuid == uuid {
+                let order_id = existing_signed_msg_order_id.order_id;
+                existing_signed_msg_order_id.order_id = 0;
+                existing_signed_msg_order_id.max_slot =
+                    existing_signed_msg_order_id.max_slot.max(max_slot);
+                return Ok((order_id != 0).then_some(order_id));
+            }
+        }
+
+        self.insert_signed_msg_order_id(SignedMsgOrderId::new(uuid, max_slot, 0))?;
+
+        Ok(None)
+    }
+
+    /// Stops orders signed before cancel_before_slot from being placed
+    pub fn cancel_signed_msg_orders_before_slot(&mut self, cancel_before_slot: u64) -> DriftResult {
+        let max_slot = cancel_before_slot.safe_add(SIGNED_MSG_ORDER_SLOT_VALIDITY)?;
+
+        for i in 0..self.len() {
+            let existing_signed_msg_order_id = self.get_mut(i);
+            if existing_signed_msg_order_id.is_cancel_before_slot() {
+                existing_signed_msg_order_id.max_slot =
+                    existing_signed_msg_order_id.max_slot.max(max_slot);
+                return Ok(());
+            }
+        }
+
+        self.insert_signed_msg_order_id(SignedMsgOrderId::new([0; 8], max_slot, 0))
+    }
+
+    pub fn is_signed_msg_order_slot_cancelled(&mut self, order_slot: u64) -> bool {
+        for i in 0..self.len() {
+            let existing_signed_msg_order_id = self.get_mut(i);
+            if existing_signed_msg_order_id.is_cancel_before_slot() {
+                return order_slot.saturating_add(SIGNED_MSG_ORDER_SLOT_VALIDITY)
+                    < existing_signed_msg_order_id.max_slot;
+            }
+        }
+
+        false
+    }
+
+    fn insert_signed_msg_order_id(&mut self, signed_msg_order_id: SignedMsgOrderId) -> DriftResult {
         for i in 0..self.len() {
             if self.get_mut(i).max_slot == 0 {
                 *self.get_mut(i) = signed_msg_order_id;
@@ -252,3 +322,18 @@ impl SignedMsgWsDelegates {
         8 + 4 + delegate_count * 32
     }
 }
+
+/**
+ * Links an Ethereum address to an authority so signed msg orders can be signed by an EVM wallet
+ * (EIP-191 or EIP-712) and verified with the secp256k1 program
+ */
+#[account]
+#[derive(Default, Eq, PartialEq, Debug)]
+pub struct SignedMsgEvmSigner {
+    pub authority: Pubkey,
+    pub evm_address: [u8; 20],
+}
+
+impl Size for SignedMsgEvmSigner {
+    const SIZE: usize = 8 + 32 + 20;
+}
@@ -137,6 +137,120 @@ mod signed_msg_order_id_eviction {
     }
 }
 
+#[cfg(test)]
+mod signed_msg_order_id_cancel {
+    use std::cell::RefCell;
+
+    use anchor_lang::prelude::Pubkey;
+
+    use crate::state::signed_msg_user::{
+        SignedMsgOrderId, SignedMsgUserOrdersFixed, SignedMsgUserOrdersZeroCopyMut,
+        SIGNED_MSG_ORDER_SLOT_VALIDITY,
+    };
+
+    #[test]
+    fn cancel_signed_msg_order_id() {
+        let fixed = RefCell::new(SignedMsgUserOrdersFixed {
+            user_pubkey: Pubkey::default(),
+            padding: 0,
+            len: 32,
+        });
+        let data = RefCell::new([0u8; 768]);
+        let mut signed_msg_user = SignedMsgUserOrdersZeroCopyMut {
+            fixed: fixed.borrow_mut(),
+            data: data.borrow_mut(),
+        };
+
+        let placed_signed_msg_order_id = SignedMsgOrderId::new([7; 8], 10, 2);
+        signed_msg_user
+            .add_signed_msg_order_id(placed_signed_msg_order_id)
+            .unwrap();
+
+        // placed order returns its order id and is tombstoned
+        assert_eq!(
+            signed_msg_user.cancel_signed_msg_order_id([7; 8], 600),
+            Ok(Some(2))
+        );
+        assert_eq!(
+            *signed_msg_user.get_mut(0),
+            SignedMsgOrderId::new([7; 8], 600, 0)
+        );
+
+        // cancelling again doesn't return the order id
+        assert_eq!(
+            signed_msg_user.cancel_signed_msg_order_id([7; 8], 600),
+            Ok(None)
+        );
+
+        // unplaced order can't be placed after it's cancelled
+        assert_eq!(
+            signed_msg_user.cancel_signed_msg_order_id([8; 8], 600),
+            Ok(None)
+        );
+        assert!(
+            signed_msg_user.check_exists_and_prune_stale_signed_msg_order_ids(
+                SignedMsgOrderId::new([8; 8], 20, 0),
+                20
+            )
+        );
+
+        // tombstone is pruned once no order signed before the cancel can be placed
+        assert!(
+            !signed_msg_user.check_exists_and_prune_stale_signed_msg_order_ids(
+                SignedMsgOrderId::new([8; 8], 700, 0),
+                700
+            )
+        );
+    }
+
+    #[test]
+    fn cancel_signed_msg_orders_before_slot() {
+        let fixed = RefCell::new(SignedMsgUserOrdersFixed {
+            user_pubkey: Pubkey::default(),
+            padding: 0,
+            len: 32,
+        });
+        let data = RefCell::new([0u8; 768]);
+        let mut signed_msg_user = SignedMsgUserOrdersZeroCopyMut {
+            fixed: fixed.borrow_mut(),
+            data: data.borrow_mut(),
+        };
+
+        assert!(!signed_msg_user.is_signed_msg_order_slot_cancelled(0));
+
+        signed_msg_user
+            .cancel_signed_msg_orders_before_slot(100)
+            .unwrap();
+        assert!(signed_msg_user.is_signed_msg_order_slot_cancelled(99));
+        assert!(!signed_msg_user.is_signed_msg_order_slot_cancelled(100));
+
+        // an earlier cancel doesn't lower the slot
+        signed_msg_user
+            .cancel_signed_msg_orders_before_slot(50)
+            .unwrap();
+        assert!(signed_msg_user.is_signed_msg_order_slot_cancelled(99));
+
+        signed_msg_user
+            .cancel_signed_msg_orders_before_slot(200)
+            .unwrap();
+        assert!(signed_msg_user.is_signed_msg_order_slot_cancelled(199));
+
+        let num_cancel_before_slot_entries = (0..32)
+            .filter(|i| signed_msg_user.get_mut(*i).is_cancel_before_slot())
+            .count();
+        assert_eq!(num_cancel_before_slot_entries, 1);
+
+        // doesn't match orders and is pruned with them
+        assert!(
+            !signed_msg_user.check_exists_and_prune_stale_signed_msg_order_ids(
+                SignedMsgOrderId::new([7; 8], 300, 0),
+                200 + SIGNED_MSG_ORDER_SLOT_VALIDITY + 11
+            )
+        );
+        assert!(!signed_msg_user.is_signed_msg_order_slot_cancelled(0));
+    }
+}
+
 #[cfg(test)]
 mod zero_copy {
     use crate::test_utils::create_account_info;
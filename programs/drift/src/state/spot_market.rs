@@ -25,6 +25,7 @@ use crate::math::stats::calculate_new_twap;
 use crate::state::oracle::{HistoricalIndexData, HistoricalOracleData, OracleSource};
 use crate::state::paused_operations::{InsuranceFundOperation, SpotOperation};
 use crate::state::perp_market::{MarketStatus, PoolBalance};
+use crate::state::state::{FeeOverrideFlag, MarketFeeOverride};
 use crate::state::traits::{MarketIndexOffset, Size};
 use crate::validate;
 
@@ -207,7 +208,26 @@ pub struct SpotMarket {
     pub fuel_boost_insurance: u8,
     pub token_program_flag: u8,
     pub pool_id: u8,
-    pub padding: [u8; 40],
+    /// Maker volume the maker rebate override applies to, 0 for no cap
+    /// precision: QUOTE_PRECISION
+    pub maker_rebate_override_volume_cap: u64,
+    /// Maker volume that has received the maker rebate override
+    /// precision: QUOTE_PRECISION
+    pub maker_rebate_override_volume: u64,
+    /// Scales the fee tier's taker fee when the TakerFee override flag is set, so volume tiers still
+    /// apply. 100 keeps the tier's fee
+    /// precision: FEE_PERCENTAGE_DENOMINATOR
+    pub taker_fee_override_multiplier: u16,
+    /// Replaces the fee tier's maker rebate when the MakerRebate override flag is set
+    /// precision: FEE_DENOMINATOR
+    pub maker_rebate_override_numerator: u16,
+    pub fee_override_flags: u8,
+    pub padding1: u8,
+    /// Price move portfolio margin scenarios shock the underlying by, 0 falls back to the larger of
+    /// the spot haircut and the perp margin ratio
+    /// precision: MARGIN_PRECISION
+    pub portfolio_margin_price_shock: u16,
+    pub padding: [u8; 16],
 }
 
 impl Default for SpotMarket {
@@ -276,7 +296,14 @@ impl Default for SpotMarket {
             fuel_boost_insurance: 0,
             token_program_flag: 0,
             pool_id: 0,
-            padding: [0; 40],
+            maker_rebate_override_volume_cap: 0,
+            maker_rebate_override_volume: 0,
+            taker_fee_override_multiplier: 0,
+            maker_rebate_override_numerator: 0,
+            fee_override_flags: 0,
+            padding1: 0,
+            portfolio_margin_price_shock: 0,
+            padding: [0; 16],
         }
     }
 }
@@ -307,6 +334,38 @@ impl SpotMarket {
         self.status == MarketStatus::ReduceOnly
     }
 
+    pub fn has_fee_override_flag(&self, flag: FeeOverrideFlag) -> bool {
+        self.fee_override_flags & flag as u8 != 0
+    }
+
+    pub fn get_fee_override(&self) -> MarketFeeOverride {
+        let maker_rebate_override_active = self.has_fee_override_flag(FeeOverrideFlag::MakerRebate)
+            && (self.maker_rebate_override_volume_cap == 0
+                || self.maker_rebate_override_volume < self.maker_rebate_override_volume_cap);
+
+        MarketFeeOverride {
+            taker_fee_multiplier: self
+                .has_fee_override_flag(FeeOverrideFlag::TakerFee)
+                .then(|| self.taker_fee_override_multiplier as u32),
+            maker_rebate_numerator: maker_rebate_override_active
+                .then(|| self.maker_rebate_override_numerator as u32),
+        }
+    }
+
+    pub fn update_maker_rebate_override_volume(
+        &mut self,
+        fee_override: &MarketFeeOverride,
+        maker_quote_asset_amount: u64,
+    ) -> DriftResult {
+        if fee_override.overrides_maker_rebate() {
+            self.maker_rebate_override_volume = self
+                .maker_rebate_override_volume
+                .safe_add(maker_quote_asset_amount)?;
+        }
+
+        Ok(())
+    }
+
     pub fn is_operation_paused(&self, operation: SpotOperation) -> bool {
         SpotOperation::is_operation_paused(self.paused_operations, operation)
     }
@@ -43,7 +43,18 @@ pub struct State {
     pub max_initialize_user_fee: u16,
     pub feature_bit_flags: u8,
     pub lp_pool_feature_bit_flags: u8,
-    pub padding: [u8; 8],
+    pub builder_tier_rebate_pcts: [u8; 4],
+    /// Slots for a liquidation auction to decay from the oracle price to the max liquidation fee.
+    /// 0 disables liquidation auctions
+    pub liquidation_auction_duration: u8,
+    /// Buffer above the maintenance requirement where keepers can deleverage the user's largest
+    /// risk position before liquidation. 0 disables deleveraging
+    /// precision: MARGIN_PRECISION
+    pub deleverage_margin_buffer_ratio: u16,
+    pub padding: [u8; 1],
+    /// 30d builder volume required to reach each builder tier above the base tier
+    /// precision: QUOTE_PRECISION
+    pub builder_tier_volume_thresholds: [u64; 4],
 }
 
 #[derive(BitFlags, Clone, Copy, PartialEq, Debug, Eq)]
@@ -130,6 +141,15 @@ impl State {
         (self.feature_bit_flags & (FeatureBitFlags::BuilderReferral as u8)) > 0
     }
 
+    pub fn portfolio_margin_enabled(&self) -> bool {
+        (self.feature_bit_flags & (FeatureBitFlags::PortfolioMargin as u8)) > 0
+    }
+
+    /// Set while the margin correlations account exists, liquidations must then include it
+    pub fn margin_correlations_enabled(&self) -> bool {
+        (self.feature_bit_flags & (FeatureBitFlags::MarginCorrelations as u8)) > 0
+    }
+
     pub fn allow_settle_lp_pool(&self) -> bool {
         (self.lp_pool_feature_bit_flags & (LpPoolFeatureBitFlags::SettleLpPool as u8)) > 0
     }
@@ -149,6 +169,8 @@ pub enum FeatureBitFlags {
     MedianTriggerPrice = 0b00000010,
     BuilderCodes = 0b00000100,
     BuilderReferral = 0b00001000,
+    PortfolioMargin = 0b00010000,
+    MarginCorrelations = 0b00100000,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
@@ -158,8 +180,13 @@ pub enum LpPoolFeatureBitFlags {
     MintRedeemLpPool = 0b00000100,
 }
 
+impl State {
+    /// Size of State before builder_tier_volume_thresholds was added
+    pub const LEGACY_SIZE: usize = 992;
+}
+
 impl Size for State {
-    const SIZE: usize = 992;
+    const SIZE: usize = 1024;
 }
 
 #[derive(Copy, AnchorSerialize, AnchorDeserialize, Clone, Debug)]
@@ -261,6 +288,71 @@ pub struct OrderFillerRewardStructure {
     pub time_based_reward_lower_bound: u128, // minimum filler reward for time-based reward
 }
 
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum FeeOverrideFlag {
+    TakerFee = 0b00000001,
+    MakerRebate = 0b00000010,
+}
+
+/// A market's multiplier on the fee tier's taker fee, over FEE_PERCENTAGE_DENOMINATOR, and
+/// replacement for the fee tier's maker rebate, over FEE_DENOMINATOR. The market's fee_adjustment
+/// still scales the overridden values
+#[derive(Clone, Copy, Default, PartialEq, Debug, Eq)]
+pub struct MarketFeeOverride {
+    pub taker_fee_multiplier: Option<u32>,
+    pub maker_rebate_numerator: Option<u32>,
+}
+
+impl MarketFeeOverride {
+    pub fn apply(&self, mut fee_tier: FeeTier) -> DriftResult<FeeTier> {
+        if let Some(taker_fee_multiplier) = self.taker_fee_multiplier {
+            fee_tier.fee_numerator = fee_tier
+                .fee_numerator
+                .safe_mul(taker_fee_multiplier)?
+                .safe_div(FEE_PERCENTAGE_DENOMINATOR)?;
+        }
+
+        if let Some(maker_rebate_numerator) = self.maker_rebate_numerator {
+            fee_tier.maker_rebate_numerator = maker_rebate_numerator;
+            fee_tier.maker_rebate_denominator = FEE_DENOMINATOR;
+        }
+
+        Ok(fee_tier)
+    }
+
+    pub fn overrides_maker_rebate(&self) -> bool {
+        self.maker_rebate_numerator.is_some()
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Debug, Eq)]
+pub struct MarketFeeOverrideParams {
+    /// Multiplier on the fee tier's taker fee, over FEE_PERCENTAGE_DENOMINATOR. None disables the
+    /// override
+    pub taker_fee_multiplier: Option<u16>,
+    /// None disables the override
+    pub maker_rebate_numerator: Option<u16>,
+    /// Quote volume the maker rebate override applies to, 0 for no cap
+    pub maker_rebate_volume_cap: u64,
+    pub reset_maker_rebate_volume: bool,
+}
+
+impl MarketFeeOverrideParams {
+    pub fn get_flags(&self) -> u8 {
+        let mut flags = 0;
+
+        if self.taker_fee_multiplier.is_some() {
+            flags |= FeeOverrideFlag::TakerFee as u8;
+        }
+
+        if self.maker_rebate_numerator.is_some() {
+            flags |= FeeOverrideFlag::MakerRebate as u8;
+        }
+
+        flags
+    }
+}
+
 impl FeeStructure {
     pub fn perps_default() -> Self {
         let mut fee_tiers = [FeeTier::default(); 10];
@@ -1,10 +1,12 @@
-use crate::controller::position::{add_new_position, get_position_index, PositionDirection};
+use crate::controller::position::{
+    add_new_position, decrease_open_bids_and_asks, get_position_index, PositionDirection,
+};
 use crate::error::{DriftResult, ErrorCode};
 use crate::math::auction::{calculate_auction_price, is_auction_complete};
 use crate::math::casting::Cast;
 use crate::math::constants::{
     EPOCH_DURATION, FUEL_OVERFLOW_THRESHOLD_U32, FUEL_START_TS, MAX_PREDICTION_MARKET_PRICE,
-    OPEN_ORDER_MARGIN_REQUIREMENT, QUOTE_PRECISION_U64, QUOTE_SPOT_MARKET_INDEX,
+    ONE_HOUR, OPEN_ORDER_MARGIN_REQUIREMENT, QUOTE_PRECISION_U64, QUOTE_SPOT_MARKET_INDEX,
     SPOT_WEIGHT_PRECISION, SPOT_WEIGHT_PRECISION_I128, THIRTY_DAY,
 };
 use crate::math::margin::MarginRequirementType;
@@ -62,7 +64,7 @@ pub enum UserStatus {
 
 // implement SIZE const for User
 impl Size for User {
-    const SIZE: usize = 4376;
+    const SIZE: usize = 5424;
 }
 
 #[account(zero_copy(unsafe))]
@@ -134,10 +136,60 @@ pub struct User {
     pub pool_id: u8,
     pub padding1: [u8; 3],
     pub last_fuel_bonus_update_ts: u32,
+    /// The open liquidation auction order placed by a keeper, 0 if there's none
+    pub liquidation_auction_order_id: u32,
+    /// The slot the user was first deleveraged in since last being above the deleverage buffer,
+    /// 0 if they haven't been
+    pub deleverage_start_slot: u64,
+    /// The margin freed by deleverage order fills since deleverage_start_slot, paces deleveraging
+    /// precision: QUOTE_PRECISION
+    pub deleverage_margin_freed: u64,
+    /// The open deleverage order placed by a keeper, 0 if there's none
+    pub deleverage_order_id: u32,
     pub padding: [u8; 12],
 }
 
 impl User {
+    /// Size of user accounts created before orders had their twap fields
+    pub const LEGACY_SIZE: usize = 4376;
+    const LEGACY_ORDER_SIZE: usize = 96;
+
+    /// Moves a legacy account's orders and the fields after them into the current layout, zeroing
+    /// the new order and user fields. The account data must already be resized to User::SIZE
+    pub fn migrate_legacy_order_layout(data: &mut [u8]) -> DriftResult {
+        validate!(
+            data.len() == User::SIZE,
+            ErrorCode::InvalidUserAccount,
+            "user account data len {} != {}",
+            data.len(),
+            User::SIZE
+        )?;
+
+        let orders_offset = 8
+            + 3 * 32
+            + std::mem::size_of::<[SpotPosition; 8]>()
+            + std::mem::size_of::<[PerpPosition; 8]>();
+        let order_size = std::mem::size_of::<Order>();
+        let legacy_orders_end = orders_offset + 32 * Self::LEGACY_ORDER_SIZE;
+        let orders_end = orders_offset + 32 * order_size;
+
+        data.copy_within(legacy_orders_end..Self::LEGACY_SIZE, orders_end);
+        data[orders_end + Self::LEGACY_SIZE - legacy_orders_end..].fill(0);
+
+        // back to front so no order is overwritten before it has been moved
+        for i in (0..32).rev() {
+            let legacy_order_offset = orders_offset + i * Self::LEGACY_ORDER_SIZE;
+            let order_offset = orders_offset + i * order_size;
+            data.copy_within(
+                legacy_order_offset..legacy_order_offset + Self::LEGACY_ORDER_SIZE,
+                order_offset,
+            );
+            data[order_offset + Self::LEGACY_ORDER_SIZE..order_offset + order_size].fill(0);
+        }
+
+        Ok(())
+    }
+
     pub fn is_being_liquidated(&self) -> bool {
         self.is_cross_margin_being_liquidated() || self.has_isolated_margin_being_liquidated()
     }
@@ -310,6 +362,19 @@ impl User {
             .ok_or(ErrorCode::OrderDoesNotExist)
     }
 
+    /// Finds the lowest order group id not held by any order slot, including filled or canceled
+    /// orders whose group hasn't been cleared yet
+    pub fn get_next_order_group(&self) -> DriftResult<u8> {
+        (1..=u8::MAX)
+            .find(|order_group| {
+                !self
+                    .orders
+                    .iter()
+                    .any(|order| order.order_group == *order_group)
+            })
+            .ok_or(ErrorCode::MaxNumberOfOrderGroups)
+    }
+
     pub fn get_order_index_by_user_order_id(&self, user_order_id: u8) -> DriftResult<usize> {
         self.orders
             .iter()
@@ -423,10 +488,11 @@ impl User {
         Ok(liquidation_id)
     }
 
-    pub fn exit_cross_margin_liquidation(&mut self) {
+    pub fn exit_cross_margin_liquidation(&mut self) -> DriftResult {
         self.remove_user_status(UserStatus::BeingLiquidated);
         self.remove_user_status(UserStatus::Bankrupt);
         self.liquidation_margin_freed = 0;
+        self.cancel_liquidation_auction_order(None)
     }
 
     pub fn enter_cross_margin_bankruptcy(&mut self) {
@@ -475,6 +541,58 @@ impl User {
         let perp_position = self.force_get_isolated_perp_position_mut(perp_market_index)?;
         perp_position.position_flag &= !(PositionFlag::BeingLiquidated as u8);
         perp_position.position_flag &= !(PositionFlag::Bankrupt as u8);
+        self.cancel_liquidation_auction_order(Some(perp_market_index))
+    }
+
+    /// Cancels the liquidation auction order and clears the auction flag for the cross margin
+    /// positions (None) or the isolated position leaving liquidation. Not every exit loads the
+    /// markets and oracles, so unlike cancel_order no order record is emitted
+    pub fn cancel_liquidation_auction_order(
+        &mut self,
+        isolated_perp_market_index: Option<u16>,
+    ) -> DriftResult {
+        let is_exiting = |perp_position: &PerpPosition| match isolated_perp_market_index {
+            Some(market_index) => perp_position.market_index == market_index,
+            None => !perp_position.is_isolated(),
+        };
+
+        let liquidation_auction_order_id = self.liquidation_auction_order_id;
+        let order_index = self.orders.iter().position(|order| {
+            liquidation_auction_order_id != 0
+                && order.order_id == liquidation_auction_order_id
+                && order.status == OrderStatus::Open
+                && order.market_type == MarketType::Perp
+        });
+
+        match order_index {
+            Some(order_index) => {
+                let order = self.orders[order_index];
+                let position_index = get_position_index(&self.perp_positions, order.market_index)?;
+
+                if is_exiting(&self.perp_positions[position_index]) {
+                    self.decrement_open_orders(order.has_auction());
+                    decrease_open_bids_and_asks(
+                        &mut self.perp_positions[position_index],
+                        &order.direction,
+                        order
+                            .base_asset_amount
+                            .safe_sub(order.base_asset_amount_filled)?,
+                        order.update_open_bids_and_asks(),
+                    )?;
+                    self.perp_positions[position_index].open_orders -= 1;
+                    self.orders[order_index].status = OrderStatus::Canceled;
+                    self.liquidation_auction_order_id = 0;
+                }
+            }
+            None => self.liquidation_auction_order_id = 0,
+        }
+
+        for perp_position in self.perp_positions.iter_mut() {
+            if is_exiting(perp_position) {
+                perp_position.position_flag &= !(PositionFlag::LiquidationAuction as u8);
+            }
+        }
+
         Ok(())
     }
 
@@ -516,6 +634,24 @@ impl User {
         Ok(())
     }
 
+    pub fn begin_deleverage(&mut self, slot: u64) {
+        if self.deleverage_start_slot == 0 {
+            self.deleverage_start_slot = slot;
+            self.deleverage_margin_freed = 0;
+        }
+    }
+
+    pub fn increment_deleverage_margin_freed(&mut self, margin_freed: u64) -> DriftResult {
+        self.deleverage_margin_freed = self.deleverage_margin_freed.safe_add(margin_freed)?;
+        Ok(())
+    }
+
+    pub fn end_deleverage(&mut self) {
+        self.deleverage_start_slot = 0;
+        self.deleverage_margin_freed = 0;
+        self.deleverage_order_id = 0;
+    }
+
     pub fn update_last_active_slot(&mut self, slot: u64) {
         if !self.is_being_liquidated() {
             self.last_active_slot = slot;
@@ -588,6 +724,10 @@ impl User {
         false
     }
 
+    pub fn is_portfolio_margin_mode(&self) -> bool {
+        self.margin_mode == MarginMode::Portfolio
+    }
+
     pub fn is_high_leverage_mode(&self, margin_type: MarginRequirementType) -> bool {
         self.margin_mode == MarginMode::HighLeverage
             || (margin_type == MarginRequirementType::Maintenance
@@ -1379,6 +1519,10 @@ impl PerpPosition {
         self.position_flag & PositionFlag::Bankrupt as u8 > 0
     }
 
+    pub fn has_liquidation_auction(&self) -> bool {
+        self.position_flag & PositionFlag::LiquidationAuction as u8 > 0
+    }
+
     pub fn can_transfer_isolated_position_deposit(&self) -> bool {
         self.is_isolated()
             && self.isolated_position_scaled_balance > 0
@@ -1486,6 +1630,7 @@ pub struct Order {
     /// precision: QUOTE_PRECISION
     pub quote_asset_amount_filled: u64,
     /// At what price the order will be triggered. Only relevant for trigger orders
+    /// For funding and divergence triggers, a signed rate in the low 32 bits instead (precision: PERCENTAGE_PRECISION)
     /// precision: PRICE_PRECISION
     pub trigger_price: u64,
     /// The start price for the auction. Only relevant for market/oracle orders
@@ -1530,7 +1675,26 @@ pub struct Order {
     /// Bitflags for further classification
     /// 0: is_signed_message
     pub bit_flags: u8,
-    pub padding: [u8; 1],
+    /// Orders sharing a non-zero group are one-cancels-other. 0 if the order isn't grouped
+    pub order_group: u8,
+    /// For twap orders, the seconds between slice releases
+    pub twap_slice_interval: u32,
+    /// For twap orders, the number of slices the order is split into
+    pub twap_num_slices: u16,
+    /// For twap orders, the number of slices released so far
+    pub twap_slices_released: u16,
+    /// For iceberg limit orders, the displayed clip size. 0 if the order isn't an iceberg
+    /// precision for perps: BASE_PRECISION
+    /// precision for spot: token mint precision
+    pub iceberg_clip_size: u64,
+    /// For good til slot orders, the last slot the order can be filled in. 0 otherwise
+    pub max_slot: u64,
+    /// For trailing stop orders, the distance the trigger price trails the oracle by
+    /// precision: PRICE_PRECISION (or bps if TrailingStopBps bit flag is set)
+    pub trailing_stop_distance: u32,
+    /// What the trigger price is compared against. Only relevant for trigger orders
+    pub trigger_source: OrderTriggerSource,
+    pub padding: [u8; 3],
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Eq, Debug)]
@@ -1540,14 +1704,103 @@ pub enum AssetType {
 }
 
 impl Order {
-    pub fn seconds_til_expiry(self, now: i64) -> i64 {
-        (self.max_ts - now).max(0)
+    /// Good til slot orders approximate the time left from the slots left at 400ms a slot,
+    /// capped at an hour
+    pub fn seconds_til_expiry(self, now: i64, slot: u64) -> i64 {
+        match self.get_max_slot() {
+            Some(max_slot) => {
+                (max_slot.saturating_sub(slot).saturating_mul(2) / 5).min(ONE_HOUR as u64) as i64
+            }
+            None => (self.max_ts - now).max(0),
+        }
+    }
+
+    pub fn get_max_slot(&self) -> Option<u64> {
+        if self.max_slot != 0 {
+            Some(self.max_slot)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_expired(&self, now: i64, slot: u64) -> bool {
+        match self.get_max_slot() {
+            Some(max_slot) => slot > max_slot,
+            None => self.max_ts != 0 && now > self.max_ts,
+        }
     }
 
     pub fn has_oracle_price_offset(self) -> bool {
         self.oracle_price_offset != 0
     }
 
+    pub fn get_trigger_threshold(&self) -> DriftResult<i64> {
+        self.trigger_source.get_threshold(self.trigger_price)
+    }
+
+    pub fn is_trailing_stop(&self) -> bool {
+        self.order_type == OrderType::TrailingStop
+    }
+
+    pub fn is_iceberg(&self) -> bool {
+        self.order_type == OrderType::Limit && self.iceberg_clip_size != 0
+    }
+
+    /// The part of the iceberg's current clip that is still unfilled. Once a clip is filled the next
+    /// one is shown from the reserve
+    pub fn get_iceberg_base_asset_amount_visible(&self) -> DriftResult<u64> {
+        let clip_size = self.iceberg_clip_size;
+        let base_asset_amount_unfilled = self
+            .base_asset_amount
+            .safe_sub(self.base_asset_amount_filled)?;
+
+        let clip_filled = self
+            .base_asset_amount_filled
+            .checked_rem(clip_size)
+            .ok_or_else(math_error!())?;
+        let clip_unfilled = clip_size.safe_sub(clip_filled)?;
+
+        Ok(clip_unfilled.min(base_asset_amount_unfilled))
+    }
+
+    pub fn is_twap(&self) -> bool {
+        self.order_type == OrderType::Twap
+    }
+
+    pub fn set_twap_params(&mut self, slice_interval: u32, num_slices: u16, slices_released: u16) {
+        self.twap_slice_interval = slice_interval;
+        self.twap_num_slices = num_slices;
+        self.twap_slices_released = slices_released;
+    }
+
+    pub fn get_twap_slice_interval(&self) -> u32 {
+        self.twap_slice_interval
+    }
+
+    pub fn get_twap_num_slices(&self) -> u16 {
+        self.twap_num_slices
+    }
+
+    pub fn get_twap_slices_released(&self) -> u16 {
+        self.twap_slices_released
+    }
+
+    /// Base asset amount released to the twap's slices so far. The last slice releases any remainder
+    pub fn get_twap_base_asset_amount_released(&self) -> DriftResult<u64> {
+        let num_slices = self.get_twap_num_slices();
+        let slices_released = self.get_twap_slices_released();
+
+        if num_slices == 0 || slices_released >= num_slices {
+            return Ok(self.base_asset_amount);
+        }
+
+        self.base_asset_amount
+            .cast::<u128>()?
+            .safe_mul(slices_released.cast()?)?
+            .safe_div(num_slices.cast()?)?
+            .cast()
+    }
+
     pub fn get_limit_price(
         &self,
         valid_oracle_price: Option<i64>,
@@ -1671,6 +1924,7 @@ impl Order {
     }
 
     /// Passing in an existing_position forces the function to consider the order's reduce only status
+    /// and, for twap and iceberg orders, to only count the size released to slices or shown in the clip
     pub fn get_base_asset_amount_unfilled(
         &self,
         existing_position: Option<i64>,
@@ -1686,6 +1940,15 @@ impl Order {
             }
         };
 
+        let base_asset_amount_unfilled = if self.is_twap() {
+            self.get_twap_base_asset_amount_released()?
+                .saturating_sub(self.base_asset_amount_filled)
+        } else if self.is_iceberg() {
+            self.get_iceberg_base_asset_amount_visible()?
+        } else {
+            base_asset_amount_unfilled
+        };
+
         if !self.reduce_only {
             return Ok(base_asset_amount_unfilled);
         }
@@ -1728,7 +1991,7 @@ impl Order {
     pub fn must_be_triggered(&self) -> bool {
         matches!(
             self.order_type,
-            OrderType::TriggerMarket | OrderType::TriggerLimit
+            OrderType::TriggerMarket | OrderType::TriggerLimit | OrderType::TrailingStop
         )
     }
 
@@ -1761,7 +2024,10 @@ impl Order {
     pub fn is_market_order(&self) -> bool {
         matches!(
             self.order_type,
-            OrderType::Market | OrderType::TriggerMarket | OrderType::Oracle
+            OrderType::Market
+                | OrderType::TriggerMarket
+                | OrderType::Oracle
+                | OrderType::TrailingStop
         )
     }
 
@@ -1797,6 +2063,14 @@ impl Order {
         self.status != OrderStatus::Open
     }
 
+    pub fn is_bracket_pending(&self) -> bool {
+        self.is_bit_flag_set(OrderBitFlag::BracketPending)
+    }
+
+    pub fn remove_bit_flag(&mut self, flag: OrderBitFlag) {
+        self.bit_flags &= !(flag as u8);
+    }
+
     pub fn update_open_bids_and_asks(&self) -> bool {
         !self.must_be_triggered()
             || (self.triggered()
@@ -1857,7 +2131,15 @@ impl Default for Order {
             max_ts: 0,
             posted_slot_tail: 0,
             bit_flags: 0,
-            padding: [0; 1],
+            order_group: 0,
+            twap_slice_interval: 0,
+            twap_num_slices: 0,
+            twap_slices_released: 0,
+            iceberg_clip_size: 0,
+            max_slot: 0,
+            trailing_stop_distance: 0,
+            trigger_source: OrderTriggerSource::Oracle,
+            padding: [0; 3],
         }
     }
 }
@@ -1883,6 +2165,13 @@ pub enum OrderType {
     TriggerLimit,
     /// Market order where the auction prices are oracle offsets
     Oracle,
+    /// Trigger market order whose trigger price ratchets with the oracle, trailing it by
+    /// trailing_stop_distance. The trigger price only ratchets when trigger_order is called, and a
+    /// call that only ratchets it doesn't pay a filler reward
+    TrailingStop,
+    /// Parent order that releases base_asset_amount in equal slices every slice interval until max_ts,
+    /// each slice running its own auction bounded by price or oracle_price_offset
+    Twap,
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq, Default)]
@@ -1894,6 +2183,49 @@ pub enum OrderTriggerCondition {
     TriggeredBelow, // below condition has been triggered
 }
 
+/// What a trigger order's trigger price is compared against. Order params without one trigger off
+/// the oracle
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq, Default)]
+pub enum OrderTriggerSource {
+    #[default]
+    Oracle,
+    MarkTwap,             // 5min mark twap, threshold is a price
+    FundingRate, // last funding rate over the oracle twap, threshold in PERCENTAGE_PRECISION
+    OracleMarkDivergence, // 5min mark twap vs 5min oracle twap, threshold in PERCENTAGE_PRECISION
+}
+
+impl OrderTriggerSource {
+    pub fn is_price(&self) -> bool {
+        matches!(
+            self,
+            OrderTriggerSource::Oracle | OrderTriggerSource::MarkTwap
+        )
+    }
+
+    /// Price thresholds are the order's trigger price, rate thresholds are a signed i32 in its low 32 bits
+    pub fn get_threshold(&self, trigger_price: u64) -> DriftResult<i64> {
+        if self.is_price() {
+            trigger_price.cast()
+        } else {
+            validate!(
+                trigger_price <= u32::MAX as u64,
+                ErrorCode::InvalidOrderTrigger,
+                "rate trigger threshold must fit in 32 bits"
+            )?;
+
+            Ok(trigger_price as u32 as i32 as i64)
+        }
+    }
+
+    pub fn get_trigger_price(&self, threshold: i64) -> DriftResult<u64> {
+        if self.is_price() {
+            threshold.cast()
+        } else {
+            Ok(threshold.cast::<i32>()? as u32 as u64)
+        }
+    }
+}
+
 #[derive(Default, Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
 pub enum MarketType {
     #[default]
@@ -1921,6 +2253,8 @@ pub enum OrderBitFlag {
     NewTriggerReduceOnly = 0b00001000,
     HasBuilder = 0b00010000,
     IsIsolatedPosition = 0b00100000,
+    TrailingStopBps = 0b01000000,
+    BracketPending = 0b10000000,
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
@@ -1928,6 +2262,8 @@ pub enum PositionFlag {
     IsolatedPosition = 0b00000001,
     BeingLiquidated = 0b00000010,
     Bankrupt = 0b00000100,
+    /// The position has an open liquidation auction order
+    LiquidationAuction = 0b00001000,
 }
 
 #[account(zero_copy(unsafe))]
@@ -1996,7 +2332,9 @@ pub struct UserStats {
     /// last unix ts user stats data was used to update if fuel (u32 to save space)
     pub last_fuel_if_bonus_update_ts: u32,
 
-    pub padding: [u8; 12],
+    /// whether the authority has a WithdrawPolicy account
+    pub withdraw_policy_status: u8,
+    pub padding: [u8; 11],
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
@@ -2005,6 +2343,7 @@ pub enum ReferrerStatus {
     IsReferrer = 0b00000001,
     IsReferred = 0b00000010,
     BuilderReferral = 0b00000100,
+    HasReferralCampaign = 0b00001000,
 }
 
 impl ReferrerStatus {
@@ -2019,6 +2358,10 @@ impl ReferrerStatus {
     pub fn has_builder_referral(status: u8) -> bool {
         status & ReferrerStatus::BuilderReferral as u8 != 0
     }
+
+    pub fn has_referral_campaign(status: u8) -> bool {
+        status & ReferrerStatus::HasReferralCampaign as u8 != 0
+    }
 }
 
 impl Size for UserStats {
@@ -2233,6 +2576,18 @@ impl UserStats {
         }
     }
 
+    pub fn update_referral_campaign_status(&mut self, has_referral_campaign: bool) {
+        if has_referral_campaign {
+            self.referrer_status |= ReferrerStatus::HasReferralCampaign as u8;
+        } else {
+            self.referrer_status &= !(ReferrerStatus::HasReferralCampaign as u8);
+        }
+    }
+
+    pub fn has_referral_campaign(&self) -> bool {
+        ReferrerStatus::has_referral_campaign(self.referrer_status)
+    }
+
     pub fn update_fuel_overflow_status(&mut self, has_overflow: bool) {
         if has_overflow {
             self.fuel_overflow_status |= FuelOverflowStatus::Exists as u8;
@@ -2241,6 +2596,18 @@ impl UserStats {
         }
     }
 
+    pub fn update_withdraw_policy_status(&mut self, has_withdraw_policy: bool) {
+        if has_withdraw_policy {
+            self.withdraw_policy_status |= WithdrawPolicyStatus::Exists as u8;
+        } else {
+            self.withdraw_policy_status &= !(WithdrawPolicyStatus::Exists as u8);
+        }
+    }
+
+    pub fn has_withdraw_policy(&self) -> bool {
+        WithdrawPolicyStatus::exists(self.withdraw_policy_status)
+    }
+
     pub fn can_sweep_fuel(&self) -> bool {
         if self.fuel_insurance > FUEL_OVERFLOW_THRESHOLD_U32 {
             return true;
@@ -2370,6 +2737,9 @@ pub enum MarginMode {
     Default,
     HighLeverage,
     HighLeverageMaintenance,
+    /// Spot and perp positions sharing an underlying are margined together on their worst loss
+    /// across a grid of oracle price shocks
+    Portfolio,
 }
 
 #[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq)]
@@ -2383,6 +2753,17 @@ impl FuelOverflowStatus {
         status & FuelOverflowStatus::Exists as u8 != 0
     }
 }
+
+pub enum WithdrawPolicyStatus {
+    Exists = 0b00000001,
+}
+
+impl WithdrawPolicyStatus {
+    pub fn exists(status: u8) -> bool {
+        status & WithdrawPolicyStatus::Exists as u8 != 0
+    }
+}
+
 #[account(zero_copy(unsafe))]
 #[derive(Default, Debug)]
 #[repr(C)]
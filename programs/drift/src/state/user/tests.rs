@@ -1706,7 +1706,7 @@ mod update_user_status {
         assert!(user.is_cross_margin_bankrupt());
         assert!(user.status & UserStatus::ReduceOnly as u8 > 0);
 
-        user.exit_cross_margin_liquidation();
+        user.exit_cross_margin_liquidation().unwrap();
         assert!(!user.is_cross_margin_being_liquidated());
         assert!(!user.is_cross_margin_bankrupt());
         assert!(user.status & UserStatus::ReduceOnly as u8 > 0);
@@ -2350,7 +2350,7 @@ mod next_liquidation_id {
 
         user.exit_isolated_margin_liquidation(1).unwrap();
 
-        user.exit_cross_margin_liquidation();
+        user.exit_cross_margin_liquidation().unwrap();
 
         let liquidation_id = user.enter_isolated_margin_liquidation(1, 4).unwrap();
         assert_eq!(liquidation_id, 2);
@@ -2366,6 +2366,98 @@ mod next_liquidation_id {
     }
 }
 
+mod cancel_liquidation_auction_order {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::BASE_PRECISION_U64;
+    use crate::state::user::{
+        MarketType, Order, OrderStatus, OrderType, PerpPosition, PositionFlag, User,
+    };
+    use crate::test_utils::get_orders;
+
+    fn get_user(auction_position_flag: u8) -> User {
+        let mut user = User {
+            orders: get_orders(Order {
+                market_index: 1,
+                order_id: 5,
+                status: OrderStatus::Open,
+                order_type: OrderType::Oracle,
+                market_type: MarketType::Perp,
+                direction: PositionDirection::Short,
+                base_asset_amount: BASE_PRECISION_U64,
+                reduce_only: true,
+                auction_duration: 20,
+                ..Order::default()
+            }),
+            open_orders: 1,
+            has_open_order: true,
+            open_auctions: 1,
+            has_open_auction: true,
+            liquidation_auction_order_id: 5,
+            ..User::default()
+        };
+        user.perp_positions[0] = PerpPosition {
+            market_index: 1,
+            base_asset_amount: BASE_PRECISION_U64 as i64,
+            open_orders: 1,
+            open_asks: -(BASE_PRECISION_U64 as i64),
+            position_flag: auction_position_flag | PositionFlag::LiquidationAuction as u8,
+            ..PerpPosition::default()
+        };
+        user
+    }
+
+    #[test]
+    fn cross_margin_exit() {
+        let mut user = get_user(0);
+        user.enter_cross_margin_liquidation(0).unwrap();
+
+        user.exit_cross_margin_liquidation().unwrap();
+
+        assert_eq!(user.orders[0].status, OrderStatus::Canceled);
+        assert_eq!(user.liquidation_auction_order_id, 0);
+        assert_eq!(user.open_orders, 0);
+        assert_eq!(user.open_auctions, 0);
+        assert_eq!(user.perp_positions[0].open_orders, 0);
+        assert_eq!(user.perp_positions[0].open_asks, 0);
+        assert!(!user.perp_positions[0].has_liquidation_auction());
+    }
+
+    #[test]
+    fn isolated_exit() {
+        let mut user = get_user(PositionFlag::IsolatedPosition as u8);
+        user.enter_isolated_margin_liquidation(1, 0).unwrap();
+
+        user.exit_cross_margin_liquidation().unwrap();
+
+        assert_eq!(user.orders[0].status, OrderStatus::Open);
+        assert_eq!(user.liquidation_auction_order_id, 5);
+        assert!(user.perp_positions[0].has_liquidation_auction());
+
+        user.exit_isolated_margin_liquidation(1).unwrap();
+
+        assert_eq!(user.orders[0].status, OrderStatus::Canceled);
+        assert_eq!(user.liquidation_auction_order_id, 0);
+        assert_eq!(user.perp_positions[0].open_orders, 0);
+        assert_eq!(user.perp_positions[0].open_asks, 0);
+        assert!(!user.perp_positions[0].has_liquidation_auction());
+    }
+
+    #[test]
+    fn filled_order() {
+        let mut user = get_user(0);
+        user.orders[0].status = OrderStatus::Filled;
+        user.open_orders = 0;
+        user.perp_positions[0].open_orders = 0;
+        user.perp_positions[0].open_asks = 0;
+
+        user.exit_cross_margin_liquidation().unwrap();
+
+        assert_eq!(user.orders[0].status, OrderStatus::Filled);
+        assert_eq!(user.liquidation_auction_order_id, 0);
+        assert!(!user.perp_positions[0].has_liquidation_auction());
+    }
+}
+
 mod force_get_isolated_perp_position_mut {
     use crate::state::user::{PerpPosition, PositionFlag, User};
 
@@ -2859,3 +2951,255 @@ mod force_get_user_perp_position_mut {
         assert_eq!(user.perp_positions[0].max_margin_ratio, 0);
     }
 }
+
+mod get_next_order_group {
+    use crate::state::user::{Order, OrderStatus, User};
+
+    #[test]
+    fn skips_groups_held_by_any_order() {
+        let mut user = User::default();
+        assert_eq!(user.get_next_order_group(), Ok(1));
+
+        user.orders[0] = Order {
+            status: OrderStatus::Open,
+            order_group: 1,
+            ..Order::default()
+        };
+        // filled orders keep their group until it is resolved
+        user.orders[1] = Order {
+            status: OrderStatus::Filled,
+            order_group: 2,
+            ..Order::default()
+        };
+        assert_eq!(user.get_next_order_group(), Ok(3));
+
+        for order in user.orders.iter_mut() {
+            order.order_group = 0;
+        }
+        assert_eq!(user.get_next_order_group(), Ok(1));
+    }
+}
+
+mod get_iceberg_base_asset_amount_visible {
+    use crate::controller::position::PositionDirection;
+    use crate::math::constants::BASE_PRECISION_U64;
+    use crate::state::user::{Order, OrderType};
+
+    #[test]
+    fn clip_refreshes_from_reserve() {
+        let mut order = Order {
+            order_type: OrderType::Limit,
+            direction: PositionDirection::Long,
+            base_asset_amount: 10 * BASE_PRECISION_U64,
+            iceberg_clip_size: 3 * BASE_PRECISION_U64,
+            ..Order::default()
+        };
+        assert!(order.is_iceberg());
+
+        // margin sees the full reserve, matching only the clip
+        assert_eq!(
+            order.get_base_asset_amount_unfilled(None).unwrap(),
+            10 * BASE_PRECISION_U64
+        );
+        assert_eq!(
+            order.get_base_asset_amount_unfilled(Some(0)).unwrap(),
+            3 * BASE_PRECISION_U64
+        );
+
+        order.base_asset_amount_filled = 2 * BASE_PRECISION_U64;
+        assert_eq!(
+            order.get_base_asset_amount_unfilled(Some(0)).unwrap(),
+            BASE_PRECISION_U64
+        );
+
+        order.base_asset_amount_filled = 3 * BASE_PRECISION_U64;
+        assert_eq!(
+            order.get_base_asset_amount_unfilled(Some(0)).unwrap(),
+            3 * BASE_PRECISION_U64
+        );
+
+        // last clip is whatever is left of the reserve
+        order.base_asset_amount_filled = 9 * BASE_PRECISION_U64;
+        assert_eq!(
+            order.get_base_asset_amount_unfilled(Some(0)).unwrap(),
+            BASE_PRECISION_U64
+        );
+    }
+
+    #[test]
+    fn trigger_limit_is_not_iceberg() {
+        let order = Order {
+            order_type: OrderType::TriggerLimit,
+            trigger_price: 100,
+            ..Order::default()
+        };
+        assert!(!order.is_iceberg());
+    }
+}
+
+mod migrate_legacy_order_layout {
+    use crate::create_anchor_account_info;
+    use crate::state::traits::Size;
+    use crate::state::user::{Order, OrderStatus, PerpPosition, User, UserStats};
+    use crate::state::user_map::{load_user_map, load_user_maps};
+    use crate::test_utils::{create_account_info, get_anchor_account_bytes, get_positions};
+    use anchor_lang::prelude::Pubkey;
+    use anchor_lang::{Discriminator, Owner};
+
+    #[test]
+    fn moves_orders_and_trailing_fields() {
+        let mut user = User {
+            perp_positions: get_positions(PerpPosition {
+                base_asset_amount: 1,
+                ..PerpPosition::default()
+            }),
+            next_order_id: 7,
+            status: 2,
+            last_fuel_bonus_update_ts: 11,
+            ..User::default()
+        };
+        for (i, order) in user.orders.iter_mut().enumerate() {
+            *order = Order {
+                status: OrderStatus::Open,
+                order_id: i as u32 + 1,
+                base_asset_amount: 100 + i as u64,
+                order_group: 3,
+                ..Order::default()
+            };
+        }
+
+        let mut expected = User::discriminator().to_vec();
+        expected.extend_from_slice(bytemuck::bytes_of(&user));
+
+        let order_size = std::mem::size_of::<Order>();
+        let orders_offset = 8 + (&user.orders as *const _ as usize - &user as *const _ as usize);
+        let orders_end = orders_offset + 32 * order_size;
+
+        let mut legacy = expected[..orders_offset].to_vec();
+        for i in 0..32 {
+            let order_offset = orders_offset + i * order_size;
+            legacy.extend_from_slice(&expected[order_offset..order_offset + 96]);
+        }
+        let legacy_tail_len = User::LEGACY_SIZE - legacy.len();
+        legacy.extend_from_slice(&expected[orders_end..orders_end + legacy_tail_len]);
+        assert_eq!(legacy.len(), User::LEGACY_SIZE);
+
+        legacy.resize(User::SIZE, u8::MAX);
+        User::migrate_legacy_order_layout(&mut legacy).unwrap();
+
+        assert_eq!(legacy, expected);
+    }
+
+    #[test]
+    fn rejects_unresized_data() {
+        let mut data = vec![0_u8; User::LEGACY_SIZE];
+        assert!(User::migrate_legacy_order_layout(&mut data).is_err());
+    }
+
+    #[test]
+    fn user_maps_skip_legacy_users() {
+        let legacy_authority = Pubkey::new_unique();
+        let mut legacy_user = User {
+            authority: legacy_authority,
+            ..User::default()
+        };
+        let mut legacy_user_stats = UserStats {
+            authority: legacy_authority,
+            ..UserStats::default()
+        };
+        let authority = Pubkey::new_unique();
+        let mut user = User {
+            authority,
+            ..User::default()
+        };
+        let mut user_stats = UserStats {
+            authority,
+            ..UserStats::default()
+        };
+
+        let legacy_user_key = Pubkey::new_unique();
+        let mut legacy_lamports = 0;
+        let mut legacy_data = get_anchor_account_bytes(&mut legacy_user);
+        legacy_data.truncate(User::LEGACY_SIZE);
+        let legacy_user_account_info = create_account_info(
+            &legacy_user_key,
+            true,
+            &mut legacy_lamports,
+            &mut legacy_data[..],
+            &crate::ID,
+        );
+        create_anchor_account_info!(
+            legacy_user_stats,
+            &Pubkey::new_unique(),
+            UserStats,
+            legacy_user_stats_account_info
+        );
+        let user_key = Pubkey::new_unique();
+        create_anchor_account_info!(user, &user_key, User, user_account_info);
+        create_anchor_account_info!(
+            user_stats,
+            &Pubkey::new_unique(),
+            UserStats,
+            user_stats_account_info
+        );
+
+        let account_infos = [
+            legacy_user_account_info,
+            legacy_user_stats_account_info,
+            user_account_info,
+            user_stats_account_info,
+        ];
+        let (user_map, user_stats_map) =
+            load_user_maps(&mut account_infos.iter().peekable(), true).unwrap();
+
+        assert_eq!(user_map.0.len(), 1);
+        assert!(user_map.0.contains_key(&user_key));
+        assert!(user_map.has_legacy_user_for_authority(&legacy_authority));
+        assert!(!user_map.has_legacy_user_for_authority(&authority));
+        assert!(user_stats_map.0.contains_key(&legacy_authority));
+        assert!(user_stats_map.0.contains_key(&authority));
+
+        let user_map = load_user_map(&mut account_infos.iter().peekable(), true).unwrap();
+
+        assert_eq!(user_map.0.len(), 1);
+        assert!(user_map.0.contains_key(&user_key));
+        assert!(user_map.has_legacy_user_for_authority(&legacy_authority));
+    }
+}
+
+mod seconds_til_expiry {
+    use crate::state::user::{Order, OrderStatus};
+
+    #[test]
+    fn good_til_slot() {
+        let order = Order {
+            status: OrderStatus::Open,
+            max_slot: 150,
+            ..Order::default()
+        };
+
+        // 50 slots at 400ms
+        assert_eq!(order.seconds_til_expiry(1000, 100), 20);
+        assert_eq!(order.seconds_til_expiry(1000, 151), 0);
+        assert!(order.is_expired(0, 151));
+        assert!(!order.is_expired(i64::MAX, 150));
+
+        let far_order = Order {
+            max_slot: u64::MAX,
+            ..order
+        };
+        assert_eq!(far_order.seconds_til_expiry(1000, 100), 3600);
+    }
+
+    #[test]
+    fn max_ts() {
+        let order = Order {
+            status: OrderStatus::Open,
+            max_ts: 1030,
+            ..Order::default()
+        };
+
+        assert_eq!(order.seconds_til_expiry(1000, 100), 30);
+        assert_eq!(order.get_max_slot(), None);
+    }
+}
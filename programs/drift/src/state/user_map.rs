@@ -10,13 +10,18 @@ use arrayref::array_ref;
 use solana_program::account_info::AccountInfo;
 use solana_program::pubkey::Pubkey;
 use std::cell::{Ref, RefMut};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
 use std::iter::Peekable;
 use std::panic::Location;
 use std::slice::Iter;
 
-pub struct UserMap<'a>(pub BTreeMap<Pubkey, AccountLoader<'a, User>>);
+/// The second field holds the authorities of users skipped because their account still has the
+/// legacy order layout
+pub struct UserMap<'a>(
+    pub BTreeMap<Pubkey, AccountLoader<'a, User>>,
+    pub BTreeSet<Pubkey>,
+);
 
 impl<'a> UserMap<'a> {
     #[track_caller]
@@ -99,14 +104,18 @@ impl<'a> UserMap<'a> {
     }
 
     pub fn empty() -> UserMap<'a> {
-        UserMap(BTreeMap::new())
+        UserMap(BTreeMap::new(), BTreeSet::new())
+    }
+
+    pub fn has_legacy_user_for_authority(&self, authority: &Pubkey) -> bool {
+        self.1.contains(authority)
     }
 }
 
 #[cfg(test)]
 impl<'a> UserMap<'a> {
     pub fn load_one<'b: 'a>(account_info: &'b AccountInfo<'a>) -> DriftResult<UserMap<'a>> {
-        let mut user_map = UserMap(BTreeMap::new());
+        let mut user_map = UserMap::empty();
 
         let user_discriminator: [u8; 8] = User::discriminator();
 
@@ -289,8 +298,9 @@ pub fn load_user_maps<'a: 'b, 'b>(
             .try_borrow_data()
             .or(Err(ErrorCode::CouldNotLoadUserData))?;
 
+        let is_legacy_user = data.len() == User::LEGACY_SIZE;
         let expected_data_len = User::SIZE;
-        if data.len() < expected_data_len {
+        if data.len() < expected_data_len && !is_legacy_user {
             break;
         }
 
@@ -301,15 +311,22 @@ pub fn load_user_maps<'a: 'b, 'b>(
 
         let user_account_info = account_info_iter.next().safe_unwrap()?;
 
-        let is_writable = user_account_info.is_writable;
-        if !is_writable && must_be_writable {
-            return Err(ErrorCode::UserWrongMutability);
-        }
+        // legacy order layout users can't be loaded until migrated, skip them but keep their stats
+        if is_legacy_user {
+            msg!("Skipping user {} with legacy order layout", user_key);
+            user_map.1.insert(Pubkey::from(*array_ref![data, 8, 32]));
+        } else {
+            let is_writable = user_account_info.is_writable;
+            if !is_writable && must_be_writable {
+                return Err(ErrorCode::UserWrongMutability);
+            }
 
-        let user_account_loader: AccountLoader<User> =
-            AccountLoader::try_from(user_account_info).or(Err(ErrorCode::InvalidUserAccount))?;
+            let user_account_loader: AccountLoader<User> =
+                AccountLoader::try_from(user_account_info)
+                    .or(Err(ErrorCode::InvalidUserAccount))?;
 
-        user_map.0.insert(*user_key, user_account_loader);
+            user_map.0.insert(*user_key, user_account_loader);
+        }
 
         validate!(
             account_info_iter.peek().is_some(),
@@ -391,6 +408,12 @@ pub fn load_user_map<'a: 'b, 'b>(
 
         let user_account_info = account_info_iter.next().safe_unwrap()?;
 
+        if data.len() == User::LEGACY_SIZE {
+            msg!("Skipping user {} with legacy order layout", user_key);
+            user_map.1.insert(Pubkey::from(*array_ref![data, 8, 32]));
+            continue;
+        }
+
         let is_writable = user_account_info.is_writable;
         if !is_writable && must_be_writable {
             return Err(ErrorCode::UserWrongMutability);
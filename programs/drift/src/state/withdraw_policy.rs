@@ -0,0 +1,179 @@
+use crate::error::{DriftResult, ErrorCode};
+use crate::math::casting::Cast;
+use crate::math::safe_math::SafeMath;
+use crate::state::traits::Size;
+use crate::validate;
+use anchor_lang::prelude::*;
+
+#[cfg(test)]
+mod tests;
+
+pub const WITHDRAW_POLICY_PDA_SEED: &str = "WITHDRAW_POLICY";
+pub const MAX_WITHDRAW_DESTINATIONS: usize = 8;
+pub const MAX_WITHDRAW_POLICY_TIMELOCK: u32 = 60 * 60 * 24 * 30; // 30 days
+
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq, Debug, Eq, Default)]
+pub enum WithdrawPolicyChange {
+    #[default]
+    None,
+    Update,
+    Delete,
+}
+
+/// Limits where an authority's funds can leave to. Once it exists, withdraw, transfer_deposit and
+/// transfer_pools for every sub account of the authority need it in their remaining accounts.
+/// Changes to the policy, including deleting it, only take effect after the timelock
+#[account(zero_copy(unsafe))]
+#[derive(Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct WithdrawPolicy {
+    pub authority: Pubkey,
+    /// Token accounts that can be withdrawn to and sub accounts that can be transferred to
+    pub destinations: [Pubkey; 8],
+    /// Destinations that replace destinations once pending_ts is reached
+    pub pending_destinations: [Pubkey; 8],
+    /// When the pending change takes effect. 0 if there's no pending change
+    pub pending_ts: i64,
+    /// Delay before a change to the policy takes effect, in seconds
+    pub timelock: u32,
+    pub pending_timelock: u32,
+    pub num_destinations: u8,
+    pub num_pending_destinations: u8,
+    /// WithdrawPolicyChange
+    pub pending_change: WithdrawPolicyChange,
+    pub padding: [u8; 13],
+}
+
+impl Size for WithdrawPolicy {
+    const SIZE: usize = 584;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Eq, PartialEq, Debug)]
+pub struct WithdrawPolicyParams {
+    pub destinations: Vec<Pubkey>,
+    pub timelock: u32,
+}
+
+impl WithdrawPolicyParams {
+    pub fn validate(&self) -> DriftResult {
+        validate!(
+            self.destinations.len() <= MAX_WITHDRAW_DESTINATIONS,
+            ErrorCode::InvalidWithdrawPolicy,
+            "withdraw policy can have at most {} destinations",
+            MAX_WITHDRAW_DESTINATIONS
+        )?;
+
+        validate!(
+            self.timelock <= MAX_WITHDRAW_POLICY_TIMELOCK,
+            ErrorCode::InvalidWithdrawPolicy,
+            "withdraw policy timelock {} above max {}",
+            self.timelock,
+            MAX_WITHDRAW_POLICY_TIMELOCK
+        )?;
+
+        Ok(())
+    }
+}
+
+impl WithdrawPolicy {
+    pub fn initialize(&mut self, authority: Pubkey, params: &WithdrawPolicyParams) -> DriftResult {
+        params.validate()?;
+
+        self.authority = authority;
+        self.timelock = params.timelock;
+        self.num_destinations = params.destinations.len().cast()?;
+        self.destinations = [Pubkey::default(); MAX_WITHDRAW_DESTINATIONS];
+        self.destinations[..params.destinations.len()].copy_from_slice(&params.destinations);
+
+        Ok(())
+    }
+
+    pub fn has_pending_change(&self) -> bool {
+        self.pending_change != WithdrawPolicyChange::None
+    }
+
+    fn is_pending_change_unlocked(&self, now: i64) -> bool {
+        self.has_pending_change() && now >= self.pending_ts
+    }
+
+    /// The destinations in effect at now, including a pending update whose timelock has passed
+    pub fn get_destinations(&self, now: i64) -> &[Pubkey] {
+        if self.pending_change == WithdrawPolicyChange::Update
+            && self.is_pending_change_unlocked(now)
+        {
+            &self.pending_destinations[..self.num_pending_destinations as usize]
+        } else {
+            &self.destinations[..self.num_destinations as usize]
+        }
+    }
+
+    pub fn is_destination_allowed(&self, destination: &Pubkey, now: i64) -> bool {
+        self.get_destinations(now).contains(destination)
+    }
+
+    /// Makes a pending update whose timelock has passed the current policy. Returns true if applied
+    pub fn apply_pending_update(&mut self, now: i64) -> DriftResult<bool> {
+        if self.pending_change != WithdrawPolicyChange::Update
+            || !self.is_pending_change_unlocked(now)
+        {
+            return Ok(false);
+        }
+
+        self.destinations = self.pending_destinations;
+        self.num_destinations = self.num_pending_destinations;
+        self.timelock = self.pending_timelock;
+        self.clear_pending_change();
+
+        Ok(true)
+    }
+
+    /// Schedules a change to take effect after the current timelock, replacing any pending change.
+    /// Returns when it takes effect
+    pub fn request_change(
+        &mut self,
+        change: WithdrawPolicyChange,
+        params: &WithdrawPolicyParams,
+        now: i64,
+    ) -> DriftResult<i64> {
+        params.validate()?;
+
+        validate!(
+            change != WithdrawPolicyChange::None,
+            ErrorCode::InvalidWithdrawPolicy,
+            "must request an update or delete"
+        )?;
+
+        self.apply_pending_update(now)?;
+
+        self.pending_change = change;
+        self.pending_ts = now.safe_add(self.timelock.cast()?)?;
+        self.pending_timelock = params.timelock;
+        self.num_pending_destinations = params.destinations.len().cast()?;
+        self.pending_destinations = [Pubkey::default(); MAX_WITHDRAW_DESTINATIONS];
+        self.pending_destinations[..params.destinations.len()]
+            .copy_from_slice(&params.destinations);
+
+        Ok(self.pending_ts)
+    }
+
+    pub fn clear_pending_change(&mut self) {
+        self.pending_change = WithdrawPolicyChange::None;
+        self.pending_ts = 0;
+        self.pending_timelock = 0;
+        self.num_pending_destinations = 0;
+        self.pending_destinations = [Pubkey::default(); MAX_WITHDRAW_DESTINATIONS];
+    }
+
+    pub fn validate_delete(&self, now: i64) -> DriftResult {
+        validate!(
+            self.pending_change == WithdrawPolicyChange::Delete
+                && self.is_pending_change_unlocked(now),
+            ErrorCode::WithdrawPolicyTimelocked,
+            "withdraw policy delete must be requested and unlocked (pending ts {}, now {})",
+            self.pending_ts,
+            now
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::ErrorCode;
+use crate::state::withdraw_policy::{
+    WithdrawPolicy, WithdrawPolicyChange, WithdrawPolicyParams, MAX_WITHDRAW_POLICY_TIMELOCK,
+};
+use anchor_lang::prelude::Pubkey;
+
+#[test]
+fn initialize() {
+    let authority = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+
+    let mut withdraw_policy = WithdrawPolicy::default();
+    assert_eq!(
+        withdraw_policy.initialize(
+            authority,
+            &WithdrawPolicyParams {
+                destinations: vec![destination; 9],
+                timelock: 0,
+            }
+        ),
+        Err(ErrorCode::InvalidWithdrawPolicy)
+    );
+
+    assert_eq!(
+        withdraw_policy.initialize(
+            authority,
+            &WithdrawPolicyParams {
+                destinations: vec![destination],
+                timelock: MAX_WITHDRAW_POLICY_TIMELOCK + 1,
+            }
+        ),
+        Err(ErrorCode::InvalidWithdrawPolicy)
+    );
+
+    withdraw_policy
+        .initialize(
+            authority,
+            &WithdrawPolicyParams {
+                destinations: vec![destination],
+                timelock: 3600,
+            },
+        )
+        .unwrap();
+
+    assert!(withdraw_policy.is_destination_allowed(&destination, 0));
+    assert!(!withdraw_policy.is_destination_allowed(&Pubkey::new_unique(), 0));
+}
+
+#[test]
+fn timelocked_update() {
+    let old_destination = Pubkey::new_unique();
+    let new_destination = Pubkey::new_unique();
+
+    let mut withdraw_policy = WithdrawPolicy::default();
+    withdraw_policy
+        .initialize(
+            Pubkey::new_unique(),
+            &WithdrawPolicyParams {
+                destinations: vec![old_destination],
+                timelock: 3600,
+            },
+        )
+        .unwrap();
+
+    let now = 1_000;
+    let effective_ts = withdraw_policy
+        .request_change(
+            WithdrawPolicyChange::Update,
+            &WithdrawPolicyParams {
+                destinations: vec![new_destination],
+                timelock: 60,
+            },
+            now,
+        )
+        .unwrap();
+    assert_eq!(effective_ts, now + 3600);
+
+    // old destinations until the timelock passes
+    assert!(withdraw_policy.is_destination_allowed(&old_destination, effective_ts - 1));
+    assert!(!withdraw_policy.is_destination_allowed(&new_destination, effective_ts - 1));
+    assert!(!withdraw_policy
+        .apply_pending_update(effective_ts - 1)
+        .unwrap());
+
+    assert!(!withdraw_policy.is_destination_allowed(&old_destination, effective_ts));
+    assert!(withdraw_policy.is_destination_allowed(&new_destination, effective_ts));
+
+    assert!(withdraw_policy.apply_pending_update(effective_ts).unwrap());
+    assert_eq!(withdraw_policy.timelock, 60);
+    assert!(!withdraw_policy.has_pending_change());
+    assert!(withdraw_policy.is_destination_allowed(&new_destination, effective_ts));
+
+    // a new request waits on the new timelock
+    let effective_ts = withdraw_policy
+        .request_change(
+            WithdrawPolicyChange::Update,
+            &WithdrawPolicyParams::default(),
+            now + 4000,
+        )
+        .unwrap();
+    assert_eq!(effective_ts, now + 4060);
+
+    withdraw_policy.clear_pending_change();
+    assert!(withdraw_policy.is_destination_allowed(&new_destination, effective_ts));
+}
+
+#[test]
+fn timelocked_delete() {
+    let mut withdraw_policy = WithdrawPolicy::default();
+    withdraw_policy
+        .initialize(
+            Pubkey::new_unique(),
+            &WithdrawPolicyParams {
+                destinations: vec![Pubkey::new_unique()],
+                timelock: 3600,
+            },
+        )
+        .unwrap();
+
+    assert_eq!(
+        withdraw_policy.validate_delete(0),
+        Err(ErrorCode::WithdrawPolicyTimelocked)
+    );
+
+    let effective_ts = withdraw_policy
+        .request_change(
+            WithdrawPolicyChange::Delete,
+            &WithdrawPolicyParams::default(),
+            0,
+        )
+        .unwrap();
+
+    assert_eq!(
+        withdraw_policy.validate_delete(effective_ts - 1),
+        Err(ErrorCode::WithdrawPolicyTimelocked)
+    );
+    assert!(withdraw_policy.validate_delete(effective_ts).is_ok());
+
+    // a pending delete doesn't change the destinations
+    assert_eq!(withdraw_policy.get_destinations(effective_ts).len(), 1);
+}
@@ -5,7 +5,7 @@ use crate::math::constants::{
     FEE_DENOMINATOR, FEE_PERCENTAGE_DENOMINATOR, OPEN_ORDER_MARGIN_REQUIREMENT,
     PERCENTAGE_PRECISION,
 };
-use crate::state::state::{FeeStructure, FeeTier};
+use crate::state::state::{FeeStructure, FeeTier, MarketFeeOverrideParams};
 use crate::validate;
 
 #[cfg(test)]
@@ -116,3 +116,26 @@ pub fn validate_fee_tier(
 
     Ok(())
 }
+
+pub fn validate_market_fee_override(params: &MarketFeeOverrideParams) -> DriftResult {
+    if let Some(taker_fee_multiplier) = params.taker_fee_multiplier {
+        validate!(
+            taker_fee_multiplier as u32 <= 3 * FEE_PERCENTAGE_DENOMINATOR, // <= 3x the tier's fee
+            ErrorCode::InvalidFeeStructure,
+            "invalid taker fee override multiplier ({})",
+            taker_fee_multiplier
+        )?;
+    }
+
+    // rebates above the taker fee are capped at fill time
+    if let Some(maker_rebate_numerator) = params.maker_rebate_numerator {
+        validate!(
+            maker_rebate_numerator <= 100, // <= 10bps
+            ErrorCode::InvalidFeeStructure,
+            "invalid maker rebate override numerator ({})",
+            maker_rebate_numerator
+        )?;
+    }
+
+    Ok(())
+}
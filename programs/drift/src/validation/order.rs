@@ -8,9 +8,13 @@ use crate::math::constants::MAX_PREDICTION_MARKET_PRICE;
 use crate::math::orders::{
     calculate_base_asset_amount_to_fill_up_to_limit_price, is_multiple_of_step_size,
 };
+use crate::math::safe_math::SafeMath;
+use crate::state::order_params::OrderParams;
 use crate::state::paused_operations::PerpOperation;
 use crate::state::perp_market::PerpMarket;
-use crate::state::user::{Order, OrderTriggerCondition, OrderType};
+use crate::state::user::{
+    Order, OrderBitFlag, OrderTriggerCondition, OrderTriggerSource, OrderType,
+};
 use crate::validate;
 
 #[cfg(test)]
@@ -40,6 +44,14 @@ pub fn validate_order(
         OrderType::Oracle => {
             validate_oracle_order(order, market.amm.order_step_size, market.amm.min_order_size)?
         }
+        OrderType::TrailingStop => validate_trailing_stop_order(
+            order,
+            market.amm.order_step_size,
+            market.amm.min_order_size,
+        )?,
+        OrderType::Twap => {
+            validate_twap_order(order, market.amm.order_step_size, market.amm.min_order_size)?
+        }
     }
 
     if market.is_prediction_market() {
@@ -161,6 +173,10 @@ fn validate_limit_order(
         return Err(ErrorCode::InvalidOrderTrigger);
     }
 
+    if order.is_iceberg() {
+        validate_iceberg_order(order, market.amm.order_step_size)?;
+    }
+
     if order.post_only {
         validate!(
             !order.has_auction(),
@@ -280,10 +296,7 @@ fn validate_trigger_limit_order(order: &Order, step_size: u64, min_order_size: u
         return Err(ErrorCode::InvalidOrderLimitPrice);
     }
 
-    if order.trigger_price == 0 {
-        msg!("Trigger price == 0");
-        return Err(ErrorCode::InvalidOrderTrigger);
-    }
+    validate_trigger_threshold(order)?;
 
     if order.post_only {
         msg!("Trigger limit order can not be post only");
@@ -318,10 +331,7 @@ fn validate_trigger_market_order(
         return Err(ErrorCode::InvalidOrderLimitPrice);
     }
 
-    if order.trigger_price == 0 {
-        msg!("Trigger market order trigger_price == 0");
-        return Err(ErrorCode::InvalidOrderTrigger);
-    }
+    validate_trigger_threshold(order)?;
 
     if order.post_only {
         msg!("Trigger market order can not be post only");
@@ -336,6 +346,145 @@ fn validate_trigger_market_order(
     Ok(())
 }
 
+/// Price thresholds must be set, rate thresholds can be zero or negative (e.g. trigger when funding flips)
+fn validate_trigger_threshold(order: &Order) -> DriftResult {
+    let trigger_source = order.trigger_source;
+    let threshold = order.get_trigger_threshold()?;
+
+    if trigger_source.is_price() && threshold == 0 {
+        msg!("Trigger price == 0 for {:?} trigger", trigger_source);
+        return Err(ErrorCode::InvalidOrderTrigger);
+    }
+
+    Ok(())
+}
+
+fn validate_trailing_stop_order(order: &Order, step_size: u64, min_order_size: u64) -> DriftResult {
+    validate_base_asset_amount(order, step_size, min_order_size, order.reduce_only)?;
+
+    if order.trigger_source != OrderTriggerSource::Oracle {
+        msg!("Trailing stop order must trigger off the oracle");
+        return Err(ErrorCode::InvalidOrderTriggerSource);
+    }
+
+    match (order.direction, order.trigger_condition) {
+        (PositionDirection::Long, OrderTriggerCondition::Above)
+        | (PositionDirection::Short, OrderTriggerCondition::Below) => {}
+        _ => {
+            msg!("Trailing stop must be a long above or short below trigger");
+            return Err(ErrorCode::InvalidTriggerOrderCondition);
+        }
+    }
+
+    if order.price > 0 {
+        msg!("Trailing stop order should not have price");
+        return Err(ErrorCode::InvalidOrderLimitPrice);
+    }
+
+    if order.trigger_price == 0 {
+        msg!("Trailing stop order trigger_price == 0");
+        return Err(ErrorCode::InvalidOrderTrigger);
+    }
+
+    if order.post_only {
+        msg!("Trailing stop order can not be post only");
+        return Err(ErrorCode::InvalidOrderPostOnly);
+    }
+
+    if order.oracle_price_offset != 0 {
+        msg!("Trailing stop order should not have oracle price offset");
+        return Err(ErrorCode::InvalidOrderOracleOffset);
+    }
+
+    if order.trailing_stop_distance == 0 {
+        msg!("Trailing stop order must have a trailing stop distance");
+        return Err(ErrorCode::InvalidOrderOracleOffset);
+    }
+
+    if order.is_bit_flag_set(OrderBitFlag::TrailingStopBps)
+        && order.trailing_stop_distance >= 10_000
+    {
+        msg!("Trailing stop order bps offset must be < 10000");
+        return Err(ErrorCode::InvalidOrderOracleOffset);
+    }
+
+    Ok(())
+}
+
+fn validate_twap_order(order: &Order, step_size: u64, min_order_size: u64) -> DriftResult {
+    validate_base_asset_amount(order, step_size, min_order_size, false)?;
+
+    if order.price == 0 && !order.has_oracle_price_offset() {
+        msg!("Twap order must have a price or oracle price offset limit");
+        return Err(ErrorCode::InvalidOrderLimitPrice);
+    }
+
+    if order.has_oracle_price_offset() && order.price != 0 {
+        msg!("Twap order price must be 0 for oracle offset twap");
+        return Err(ErrorCode::InvalidOrderOracleOffset);
+    }
+
+    if order.post_only {
+        msg!("Twap order can not be post only");
+        return Err(ErrorCode::InvalidOrderPostOnly);
+    }
+
+    if order.reduce_only {
+        msg!("Twap order can not be reduce only");
+        return Err(ErrorCode::InvalidTwapOrder);
+    }
+
+    if order.immediate_or_cancel {
+        msg!("Twap order can not be immediate or cancel");
+        return Err(ErrorCode::InvalidOrderIOC);
+    }
+
+    let num_slices = order.get_twap_num_slices();
+    validate!(
+        num_slices >= 2 && order.get_twap_slice_interval() > 0,
+        ErrorCode::InvalidTwapOrder,
+        "Twap order needs at least 2 slices and a non-zero slice interval"
+    )?;
+
+    let slice_size = order.base_asset_amount.safe_div(num_slices.cast()?)?;
+    validate!(
+        slice_size >= step_size,
+        ErrorCode::InvalidTwapOrder,
+        "Twap slice size ({}) < step size ({})",
+        slice_size,
+        step_size
+    )?;
+
+    Ok(())
+}
+
+fn validate_iceberg_order(order: &Order, step_size: u64) -> DriftResult {
+    let clip_size = order.iceberg_clip_size;
+
+    validate!(
+        is_multiple_of_step_size(clip_size, step_size)?,
+        ErrorCode::InvalidIcebergOrder,
+        "Iceberg clip size ({}) not a multiple of the step size ({})",
+        clip_size,
+        step_size
+    )?;
+
+    validate!(
+        clip_size < order.base_asset_amount,
+        ErrorCode::InvalidIcebergOrder,
+        "Iceberg clip size ({}) must be less than base asset amount ({})",
+        clip_size,
+        order.base_asset_amount
+    )?;
+
+    if order.immediate_or_cancel {
+        msg!("Iceberg order can not be immediate or cancel");
+        return Err(ErrorCode::InvalidOrderIOC);
+    }
+
+    Ok(())
+}
+
 fn validate_base_asset_amount(
     order: &Order,
     step_size: u64,
@@ -473,6 +622,11 @@ fn validate_oracle_auction_params(order: &Order) -> DriftResult {
 }
 
 pub fn validate_spot_order(order: &Order, step_size: u64, min_order_size: u64) -> DriftResult {
+    if order.must_be_triggered() && order.trigger_source != OrderTriggerSource::Oracle {
+        msg!("Spot trigger orders must trigger off the oracle");
+        return Err(ErrorCode::InvalidOrderTriggerSource);
+    }
+
     match order.order_type {
         OrderType::Market => validate_market_order(order, step_size, min_order_size)?,
         OrderType::Limit => validate_spot_limit_order(order, step_size, min_order_size)?,
@@ -481,6 +635,11 @@ pub fn validate_spot_order(order: &Order, step_size: u64, min_order_size: u64) -
         }
         OrderType::TriggerLimit => validate_trigger_limit_order(order, step_size, min_order_size)?,
         OrderType::Oracle => validate_oracle_order(order, step_size, min_order_size)?,
+        OrderType::TrailingStop => validate_trailing_stop_order(order, step_size, min_order_size)?,
+        OrderType::Twap => {
+            msg!("Twap orders are only supported for perp markets");
+            return Err(ErrorCode::InvalidTwapOrder);
+        }
     }
 
     Ok(())
@@ -504,6 +663,10 @@ fn validate_spot_limit_order(order: &Order, step_size: u64, min_order_size: u64)
         return Err(ErrorCode::InvalidOrderTrigger);
     }
 
+    if order.is_iceberg() {
+        validate_iceberg_order(order, step_size)?;
+    }
+
     if order.post_only {
         validate!(
             !order.has_auction(),
@@ -517,6 +680,75 @@ fn validate_spot_limit_order(order: &Order, step_size: u64, min_order_size: u64)
     Ok(())
 }
 
+/// Orders flagged with OrderGroup in a single place_orders call are linked as one-cancels-other.
+/// They must share a market, so resolving the group only touches the market being filled,
+/// triggered or canceled. If one of them is a bracket entry, the rest must be trigger orders
+/// closing the entry's position
+pub fn validate_order_group(order_params: &[OrderParams]) -> DriftResult<bool> {
+    let grouped_orders = order_params
+        .iter()
+        .filter(|params| params.is_order_group())
+        .collect::<Vec<_>>();
+
+    if grouped_orders.is_empty() {
+        validate!(
+            !order_params.iter().any(|params| params.is_bracket_entry()),
+            ErrorCode::InvalidOrderGroup,
+            "bracket entry must be part of an order group"
+        )?;
+
+        return Ok(false);
+    }
+
+    validate!(
+        grouped_orders.len() >= 2,
+        ErrorCode::InvalidOrderGroup,
+        "order group must have at least 2 orders"
+    )?;
+
+    let first = grouped_orders[0];
+    validate!(
+        grouped_orders
+            .iter()
+            .all(|params| params.market_type == first.market_type
+                && params.market_index == first.market_index),
+        ErrorCode::InvalidOrderGroup,
+        "order group orders must be in the same market"
+    )?;
+
+    let entries = grouped_orders
+        .iter()
+        .filter(|params| params.is_bracket_entry())
+        .collect::<Vec<_>>();
+
+    validate!(
+        entries.len() <= 1,
+        ErrorCode::InvalidOrderGroup,
+        "order group can only have one bracket entry"
+    )?;
+
+    if let Some(entry) = entries.first() {
+        for params in grouped_orders
+            .iter()
+            .filter(|params| !params.is_bracket_entry())
+        {
+            validate!(
+                params.is_trigger_order(),
+                ErrorCode::InvalidOrderGroup,
+                "bracket orders must be trigger orders"
+            )?;
+
+            validate!(
+                params.direction != entry.direction,
+                ErrorCode::InvalidOrderGroup,
+                "bracket orders must be opposite direction of the entry"
+            )?;
+        }
+    }
+
+    Ok(true)
+}
+
 pub fn validate_order_for_force_reduce_only(order: &Order, existing_position: i64) -> DriftResult {
     validate!(
         order.reduce_only,
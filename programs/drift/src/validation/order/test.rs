@@ -109,3 +109,119 @@ mod prediction_markets {
         assert_eq!(res, Err(ErrorCode::InvalidPredictionMarketOrder));
     }
 }
+
+mod order_group {
+    use crate::error::ErrorCode;
+    use crate::state::order_params::{OrderParams, OrderParamsBitFlag};
+    use crate::state::user::{OrderTriggerCondition, OrderType};
+    use crate::validation::order::validate_order_group;
+    use crate::{MarketType, PositionDirection, BASE_PRECISION_U64, PRICE_PRECISION_U64};
+
+    fn entry() -> OrderParams {
+        OrderParams {
+            order_type: OrderType::Limit,
+            market_type: MarketType::Perp,
+            direction: PositionDirection::Long,
+            base_asset_amount: BASE_PRECISION_U64,
+            price: 100 * PRICE_PRECISION_U64,
+            bit_flags: OrderParamsBitFlag::OrderGroup as u8
+                | OrderParamsBitFlag::BracketEntry as u8,
+            ..OrderParams::default()
+        }
+    }
+
+    fn stop_loss() -> OrderParams {
+        OrderParams {
+            order_type: OrderType::TriggerMarket,
+            market_type: MarketType::Perp,
+            direction: PositionDirection::Short,
+            base_asset_amount: BASE_PRECISION_U64,
+            trigger_price: Some(90 * PRICE_PRECISION_U64),
+            trigger_condition: OrderTriggerCondition::Below,
+            reduce_only: true,
+            bit_flags: OrderParamsBitFlag::OrderGroup as u8,
+            ..OrderParams::default()
+        }
+    }
+
+    #[test]
+    fn no_group() {
+        let params = vec![OrderParams::default(), OrderParams::default()];
+        assert_eq!(validate_order_group(&params), Ok(false));
+    }
+
+    #[test]
+    fn one_cancels_other() {
+        let take_profit = OrderParams {
+            order_type: OrderType::Limit,
+            price: 110 * PRICE_PRECISION_U64,
+            trigger_price: None,
+            ..stop_loss()
+        };
+
+        let params = vec![take_profit, stop_loss()];
+        assert_eq!(validate_order_group(&params), Ok(true));
+
+        let params = vec![stop_loss()];
+        assert_eq!(
+            validate_order_group(&params),
+            Err(ErrorCode::InvalidOrderGroup)
+        );
+
+        // siblings must share a market
+        let other_market = OrderParams {
+            market_index: 1,
+            ..take_profit
+        };
+        let params = vec![other_market, stop_loss()];
+        assert_eq!(
+            validate_order_group(&params),
+            Err(ErrorCode::InvalidOrderGroup)
+        );
+
+        let other_market_type = OrderParams {
+            market_type: MarketType::Spot,
+            ..take_profit
+        };
+        let params = vec![other_market_type, stop_loss()];
+        assert_eq!(
+            validate_order_group(&params),
+            Err(ErrorCode::InvalidOrderGroup)
+        );
+    }
+
+    #[test]
+    fn bracket() {
+        let params = vec![entry(), stop_loss()];
+        assert_eq!(validate_order_group(&params), Ok(true));
+
+        // bracket orders must be trigger orders
+        let take_profit = OrderParams {
+            order_type: OrderType::Limit,
+            ..stop_loss()
+        };
+        let params = vec![entry(), take_profit];
+        assert_eq!(
+            validate_order_group(&params),
+            Err(ErrorCode::InvalidOrderGroup)
+        );
+
+        // bracket orders must close the entry
+        let stop_loss_same_direction = OrderParams {
+            direction: PositionDirection::Long,
+            ..stop_loss()
+        };
+        let params = vec![entry(), stop_loss_same_direction];
+        assert_eq!(
+            validate_order_group(&params),
+            Err(ErrorCode::InvalidOrderGroup)
+        );
+
+        // only one entry
+        let params = vec![entry(), entry(), stop_loss()];
+        assert_eq!(
+            validate_order_group(&params),
+            Err(ErrorCode::InvalidOrderGroup)
+        );
+    }
+}
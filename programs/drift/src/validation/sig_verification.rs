@@ -1,8 +1,9 @@
 use crate::error::ErrorCode;
 use crate::state::order_params::{
-    OrderParams, SignedMsgOrderParamsDelegateMessage, SignedMsgOrderParamsMessage,
-    SignedMsgTriggerOrderParams,
+    OrderParams, SignedMsgBatchOrderParamsDelegateMessage, SignedMsgBatchOrderParamsMessage,
+    SignedMsgOrderParamsDelegateMessage, SignedMsgOrderParamsMessage, SignedMsgTriggerOrderParams,
 };
+use crate::state::user::MarketType;
 use anchor_lang::prelude::*;
 use bytemuck::try_cast_slice;
 use bytemuck::{Pod, Zeroable};
@@ -10,7 +11,9 @@ use byteorder::ByteOrder;
 use byteorder::LE;
 use solana_program::ed25519_program::ID as ED25519_ID;
 use solana_program::instruction::Instruction;
+use solana_program::keccak;
 use solana_program::program_memory::sol_memcmp;
+use solana_program::secp256k1_program::ID as SECP256K1_ID;
 use solana_program::sysvar;
 use std::convert::TryInto;
 
@@ -23,6 +26,32 @@ const SIGNATURE_LEN: u16 = 64;
 const PUBKEY_LEN: u16 = 32;
 const MESSAGE_SIZE_LEN: u16 = 2;
 
+const SECP256K1_SIGNATURE_OFFSETS_START: usize = 1;
+const SECP256K1_SIGNATURE_OFFSETS_LEN: usize = 11;
+const SECP256K1_SIGNATURE_LEN: usize = 64;
+const ETH_ADDRESS_LEN: usize = 20;
+
+const EIP191_PREFIX: &[u8] = b"\x19Ethereum Signed Message:\n";
+const EIP712_DOMAIN_TYPE: &[u8] = b"EIP712Domain(string name,string version,bytes32 salt)";
+const EIP712_DOMAIN_NAME: &[u8] = b"Drift Protocol";
+const EIP712_DOMAIN_VERSION: &[u8] = b"1";
+const EIP712_SIGNED_MSG_TYPE: &[u8] = b"SignedMsg(bytes message)";
+
+pub const MAX_SIGNED_MSG_BATCH_ORDERS: usize = 8;
+
+/// sha256("global:SignedMsgBatchOrderParamsMessage")[..8]
+const SIGNED_MSG_BATCH_ORDER_PARAMS_MESSAGE_DISCRIMINATOR: [u8; 8] =
+    [252, 231, 72, 75, 121, 118, 245, 170];
+/// sha256("global:SignedMsgBatchOrderParamsDelegateMessage")[..8]
+const SIGNED_MSG_BATCH_ORDER_PARAMS_DELEGATE_MESSAGE_DISCRIMINATOR: [u8; 8] =
+    [154, 39, 239, 198, 152, 177, 60, 15];
+/// sha256("global:SignedMsgCancelOrdersMessage")[..8]
+pub const SIGNED_MSG_CANCEL_ORDERS_MESSAGE_DISCRIMINATOR: [u8; 8] =
+    [144, 99, 170, 168, 152, 91, 194, 40];
+/// sha256("global:SignedMsgCancelOrdersBeforeSlotMessage")[..8]
+pub const SIGNED_MSG_CANCEL_ORDERS_BEFORE_SLOT_MESSAGE_DISCRIMINATOR: [u8; 8] =
+    [40, 243, 79, 202, 156, 220, 144, 120];
+
 /// Part of the inputs to the built-in `ed25519_program` on Solana that represents a single
 /// signature verification request.
 ///
@@ -61,6 +90,8 @@ pub struct VerifiedMessage {
     pub builder_idx: Option<u8>,
     pub builder_fee_tenth_bps: Option<u16>,
     pub isolated_position_deposit: Option<u64>,
+    /// Orders placed after signed_msg_order_params for batch messages
+    pub batch_order_params: Vec<OrderParams>,
     pub signature: [u8; 64],
 }
 
@@ -73,6 +104,15 @@ pub fn deserialize_into_verified_message(
     signature: &[u8; 64],
     is_delegate_signer: bool,
 ) -> Result<VerifiedMessage> {
+    let batch_discriminator = if is_delegate_signer {
+        SIGNED_MSG_BATCH_ORDER_PARAMS_DELEGATE_MESSAGE_DISCRIMINATOR
+    } else {
+        SIGNED_MSG_BATCH_ORDER_PARAMS_MESSAGE_DISCRIMINATOR
+    };
+    if payload.len() >= 8 && payload[..8] == batch_discriminator {
+        return deserialize_into_verified_batch_message(payload, signature, is_delegate_signer);
+    }
+
     if is_delegate_signer {
         if payload.len() < 8 {
             return Err(SignatureVerificationError::InvalidMessageDataSize.into());
@@ -90,7 +130,7 @@ pub fn deserialize_into_verified_message(
             SignatureVerificationError::InvalidMessageDataSize
         })?;
 
-        return Ok(VerifiedMessage {
+        let verified_message = VerifiedMessage {
             signed_msg_order_params: deserialized.signed_msg_order_params,
             sub_account_id: None,
             delegate_signed_taker_pubkey: Some(deserialized.taker_pubkey),
@@ -102,8 +142,12 @@ pub fn deserialize_into_verified_message(
             builder_idx: deserialized.builder_idx,
             builder_fee_tenth_bps: deserialized.builder_fee_tenth_bps,
             isolated_position_deposit: deserialized.isolated_position_deposit,
+            batch_order_params: vec![],
             signature: *signature,
-        });
+        };
+        validate_verified_message_market_type(&verified_message)?;
+
+        return Ok(verified_message);
     } else {
         if payload.len() < 8 {
             return Err(SignatureVerificationError::InvalidMessageDataSize.into());
@@ -120,7 +164,7 @@ pub fn deserialize_into_verified_message(
             msg!("Invalid delegate message encoding for with is_delegate_signer = false");
             SignatureVerificationError::InvalidMessageDataSize
         })?;
-        return Ok(VerifiedMessage {
+        let verified_message = VerifiedMessage {
             signed_msg_order_params: deserialized.signed_msg_order_params,
             sub_account_id: Some(deserialized.sub_account_id),
             delegate_signed_taker_pubkey: None,
@@ -132,9 +176,87 @@ pub fn deserialize_into_verified_message(
             builder_idx: deserialized.builder_idx,
             builder_fee_tenth_bps: deserialized.builder_fee_tenth_bps,
             isolated_position_deposit: deserialized.isolated_position_deposit,
+            batch_order_params: vec![],
             signature: *signature,
-        });
+        };
+        validate_verified_message_market_type(&verified_message)?;
+
+        return Ok(verified_message);
+    }
+}
+
+fn deserialize_into_verified_batch_message(
+    payload: Vec<u8>,
+    signature: &[u8; 64],
+    is_delegate_signer: bool,
+) -> Result<VerifiedMessage> {
+    let (mut signed_msg_order_params, sub_account_id, delegate_signed_taker_pubkey, slot, uuid) =
+        if is_delegate_signer {
+            let deserialized: SignedMsgBatchOrderParamsDelegateMessage =
+                deserialize_signed_msg(payload)?;
+            (
+                deserialized.signed_msg_order_params,
+                None,
+                Some(deserialized.taker_pubkey),
+                deserialized.slot,
+                deserialized.uuid,
+            )
+        } else {
+            let deserialized: SignedMsgBatchOrderParamsMessage = deserialize_signed_msg(payload)?;
+            (
+                deserialized.signed_msg_order_params,
+                Some(deserialized.sub_account_id),
+                None,
+                deserialized.slot,
+                deserialized.uuid,
+            )
+        };
+
+    if signed_msg_order_params.len() < 2
+        || signed_msg_order_params.len() > MAX_SIGNED_MSG_BATCH_ORDERS
+    {
+        msg!(
+            "SignedMsg batch must have between 2 and {} orders",
+            MAX_SIGNED_MSG_BATCH_ORDERS
+        );
+        return Err(ErrorCode::InvalidSignedMsgOrderParam.into());
+    }
+
+    let batch_order_params = signed_msg_order_params.split_off(1);
+
+    Ok(VerifiedMessage {
+        signed_msg_order_params: signed_msg_order_params[0],
+        sub_account_id,
+        delegate_signed_taker_pubkey,
+        slot,
+        uuid,
+        take_profit_order_params: None,
+        stop_loss_order_params: None,
+        max_margin_ratio: None,
+        builder_idx: None,
+        builder_fee_tenth_bps: None,
+        isolated_position_deposit: None,
+        batch_order_params,
+        signature: *signature,
+    })
+}
+
+/// Signed spot takers only place the taker order. Trigger orders, margin ratios, isolated deposits
+/// and builder fees are perp only
+fn validate_verified_message_market_type(verified_message: &VerifiedMessage) -> Result<()> {
+    if verified_message.signed_msg_order_params.market_type == MarketType::Spot
+        && (verified_message.take_profit_order_params.is_some()
+            || verified_message.stop_loss_order_params.is_some()
+            || verified_message.max_margin_ratio.is_some()
+            || verified_message.isolated_position_deposit.is_some()
+            || verified_message.builder_idx.is_some()
+            || verified_message.builder_fee_tenth_bps.is_some())
+    {
+        msg!("SignedMsg spot order can not have perp only params");
+        return Err(ErrorCode::InvalidSignedMsgOrderParam.into());
     }
+
+    Ok(())
 }
 
 /// Check Ed25519Program instruction data verifies the given msg
@@ -153,6 +275,67 @@ pub fn verify_and_decode_ed25519_msg(
     msg: &[u8],
     is_delegate_signer: bool,
 ) -> Result<VerifiedMessage> {
+    let (payload, signature) = verify_ed25519_msg(
+        ed25519_ix,
+        instructions_sysvar,
+        current_ix_index,
+        signer,
+        msg,
+    )?;
+
+    deserialize_into_verified_message(payload, &signature, is_delegate_signer)
+}
+
+/// Verifies a signed msg message other than an order, e.g. SignedMsgCancelOrdersMessage, the same
+/// way as verify_and_decode_ed25519_msg
+pub fn verify_and_decode_ed25519_signed_msg<T: AnchorDeserialize>(
+    ed25519_ix: &Instruction,
+    instructions_sysvar: &AccountInfo,
+    current_ix_index: u16,
+    signer: &[u8; 32],
+    msg: &[u8],
+    discriminator: [u8; 8],
+) -> Result<T> {
+    let (payload, _) = verify_ed25519_msg(
+        ed25519_ix,
+        instructions_sysvar,
+        current_ix_index,
+        signer,
+        msg,
+    )?;
+
+    deserialize_signed_msg(payload, discriminator)
+}
+
+/// The payload must start with the message's discriminator and be consumed entirely, so a message
+/// signed for one purpose can't be decoded as another
+pub fn deserialize_signed_msg<T: AnchorDeserialize>(
+    payload: Vec<u8>,
+    discriminator: [u8; 8],
+) -> Result<T> {
+    if payload.len() < 8 {
+        return Err(SignatureVerificationError::InvalidMessageDataSize.into());
+    }
+
+    if payload[..8] != discriminator {
+        msg!("Invalid signed msg message discriminator");
+        return Err(SignatureVerificationError::InvalidMessageDataSize.into());
+    }
+
+    T::try_from_slice(&payload[8..]).map_err(|_| {
+        msg!("Invalid signed msg message encoding");
+        SignatureVerificationError::InvalidMessageDataSize.into()
+    })
+}
+
+/// Returns the decoded payload and signature
+fn verify_ed25519_msg(
+    ed25519_ix: &Instruction,
+    instructions_sysvar: &AccountInfo,
+    current_ix_index: u16,
+    signer: &[u8; 32],
+    msg: &[u8],
+) -> Result<(Vec<u8>, [u8; 64])> {
     if ed25519_ix.program_id != ED25519_ID || ed25519_ix.accounts.len() != 0 {
         msg!("Invalid Ix: program ID: {:?}", ed25519_ix.program_id);
         msg!("Invalid Ix: accounts: {:?}", ed25519_ix.accounts.len());
@@ -309,7 +492,120 @@ pub fn verify_and_decode_ed25519_msg(
     let payload =
         hex::decode(payload).map_err(|_| SignatureVerificationError::InvalidMessageHex)?;
 
-    deserialize_into_verified_message(payload, signature, is_delegate_signer)
+    Ok((payload, *signature))
+}
+
+/// EIP-191 personal_sign message for the hex encoded signed msg
+pub fn get_eip191_message(msg: &[u8]) -> Vec<u8> {
+    let mut message = EIP191_PREFIX.to_vec();
+    message.extend_from_slice(msg.len().to_string().as_bytes());
+    message.extend_from_slice(msg);
+    message
+}
+
+/// EIP-712 typed data for SignedMsg(bytes message) in the Drift Protocol domain, where message is
+/// the decoded signed msg. The domain salt is the program id, so signatures can't be replayed
+/// against another deployment
+pub fn get_eip712_message(payload: &[u8]) -> Vec<u8> {
+    let domain_separator = keccak::hashv(&[
+        keccak::hash(EIP712_DOMAIN_TYPE).as_ref(),
+        keccak::hash(EIP712_DOMAIN_NAME).as_ref(),
+        keccak::hash(EIP712_DOMAIN_VERSION).as_ref(),
+        crate::id().as_ref(),
+    ]);
+    let struct_hash = keccak::hashv(&[
+        keccak::hash(EIP712_SIGNED_MSG_TYPE).as_ref(),
+        keccak::hash(payload).as_ref(),
+    ]);
+
+    let mut message = vec![0x19, 0x01];
+    message.extend_from_slice(domain_separator.as_ref());
+    message.extend_from_slice(struct_hash.as_ref());
+    message
+}
+
+/// Check Secp256k1Program instruction data verifies the given msg was signed by evm_address, either
+/// with EIP-191 (personal_sign of the hex encoded msg) or EIP-712
+///
+/// Unlike ed25519, the signature, eth address and signed message are read from the secp256k1
+/// instruction's own data
+pub fn verify_and_decode_secp256k1_msg(
+    secp256k1_ix: &Instruction,
+    secp256k1_ix_index: u16,
+    evm_address: &[u8; 20],
+    msg: &[u8],
+    is_delegate_signer: bool,
+) -> Result<VerifiedMessage> {
+    if secp256k1_ix.program_id != SECP256K1_ID || secp256k1_ix.accounts.len() != 0 {
+        msg!("Invalid Ix: program ID: {:?}", secp256k1_ix.program_id);
+        msg!("Invalid Ix: accounts: {:?}", secp256k1_ix.accounts.len());
+        return Err(ErrorCode::SigVerificationFailed.into());
+    }
+
+    let secp256k1_ix_data = &secp256k1_ix.data;
+    if secp256k1_ix_data.len() < SECP256K1_SIGNATURE_OFFSETS_START + SECP256K1_SIGNATURE_OFFSETS_LEN
+    {
+        msg!(
+            "Invalid Ix, should have one signature offsets. data: {:?}",
+            secp256k1_ix_data.len(),
+        );
+        return Err(SignatureVerificationError::InvalidSecp256k1InstructionDataLength.into());
+    }
+
+    // Parse the ix data into the offsets
+    let num_signatures = secp256k1_ix_data[0];
+    if num_signatures != 1 {
+        return Err(SignatureVerificationError::InvalidSignatureIndex.into());
+    }
+
+    let offsets = &secp256k1_ix_data[SECP256K1_SIGNATURE_OFFSETS_START
+        ..SECP256K1_SIGNATURE_OFFSETS_START + SECP256K1_SIGNATURE_OFFSETS_LEN];
+    let signature_offset = usize::from(LE::read_u16(&offsets[0..2]));
+    let signature_instruction_index = offsets[2];
+    let eth_address_offset = usize::from(LE::read_u16(&offsets[3..5]));
+    let eth_address_instruction_index = offsets[5];
+    let message_data_offset = usize::from(LE::read_u16(&offsets[6..8]));
+    let message_data_size = usize::from(LE::read_u16(&offsets[8..10]));
+    let message_instruction_index = offsets[10];
+
+    let secp256k1_ix_index: u8 = secp256k1_ix_index
+        .try_into()
+        .map_err(|_| SignatureVerificationError::InvalidInstructionIndex)?;
+    if signature_instruction_index != secp256k1_ix_index
+        || eth_address_instruction_index != secp256k1_ix_index
+        || message_instruction_index != secp256k1_ix_index
+    {
+        return Err(SignatureVerificationError::InvalidInstructionIndex.into());
+    }
+
+    let eth_address = secp256k1_ix_data
+        .get(eth_address_offset..eth_address_offset + ETH_ADDRESS_LEN)
+        .ok_or(SignatureVerificationError::InvalidPublicKeyOffset)?;
+    if eth_address != evm_address {
+        msg!("Invalid Ix: message signed by: {:?}", eth_address);
+        msg!("Invalid Ix: expected eth address: {:?}", evm_address);
+        return Err(ErrorCode::SigVerificationFailed.into());
+    }
+
+    let signature: [u8; 64] = secp256k1_ix_data
+        .get(signature_offset..signature_offset + SECP256K1_SIGNATURE_LEN)
+        .ok_or(SignatureVerificationError::InvalidSignatureOffset)?
+        .try_into()
+        .unwrap();
+
+    let signed_message = secp256k1_ix_data
+        .get(message_data_offset..message_data_offset + message_data_size)
+        .ok_or(SignatureVerificationError::InvalidMessageOffset)?;
+
+    let payload = hex::decode(msg).map_err(|_| SignatureVerificationError::InvalidMessageHex)?;
+
+    if !slice_eq(signed_message, &get_eip191_message(msg))
+        && !slice_eq(signed_message, &get_eip712_message(&payload))
+    {
+        return Err(SignatureVerificationError::InvalidMessageData.into());
+    }
+
+    deserialize_into_verified_message(payload, &signature, is_delegate_signer)
 }
 
 #[error_code]
@@ -339,4 +635,6 @@ pub enum SignatureVerificationError {
     InvalidMessageData,
     #[msg("loading custom ix at index failed")]
     LoadInstructionAtFailed,
+    #[msg("invalid secp256k1 instruction data length")]
+    InvalidSecp256k1InstructionDataLength,
 }
@@ -456,4 +456,262 @@ mod sig_verification {
         assert_eq!(order_params.auction_start_price, Some(240000000i64));
         assert_eq!(order_params.auction_end_price, Some(238000000i64));
     }
+
+    #[test]
+    fn test_deserialize_into_verified_message_spot() {
+        use anchor_lang::AnchorSerialize;
+
+        use crate::state::order_params::{
+            OrderParams, SignedMsgOrderParamsMessage, SignedMsgTriggerOrderParams,
+        };
+        use crate::state::user::{MarketType, OrderType};
+
+        let signature = [1u8; 64];
+        let message = SignedMsgOrderParamsMessage {
+            signed_msg_order_params: OrderParams {
+                order_type: OrderType::Limit,
+                market_type: MarketType::Spot,
+                market_index: 1,
+                direction: PositionDirection::Short,
+                base_asset_amount: 1000000000,
+                price: 100000000,
+                auction_duration: Some(10),
+                auction_start_price: Some(101000000),
+                auction_end_price: Some(100000000),
+                ..OrderParams::default()
+            },
+            sub_account_id: 1,
+            slot: 1000,
+            uuid: [1; 8],
+            ..SignedMsgOrderParamsMessage::default()
+        };
+
+        let mut payload = vec![0u8; 8];
+        payload.extend(message.try_to_vec().unwrap());
+
+        let verified_message =
+            deserialize_into_verified_message(payload, &signature, false).unwrap();
+        assert_eq!(verified_message.sub_account_id, Some(1));
+        assert_eq!(
+            verified_message.signed_msg_order_params,
+            message.signed_msg_order_params
+        );
+
+        // perp only params are rejected for spot
+        let message = SignedMsgOrderParamsMessage {
+            stop_loss_order_params: Some(SignedMsgTriggerOrderParams {
+                trigger_price: 90000000,
+                base_asset_amount: 1000000000,
+            }),
+            ..message
+        };
+
+        let mut payload = vec![0u8; 8];
+        payload.extend(message.try_to_vec().unwrap());
+
+        assert!(deserialize_into_verified_message(payload, &signature, false).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_signed_msg_cancel_orders() {
+        use anchor_lang::AnchorSerialize;
+
+        use crate::state::order_params::{
+            SignedMsgCancelOrdersBeforeSlotMessage, SignedMsgCancelOrdersMessage,
+        };
+        use crate::validation::sig_verification::{
+            deserialize_signed_msg, SIGNED_MSG_CANCEL_ORDERS_BEFORE_SLOT_MESSAGE_DISCRIMINATOR,
+            SIGNED_MSG_CANCEL_ORDERS_MESSAGE_DISCRIMINATOR,
+        };
+
+        let message = SignedMsgCancelOrdersMessage {
+            sub_account_id: 2,
+            slot: 1000,
+            uuids: vec![[1; 8], [2; 8]],
+        };
+
+        // sha256("global:SignedMsgCancelOrdersMessage")[..8]
+        let mut payload = vec![144, 99, 170, 168, 152, 91, 194, 40];
+        payload.extend(message.try_to_vec().unwrap());
+
+        let deserialized: SignedMsgCancelOrdersMessage = deserialize_signed_msg(
+            payload.clone(),
+            SIGNED_MSG_CANCEL_ORDERS_MESSAGE_DISCRIMINATOR,
+        )
+        .unwrap();
+        assert_eq!(deserialized, message);
+
+        let result: anchor_lang::Result<SignedMsgCancelOrdersMessage> =
+            deserialize_signed_msg(vec![0u8; 4], SIGNED_MSG_CANCEL_ORDERS_MESSAGE_DISCRIMINATOR);
+        assert!(result.is_err());
+
+        // trailing bytes aren't ignored
+        let mut trailing_payload = payload.clone();
+        trailing_payload.push(0);
+        let result: anchor_lang::Result<SignedMsgCancelOrdersMessage> = deserialize_signed_msg(
+            trailing_payload,
+            SIGNED_MSG_CANCEL_ORDERS_MESSAGE_DISCRIMINATOR,
+        );
+        assert!(result.is_err());
+
+        // a cancel orders message can't be used as a cancel before slot message
+        let result: anchor_lang::Result<SignedMsgCancelOrdersBeforeSlotMessage> =
+            deserialize_signed_msg(
+                payload,
+                SIGNED_MSG_CANCEL_ORDERS_BEFORE_SLOT_MESSAGE_DISCRIMINATOR,
+            );
+        assert!(result.is_err());
+
+        let message = SignedMsgCancelOrdersBeforeSlotMessage {
+            sub_account_id: 2,
+            slot: 1000,
+            cancel_before_slot: 900,
+        };
+
+        // sha256("global:SignedMsgCancelOrdersBeforeSlotMessage")[..8]
+        let mut payload = vec![40, 243, 79, 202, 156, 220, 144, 120];
+        payload.extend(message.try_to_vec().unwrap());
+
+        let deserialized: SignedMsgCancelOrdersBeforeSlotMessage = deserialize_signed_msg(
+            payload,
+            SIGNED_MSG_CANCEL_ORDERS_BEFORE_SLOT_MESSAGE_DISCRIMINATOR,
+        )
+        .unwrap();
+        assert_eq!(deserialized, message);
+    }
+
+    #[test]
+    fn test_deserialize_into_verified_message_batch() {
+        use anchor_lang::AnchorSerialize;
+
+        use crate::state::order_params::{OrderParams, SignedMsgBatchOrderParamsMessage};
+        use crate::state::user::{MarketType, OrderType};
+
+        let signature = [1u8; 64];
+        let perp_order = OrderParams {
+            order_type: OrderType::Market,
+            market_type: MarketType::Perp,
+            market_index: 0,
+            direction: PositionDirection::Long,
+            base_asset_amount: 1000000000,
+            auction_duration: Some(10),
+            auction_start_price: Some(100000000),
+            auction_end_price: Some(101000000),
+            ..OrderParams::default()
+        };
+        let spot_order = OrderParams {
+            market_type: MarketType::Spot,
+            market_index: 1,
+            ..perp_order
+        };
+        let message = SignedMsgBatchOrderParamsMessage {
+            signed_msg_order_params: vec![perp_order, spot_order],
+            sub_account_id: 1,
+            slot: 1000,
+            uuid: [1; 8],
+        };
+
+        // sha256("global:SignedMsgBatchOrderParamsMessage")[..8]
+        let mut payload = vec![252, 231, 72, 75, 121, 118, 245, 170];
+        payload.extend(message.try_to_vec().unwrap());
+
+        let verified_message =
+            deserialize_into_verified_message(payload, &signature, false).unwrap();
+        assert_eq!(verified_message.sub_account_id, Some(1));
+        assert_eq!(verified_message.slot, 1000);
+        assert_eq!(verified_message.uuid, [1; 8]);
+        assert_eq!(verified_message.signed_msg_order_params, perp_order);
+        assert_eq!(verified_message.batch_order_params, vec![spot_order]);
+
+        // batches need at least two orders
+        let message = SignedMsgBatchOrderParamsMessage {
+            signed_msg_order_params: vec![perp_order],
+            ..message
+        };
+        let mut payload = vec![252, 231, 72, 75, 121, 118, 245, 170];
+        payload.extend(message.try_to_vec().unwrap());
+        assert!(deserialize_into_verified_message(payload, &signature, false).is_err());
+    }
+
+    #[test]
+    fn test_verify_and_decode_secp256k1_msg() {
+        use crate::validation::sig_verification::{
+            get_eip191_message, get_eip712_message, verify_and_decode_secp256k1_msg,
+        };
+        use solana_program::instruction::Instruction;
+        use solana_program::keccak;
+        use solana_program::secp256k1_program::ID as SECP256K1_ID;
+
+        let payload = vec![
+            200, 213, 166, 94, 34, 52, 245, 93, 0, 1, 0, 1, 0, 202, 154, 59, 0, 0, 0, 0, 0, 248,
+            89, 13, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 10, 1, 192, 181, 74, 13, 0, 0, 0, 0,
+            1, 0, 248, 89, 13, 0, 0, 0, 0, 0, 0, 232, 3, 0, 0, 0, 0, 0, 0, 72, 112, 54, 84, 106,
+            83, 48, 107, 0, 0,
+        ];
+        let msg = hex::encode(&payload).into_bytes();
+        let evm_address = [7u8; 20];
+        let signature = [3u8; 64];
+        let ix_index = 0u8;
+
+        // secp256k1 ix layout: count || offsets || eth address || signature || recovery id || message
+        let build_ix = |message: &[u8]| {
+            let eth_address_offset: u16 = 12;
+            let signature_offset: u16 = eth_address_offset + 20;
+            let message_offset: u16 = signature_offset + 65;
+
+            let mut data = vec![1u8];
+            data.extend_from_slice(&signature_offset.to_le_bytes());
+            data.push(ix_index);
+            data.extend_from_slice(&eth_address_offset.to_le_bytes());
+            data.push(ix_index);
+            data.extend_from_slice(&message_offset.to_le_bytes());
+            data.extend_from_slice(&(message.len() as u16).to_le_bytes());
+            data.push(ix_index);
+            data.extend_from_slice(&evm_address);
+            data.extend_from_slice(&signature);
+            data.push(0);
+            data.extend_from_slice(message);
+
+            Instruction {
+                program_id: SECP256K1_ID,
+                accounts: vec![],
+                data,
+            }
+        };
+
+        let eip191_ix = build_ix(&get_eip191_message(&msg));
+        let verified_message =
+            verify_and_decode_secp256k1_msg(&eip191_ix, 0, &evm_address, &msg, false).unwrap();
+        assert_eq!(verified_message.signature, signature);
+        assert_eq!(verified_message.slot, 1000);
+        assert_eq!(verified_message.uuid, [72, 112, 54, 84, 106, 83, 48, 107]);
+
+        let eip712_ix = build_ix(&get_eip712_message(&payload));
+        let verified_message =
+            verify_and_decode_secp256k1_msg(&eip712_ix, 0, &evm_address, &msg, false).unwrap();
+        assert_eq!(verified_message.uuid, [72, 112, 54, 84, 106, 83, 48, 107]);
+
+        // typed data without the program id salt in the domain
+        let unsalted_domain = keccak::hashv(&[
+            keccak::hash(b"EIP712Domain(string name,string version)").as_ref(),
+            keccak::hash(b"Drift Protocol").as_ref(),
+            keccak::hash(b"1").as_ref(),
+        ]);
+        let mut unsalted_message = get_eip712_message(&payload);
+        unsalted_message[2..34].copy_from_slice(unsalted_domain.as_ref());
+        let unsalted_ix = build_ix(&unsalted_message);
+        assert!(
+            verify_and_decode_secp256k1_msg(&unsalted_ix, 0, &evm_address, &msg, false).is_err()
+        );
+
+        // signed by a different eth address
+        assert!(verify_and_decode_secp256k1_msg(&eip191_ix, 0, &[8u8; 20], &msg, false).is_err());
+
+        // offsets point at a different instruction
+        assert!(verify_and_decode_secp256k1_msg(&eip191_ix, 1, &evm_address, &msg, false).is_err());
+
+        // signed bytes do not match the msg
+        let wrong_ix = build_ix(&payload);
+        assert!(verify_and_decode_secp256k1_msg(&wrong_ix, 0, &evm_address, &msg, false).is_err());
+    }
 }
@@ -371,6 +371,15 @@ export function getHighLeverageModeConfigPublicKey(
 	)[0];
 }
 
+export function getMarginCorrelationsPublicKey(
+	programId: PublicKey
+): PublicKey {
+	return PublicKey.findProgramAddressSync(
+		[Buffer.from(anchor.utils.bytes.utf8.encode('margin_correlations'))],
+		programId
+	)[0];
+}
+
 export function getProtectedMakerModeConfigPublicKey(
 	programId: PublicKey
 ): PublicKey {
@@ -412,6 +421,19 @@ export function getRevenueShareAccountPublicKey(
 	)[0];
 }
 
+export function getReferralCampaignPublicKey(
+	programId: PublicKey,
+	referrer: PublicKey
+): PublicKey {
+	return PublicKey.findProgramAddressSync(
+		[
+			Buffer.from(anchor.utils.bytes.utf8.encode('REFERRAL_CAMPAIGN')),
+			referrer.toBuffer(),
+		],
+		programId
+	)[0];
+}
+
 export function getRevenueShareEscrowAccountPublicKey(
 	programId: PublicKey,
 	authority: PublicKey
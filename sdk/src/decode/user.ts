@@ -5,6 +5,7 @@ import {
 	Order,
 	OrderStatus,
 	OrderTriggerCondition,
+	OrderTriggerSource,
 	OrderType,
 	PerpPosition,
 	PositionDirection,
@@ -15,6 +16,7 @@ import {
 } from '../types';
 import { PublicKey } from '@solana/web3.js';
 import { ZERO } from '../constants/numericConstants';
+import { isLegacyUserAccount } from '../memcmp';
 
 function readUnsignedBigInt64LE(buffer: Buffer, offset: number): BN {
 	return new BN(buffer.subarray(offset, offset + 8), 10, 'le');
@@ -31,6 +33,9 @@ function readSignedBigInt64LE(buffer: Buffer, offset: number): BN {
 }
 
 export function decodeUser(buffer: Buffer): UserAccount {
+	// accounts not yet migrated have 96 byte orders without the fields after bitFlags
+	const legacyOrderLayout = isLegacyUserAccount(buffer);
+	const orderSize = legacyOrderLayout ? 96 : 128;
 	let offset = 8;
 	const authority = new PublicKey(buffer.slice(offset, offset + 32));
 	offset += 32;
@@ -152,7 +157,7 @@ export function decodeUser(buffer: Buffer): UserAccount {
 	for (let i = 0; i < 32; i++) {
 		// skip order if it's not open
 		if (buffer.readUint8(offset + 82) !== 1) {
-			offset += 96;
+			offset += orderSize;
 			continue;
 		}
 
@@ -201,6 +206,10 @@ export function decodeUser(buffer: Buffer): UserAccount {
 			orderType = OrderType.TRIGGER_LIMIT;
 		} else if (orderTypeNum === 4) {
 			orderType = OrderType.ORACLE;
+		} else if (orderTypeNum === 5) {
+			orderType = OrderType.TRAILING_STOP;
+		} else if (orderTypeNum === 6) {
+			orderType = OrderType.TWAP;
 		}
 		offset += 1;
 		const marketTypeNum = buffer.readUInt8(offset);
@@ -253,7 +262,42 @@ export function decodeUser(buffer: Buffer): UserAccount {
 		offset += 1;
 		const bitFlags = buffer.readUint8(offset);
 		offset += 1;
-		offset += 1; // padding
+		let orderGroup = 0;
+		let twapSliceInterval = 0;
+		let twapNumSlices = 0;
+		let twapSlicesReleased = 0;
+		let icebergClipSize = ZERO;
+		let maxSlot = ZERO;
+		let trailingStopDistance = 0;
+		let triggerSource = OrderTriggerSource.ORACLE;
+		if (legacyOrderLayout) {
+			offset += 1; // padding
+		} else {
+			orderGroup = buffer.readUint8(offset);
+			offset += 1;
+			twapSliceInterval = buffer.readUInt32LE(offset);
+			offset += 4;
+			twapNumSlices = buffer.readUInt16LE(offset);
+			offset += 2;
+			twapSlicesReleased = buffer.readUInt16LE(offset);
+			offset += 2;
+			icebergClipSize = readUnsignedBigInt64LE(buffer, offset);
+			offset += 8;
+			maxSlot = readUnsignedBigInt64LE(buffer, offset);
+			offset += 8;
+			trailingStopDistance = buffer.readUInt32LE(offset);
+			offset += 4;
+			const triggerSourceNum = buffer.readUInt8(offset);
+			if (triggerSourceNum === 1) {
+				triggerSource = OrderTriggerSource.MARK_TWAP;
+			} else if (triggerSourceNum === 2) {
+				triggerSource = OrderTriggerSource.FUNDING_RATE;
+			} else if (triggerSourceNum === 3) {
+				triggerSource = OrderTriggerSource.ORACLE_MARK_DIVERGENCE;
+			}
+			offset += 1;
+			offset += 3; // padding
+		}
 		orders.push({
 			slot,
 			price,
@@ -281,6 +325,14 @@ export function decodeUser(buffer: Buffer): UserAccount {
 			auctionDuration,
 			bitFlags,
 			postedSlotTail,
+			orderGroup,
+			twapSliceInterval,
+			twapNumSlices,
+			twapSlicesReleased,
+			icebergClipSize,
+			maxSlot,
+			trailingStopDistance,
+			triggerSource,
 		});
 	}
 
@@ -366,6 +418,14 @@ export function decodeUser(buffer: Buffer): UserAccount {
 
 	const lastFuelBonusUpdateTs = buffer.readUint32LE(offset);
 	offset += 4;
+	const liquidationAuctionOrderId = buffer.readUint32LE(offset);
+	offset += 4;
+	const deleverageStartSlot = readUnsignedBigInt64LE(buffer, offset);
+	offset += 8;
+	const deleverageMarginFreed = readUnsignedBigInt64LE(buffer, offset);
+	offset += 8;
+	const deleverageOrderId = buffer.readUint32LE(offset);
+	offset += 4;
 	return {
 		authority,
 		delegate,
@@ -396,5 +456,9 @@ export function decodeUser(buffer: Buffer): UserAccount {
 		marginMode,
 		poolId,
 		lastFuelBonusUpdateTs,
+		liquidationAuctionOrderId,
+		deleverageStartSlot,
+		deleverageMarginFreed,
+		deleverageOrderId,
 	};
 }
@@ -40,6 +40,7 @@ import {
 	Order,
 	OrderParams,
 	OrderTriggerCondition,
+	OrderTriggerSource,
 	OrderType,
 	PerpMarketAccount,
 	PerpMarketExtendedInfo,
@@ -128,6 +129,7 @@ import {
 	getConstituentVaultPublicKey,
 	getConstituentCorrelationsPublicKey,
 	getLpPoolTokenTokenAccountPublicKey,
+	getMarginCorrelationsPublicKey,
 } from './addresses/pda';
 import {
 	DataAndSlot,
@@ -211,6 +213,7 @@ import { sha256 } from '@noble/hashes/sha256';
 import { getOracleConfidenceFromMMOracleData } from './oracles/utils';
 import { ConstituentMap } from './constituentMap/constituentMap';
 import { hasBuilder } from './math/orders';
+import { marginCorrelationsEnabled } from './math/state';
 import { RevenueShareEscrowMap } from './userMap/revenueShareEscrowMap';
 import {
 	isBuilderOrderReferral,
@@ -2747,11 +2750,22 @@ export class DriftClient {
 			}
 		}
 
-		return [
+		const remainingAccounts = [
 			...oracleAccountMap.values(),
 			...spotMarketAccountMap.values(),
 			...perpMarketAccountMap.values(),
 		];
+
+		// required right after the perp markets once enabled
+		if (marginCorrelationsEnabled(this.getStateAccount())) {
+			remainingAccounts.push({
+				pubkey: getMarginCorrelationsPublicKey(this.program.programId),
+				isSigner: false,
+				isWritable: false,
+			});
+		}
+
+		return remainingAccounts;
 	}
 
 	addPerpMarketToRemainingAccountMaps(
@@ -2832,6 +2846,23 @@ export class DriftClient {
 		}
 	}
 
+	/**
+	 * Fills for a referee require the referrer's referral campaign while it has one. The program
+	 * finds it anywhere in the remaining accounts, so it is added last
+	 */
+	addReferralCampaignToRemainingAccounts(
+		remainingAccounts: AccountMeta[],
+		referrerInfo?: ReferrerInfo
+	): void {
+		if (referrerInfo?.referralCampaign) {
+			remainingAccounts.push({
+				pubkey: referrerInfo.referralCampaign,
+				isSigner: false,
+				isWritable: true,
+			});
+		}
+	}
+
 	getRemainingAccountMapsForUsers(userAccounts: UserAccount[]): {
 		oracleAccountMap: Map<string, AccountMeta>;
 		spotMarketAccountMap: Map<number, AccountMeta>;
@@ -5681,6 +5712,11 @@ export class DriftClient {
 			postOnly: params.postOnly,
 			bitFlags: params.bitFlags,
 			maxTs: params.maxTs,
+			priceDistribution: params.priceDistribution ?? null,
+			sizeRatio: params.sizeRatio ?? null,
+			sizeWeights: params.sizeWeights ?? null,
+			startOraclePriceOffset: params.startOraclePriceOffset ?? null,
+			endOraclePriceOffset: params.endOraclePriceOffset ?? null,
 		};
 
 		return await this.program.instruction.placeScaleOrders(formattedParams, {
@@ -5847,6 +5883,11 @@ export class DriftClient {
 			});
 		}
 
+		this.addReferralCampaignToRemainingAccounts(
+			remainingAccounts,
+			referrerInfo
+		);
+
 		const orderId = isSignedMsg ? null : order.orderId;
 		return await this.program.instruction.fillPerpOrder(orderId, null, {
 			accounts: {
@@ -7220,6 +7261,38 @@ export class DriftClient {
 		});
 	}
 
+	/**
+	 * Migrates a user account with the legacy order layout. Permissionless and
+	 * the wallet pays the extra rent, so keepers can prepend it to fills,
+	 * triggers and liquidations of legacy users
+	 */
+	public async migrateUserOrderLayout(
+		userAccountPublicKey: PublicKey,
+		txParams?: TxParams
+	): Promise<TransactionSignature> {
+		const { txSig } = await this.sendTransaction(
+			await this.buildTransaction(
+				await this.getMigrateUserOrderLayoutIx(userAccountPublicKey),
+				txParams
+			),
+			[],
+			this.opts
+		);
+		return txSig;
+	}
+
+	public async getMigrateUserOrderLayoutIx(
+		userAccountPublicKey: PublicKey
+	): Promise<TransactionInstruction> {
+		return await this.program.instruction.migrateUserOrderLayout({
+			accounts: {
+				user: userAccountPublicKey,
+				payer: this.wallet.publicKey,
+				systemProgram: anchor.web3.SystemProgram.programId,
+			},
+		});
+	}
+
 	public async logUserBalances(
 		userAccountPublicKey: PublicKey,
 		txParams?: TxParams
@@ -7737,6 +7810,11 @@ export class DriftClient {
 
 		const authority = overrides?.authority ?? this.wallet.publicKey;
 
+		this.addReferralCampaignToRemainingAccounts(
+			remainingAccounts,
+			referrerInfo
+		);
+
 		return await this.program.instruction.placeAndTakePerpOrder(
 			orderParams,
 			optionalParams,
@@ -7821,6 +7899,12 @@ export class DriftClient {
 				isSigner: false,
 			});
 		}
+
+		this.addReferralCampaignToRemainingAccounts(
+			remainingAccounts,
+			referrerInfo
+		);
+
 		return await this.program.instruction.placeAndMakePerpOrder(
 			orderParams,
 			takerOrderId,
@@ -8208,6 +8292,11 @@ export class DriftClient {
 			});
 		}
 
+		this.addReferralCampaignToRemainingAccounts(
+			remainingAccounts,
+			referrerInfo
+		);
+
 		const placeAndMakeIx =
 			await this.program.instruction.placeAndMakeSignedMsgPerpOrder(
 				orderParams,
@@ -8534,7 +8623,9 @@ export class DriftClient {
 	 * @param orderParams.newBaseAmount: The new base amount for the order
 	 * @param orderParams.newLimitPice: The new limit price for the order
 	 * @param orderParams.newOraclePriceOffset: The new oracle price offset for the order
+	 * @param orderParams.newTrailingStopDistance: Optional - The new trailing stop distance for a trailing stop order
 	 * @param orderParams.newTriggerPrice: Optional - Thew new trigger price for the order.
+	 * @param orderParams.newTriggerSource: Optional - The new trigger source for a trigger order
 	 * @param orderParams.auctionDuration:
 	 * @param orderParams.auctionStartPrice:
 	 * @param orderParams.auctionEndPrice:
@@ -8552,8 +8643,10 @@ export class DriftClient {
 			newBaseAmount?: BN;
 			newLimitPrice?: BN;
 			newOraclePriceOffset?: number;
+			newTrailingStopDistance?: number;
 			newTriggerPrice?: BN;
 			newTriggerCondition?: OrderTriggerCondition;
+			newTriggerSource?: OrderTriggerSource;
 			auctionDuration?: number;
 			auctionStartPrice?: BN;
 			auctionEndPrice?: BN;
@@ -8590,8 +8683,10 @@ export class DriftClient {
 			newBaseAmount,
 			newLimitPrice,
 			newOraclePriceOffset,
+			newTrailingStopDistance,
 			newTriggerPrice,
 			newTriggerCondition,
+			newTriggerSource,
 			auctionDuration,
 			auctionStartPrice,
 			auctionEndPrice,
@@ -8606,8 +8701,10 @@ export class DriftClient {
 			newBaseAmount?: BN;
 			newLimitPrice?: BN;
 			newOraclePriceOffset?: number;
+			newTrailingStopDistance?: number;
 			newTriggerPrice?: BN;
 			newTriggerCondition?: OrderTriggerCondition;
+			newTriggerSource?: OrderTriggerSource;
 			auctionDuration?: number;
 			auctionStartPrice?: BN;
 			auctionEndPrice?: BN;
@@ -8641,6 +8738,7 @@ export class DriftClient {
 			oraclePriceOffset: newOraclePriceOffset || null,
 			triggerPrice: newTriggerPrice || null,
 			triggerCondition: newTriggerCondition || null,
+			triggerSource: newTriggerSource || null,
 			auctionDuration: auctionDuration || null,
 			auctionStartPrice: auctionStartPrice || null,
 			auctionEndPrice: auctionEndPrice || null,
@@ -8649,6 +8747,7 @@ export class DriftClient {
 			bitFlags: bitFlags != undefined ? bitFlags : null,
 			policy: policy || null,
 			maxTs: maxTs || null,
+			trailingStopDistance: newTrailingStopDistance || null,
 		};
 
 		const authority =
@@ -8673,7 +8772,9 @@ export class DriftClient {
 	 * @param orderParams.newBaseAmount: The new base amount for the order
 	 * @param orderParams.newLimitPice: The new limit price for the order
 	 * @param orderParams.newOraclePriceOffset: The new oracle price offset for the order
+	 * @param orderParams.newTrailingStopDistance: Optional - The new trailing stop distance for a trailing stop order
 	 * @param orderParams.newTriggerPrice: Optional - Thew new trigger price for the order.
+	 * @param orderParams.newTriggerSource: Optional - The new trigger source for a trigger order
 	 * @param orderParams.auctionDuration: Only required if order type changed to market from something else
 	 * @param orderParams.auctionStartPrice: Only required if order type changed to market from something else
 	 * @param orderParams.auctionEndPrice: Only required if order type changed to market from something else
@@ -8691,8 +8792,10 @@ export class DriftClient {
 			newBaseAmount?: BN;
 			newLimitPrice?: BN;
 			newOraclePriceOffset?: number;
+			newTrailingStopDistance?: number;
 			newTriggerPrice?: BN;
 			newTriggerCondition?: OrderTriggerCondition;
+			newTriggerSource?: OrderTriggerSource;
 			auctionDuration?: number;
 			auctionStartPrice?: BN;
 			auctionEndPrice?: BN;
@@ -8723,8 +8826,10 @@ export class DriftClient {
 			newBaseAmount,
 			newLimitPrice,
 			newOraclePriceOffset,
+			newTrailingStopDistance,
 			newTriggerPrice,
 			newTriggerCondition,
+			newTriggerSource,
 			auctionDuration,
 			auctionStartPrice,
 			auctionEndPrice,
@@ -8739,8 +8844,10 @@ export class DriftClient {
 			newBaseAmount?: BN;
 			newLimitPrice?: BN;
 			newOraclePriceOffset?: number;
+			newTrailingStopDistance?: number;
 			newTriggerPrice?: BN;
 			newTriggerCondition?: OrderTriggerCondition;
+			newTriggerSource?: OrderTriggerSource;
 			auctionDuration?: number;
 			auctionStartPrice?: BN;
 			auctionEndPrice?: BN;
@@ -8766,6 +8873,7 @@ export class DriftClient {
 			oraclePriceOffset: newOraclePriceOffset || null,
 			triggerPrice: newTriggerPrice || null,
 			triggerCondition: newTriggerCondition || null,
+			triggerSource: newTriggerSource || null,
 			auctionDuration: auctionDuration || null,
 			auctionStartPrice: auctionStartPrice || null,
 			auctionEndPrice: auctionEndPrice || null,
@@ -8774,6 +8882,7 @@ export class DriftClient {
 			bitFlags: bitFlags || null,
 			policy: policy || null,
 			maxTs: maxTs || null,
+			trailingStopDistance: newTrailingStopDistance || null,
 		};
 
 		return await this.program.instruction.modifyOrderByUserId(
@@ -46,3 +46,9 @@ export function builderCodesEnabled(stateAccount: StateAccount): boolean {
 export function builderReferralEnabled(stateAccount: StateAccount): boolean {
 	return (stateAccount.featureBitFlags & FeatureBitFlags.BUILDER_REFERRAL) > 0;
 }
+
+export function marginCorrelationsEnabled(stateAccount: StateAccount): boolean {
+	return (
+		(stateAccount.featureBitFlags & FeatureBitFlags.MARGIN_CORRELATIONS) > 0
+	);
+}
@@ -1,8 +1,37 @@
-import { MemcmpFilter, PublicKey } from '@solana/web3.js';
+import { DataSizeFilter, MemcmpFilter, PublicKey } from '@solana/web3.js';
 import bs58 from 'bs58';
 import { BorshAccountsCoder } from '@coral-xyz/anchor';
 import { encodeName } from './userName';
 
+export const USER_ACCOUNT_SIZE = 5424;
+export const LEGACY_USER_ACCOUNT_SIZE = 4376;
+
+/**
+ * Offset of a user field after the orders array. Accounts not yet migrated
+ * with migrateUserOrderLayout have the legacy 96 byte orders, which shifts
+ * these fields
+ */
+export function getUserTailOffset(
+	offset: number,
+	legacyOrderLayout = false
+): number {
+	return legacyOrderLayout
+		? offset - (USER_ACCOUNT_SIZE - LEGACY_USER_ACCOUNT_SIZE)
+		: offset;
+}
+
+export function isLegacyUserAccount(buffer: Buffer): boolean {
+	return buffer.length === LEGACY_USER_ACCOUNT_SIZE;
+}
+
+export function getUserAccountSizeFilter(
+	legacyOrderLayout = false
+): DataSizeFilter {
+	return {
+		dataSize: legacyOrderLayout ? LEGACY_USER_ACCOUNT_SIZE : USER_ACCOUNT_SIZE,
+	};
+}
+
 export function getUserFilter(): MemcmpFilter {
 	return {
 		memcmp: {
@@ -12,46 +41,56 @@ export function getUserFilter(): MemcmpFilter {
 	};
 }
 
-export function getNonIdleUserFilter(): MemcmpFilter {
+export function getNonIdleUserFilter(
+	legacyOrderLayout = false
+): MemcmpFilter {
 	return {
 		memcmp: {
-			offset: 4350,
+			offset: getUserTailOffset(5374, legacyOrderLayout),
 			bytes: bs58.encode(Uint8Array.from([0])),
 		},
 	};
 }
 
-export function getUserWithOrderFilter(): MemcmpFilter {
+export function getUserWithOrderFilter(
+	legacyOrderLayout = false
+): MemcmpFilter {
 	return {
 		memcmp: {
-			offset: 4352,
+			offset: getUserTailOffset(5376, legacyOrderLayout),
 			bytes: bs58.encode(Uint8Array.from([1])),
 		},
 	};
 }
 
-export function getUserWithoutOrderFilter(): MemcmpFilter {
+export function getUserWithoutOrderFilter(
+	legacyOrderLayout = false
+): MemcmpFilter {
 	return {
 		memcmp: {
-			offset: 4352,
+			offset: getUserTailOffset(5376, legacyOrderLayout),
 			bytes: bs58.encode(Uint8Array.from([0])),
 		},
 	};
 }
 
-export function getUserWithAuctionFilter(): MemcmpFilter {
+export function getUserWithAuctionFilter(
+	legacyOrderLayout = false
+): MemcmpFilter {
 	return {
 		memcmp: {
-			offset: 4354,
+			offset: getUserTailOffset(5378, legacyOrderLayout),
 			bytes: bs58.encode(Uint8Array.from([1])),
 		},
 	};
 }
 
-export function getUserThatHasBeenLP(): MemcmpFilter {
+export function getUserThatHasBeenLP(
+	legacyOrderLayout = false
+): MemcmpFilter {
 	return {
 		memcmp: {
-			offset: 4267,
+			offset: getUserTailOffset(5291, legacyOrderLayout),
 			bytes: bs58.encode(Uint8Array.from([99])),
 		},
 	};
@@ -66,10 +105,13 @@ export function getUserWithName(name: string): MemcmpFilter {
 	};
 }
 
-export function getUsersWithPoolId(poolId: number): MemcmpFilter {
+export function getUsersWithPoolId(
+	poolId: number,
+	legacyOrderLayout = false
+): MemcmpFilter {
 	return {
 		memcmp: {
-			offset: 4356,
+			offset: getUserTailOffset(5380, legacyOrderLayout),
 			bytes: bs58.encode(Uint8Array.from([poolId])),
 		},
 	};
@@ -141,6 +183,17 @@ export function getRevenueShareEscrowFilter(): MemcmpFilter {
 	};
 }
 
+export function getReferralCampaignFilter(): MemcmpFilter {
+	return {
+		memcmp: {
+			offset: 0,
+			bytes: bs58.encode(
+				BorshAccountsCoder.accountDiscriminator('ReferralCampaign')
+			),
+		},
+	};
+}
+
 export function getConstituentFilter(): MemcmpFilter {
 	return {
 		memcmp: {
@@ -2,8 +2,11 @@ import { DriftClient } from '../driftClient';
 import { UserAccount } from '../types';
 import {
 	getNonIdleUserFilter,
+	getUserAccountSizeFilter,
 	getUserFilter,
+	getUserTailOffset,
 	getUserWithOrderFilter,
+	isLegacyUserAccount,
 } from '../memcmp';
 import { Commitment, PublicKey, RpcResponseAndContext } from '@solana/web3.js';
 import { Buffer } from 'buffer';
@@ -73,10 +76,15 @@ export class OrderSubscriber {
 		if (config.fastDecode ?? true) {
 			this.decodeFn = (name, data) => decodeUser(data);
 		} else {
-			this.decodeFn =
+			const anchorDecodeFn =
 				this.driftClient.program.account.user.coder.accounts.decodeUnchecked.bind(
 					this.driftClient.program.account.user.coder.accounts
 				);
+			// the idl only describes the current order layout
+			this.decodeFn = (name, data) =>
+				isLegacyUserAccount(data)
+					? decodeUser(data)
+					: anchorDecodeFn(name, data);
 		}
 		this.eventEmitter = new EventEmitter();
 		this.fetchAllNonIdleUsers = config.fetchAllNonIdleUsers;
@@ -95,49 +103,56 @@ export class OrderSubscriber {
 			this.fetchPromiseResolver = resolver;
 		});
 
-		const filters = this.fetchAllNonIdleUsers
-			? [getUserFilter(), getNonIdleUserFilter()]
-			: [getUserFilter(), getUserWithOrderFilter()];
-
 		try {
-			const rpcRequestArgs = [
-				this.driftClient.program.programId.toBase58(),
-				{
-					commitment: this.commitment,
-					filters,
-					encoding: 'base64',
-					withContext: true,
-				},
-			];
+			// legacy layout accounts have other tail offsets, fetch them separately
+			for (const legacyOrderLayout of [false, true]) {
+				const filters = [
+					getUserFilter(),
+					getUserAccountSizeFilter(legacyOrderLayout),
+					this.fetchAllNonIdleUsers
+						? getNonIdleUserFilter(legacyOrderLayout)
+						: getUserWithOrderFilter(legacyOrderLayout),
+				];
 
-			const rpcJSONResponse: any =
-				// @ts-ignore
-				await this.driftClient.connection._rpcRequest(
-					'getProgramAccounts',
-					rpcRequestArgs
-				);
+				const rpcRequestArgs = [
+					this.driftClient.program.programId.toBase58(),
+					{
+						commitment: this.commitment,
+						filters,
+						encoding: 'base64',
+						withContext: true,
+					},
+				];
 
-			const rpcResponseAndContext: RpcResponseAndContext<
-				Array<{
-					pubkey: PublicKey;
-					account: {
-						data: [string, string];
-					};
-				}>
-			> = rpcJSONResponse.result;
+				const rpcJSONResponse: any =
+					// @ts-ignore
+					await this.driftClient.connection._rpcRequest(
+						'getProgramAccounts',
+						rpcRequestArgs
+					);
 
-			const slot: number = rpcResponseAndContext.context.slot;
+				const rpcResponseAndContext: RpcResponseAndContext<
+					Array<{
+						pubkey: PublicKey;
+						account: {
+							data: [string, string];
+						};
+					}>
+				> = rpcJSONResponse.result;
 
-			for (const programAccount of rpcResponseAndContext.value) {
-				const key = programAccount.pubkey.toString();
-				this.tryUpdateUserAccount(
-					key,
-					'raw',
-					programAccount.account.data,
-					slot
-				);
-				// give event loop a chance to breathe
-				await new Promise((resolve) => setTimeout(resolve, 0));
+				const slot: number = rpcResponseAndContext.context.slot;
+
+				for (const programAccount of rpcResponseAndContext.value) {
+					const key = programAccount.pubkey.toString();
+					this.tryUpdateUserAccount(
+						key,
+						'raw',
+						programAccount.account.data,
+						slot
+					);
+					// give event loop a chance to breathe
+					await new Promise((resolve) => setTimeout(resolve, 0));
+				}
 			}
 		} catch (e) {
 			console.error(e);
@@ -172,8 +187,12 @@ export class OrderSubscriber {
 				// @ts-ignore
 				const buffer = Buffer.from(data[0], data[1]);
 
+				const lastActiveSlotOffset = getUserTailOffset(
+					5352,
+					isLegacyUserAccount(buffer)
+				);
 				const newLastActiveSlot = new BN(
-					buffer.subarray(4328, 4328 + 8),
+					buffer.subarray(lastActiveSlotOffset, lastActiveSlotOffset + 8),
 					undefined,
 					'le'
 				);
@@ -187,8 +206,12 @@ export class OrderSubscriber {
 				userAccount = this.decodeFn('User', buffer) as UserAccount;
 			} else if (dataType === 'buffer') {
 				const buffer: Buffer = data as Buffer;
+				const lastActiveSlotOffset = getUserTailOffset(
+					5352,
+					isLegacyUserAccount(buffer)
+				);
 				const newLastActiveSlot = new BN(
-					buffer.subarray(4328, 4328 + 8),
+					buffer.subarray(lastActiveSlotOffset, lastActiveSlotOffset + 8),
 					undefined,
 					'le'
 				);
@@ -33,6 +33,8 @@ export enum FeatureBitFlags {
 	MEDIAN_TRIGGER_PRICE = 2,
 	BUILDER_CODES = 4,
 	BUILDER_REFERRAL = 8,
+	PORTFOLIO_MARGIN = 16,
+	MARGIN_CORRELATIONS = 32,
 }
 
 export class MarketStatus {
@@ -96,6 +98,7 @@ export class MarginMode {
 	static readonly HIGH_LEVERAGE_MAINTENANCE = {
 		highLeverageMaintenance: {},
 	};
+	static readonly PORTFOLIO = { portfolio: {} };
 }
 
 export class ContractType {
@@ -190,6 +193,8 @@ export class OrderType {
 	static readonly TRIGGER_LIMIT = { triggerLimit: {} };
 	static readonly MARKET = { market: {} };
 	static readonly ORACLE = { oracle: {} };
+	static readonly TRAILING_STOP = { trailingStop: {} };
+	static readonly TWAP = { twap: {} };
 }
 
 export declare type MarketTypeStr = 'perp' | 'spot';
@@ -218,6 +223,9 @@ export class OrderAction {
 	static readonly EXPIRE = { expire: {} };
 	static readonly FILL = { fill: {} };
 	static readonly TRIGGER = { trigger: {} };
+	static readonly UPDATE_TRIGGER = { updateTrigger: {} };
+	static readonly RELEASE_SLICE = { releaseSlice: {} };
+	static readonly REPLACE = { replace: {} };
 }
 
 export class OrderActionExplanation {
@@ -279,6 +287,15 @@ export class OrderActionExplanation {
 	static readonly TRANSFER_PERP_POSITION = {
 		transferPerpPosition: {},
 	};
+	static readonly ORDER_GROUP_SIBLING_FILLED = {
+		orderGroupSiblingFilled: {},
+	};
+	static readonly ORDER_REPLACED = {
+		orderReplaced: {},
+	};
+	static readonly DELEVERAGE = {
+		deleverage: {},
+	};
 }
 
 export class OrderTriggerCondition {
@@ -288,6 +305,13 @@ export class OrderTriggerCondition {
 	static readonly TRIGGERED_BELOW = { triggeredBelow: {} }; // below condition has been triggered
 }
 
+export class OrderTriggerSource {
+	static readonly ORACLE = { oracle: {} };
+	static readonly MARK_TWAP = { markTwap: {} };
+	static readonly FUNDING_RATE = { fundingRate: {} };
+	static readonly ORACLE_MARK_DIVERGENCE = { oracleMarkDivergence: {} };
+}
+
 export class SpotFulfillmentType {
 	static readonly EXTERNAL = { external: {} };
 	static readonly MATCH = { match: {} };
@@ -536,6 +560,9 @@ export class LiquidationType {
 	static readonly LIQUIDATE_SPOT = {
 		liquidateSpot: {},
 	};
+	static readonly PERP_AUTO_DELEVERAGE = {
+		perpAutoDeleverage: {},
+	};
 }
 
 export type LiquidatePerpRecord = {
@@ -661,6 +688,7 @@ export type OrderActionRecord = {
 	takerExistingBaseAssetAmount: BN | null;
 	makerExistingQuoteEntryAmount: BN | null;
 	makerExistingBaseAssetAmount: BN | null;
+	replacedOrderId: number | null;
 };
 
 export type SwapRecord = {
@@ -1230,6 +1258,10 @@ export type UserAccount = {
 	openAuctions: number;
 	hasOpenAuction: boolean;
 	lastFuelBonusUpdateTs: number;
+	liquidationAuctionOrderId: number;
+	deleverageStartSlot: BN;
+	deleverageMarginFreed: BN;
+	deleverageOrderId: number;
 	marginMode: MarginMode;
 	poolId: number;
 };
@@ -1271,6 +1303,14 @@ export type Order = {
 	maxTs: BN;
 	bitFlags: number;
 	postedSlotTail: number;
+	orderGroup: number;
+	twapSliceInterval: number;
+	twapNumSlices: number;
+	twapSlicesReleased: number;
+	icebergClipSize: BN;
+	maxSlot: BN;
+	trailingStopDistance: number;
+	triggerSource: OrderTriggerSource;
 };
 
 export type OrderParams = {
@@ -1291,6 +1331,17 @@ export type OrderParams = {
 	maxTs: BN | null;
 	auctionStartPrice: BN | null;
 	auctionEndPrice: BN | null;
+	/** Trailing stop orders only, in PRICE_PRECISION or bps with the trailing stop bps bit flag */
+	trailingStopDistance?: number | null;
+	/** Twap orders only, seconds between slices */
+	twapSliceInterval?: number | null;
+	/** Limit orders only, base shown per clip. Makes the order an iceberg */
+	icebergClipSize?: BN | null;
+	/**
+	 * Trigger orders only, defaults to the oracle. Rate sources pass the signed
+	 * threshold's u32 bits as triggerPrice
+	 */
+	triggerSource?: OrderTriggerSource | null;
 };
 
 export class PostOnlyParams {
@@ -1307,6 +1358,16 @@ export class SizeDistribution {
 	static readonly FLAT = { flat: {} }; // Equal size for all orders
 	static readonly ASCENDING = { ascending: {} }; // Smallest at start price, largest at end price
 	static readonly DESCENDING = { descending: {} }; // Largest at start price, smallest at end price
+	static readonly EXPONENTIAL = { exponential: {} }; // Each order sizeRatio times the size of the previous one
+	static readonly CUSTOM = { custom: {} }; // Sizes proportional to sizeWeights
+}
+
+/**
+ * How to space prices across scale orders
+ */
+export class PriceDistribution {
+	static readonly LINEAR = { linear: {} }; // Equal price steps between orders
+	static readonly GEOMETRIC = { geometric: {} }; // Equal percentage steps between orders
 }
 
 /**
@@ -1334,6 +1395,16 @@ export type ScaleOrderParams = {
 	bitFlags: number;
 	/** Maximum timestamp for orders to be valid */
 	maxTs: BN | null;
+	/** How to space prices between start and end price. Defaults to linear */
+	priceDistribution?: PriceDistribution | null;
+	/** Size of each order relative to the previous one for exponential distribution (in PERCENTAGE_PRECISION) */
+	sizeRatio?: number | null;
+	/** Relative size of each order for custom distribution, one weight per order */
+	sizeWeights?: number[] | null;
+	/** Starting oracle price offset (in PRICE_PRECISION). Set with endOraclePriceOffset and startPrice and endPrice left at 0 for a ladder of oracle offset limit orders */
+	startOraclePriceOffset?: number | null;
+	/** Ending oracle price offset (in PRICE_PRECISION) */
+	endOraclePriceOffset?: number | null;
 };
 
 export class OrderParamsBitFlag {
@@ -1435,11 +1506,17 @@ export type TakerInfo = {
 export type ReferrerInfo = {
 	referrer: PublicKey;
 	referrerStats: PublicKey;
+	/**
+	 * The referrer's ReferralCampaign, required on fills while the referrer has one
+	 */
+	referralCampaign?: PublicKey;
 };
 
 export enum ReferrerStatus {
 	IsReferrer = 1,
 	IsReferred = 2,
+	BuilderReferral = 4,
+	HasReferralCampaign = 8,
 }
 
 export enum FuelOverflowStatus {
@@ -1793,6 +1870,9 @@ export type RevenueShareSettleRecord = {
 	builderTotalReferrerRewards: BN;
 	builderTotalBuilderRewards: BN;
 	builderSubAccountId: number;
+	builderRebate: BN;
+	builderRebateUnpaid: BN;
+	builderVolume30d: BN;
 };
 
 export type AddAmmConstituentMappingDatum = {
@@ -6,10 +6,12 @@ import {
 import { DriftClient } from '../driftClient';
 import { ReferrerInfo } from '../types';
 import {
+	getReferralCampaignPublicKey,
 	getUserAccountPublicKeySync,
 	getUserStatsAccountPublicKey,
 } from '../addresses/pda';
 import {
+	getReferralCampaignFilter,
 	getUserStatsFilter,
 	getUserStatsIsReferredFilter,
 	getUserStatsIsReferredOrReferrerFilter,
@@ -28,6 +30,10 @@ export class ReferrerMap {
 	 * Will be undefined if the referrer is not in the map yet.
 	 */
 	private referrerReferrerInfoMap = new Map<string, ReferrerInfo>();
+	/**
+	 * referrers with a ReferralCampaign account, which fills for their referees must include
+	 */
+	private referralCampaignReferrers = new Set<string>();
 	private driftClient: DriftClient;
 	private parallelSync: boolean;
 
@@ -126,6 +132,12 @@ export class ReferrerMap {
 				this.driftClient.program.programId,
 				referrerKey
 			),
+			referralCampaign: this.referralCampaignReferrers.has(referrer)
+				? getReferralCampaignPublicKey(
+						this.driftClient.program.programId,
+						referrerKey
+				  )
+				: undefined,
 		};
 
 		this.referrerReferrerInfoMap.set(referrer, referrerInfo);
@@ -157,11 +169,13 @@ export class ReferrerMap {
 					this.syncAll(),
 					this.syncReferrer(getUserStatsIsReferredFilter()),
 					this.syncReferrer(getUserStatsIsReferredOrReferrerFilter()),
+					this.syncReferralCampaigns(),
 				]);
 			} else {
 				await this.syncAll();
 				await this.syncReferrer(getUserStatsIsReferredFilter());
 				await this.syncReferrer(getUserStatsIsReferredOrReferrerFilter());
+				await this.syncReferralCampaigns();
 			}
 		} finally {
 			this.fetchPromiseResolver();
@@ -260,8 +274,58 @@ export class ReferrerMap {
 		}
 	}
 
+	/**
+	 * Campaigns are included until they're deleted, since the fill that includes an ended campaign
+	 * is what clears the referrer's requirement for it
+	 */
+	async syncReferralCampaigns(): Promise<void> {
+		const rpcRequestArgs = [
+			this.driftClient.program.programId.toBase58(),
+			{
+				commitment: this.driftClient.opts.commitment,
+				filters: [getReferralCampaignFilter()],
+				encoding: 'base64',
+				dataSlice: {
+					offset: 8,
+					length: 32,
+				},
+				withContext: true,
+			},
+		];
+
+		const rpcJSONResponse: any =
+			// @ts-ignore
+			await this.driftClient.connection._rpcRequest(
+				'getProgramAccounts',
+				rpcRequestArgs
+			);
+
+		const rpcResponseAndContext: RpcResponseAndContext<
+			Array<{
+				pubkey: string;
+				account: {
+					data: [string, string];
+				};
+			}>
+		> = rpcJSONResponse.result;
+
+		this.referralCampaignReferrers.clear();
+		for (const programAccount of rpcResponseAndContext.value) {
+			const buffer = Buffer.from(
+				programAccount.account.data[0],
+				// @ts-ignore
+				programAccount.account.data[1]
+			);
+			this.referralCampaignReferrers.add(bs58.encode(buffer));
+		}
+
+		// referrer infos are cached, rebuild them with the current campaigns
+		this.referrerReferrerInfoMap.clear();
+	}
+
 	public async unsubscribe() {
 		this.authorityReferrerMap.clear();
 		this.referrerReferrerInfoMap.clear();
+		this.referralCampaignReferrers.clear();
 	}
 }
@@ -23,6 +23,7 @@ import { ProtectMakerParamsMap } from '../dlob/types';
 import {
 	Commitment,
 	Connection,
+	GetProgramAccountsFilter,
 	MemcmpFilter,
 	PublicKey,
 	RpcResponseAndContext,
@@ -31,8 +32,10 @@ import { Buffer } from 'buffer';
 import { ZSTDDecoder } from 'zstddec';
 import {
 	getNonIdleUserFilter,
+	getUserAccountSizeFilter,
 	getUserFilter,
 	getUsersWithPoolId,
+	isLegacyUserAccount,
 } from '../memcmp';
 import {
 	SyncConfig,
@@ -46,7 +49,7 @@ import { grpcSubscription } from './grpcSubscription';
 import StrictEventEmitter from 'strict-event-emitter-types';
 import { EventEmitter } from 'events';
 
-const MAX_USER_ACCOUNT_SIZE_BYTES = 4376;
+const MAX_USER_ACCOUNT_SIZE_BYTES = 5424;
 
 export interface UserMapInterface {
 	eventEmitter: StrictEventEmitter<EventEmitter, UserEvents>;
@@ -79,6 +82,7 @@ export interface UserMapInterface {
 
 export class UserMap implements UserMapInterface {
 	private userMap = new Map<string, DataAndSlot<User>>();
+	private legacyOrderLayoutUsers = new Set<string>();
 	driftClient: DriftClient;
 	eventEmitter: StrictEventEmitter<EventEmitter, UserEvents>;
 	private connection: Connection;
@@ -133,10 +137,15 @@ export class UserMap implements UserMapInterface {
 		if (config.fastDecode ?? true) {
 			decodeFn = (name, buffer) => decodeUser(buffer);
 		} else {
-			decodeFn =
+			const anchorDecodeFn =
 				this.driftClient.program.account.user.coder.accounts.decodeUnchecked.bind(
 					this.driftClient.program.account.user.coder.accounts
 				);
+			// the idl only describes the current order layout
+			decodeFn = (name, buffer) =>
+				isLegacyUserAccount(buffer)
+					? decodeUser(buffer)
+					: anchorDecodeFn(name, buffer);
 		}
 		this.decode = decodeFn;
 
@@ -406,13 +415,16 @@ export class UserMap implements UserMapInterface {
 		}
 	}
 
-	private getFilters(): MemcmpFilter[] {
-		const filters = [getUserFilter()];
+	private getFilters(legacyOrderLayout = false): GetProgramAccountsFilter[] {
+		const filters: GetProgramAccountsFilter[] = [
+			getUserFilter(),
+			getUserAccountSizeFilter(legacyOrderLayout),
+		];
 		if (!this.includeIdle) {
-			filters.push(getNonIdleUserFilter());
+			filters.push(getNonIdleUserFilter(legacyOrderLayout));
 		}
 		if (this.filterByPoolId !== undefined) {
-			filters.push(getUsersWithPoolId(this.filterByPoolId));
+			filters.push(getUsersWithPoolId(this.filterByPoolId, legacyOrderLayout));
 		}
 		if (this.additionalFilters) {
 			filters.push(...this.additionalFilters);
@@ -434,67 +446,74 @@ export class UserMap implements UserMapInterface {
 		});
 
 		try {
-			const rpcRequestArgs = [
-				this.driftClient.program.programId.toBase58(),
-				{
-					commitment: this.commitment,
-					filters: this.getFilters(),
-					encoding: 'base64+zstd',
-					withContext: true,
-				},
-			];
-
-			// @ts-ignore
-			const rpcJSONResponse: any = await this.connection._rpcRequest(
-				'getProgramAccounts',
-				rpcRequestArgs
-			);
-			const rpcResponseAndContext: RpcResponseAndContext<
-				Array<{ pubkey: PublicKey; account: { data: [string, string] } }>
-			> = rpcJSONResponse.result;
-			const slot = rpcResponseAndContext.context.slot;
-
-			this.updateLatestSlot(slot);
-
 			const programAccountBufferMap = new Map<string, Buffer>();
-			const decodingPromises = rpcResponseAndContext.value.map(
-				async (programAccount) => {
-					const compressedUserData = Buffer.from(
-						programAccount.account.data[0],
-						'base64'
-					);
-					const decoder = new ZSTDDecoder();
-					await decoder.init();
-					const userBuffer = decoder.decode(
-						compressedUserData,
-						MAX_USER_ACCOUNT_SIZE_BYTES
-					);
-					programAccountBufferMap.set(
-						programAccount.pubkey.toString(),
-						Buffer.from(userBuffer)
-					);
-				}
-			);
-
-			await Promise.all(decodingPromises);
+			// legacy layout accounts have other tail offsets, fetch them separately
+			for (const legacyOrderLayout of [false, true]) {
+				const rpcRequestArgs = [
+					this.driftClient.program.programId.toBase58(),
+					{
+						commitment: this.commitment,
+						filters: this.getFilters(legacyOrderLayout),
+						encoding: 'base64+zstd',
+						withContext: true,
+					},
+				];
+
+				// @ts-ignore
+				const rpcJSONResponse: any = await this.connection._rpcRequest(
+					'getProgramAccounts',
+					rpcRequestArgs
+				);
+				const rpcResponseAndContext: RpcResponseAndContext<
+					Array<{ pubkey: PublicKey; account: { data: [string, string] } }>
+				> = rpcJSONResponse.result;
+				const slot = rpcResponseAndContext.context.slot;
+
+				this.updateLatestSlot(slot);
+
+				const layoutBufferMap = new Map<string, Buffer>();
+				const decodingPromises = rpcResponseAndContext.value.map(
+					async (programAccount) => {
+						const compressedUserData = Buffer.from(
+							programAccount.account.data[0],
+							'base64'
+						);
+						const decoder = new ZSTDDecoder();
+						await decoder.init();
+						const userBuffer = decoder.decode(
+							compressedUserData,
+							MAX_USER_ACCOUNT_SIZE_BYTES
+						);
+						layoutBufferMap.set(
+							programAccount.pubkey.toString(),
+							Buffer.from(userBuffer)
+						);
+					}
+				);
 
-			const promises = Array.from(programAccountBufferMap.entries()).map(
-				([key, buffer]) =>
-					(async () => {
-						const currAccountWithSlot = this.getWithSlot(key);
-						if (currAccountWithSlot) {
-							if (slot >= currAccountWithSlot.slot) {
+				await Promise.all(decodingPromises);
+
+				const promises = Array.from(layoutBufferMap.entries()).map(
+					([key, buffer]) =>
+						(async () => {
+							programAccountBufferMap.set(key, buffer);
+							const currAccountWithSlot = this.getWithSlot(key);
+							if (currAccountWithSlot) {
+								if (slot >= currAccountWithSlot.slot) {
+									const userAccount = this.decode('User', buffer);
+									this.updateUserAccount(key, userAccount, slot);
+									this.updateLegacyOrderLayout(key, buffer);
+								}
+							} else {
 								const userAccount = this.decode('User', buffer);
-								this.updateUserAccount(key, userAccount, slot);
+								await this.addPubkey(new PublicKey(key), userAccount, slot);
+								this.updateLegacyOrderLayout(key, buffer);
 							}
-						} else {
-							const userAccount = this.decode('User', buffer);
-							await this.addPubkey(new PublicKey(key), userAccount, slot);
-						}
-					})()
-			);
+						})()
+				);
 
-			await Promise.all(promises);
+				await Promise.all(promises);
+			}
 
 			for (const [key] of this.entries()) {
 				if (!programAccountBufferMap.has(key)) {
@@ -532,16 +551,19 @@ export class UserMap implements UserMapInterface {
 		});
 
 		try {
-			const accountsPrefetch = await this.connection.getProgramAccounts(
-				this.driftClient.program.programId,
-				{
-					dataSlice: { offset: 0, length: 0 },
-					filters: this.getFilters(),
-				}
-			);
-			const accountPublicKeys = accountsPrefetch.map(
-				(account) => account.pubkey
-			);
+			const accountPublicKeys: PublicKey[] = [];
+			for (const legacyOrderLayout of [false, true]) {
+				const accountsPrefetch = await this.connection.getProgramAccounts(
+					this.driftClient.program.programId,
+					{
+						dataSlice: { offset: 0, length: 0 },
+						filters: this.getFilters(legacyOrderLayout),
+					}
+				);
+				accountPublicKeys.push(
+					...accountsPrefetch.map((account) => account.pubkey)
+				);
+			}
 
 			const limitConcurrency = async (tasks, limit) => {
 				const executing = [];
@@ -609,6 +631,7 @@ export class UserMap implements UserMapInterface {
 								accountInfosSlot
 							);
 						}
+						this.updateLegacyOrderLayout(publicKeyString, buffer);
 					}
 				});
 			}
@@ -664,6 +687,8 @@ export class UserMap implements UserMapInterface {
 		userAccount: UserAccount,
 		slot: number
 	) {
+		// legacy accounts only change by being migrated, syncs mark them again
+		this.legacyOrderLayoutUsers.delete(key);
 		const userWithSlot = this.getWithSlot(key);
 		this.updateLatestSlot(slot);
 		if (userWithSlot) {
@@ -680,6 +705,23 @@ export class UserMap implements UserMapInterface {
 		}
 	}
 
+	private updateLegacyOrderLayout(key: string, buffer: Buffer): void {
+		if (isLegacyUserAccount(buffer)) {
+			this.legacyOrderLayoutUsers.add(key);
+		} else {
+			this.legacyOrderLayoutUsers.delete(key);
+		}
+	}
+
+	/**
+	 * Whether the user account still has the legacy order layout as of the last
+	 * sync. Its fills, triggers and liquidations need
+	 * driftClient.getMigrateUserOrderLayoutIx prepended
+	 */
+	public isLegacyOrderLayout(key: string): boolean {
+		return this.legacyOrderLayoutUsers.has(key);
+	}
+
 	updateLatestSlot(slot: number): void {
 		this.mostRecentSlot = Math.max(slot, this.mostRecentSlot);
 	}
@@ -11,6 +11,7 @@ import {
 	OrderStatus,
 	OrderType,
 	OrderTriggerCondition,
+	OrderTriggerSource,
 	PRICE_PRECISION,
 	DLOBNode,
 	OraclePriceData,
@@ -96,6 +97,14 @@ function insertOrderToDLOB(
 			maxTs,
 			bitFlags: 0,
 			postedSlotTail: 0,
+			orderGroup: 0,
+			twapSliceInterval: 0,
+			twapNumSlices: 0,
+			twapSlicesReleased: 0,
+			icebergClipSize: ZERO,
+			maxSlot: ZERO,
+			trailingStopDistance: 0,
+			triggerSource: OrderTriggerSource.ORACLE,
 		},
 		userAccount.toString(),
 		slot.toNumber(),
@@ -151,6 +160,14 @@ function insertTriggerOrderToDLOB(
 			maxTs,
 			bitFlags: 0,
 			postedSlotTail: 0,
+			orderGroup: 0,
+			twapSliceInterval: 0,
+			twapNumSlices: 0,
+			twapSlicesReleased: 0,
+			icebergClipSize: ZERO,
+			maxSlot: ZERO,
+			trailingStopDistance: 0,
+			triggerSource: OrderTriggerSource.ORACLE,
 		},
 		userAccount.toString(),
 		slot.toNumber(),
@@ -3946,6 +3963,14 @@ describe('DLOB Perp Tests', () => {
 					oraclePriceOffset: 0,
 					maxTs: ZERO,
 					postedSlotTail: 0,
+					orderGroup: 0,
+					twapSliceInterval: 0,
+					twapNumSlices: 0,
+					twapSlicesReleased: 0,
+					icebergClipSize: ZERO,
+					maxSlot: ZERO,
+					trailingStopDistance: 0,
+					triggerSource: OrderTriggerSource.ORACLE,
 				};
 				dlob.insertSignedMsgOrder(
 					order,
@@ -4048,6 +4073,14 @@ describe('DLOB Perp Tests', () => {
 			oraclePriceOffset: 0,
 			maxTs: ZERO,
 			postedSlotTail: 0,
+			orderGroup: 0,
+			twapSliceInterval: 0,
+			twapNumSlices: 0,
+			twapSlicesReleased: 0,
+			icebergClipSize: ZERO,
+			maxSlot: ZERO,
+			trailingStopDistance: 0,
+			triggerSource: OrderTriggerSource.ORACLE,
 		};
 		dlob.insertSignedMsgOrder(limitOrder, user0.publicKey.toString(), false);
 
@@ -4127,6 +4160,14 @@ describe('DLOB Perp Tests', () => {
 			oraclePriceOffset: 0,
 			maxTs: ZERO,
 			postedSlotTail: 0,
+			orderGroup: 0,
+			twapSliceInterval: 0,
+			twapNumSlices: 0,
+			twapSlicesReleased: 0,
+			icebergClipSize: ZERO,
+			maxSlot: ZERO,
+			trailingStopDistance: 0,
+			triggerSource: OrderTriggerSource.ORACLE,
 		};
 		dlob.insertSignedMsgOrder(postOnlyOrder, user0.publicKey.toString(), false);
 
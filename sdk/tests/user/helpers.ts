@@ -16,6 +16,7 @@ import {
 	OrderType,
 	PositionDirection,
 	OrderTriggerCondition,
+	OrderTriggerSource,
 	ZERO,
 	MarginMode,
 	MMOraclePriceData,
@@ -50,6 +51,14 @@ export const mockOrder: Order = {
 	maxTs: ZERO,
 	bitFlags: 0,
 	postedSlotTail: 0,
+	orderGroup: 0,
+	twapSliceInterval: 0,
+	twapNumSlices: 0,
+	twapSlicesReleased: 0,
+	icebergClipSize: ZERO,
+	maxSlot: ZERO,
+	trailingStopDistance: 0,
+	triggerSource: OrderTriggerSource.ORACLE,
 };
 
 export const mockSpotPosition: SpotPosition = {
@@ -96,6 +105,10 @@ export const mockUserAccount: UserAccount = {
 	openAuctions: 0,
 	hasOpenAuction: false,
 	lastFuelBonusUpdateTs: 0,
+	liquidationAuctionOrderId: 0,
+	deleverageStartSlot: ZERO,
+	deleverageMarginFreed: ZERO,
+	deleverageOrderId: 0,
 	marginMode: MarginMode.DEFAULT,
 	poolId: 0,
 };